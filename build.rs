@@ -0,0 +1,24 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::process::Command;
+
+/// Record the git commit `mcp` is built from in `MCP_GIT_HASH`, empty if it is not built
+/// from a git checkout (as when it is installed from crates.io).
+fn main() {
+    let hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=MCP_GIT_HASH={}", hash);
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
@@ -0,0 +1,152 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::map::label;
+use crate::tape::{File, Tape};
+
+/// The size of the address space of the Z80 CPU of MSX computers
+const MEMORY_SIZE: usize = 0x10000;
+
+/// The memory taken by a binary file of a tape when it is loaded with `BLOAD`
+#[derive(Debug, PartialEq)]
+pub struct Footprint {
+    pub name: String,
+    pub begin: usize,
+    pub end: usize,
+    pub start: usize,
+}
+
+impl Footprint {
+    /// Returns `true` if the given address is loaded by this file.
+    pub fn contains(&self, addr: usize) -> bool {
+        self.begin <= addr && addr <= self.end
+    }
+
+    /// Returns `true` if this file is loaded over some of the memory of the given one.
+    pub fn overlaps(&self, other: &Footprint) -> bool {
+        self.begin <= other.end && other.begin <= self.end
+    }
+}
+
+/// The analysis of the binary files of a tape, to understand how chained loaders use memory
+///
+/// Each binary file is labelled as in `map::label()`, by its position among the binary
+/// files of the tape.
+///
+#[derive(Debug)]
+pub struct Analysis {
+    pub footprints: Vec<Footprint>,
+}
+
+impl Analysis {
+    /// Analyze the binary files of the given tape.
+    pub fn of(tape: &Tape) -> Analysis {
+        let footprints = tape
+            .files()
+            .filter_map(|file| match file {
+                File::Bin(name, begin, end, start, _) => Some(Footprint { name, begin, end, start }),
+                _ => None,
+            })
+            .collect();
+        Analysis { footprints }
+    }
+
+    /// Returns the other files loaded over some of the memory of the given one.
+    pub fn overlaps(&self, i: usize) -> Vec<usize> {
+        let file = &self.footprints[i];
+        (0..self.footprints.len()).filter(|j| *j != i && file.overlaps(&self.footprints[*j])).collect()
+    }
+
+    /// Returns the other files whose memory contains the START address of the given one.
+    ///
+    /// These are the files a loader jumps into, as long as they are loaded by then.
+    pub fn start_in(&self, i: usize) -> Vec<usize> {
+        let start = self.footprints[i].start;
+        (0..self.footprints.len()).filter(|j| *j != i && self.footprints[*j].contains(start)).collect()
+    }
+
+    /// Draw a bar of the given width with the memory loaded by the given file.
+    ///
+    /// The memory loaded by the file is drawn with its label, and its START address as `>`.
+    pub fn memory_bar(&self, i: usize, width: usize) -> String {
+        let file = &self.footprints[i];
+        let start = column(file.start, width);
+        (0..width)
+            .map(|col| match col {
+                _ if col == start => '>',
+                _ if covers(file, col, width) => label(i),
+                _ => '.',
+            })
+            .collect()
+    }
+
+    /// Draw a bar of the given width with the memory loaded by all the files.
+    ///
+    /// The memory loaded by a single file is drawn with its label, and the memory loaded by
+    /// several files as `*`.
+    pub fn overall_bar(&self, width: usize) -> String {
+        (0..width)
+            .map(|col| {
+                let files: Vec<usize> = (0..self.footprints.len()).filter(|i| covers(&self.footprints[*i], col, width)).collect();
+                match files[..] {
+                    [] => '.',
+                    [i] => label(i),
+                    _ => '*',
+                }
+            })
+            .collect()
+    }
+}
+
+/// Returns the column of a bar of the given width where the given address is drawn.
+fn column(addr: usize, width: usize) -> usize {
+    addr.min(MEMORY_SIZE - 1) * width / MEMORY_SIZE
+}
+
+/// Returns `true` if the given file loads some of the memory drawn in the given column.
+fn covers(file: &Footprint, col: usize, width: usize) -> bool {
+    let (lo, hi) = (col * MEMORY_SIZE / width, (col + 1) * MEMORY_SIZE / width);
+    file.begin < hi && lo <= file.end
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::tape::file_name;
+
+    fn bin(begin: u16, end: u16, start: u16) -> Vec<u8> {
+        [begin.to_le_bytes(), end.to_le_bytes(), start.to_le_bytes()].concat()
+    }
+
+    #[test]
+    fn should_analyze_chained_loaders() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("loader").0, &bin(0xc000, 0xc0ff, 0x9000)).unwrap();
+        tape.append_bin(&file_name("stage2").0, &bin(0x9000, 0xc03f, 0x9000)).unwrap();
+        tape.append_bin(&file_name("music").0, &bin(0x4000, 0x4fff, 0x4000)).unwrap();
+        let analysis = Analysis::of(&tape);
+        assert_eq!(3, analysis.footprints.len());
+        assert_eq!(vec![1], analysis.overlaps(0));
+        assert_eq!(vec![0], analysis.overlaps(1));
+        assert_eq!(Vec::<usize>::new(), analysis.overlaps(2));
+        assert_eq!(vec![1], analysis.start_in(0));
+        assert_eq!(Vec::<usize>::new(), analysis.start_in(1));
+    }
+
+    #[test]
+    fn should_draw_memory_bars() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("loader").0, &bin(0xc000, 0xc0ff, 0x9000)).unwrap();
+        tape.append_bin(&file_name("stage2").0, &bin(0x9000, 0xc03f, 0x9000)).unwrap();
+        let analysis = Analysis::of(&tape);
+        assert_eq!(".........>..A...", analysis.memory_bar(0, 16));
+        assert_eq!(".........>BBB...", analysis.memory_bar(1, 16));
+        assert_eq!(".........BBB*...", analysis.overall_bar(16));
+    }
+}
@@ -0,0 +1,177 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io;
+use std::path::Path;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::file;
+use crate::loader::Addresses;
+use crate::policy;
+use crate::sym::Symbols;
+use crate::tape;
+
+/// The names of the symbols taken as the entry point of a program, in any case
+const ENTRY_NAMES: &[&str] = &["start", "main", "entry"];
+
+/// What the assembler tells about a binary file it wrote
+///
+/// Assemblers such as sjasmplus, sjasm or pasmo write the listing of the program, where
+/// each line gives the address of the bytes it emits, and its symbols next to the binary
+/// file (e.g., `game.lst` and `game.sym` for `game.bin`). The first address that emits
+/// bytes is the ORG of the program, and the symbol named `start`, `main` or `entry` is its
+/// entry point.
+///
+#[derive(Debug, Default, PartialEq)]
+pub struct Build {
+    /// The address of the first byte of the binary file
+    pub org: Option<u16>,
+    /// The address the program is run from
+    pub entry: Option<u16>,
+}
+
+impl Build {
+    /// Load what the assembler tells about the given binary file, if it left a listing or a
+    /// symbol file next to it.
+    pub fn load(bin_path: &Path) -> io::Result<Option<Build>> {
+        let mut build = Build::default();
+        let listing = bin_path.with_extension("lst");
+        if file::exists(&listing) {
+            build.org = org_of(&String::from_utf8_lossy(&file::read_content(&listing)?));
+        }
+        let sym = bin_path.with_extension("sym");
+        if file::exists(&sym) {
+            let symbols = Symbols::load(&sym)?;
+            build.entry = ENTRY_NAMES.iter().find_map(|name| symbols.address_of(name));
+        }
+        if build.org.is_none() && build.entry.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(build))
+    }
+
+    /// Returns the given binary file with the header of `BLOAD`, along with its BEGIN, END
+    /// and START addresses.
+    ///
+    /// Files assembled with their header, whose BEGIN address follows the header at the
+    /// ORG of the program, are returned as they are once their START address is checked
+    /// against the entry point. Files assembled as raw code get a header loading them at
+    /// the ORG and running them from the entry point, or from their first byte if there is
+    /// none.
+    ///
+    pub fn apply(&self, data: &[u8]) -> Result<(Vec<u8>, Addresses), String> {
+        let bytes = tape::without_id_byte(data, 0xfe);
+        let header_len = (data.len() - bytes.len() + 6) as u16;
+        let headed = policy::check_bin(bytes).is_empty() &&
+            self.org.is_none_or(|org| LittleEndian::read_u16(&bytes[0..2]) == org.wrapping_add(header_len));
+        if headed {
+            let addresses = (
+                LittleEndian::read_u16(&bytes[0..2]) as usize,
+                LittleEndian::read_u16(&bytes[2..4]) as usize,
+                LittleEndian::read_u16(&bytes[4..6]) as usize,
+            );
+            match self.entry {
+                Some(entry) if entry as usize != addresses.2 => Err(tr!(
+                    "START address 0x{} does not match the entry point 0x{} of the program",
+                    format!("{:04x}", addresses.2),
+                    format!("{:04x}", entry),
+                )),
+                _ => Ok((data.to_vec(), addresses)),
+            }
+        } else if let Some(org) = self.org {
+            let begin = org as usize;
+            let end = begin + data.len().max(1) - 1;
+            let start = self.entry.map(|e| e as usize).unwrap_or(begin);
+            if data.is_empty() || end > 0xffff {
+                return Err(tr!("the program does not fit in memory from its ORG 0x{}", format!("{:04x}", org)));
+            }
+            if start < begin || start > end {
+                return Err(tr!(
+                    "the entry point 0x{} of the program is out of its code",
+                    format!("{:04x}", start),
+                ));
+            }
+            // The ID byte tells the header apart from code starting with 0xfe
+            let mut headed = vec![0xfe, 0, 0, 0, 0, 0, 0];
+            LittleEndian::write_u16(&mut headed[1..3], begin as u16);
+            LittleEndian::write_u16(&mut headed[3..5], end as u16);
+            LittleEndian::write_u16(&mut headed[5..7], start as u16);
+            headed.extend_from_slice(data);
+            Ok((headed, (begin, end, start)))
+        } else {
+            Err(tr!("the ORG of the program is unknown, as there is no listing file"))
+        }
+    }
+}
+
+/// Returns the ORG of the program of the given listing, as the first address that emits
+/// bytes.
+///
+/// Listing lines start with the line number, the address and the bytes emitted, each as
+/// a pair of hex digits, before the source code (e.g., `  12+ 9000 21 0D 90  ld hl,msg`).
+///
+pub fn org_of(listing: &str) -> Option<u16> {
+    let is_hex = |s: &str, len: usize| s.len() == len && s.chars().all(|c| c.is_ascii_hexdigit());
+    listing.lines().find_map(|line| {
+        let words: Vec<&str> = line
+            .split_whitespace()
+            .map(|w| w.trim_end_matches(['+', '~', ':']))
+            .take_while(|w| w.chars().all(|c| c.is_ascii_hexdigit()))
+            .collect();
+        words
+            .windows(2)
+            .find(|pair| is_hex(pair[0], 4) && is_hex(pair[1], 2))
+            .and_then(|pair| u16::from_str_radix(pair[0], 16).ok())
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_find_the_org_of_listings() {
+        let listing = "\
+# file opened: main.asm
+ 1    0000              CHPUT:  equ 0x00a2
+ 2    0000                      org 0x9000
+ 3    9000 21 0D 90     start:  ld hl, message
+ 4    9003 7E                   ld a, (hl)
+";
+        assert_eq!(Some(0x9000), org_of(listing));
+        assert_eq!(Some(0xc000), org_of("1000+ C000 C9   ret\n"));
+        assert_eq!(None, org_of("  1  9000          add a, b\n"));
+    }
+
+    #[test]
+    fn should_write_headers_of_raw_programs() {
+        let build = Build { org: Some(0x9000), entry: Some(0x9001) };
+        let (data, addresses) = build.apply(&[0x00, 0xc9]).unwrap();
+        assert_eq!(vec![0xfe, 0x00, 0x90, 0x01, 0x90, 0x01, 0x90, 0x00, 0xc9], data);
+        assert_eq!((0x9000, 0x9001, 0x9001), addresses);
+
+        let build = Build { org: Some(0x9000), entry: Some(0x8000) };
+        assert!(build.apply(&[0x00, 0xc9]).is_err());
+        let build = Build { org: Some(0xffff), entry: None };
+        assert!(build.apply(&[0x00, 0xc9]).is_err());
+        let build = Build { org: None, entry: Some(0x9000) };
+        assert!(build.apply(&[0x00, 0xc9]).is_err());
+    }
+
+    #[test]
+    fn should_verify_headers_of_programs() {
+        let data = [0x00, 0x90, 0x01, 0x90, 0x00, 0x90, 0x00, 0xc9];
+        let build = Build { org: Some(0x8ffa), entry: Some(0x9000) };
+        assert_eq!(Ok((data.to_vec(), (0x9000, 0x9001, 0x9000))), build.apply(&data));
+
+        let build = Build { org: None, entry: Some(0x9001) };
+        assert!(build.apply(&data).is_err());
+    }
+}
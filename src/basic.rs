@@ -0,0 +1,530 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tokenized MSX-BASIC programs
+//!
+//! Basic files hold programs as MSX-BASIC keeps them in memory from `0x8001`: a line after
+//! another, each made of the address of the next line, the line number, the tokenized text
+//! and a zero byte, up to a line whose next address is zero. Keywords are stored as one
+//! byte, or two after `0xff` for functions, and numbers in binary after a prefix byte that
+//! tells their type.
+
+use byteorder::{ByteOrder, LittleEndian};
+
+/// The address MSX-BASIC programs are loaded at, pointed to by the line pointers
+const PROGRAM_ADDR: usize = 0x8001;
+
+/// The keywords stored as one byte, from `0x81` on
+pub const KEYWORDS: [&str; 124] = [
+    "END", "FOR", "NEXT", "DATA", "INPUT", "DIM", "READ", "LET", "GOTO", "RUN", "IF", "RESTORE",
+    "GOSUB", "RETURN", "REM", "STOP", "PRINT", "CLEAR", "LIST", "NEW", "ON", "WAIT", "DEF",
+    "POKE", "CONT", "CSAVE", "CLOAD", "OUT", "LPRINT", "LLIST", "CLS", "WIDTH", "ELSE", "TRON",
+    "TROFF", "SWAP", "ERASE", "ERROR", "RESUME", "DELETE", "AUTO", "RENUM", "DEFSTR", "DEFINT",
+    "DEFSNG", "DEFDBL", "LINE", "OPEN", "FIELD", "GET", "PUT", "CLOSE", "LOAD", "MERGE", "FILES",
+    "LSET", "RSET", "SAVE", "LFILES", "CIRCLE", "COLOR", "DRAW", "PAINT", "BEEP", "PLAY", "PSET",
+    "PRESET", "SOUND", "SCREEN", "VPOKE", "SPRITE", "VDP", "BASE", "CALL", "TIME", "KEY", "MAX",
+    "MOTOR", "BLOAD", "BSAVE", "DSKO$", "SET", "NAME", "KILL", "IPL", "COPY", "CMD", "LOCATE",
+    "TO", "THEN", "TAB(", "STEP", "USR", "FN", "SPC(", "NOT", "ERL", "ERR", "STRING$", "USING",
+    "INSTR", "'", "VARPTR", "CSRLIN", "ATTR$", "DSKI$", "OFF", "INKEY$", "POINT", ">", "=", "<",
+    "+", "-", "*", "/", "^", "AND", "OR", "XOR", "EQV", "IMP", "MOD", "\\",
+];
+
+/// The functions stored as `0xff` and one byte, from `0x81` on
+pub const FUNCTIONS: [&str; 48] = [
+    "LEFT$", "RIGHT$", "MID$", "SGN", "INT", "ABS", "SQR", "RND", "SIN", "LOG", "EXP", "COS",
+    "TAN", "ATN", "FRE", "INP", "POS", "LEN", "STR$", "VAL", "ASC", "CHR$", "PEEK", "VPEEK",
+    "SPACE$", "OCT$", "HEX$", "LPOS", "BIN$", "CINT", "CSNG", "CDBL", "FIX", "STICK", "STRIG",
+    "PDL", "PAD", "DSKF", "FPOS", "CVI", "CVS", "CVD", "EOF", "LOC", "LOF", "MKI$", "MKS$",
+    "MKD$",
+];
+
+/// The tokens of the keywords handled apart while tokenizing or detokenizing
+const REM: u8 = 0x8f;
+const DATA: u8 = 0x84;
+const PRINT: u8 = 0x91;
+const ELSE: u8 = 0xa1;
+const QUOTE: u8 = 0xe6;
+const MINUS: u8 = 0xf2;
+
+/// The tokens of the keywords followed by line numbers (GOTO, RUN, RESTORE, GOSUB, LIST,
+/// LLIST, ELSE, RESUME, DELETE, AUTO, RENUM and THEN)
+const LINE_KEYWORDS: [u8; 12] = [0x89, 0x8a, 0x8c, 0x8d, 0x93, 0x9e, 0xa1, 0xa7, 0xa8, 0xa9, 0xaa, 0xda];
+
+/// The highest line number of MSX-BASIC programs
+const MAX_LINE: u32 = 65529;
+
+/// Returns the listing of the given tokenized program, as `LIST` prints it, with a line of
+/// text ended by a line feed for each line of the program.
+///
+/// The text is made of the bytes of the MSX character set found in the program, as it is in
+/// strings and comments. Anything after the last line of the program is ignored, as well as
+/// the lines whose address of the next line is out of the program.
+pub fn detokenize(program: &[u8]) -> Vec<u8> {
+    let lines = lines_of(program);
+    let mut listing = vec![];
+    for (_, number, text) in &lines {
+        listing.extend_from_slice(number.to_string().as_bytes());
+        listing.push(b' ');
+        detokenize_line(text, &lines, &mut listing);
+        listing.push(b'\n');
+    }
+    listing
+}
+
+/// Returns the offset, the number and the tokenized text of each line of the given program.
+///
+/// Lines end where the address of the next line says, as numbers may have zero bytes.
+fn lines_of(program: &[u8]) -> Vec<(usize, u16, &[u8])> {
+    let mut lines = vec![];
+    let mut offset = 0;
+    while offset + 4 <= program.len() {
+        let next = (LittleEndian::read_u16(&program[offset..]) as usize).wrapping_sub(PROGRAM_ADDR);
+        if next <= offset + 4 || next > program.len() {
+            break;
+        }
+        let number = LittleEndian::read_u16(&program[offset + 2..]);
+        lines.push((offset, number, &program[offset + 4..next - 1]));
+        offset = next;
+    }
+    lines
+}
+
+fn detokenize_line(text: &[u8], lines: &[(usize, u16, &[u8])], listing: &mut Vec<u8>) {
+    let mut i = 0;
+    // Strings and the arguments of DATA are kept as they are
+    let (mut quoted, mut data) = (false, false);
+    while i < text.len() {
+        let byte = text[i];
+        i += 1;
+        if quoted {
+            quoted = byte != b'"';
+            listing.push(byte);
+            continue;
+        }
+        if data && byte != b':' {
+            quoted = byte == b'"';
+            listing.push(byte);
+            continue;
+        }
+        data = false;
+        match byte {
+            b'"' => {
+                quoted = true;
+                listing.push(byte);
+            }
+            // ELSE and comments given with an apostrophe are stored after a colon
+            b':' if text.get(i) == Some(&ELSE) => {}
+            b':' if text.get(i) == Some(&REM) && text.get(i + 1) == Some(&QUOTE) => i += 1,
+            REM | QUOTE => {
+                listing.extend_from_slice(KEYWORDS[(byte - 0x81) as usize].as_bytes());
+                listing.extend_from_slice(&text[i..]);
+                return;
+            }
+            DATA => {
+                data = true;
+                listing.extend_from_slice(b"DATA");
+            }
+            0x81..=0xfc => listing.extend_from_slice(KEYWORDS[(byte - 0x81) as usize].as_bytes()),
+            0xff => match text.get(i).and_then(|f| f.checked_sub(0x81)).and_then(|f| FUNCTIONS.get(f as usize)) {
+                Some(function) => {
+                    listing.extend_from_slice(function.as_bytes());
+                    i += 1;
+                }
+                None => listing.push(byte),
+            },
+            0x0b..=0x1f => match number(byte, &text[i..], lines) {
+                Some((literal, len)) => {
+                    listing.extend_from_slice(literal.as_bytes());
+                    i += len;
+                }
+                None => listing.push(byte),
+            },
+            _ => listing.push(byte),
+        }
+    }
+}
+
+/// Returns the literal of the number of the given type found at the beginning of the given
+/// text, along with the bytes it takes.
+fn number(prefix: u8, text: &[u8], lines: &[(usize, u16, &[u8])]) -> Option<(String, usize)> {
+    let word = || text.get(..2).map(LittleEndian::read_u16);
+    match prefix {
+        0x0b => word().map(|n| (format!("&O{:o}", n), 2)),
+        0x0c => word().map(|n| (format!("&H{:X}", n), 2)),
+        // Line numbers are replaced by pointers to their lines once they are run
+        0x0d => word().map(|addr| {
+            let line = lines.iter().find(|(offset, _, _)| PROGRAM_ADDR + offset == addr as usize);
+            (line.map(|(_, number, _)| number.to_string()).unwrap_or_else(|| format!("&H{:X}", addr)), 2)
+        }),
+        0x0e | 0x1c => word().map(|n| (n.to_string(), 2)),
+        0x0f => text.first().map(|n| (n.to_string(), 1)),
+        0x11..=0x1a => Some(((prefix - 0x11).to_string(), 0)),
+        0x1d => text.get(..4).map(|bytes| (float(bytes, 'E', '!'), 4)),
+        0x1f => text.get(..8).map(|bytes| (float(bytes, 'D', '#'), 8)),
+        _ => None,
+    }
+}
+
+/// Returns the literal of the given BCD floating point number, made of the exponent byte
+/// and the digits of its mantissa, with the given letter of its exponent in scientific
+/// notation and the suffix of its type.
+///
+/// Single precision numbers get their suffix when they look like an integer, and double
+/// precision ones when they have no more digits than single precision ones may have.
+fn float(bytes: &[u8], exp_letter: char, suffix: char) -> String {
+    let sign = if bytes[0] & 0x80 != 0 { "-" } else { "" };
+    let exp = (bytes[0] & 0x7f) as i32 - 0x40;
+    let digits: String = bytes[1..].iter().map(|b| format!("{:02x}", b)).collect();
+    let digits = digits.trim_end_matches('0');
+    let max_digits = (bytes.len() as i32 - 1) * 2;
+    let n = digits.len() as i32;
+    let literal = if digits.is_empty() {
+        "0".to_string()
+    } else if exp > max_digits || exp < -max_digits {
+        let exp_sign = if exp > 0 { '+' } else { '-' };
+        let fraction = if n > 1 { format!(".{}", &digits[1..]) } else { String::new() };
+        format!("{}{}{}{}{:02}", &digits[..1], fraction, exp_letter, exp_sign, (exp - 1).abs())
+    } else if exp <= 0 {
+        format!(".{}{}", "0".repeat(-exp as usize), digits)
+    } else if exp >= n {
+        format!("{}{}", digits, "0".repeat((exp - n) as usize))
+    } else {
+        format!("{}.{}", &digits[..exp as usize], &digits[exp as usize..])
+    };
+    let typed = if suffix == '!' { !literal.contains(['.', exp_letter]) } else { n <= 6 };
+    if typed { format!("{}{}{}", sign, literal, suffix) } else { format!("{}{}", sign, literal) }
+}
+
+/// Returns the tokenized program of the given listing, as MSX-BASIC stores it in memory when
+/// the lines are typed, with the addresses of the next lines as if it is loaded at
+/// `0x8001`.
+///
+/// Each line of the listing, ended by a line feed or by a carriage return and a line feed,
+/// starts with its line number. Keywords are recognized in any case, even within the names
+/// of variables as MSX-BASIC does, and the rest is turned into uppercase but for strings,
+/// comments and the arguments of `DATA`. The listing ends at the first EOF byte (`0x1a`),
+/// if any.
+pub fn tokenize(listing: &[u8]) -> Result<Vec<u8>, String> {
+    let listing = &listing[..listing.iter().position(|b| *b == 0x1a).unwrap_or(listing.len())];
+    let mut program = vec![];
+    let mut last = None;
+    for line in listing.split(|b| *b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let line = &line[line.iter().take_while(|b| **b == b' ').count()..];
+        if line.is_empty() {
+            continue;
+        }
+        let digits = line.iter().take_while(|b| b.is_ascii_digit()).count();
+        let number: u32 = String::from_utf8_lossy(&line[..digits]).parse().map_err(|_| {
+            tr!("line {} of the listing has no line number", String::from_utf8_lossy(line).into_owned())
+        })?;
+        if number > MAX_LINE {
+            return Err(tr!("invalid line number {}", number));
+        }
+        if let Some(last) = last.filter(|last| *last >= number) {
+            return Err(tr!("line {} is not after line {}", number, last));
+        }
+        last = Some(number);
+
+        let text = crunch(&line[digits..])?;
+        let next = PROGRAM_ADDR + program.len() + 4 + text.len() + 1;
+        if next > 0xffff {
+            return Err(tr!("the program does not fit in memory"));
+        }
+        program.extend_from_slice(&[next as u8, (next >> 8) as u8, number as u8, (number >> 8) as u8]);
+        program.extend_from_slice(&text);
+        program.push(0);
+    }
+    program.extend_from_slice(&[0, 0]);
+    Ok(program)
+}
+
+/// Returns the tokenized text of the given line, after its line number.
+fn crunch(line: &[u8]) -> Result<Vec<u8>, String> {
+    // Blanks after the line number are not stored, as LIST writes one
+    let mut i = line.iter().take_while(|b| **b == b' ').count();
+    let mut text = vec![];
+    // Digits in the names of variables are not numbers, and numbers after some keywords
+    // are line numbers
+    let (mut ident, mut line_numbers) = (false, false);
+    while i < line.len() {
+        let byte = line[i];
+        if byte == b'"' {
+            let len = line[i + 1..].iter().position(|b| *b == b'"').map(|n| n + 2).unwrap_or(line.len() - i);
+            text.extend_from_slice(&line[i..i + len]);
+            i += len;
+            ident = false;
+            continue;
+        }
+        if byte == b'\'' {
+            text.extend_from_slice(&[b':', REM, QUOTE]);
+            text.extend_from_slice(&line[i + 1..]);
+            break;
+        }
+        if let Some((token, len)) = keyword_at(&line[i..]).filter(|_| !(ident && byte.is_ascii_digit())) {
+            i += len;
+            ident = false;
+            line_numbers = LINE_KEYWORDS.contains(&token[0]) || (line_numbers && token == [MINUS]);
+            match token[0] {
+                REM => {
+                    text.push(REM);
+                    text.extend_from_slice(&line[i..]);
+                    break;
+                }
+                DATA => {
+                    text.push(DATA);
+                    let mut quoted = false;
+                    while i < line.len() && (quoted || line[i] != b':') {
+                        quoted ^= line[i] == b'"';
+                        text.push(line[i]);
+                        i += 1;
+                    }
+                }
+                ELSE => text.extend_from_slice(&[b':', ELSE]),
+                _ => text.extend_from_slice(&token),
+            }
+            continue;
+        }
+        if byte.is_ascii_alphabetic() || (ident && byte.is_ascii_digit()) {
+            text.push(byte.to_ascii_uppercase());
+            ident = true;
+            line_numbers = false;
+            i += 1;
+            continue;
+        }
+        ident = false;
+        let next = line.get(i + 1).map(u8::to_ascii_uppercase);
+        let literal = match byte {
+            b'&' if next == Some(b'H') || next == Some(b'O') => radix_literal(&line[i..])?,
+            b'0'..=b'9' if line_numbers => {
+                let len = line[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+                let number: u32 = String::from_utf8_lossy(&line[i..i + len]).parse().unwrap_or(u32::MAX);
+                if number > MAX_LINE {
+                    return Err(tr!("invalid line number {}", number));
+                }
+                Some((vec![0x0e, number as u8, (number >> 8) as u8], len))
+            }
+            b'0'..=b'9' => Some(number_literal(&line[i..])?),
+            b'.' if next.is_some_and(|b| b.is_ascii_digit()) => Some(number_literal(&line[i..])?),
+            _ => None,
+        };
+        match literal {
+            Some((bytes, len)) => {
+                text.extend_from_slice(&bytes);
+                i += len;
+            }
+            None => {
+                if byte != b' ' && byte != b',' {
+                    line_numbers = false;
+                }
+                text.push(if byte == b'?' { PRINT } else { byte });
+                i += 1;
+            }
+        }
+    }
+    Ok(text)
+}
+
+/// Returns the token of the longest keyword or function the given text starts with, in any
+/// case, along with the length of the keyword.
+fn keyword_at(text: &[u8]) -> Option<(Vec<u8>, usize)> {
+    let starts_with = |keyword: &str| {
+        text.len() >= keyword.len() && text[..keyword.len()].eq_ignore_ascii_case(keyword.as_bytes())
+    };
+    let keywords = KEYWORDS.iter().enumerate().map(|(i, k)| (vec![0x81 + i as u8], *k));
+    let functions = FUNCTIONS.iter().enumerate().map(|(i, f)| (vec![0xff, 0x81 + i as u8], *f));
+    keywords
+        .chain(functions)
+        .filter(|(_, keyword)| *keyword != "'" && starts_with(keyword))
+        .max_by_key(|(_, keyword)| keyword.len())
+        .map(|(token, keyword)| (token, keyword.len()))
+}
+
+/// Returns the tokenized hexadecimal (`&H`) or octal (`&O`) literal the given text starts
+/// with, along with its length, or `None` if it has no digits.
+fn radix_literal(text: &[u8]) -> Result<Option<(Vec<u8>, usize)>, String> {
+    let (prefix, radix) = if text[1].eq_ignore_ascii_case(&b'H') { (0x0c, 16) } else { (0x0b, 8) };
+    let len = text[2..].iter().take_while(|b| (**b as char).is_digit(radix)).count();
+    if len == 0 {
+        return Ok(None);
+    }
+    let digits = String::from_utf8_lossy(&text[2..2 + len]);
+    let value = u16::from_str_radix(&digits, radix).map_err(|_| tr!("number {} out of range", digits.into_owned()))?;
+    Ok(Some((vec![prefix, value as u8, (value >> 8) as u8], 2 + len)))
+}
+
+/// Returns the tokenized decimal literal the given text starts with, along with its length.
+///
+/// Integers up to 32767 are stored as such, and the rest of numbers in BCD as single
+/// precision numbers, or as double precision ones if they have more than six digits or
+/// their type is given by a `#` suffix or a `D` exponent.
+fn number_literal(text: &[u8]) -> Result<(Vec<u8>, usize), String> {
+    let digits_at = |i: usize| text[i.min(text.len())..].iter().take_while(|b| b.is_ascii_digit()).count();
+    let int_len = digits_at(0);
+    let mut len = int_len;
+    let mut frac_len = 0;
+    if text.get(len) == Some(&b'.') {
+        frac_len = digits_at(len + 1);
+        len += 1 + frac_len;
+    }
+    // An exponent is only taken as such if it has digits, as in 1E+5
+    let mut exp: i32 = 0;
+    let mut exp_letter = None;
+    if let Some(letter) = text.get(len).map(u8::to_ascii_uppercase).filter(|b| *b == b'E' || *b == b'D') {
+        let sign_len = matches!(text.get(len + 1), Some(b'+') | Some(b'-')) as usize;
+        let exp_digits = digits_at(len + 1 + sign_len);
+        if exp_digits > 0 {
+            let digits = String::from_utf8_lossy(&text[len + 1 + sign_len..len + 1 + sign_len + exp_digits]);
+            exp = digits.parse().unwrap_or(i32::MAX / 2);
+            if text[len + 1] == b'-' {
+                exp = -exp;
+            }
+            exp_letter = Some(letter);
+            len += 1 + sign_len + exp_digits;
+        }
+    }
+    let suffix = text.get(len).copied().filter(|b| matches!(b, b'!' | b'#' | b'%'));
+    if suffix.is_some() {
+        len += 1;
+    }
+
+    let int_digits = &text[..int_len];
+    let frac_digits = &text[int_len + 1.min(len - int_len)..int_len + 1.min(len - int_len) + frac_len];
+    let literal = String::from_utf8_lossy(&text[..len]).into_owned();
+    if frac_len == 0 && exp_letter.is_none() && suffix != Some(b'!') && suffix != Some(b'#') {
+        let value: u32 = String::from_utf8_lossy(int_digits).parse().unwrap_or(u32::MAX);
+        match value {
+            0..=9 => return Ok((vec![0x11 + value as u8], len)),
+            10..=255 => return Ok((vec![0x0f, value as u8], len)),
+            256..=32767 => return Ok((vec![0x1c, value as u8, (value >> 8) as u8], len)),
+            _ if suffix == Some(b'%') => return Err(tr!("number {} out of range", literal)),
+            _ => {}
+        }
+    }
+
+    // The mantissa is made of the significant digits, with the decimal point before them
+    let mut digits: Vec<u8> = int_digits.iter().chain(frac_digits).map(|d| d - b'0').collect();
+    let mut exp = exp.saturating_add(int_len as i32);
+    while digits.first() == Some(&0) {
+        digits.remove(0);
+        exp -= 1;
+    }
+    let double = suffix == Some(b'#') || exp_letter == Some(b'D')
+        || (suffix != Some(b'!') && exp_letter.is_none() && digits.len() > 6);
+    let size = if double { 14 } else { 6 };
+    if digits.len() > size {
+        let round_up = digits[size] >= 5;
+        digits.truncate(size);
+        if round_up {
+            let mut i = size;
+            while i > 0 && digits[i - 1] == 9 {
+                digits[i - 1] = 0;
+                i -= 1;
+            }
+            if i == 0 {
+                digits.insert(0, 1);
+                digits.truncate(size);
+                exp += 1;
+            } else {
+                digits[i - 1] += 1;
+            }
+        }
+    }
+    digits.resize(size, 0);
+    let exp_byte = if digits.iter().all(|d| *d == 0) {
+        0
+    } else if (-0x3f..=0x3f).contains(&exp) {
+        (0x40 + exp) as u8
+    } else {
+        return Err(tr!("number {} out of range", literal));
+    };
+    let mut bytes = vec![if double { 0x1f } else { 0x1d }, exp_byte];
+    bytes.extend(digits.chunks(2).map(|pair| (pair[0] << 4) | pair[1]));
+    Ok((bytes, len))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /// Returns the given lines as a program loaded at `PROGRAM_ADDR`
+    fn program(lines: &[(u16, &[u8])]) -> Vec<u8> {
+        let mut program = vec![];
+        for (number, text) in lines {
+            let next = PROGRAM_ADDR + program.len() + 4 + text.len() + 1;
+            program.extend_from_slice(&[next as u8, (next >> 8) as u8, *number as u8, (*number >> 8) as u8]);
+            program.extend_from_slice(text);
+            program.push(0);
+        }
+        program.extend_from_slice(&[0, 0]);
+        program
+    }
+
+    #[test]
+    fn should_detokenize_programs() {
+        let program = program(&[
+            (10, b"\x91 \"HI:\":\x89 \x0e\x0a\x00"),
+            (20, b"A=\xff\x92(A$)\xf2\x12:\x8b A\xee\x0f\x14 \xda \x91 \"OK\" :\xa1 \x8d \x0e\x1e\x00"),
+            (30, b"\x84 1,\"A:B\",2:\x91 :\x8f\xe6 PRINT IT"),
+        ]);
+        assert_eq!(
+            "10 PRINT \"HI:\":GOTO 10\n\
+             20 A=LEN(A$)-1:IF A>20 THEN PRINT \"OK\" ELSE GOSUB 30\n\
+             30 DATA 1,\"A:B\",2:PRINT ' PRINT IT\n",
+            String::from_utf8_lossy(&detokenize(&program)));
+    }
+
+    #[test]
+    fn should_detokenize_numbers() {
+        let program = program(&[
+            (10, b"\x91 \x0c\xff\x00,\x0b\x08\x00,\x1c\x00\x01"),
+            (20, b"\x91 \x1d\x41\x15\x00\x00,\x1d\x45\x40\x00\x00,\x1d\x40\x50\x00\x00"),
+            (30, b"\x91 \x1f\x41\x12\x34\x56\x78\x90\x00\x00,\x1f\x41\x15\x00\x00\x00\x00\x00\x00"),
+            (40, b"\x89 \x0d\x01\x80"),
+        ]);
+        assert_eq!(
+            "10 PRINT &HFF,&O10,256\n\
+             20 PRINT 1.5,40000!,.5\n\
+             30 PRINT 1.23456789,1.5#\n\
+             40 GOTO 10\n",
+            String::from_utf8_lossy(&detokenize(&program)));
+    }
+
+    #[test]
+    fn should_tokenize_programs() {
+        let listing = b"10 print \"HI:\":goto 10\r\n\
+                        20 A=LEN(A$)-1:IF A>20 THEN ? \"OK\" ELSE GOSUB 30\r\n\
+                        \r\n\
+                        30 DATA 1,\"A:B\",2:PRINT ' print it\r\n\x1a";
+        let expected = program(&[
+            (10, b"\x91 \"HI:\":\x89 \x0e\x0a\x00"),
+            (20, b"A\xef\xff\x92(A$)\xf2\x12:\x8b A\xee\x0f\x14 \xda \x91 \"OK\" :\xa1 \x8d \x0e\x1e\x00"),
+            (30, b"\x84 1,\"A:B\",2:\x91 :\x8f\xe6 print it"),
+        ]);
+        assert_eq!(Ok(expected), tokenize(listing));
+        assert!(tokenize(b"20 PRINT\n10 PRINT\n").is_err());
+        assert!(tokenize(b"PRINT\n").is_err());
+    }
+
+    #[test]
+    fn should_tokenize_numbers() {
+        let listing = b"10 PRINT &HFF,&O10,256,A1\n\
+                        20 PRINT 1.5,40000!,.5\n\
+                        30 PRINT 1.23456789,1.5#,123456789012345678\n";
+        let expected = program(&[
+            (10, b"\x91 \x0c\xff\x00,\x0b\x08\x00,\x1c\x00\x01,A1"),
+            (20, b"\x91 \x1d\x41\x15\x00\x00,\x1d\x45\x40\x00\x00,\x1d\x40\x50\x00\x00"),
+            (30, b"\x91 \x1f\x41\x12\x34\x56\x78\x90\x00\x00,\x1f\x41\x15\x00\x00\x00\x00\x00\x00,\
+                   \x1f\x52\x12\x34\x56\x78\x90\x12\x35"),
+        ]);
+        assert_eq!(Ok(expected), tokenize(listing));
+        let listing = "10 PRINT &HFF,&O10,256\n20 PRINT 1.5,40000!,.5,1E+20\n30 PRINT 1.23456789,1.5#\n";
+        assert_eq!(listing, String::from_utf8_lossy(&detokenize(&tokenize(listing.as_bytes()).unwrap())));
+    }
+}
@@ -0,0 +1,74 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Returns the default number of parallel jobs for batch operations.
+///
+/// This is the number of CPUs available to the process, or 1 if it cannot be determined.
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Apply a function to every item using a bounded pool of threads.
+///
+/// This function runs `f` over all `items` using up to `jobs` threads. The results are
+/// returned in the same order as the items, regardless of the order in which they
+/// were processed.
+///
+pub fn run<T, R, F>(items: &[T], jobs: usize, f: F) -> Vec<R>
+where T: Sync, R: Send, F: Fn(&T) -> R + Sync {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    let workers = jobs.max(1).min(items.len());
+    thread::scope(|s| {
+        for _ in 0..workers {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                if i >= items.len() {
+                    break;
+                }
+                let result = f(&items[i]);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.expect("every item is processed"))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_run_preserving_order() {
+        let items: Vec<usize> = (0..100).collect();
+        let results = run(&items, 8, |i| i * 2);
+        assert_eq!((0..100).map(|i| i * 2).collect::<Vec<_>>(), results);
+    }
+
+    #[test]
+    fn should_run_with_no_items() {
+        let items: Vec<usize> = vec![];
+        assert!(run(&items, 4, |i| *i).is_empty());
+    }
+
+    #[test]
+    fn should_run_with_zero_jobs() {
+        let items = vec![1, 2, 3];
+        assert_eq!(vec![2, 3, 4], run(&items, 0, |i| i + 1));
+    }
+}
@@ -0,0 +1,1764 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::env::args;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+use docopt::Docopt;
+
+use mcp::charset::Charset;
+use mcp::check::Severity;
+use mcp::filter::{Filter, Rule};
+use mcp::gap::Gap;
+use mcp::i18n;
+use mcp::import::{Channel, DecoderKind};
+use mcp::policy::Policy;
+use mcp::template::{Template, FILE_FIELDS};
+use mcp::input::Format;
+use mcp::wav::Preset;
+
+pub static USAGE: &str = "
+Usage: mcp list [--input-format=<format>] [--header-id=<id>]... [--sync=<bytes>]... [--annotate] [--only=<name>] [--bytes | --verbose | --format=<format> | --json | --csv] [--times [--bauds=<bauds>]] [--preview-png=<png-file>] <cas-file>
+       mcp add [--output=<out-file>] [--backup [--suffix=<suffix>]] [--sync=<bytes>]... [--validate=<rule>]... [--headerless] [--block-size=<n>] [--numbered] [--tokenize] [--crlf] [--charset=<charset>] <cas-file> <file>...
+       mcp extract [--input-format=<format>] [--header-id=<id>]... [--sync=<bytes>]... [--jobs=<n>] [--headerless] [--numbered] [--type=<type>]... [--output-dir=<dir>] [--charset=<charset>] <cas-file>
+       mcp extract [--input-format=<format>] [--header-id=<id>]... [--sync=<bytes>]... [--charset=<charset>] --stdout <cas-file> <name>
+       mcp export [--input-format=<format>] [--preset=<preset>] [--gap=<gap>]... [--cache=<dir>] [--preview=<png-file> [--preview-block=<n>]] <cas-file> <wav-file>
+       mcp verify --dat=<dat-file> <cas-file>
+       mcp compare <cas-file> <other-file>
+       mcp export-all [--jobs=<n>] <cas-dir> <wav-dir>
+       mcp set list <set-file>
+       mcp set export [--preset=<preset>] <set-file> <wav-dir>
+       mcp import [--report=<json-file>] [--channel=<channel>] [--decoder=<decoder>] <wav-file> <cas-file>
+       mcp import-all [--jobs=<n>] [--channel=<channel>] [--decoder=<decoder>] <wav-dir> <cas-dir>
+       mcp roundtrip [--preset=<preset>] [--gap=<gap>]... [--decoder=<decoder>] <cas-file> <wav-file>
+       mcp meta show <cas-file>
+       mcp meta get <cas-file> <key>
+       mcp meta set <cas-file> <key> <value>
+       mcp note get <cas-file> <name>
+       mcp note set <cas-file> <name> <note>
+       mcp dedup-library [--jobs=<n>] [--link | --remove] [--yes] <cas-dir>
+       mcp check [--report=<json-file>] [--fail-on=<severity>] <cas-file>
+       mcp map [--width=<n>] [--input-format=<format>] <cas-file>
+       mcp analyze [--width=<n>] [--input-format=<format>] [--sym=<sym-file>] <cas-file>
+       mcp disasm [--input-format=<format>] [--sym=<sym-file>] <cas-file> <name>
+       mcp dump [--input-format=<format>] (--block=<n> | --file=<name>) <cas-file>
+       mcp cat [--input-format=<format>] <cas-file> <name>
+       mcp filter [--keep=<rule>]... [--drop=<rule>]... [--drop-custom] <cas-file> <out-file>
+       mcp remove [--output=<out-file>] [--yes] <cas-file> <file-name>...
+       mcp rename [--output=<out-file>] <cas-file> <old-name> <new-name>
+       mcp rename-bulk [--output=<out-file>] <cas-file> <rules-file>
+       mcp undo [--suffix=<suffix>] [--yes] <cas-file>
+       mcp repair [--trim] [--normalize] [--join=<blocks>] [--recover-bin] [--output=<out-file>] [--sync=<bytes>]... [--yes] <cas-file>
+       mcp serve [--port=<port>] <cas-dir>
+       mcp add-loader [--name=<name>] [--output=<out-file>] <cas-file>
+       mcp add-verifier [--output=<out-file>] <cas-file>
+       mcp inlay [--output=<out-file>] <cas-file>
+       mcp embed [--wav [--preset=<preset>]] [--uri | --wrap=<n>] [--output=<out-file>] <cas-file>
+       mcp manifest <cas-file>
+       mcp optimize [--join-custom] [--output=<out-file>] [--yes] <cas-file>
+       mcp defrag [--output=<out-file>] [--yes] <cas-file>
+       mcp init <project-dir>
+       mcp gen-docs (man | markdown)
+       mcp --rpc
+       mcp --help
+       mcp --version [--json]
+
+Options:
+    -h, --help                  Print this message
+    -v, --version               Print the mcp version
+    --lang=<lang>               Language of the messages (en, es or ja), given
+                                before the command. Defaults to the language of
+                                the MCP_LANG variable or the locale.
+    --annotate                  Annotate binary files whose START address is
+                                outside their BEGIN-END range
+    --only=<name>               List only the files with the given name, as
+                                stored in the tape or as extracted
+    --bytes                     List the exact size of the files in bytes,
+                                instead of in human units
+    --verbose                   List the offset of each file in the CAS file, the
+                                blocks it spans and its size with its headers
+    --format=<format>           Print each listed file with the given format, with
+                                placeholders as {name}: type, name, bytes, size,
+                                time, begin, end, start, crc32 and sha1
+    --times                     Print whether the listed files fit in a side of a
+                                C60 tape
+    --bauds=<bauds>             Speed the load times are computed at
+                                [default: 1200]
+    --preview-png=<png-file>    Render the SCREEN 2 images found in custom files
+                                into PNG images, named after the given file and
+                                the position of each file (e.g. screen-3.png)
+    -o, --output=<out-file>     Write the resulting tape into the given file,
+                                leaving the original CAS file untouched
+    --backup                    Copy the tape into a backup file before it is
+                                modified
+    --suffix=<suffix>           Suffix appended to the tape file name to name its
+                                backup [default: .bak]
+    --validate=<rule>           Validation of the added files: off, warn or strict
+                                for every check, or <check>=<level> for one of
+                                them (bin-header, basic, name or alignment). It
+                                may be given several times.
+    --headerless                Add every file as a raw data block, with no file
+                                header, or extract every block of the tape as it
+                                is, for loaders that read header-less streams
+    --block-size=<n>            Split the custom files into consecutive blocks of
+                                the given size, a multiple of 8
+    --numbered                  Prefix the extracted files with their position in
+                                the tape (e.g., 01_LOADER.bin), or drop such
+                                prefixes from the names of the added files
+    --tokenize                  Tokenize the Basic files given as plain-text
+                                listings before adding them
+    --crlf                      End the lines of the added ASCII files with a
+                                carriage return and a line feed, as MSX-BASIC
+                                expects, rather than a bare line feed
+    --type=<type>               Extract only the files of the given type (bin,
+                                bas, asc or custom). It may be given several
+                                times.
+    --output-dir=<dir>          Extract the files into the given directory,
+                                created if needed, instead of the current one.
+                                Given to extract, -o stands for this option.
+    --stdout                    Write the file with the given name into the
+                                standard output, and the messages into the
+                                standard error
+    --charset=<charset>         Character set of the ASCII files, converted from
+                                UTF-8 when added and into UTF-8 when extracted:
+                                msx-int (international machines), msx-jp
+                                (Japanese machines) or raw (no conversion)
+                                [default: raw]
+    --input-format=<format>     Format of the input tape (cas, tsx, csw or wav).
+                                Detected from its content by default.
+    --header-id=<id>            Consider blocks made of ten times the given ID
+                                byte (in hex, as C5) as file headers, besides the
+                                standard ones. It may be given several times.
+    --sync=<bytes>              Accept the given 8-byte sequence (in hex, as
+                                1FA6DEBACC137D75) as block sync besides the
+                                standard one. It may be given several times.
+    --preset=<preset>           Settings of the exported WAV file: default (sine
+                                wave for sound cards) or tzxduino (square wave
+                                for Arduino-based players) [default: default]
+    --gap=<gap>                 Add an extra silence before a block, given as
+                                <block>:<millis> (e.g. 3:1500), or before the
+                                files of a type, given as <type>:<millis> (e.g.
+                                custom:1500). With = instead of :, the silence
+                                replaces the one before the block (e.g.
+                                custom=300). It may be given several times.
+    --cache=<dir>               Directory where encoded blocks are cached, so only
+                                the blocks that changed are encoded again
+    --preview=<png-file>        Render the exported signal into the given PNG
+                                image
+    --preview-block=<n>         Render only a close-up of the given block (the
+                                end of its header and the beginning of its data)
+    --dat=<dat-file>            DAT (Logiqx XML) or CSV file with the hashes of
+                                known-good dumps to verify against
+    -j, --jobs=<n>              Number of files processed in parallel by batch
+                                commands and extraction. Defaults to the number
+                                of CPUs.
+    --channel=<channel>         Channel of stereo recordings to decode (left,
+                                right or mix). Defaults to the channel with the
+                                strongest signal.
+    --decoder=<decoder>         Decoding algorithm of recordings: zero-crossing
+                                (copes with speed changes) or fsk (copes with
+                                noise) [default: zero-crossing]
+    --link                      Replace duplicated tapes by hard links
+    --remove                    Remove duplicated tapes
+    -y, --yes                   Do not ask for confirmation before removing or
+                                overwriting tapes
+    --report=<json-file>        Write the report of the check or the import in
+                                JSON format into the given file
+    --fail-on=<severity>        Fail if any issue of the given severity or worse
+                                is found (info, warning or error)
+                                [default: error]
+    --width=<n>                 Width in characters of the bars drawn by map and
+                                analyze [default: 64]
+    --sym=<sym-file>            Symbol file of the program (as written by sjasm,
+                                pasmo or tniasm), to name the addresses shown
+    --block=<n>                 Dump the data of the given block, counted from 0,
+                                without its sync sequence
+    --file=<name>               Dump the file with the given name, as stored in
+                                the tape or as extracted
+    --trim                      Remove the garbage found after the last file of
+                                the tape
+    --normalize                 Turn the tape into its canonical form: standard
+                                sync sequences, 16-byte file headers and
+                                aligned blocks with deterministic padding
+    --join=<blocks>             Join the custom blocks in the given range, given
+                                as <first>..<last> (e.g. 3..7), into one block
+    --recover-bin               Replace the implausible addresses of binary files
+                                by the ones inferred from their data
+    --join-custom               Join every run of consecutive custom blocks into
+                                one block, for loaders that read them as a
+                                single stream
+    --keep=<rule>               Keep only the files selected by the given rule: a
+                                name pattern with * and ? (e.g. STAGE*), a type
+                                (e.g. type:bin) or a range of positions counted
+                                from 0 (e.g. 2..5). It may be given several times.
+    --drop=<rule>               Drop the files selected by the given rule, given
+                                as for --keep. It may be given several times.
+    --drop-custom               Drop the custom files, as --drop type:custom
+    --wav                       Embed the WAV file exported from the tape, instead
+                                of the tape itself
+    --uri                       Embed the file as a data: URI, ready to be given
+                                as the URL of the tape to web launchers
+    --wrap=<n>                  Break the embedded base64 text into lines of the
+                                given number of characters
+    --port=<port>               Local port where the HTTP API is served
+                                [default: 8080]
+    --name=<name>               Name of the BASIC loader added to the tape
+                                [default: LOADER]
+    --rpc                       Serve JSON-RPC requests, one per line, from the
+                                standard input to the standard output
+    --json                      Print the listed files in JSON, or the version, the
+                                git commit, the enabled features and the
+                                supported formats
+    --csv                       Print the listed files in CSV, with a header row
+";
+
+/// A command introduced through the command line interface
+///
+/// An enumeration of the commands accepted by `mcp`.
+///
+/// * `Version(json: bool)`, prints the `mcp` version, optionally with its build information in
+///   JSON format
+/// * `List(path: PathBuf, format: Option<Format>, header_ids: Vec<u8>, syncs: Vec<[u8; 8]>,
+///   annotate: bool, only: Option<String>, style: Style, times: Option<u32>, screens: Option<PathBuf>)`,
+///   lists the contents of the given CAS file in the given style, optionally only the files
+///   with the given name, optionally with their total load time at the given bauds, and
+///   optionally rendering the SCREEN 2 images found into PNG files named after the given one
+/// * `Add(path: PathBuf, files: Vec<PathBuf>, output: Option<PathBuf>, backup: Option<String>,
+///   syncs: Vec<[u8; 8]>, policy: Policy, layout: Layout)`,
+///   adds files to the given CAS file validating them with the given policy and laying them
+///   out in blocks as given, optionally writing the result into another file and backing up the
+///   file to be modified using the given suffix
+/// * `Extract(path: PathBuf, format: Option<Format>, header_ids: Vec<u8>, syncs: Vec<[u8; 8]>,
+///   jobs: Option<usize>, extraction: Extraction)`,
+///   extract the contents of the given CAS file, writing up to `jobs` files in parallel as
+///   given
+/// * `Export(path: PathBuf, format: Option<Format>, output: PathBuf, preview: Option<Preview>,
+///   cache: Option<PathBuf>, preset: Preset, gaps: Vec<Gap>)`, export the given CAS file into
+///   given output WAV file using the settings of the given preset and adding the given gaps,
+///   optionally rendering the signal into an image and caching the encoded blocks in a directory
+/// * `Verify(path: PathBuf, dat: PathBuf)`, verify the given CAS file against the given DAT file
+/// * `Compare(path: PathBuf, other: PathBuf)`, compare the blocks of the given tapes, in any
+///   input format, ignoring their timing and the padding of their files
+/// * `ExportAll(dir: PathBuf, output: PathBuf, jobs: Option<usize>)`, export all CAS files in
+///   the given directory into WAV files in the output directory
+/// * `SetList(path: PathBuf)`, list the files of each side of the given tape set, along with
+///   the totals of the set and the files found in more than one side
+/// * `SetExport(path: PathBuf, output: PathBuf, preset: Preset)`, export each side of the given
+///   tape set into a WAV file named after its label in the output directory
+/// * `Import(input: PathBuf, path: PathBuf, report: Option<PathBuf>, channel: Option<Channel>,
+///   decoder: DecoderKind)`, decode the given WAV file into the given CAS file using the given
+///   decoder, optionally writing the decoding report into a JSON file
+/// * `ImportAll(dir: PathBuf, output: PathBuf, jobs: Option<usize>, channel: Option<Channel>,
+///   decoder: DecoderKind)`, decode all WAV files in the given directory into CAS files in the
+///   output directory
+/// * `Roundtrip(path: PathBuf, output: PathBuf, preset: Preset, gaps: Vec<Gap>,
+///   decoder: DecoderKind)`, export the given CAS file into the given WAV file as `Export` does,
+///   and check that decoding it with the given decoder gives back the blocks of the tape
+/// * `MetaShow(path: PathBuf)`, show the metadata of the given CAS file
+/// * `MetaGet(path: PathBuf, key: String)`, print a metadata field of the given CAS file
+/// * `MetaSet(path: PathBuf, key: String, value: String)`, set a metadata field of the given
+///   CAS file
+/// * `NoteGet(path: PathBuf, name: String)`, print the note of a file of the given CAS file
+/// * `NoteSet(path: PathBuf, name: String, note: String)`, set the note of a file of the given
+///   CAS file
+/// * `DedupLibrary(dir: PathBuf, jobs: Option<usize>, action: Duplicates, yes: bool)`, find
+///   duplicated tapes in the given directory, and optionally get rid of them
+/// * `Check(path: PathBuf, report: Option<PathBuf>, fail_on: Severity)`, check the health of the
+///   given CAS file
+/// * `Undo(path: PathBuf, suffix: String, yes: bool)`, restore the given CAS file from its backup
+/// * `Map(path: PathBuf, format: Option<Format>, width: usize)`, draw where each file of the given CAS file sits in
+///   the tape and in the audio timeline
+/// * `Analyze(path: PathBuf, format: Option<Format>, width: usize, sym: Option<PathBuf>)`, report
+///   the memory taken by the binary files of the given CAS file, and how they overlap, as to
+///   understand chained loaders, optionally naming their addresses with the symbols of a file
+/// * `Disasm(path: PathBuf, format: Option<Format>, name: String, sym: Option<PathBuf>)`, print
+///   the disassembly of the binary file with the given name, from its START address on,
+///   optionally naming its addresses with the symbols of the given file
+/// * `Dump(path: PathBuf, format: Option<Format>, target: DumpTarget)`, print the hex+ASCII dump
+///   of the given block or file of the given CAS file
+/// * `Cat(path: PathBuf, format: Option<Format>, name: String)`, print the ASCII or Basic file
+///   with the given name of the given CAS file as text, listing Basic programs
+/// * `Filter(path: PathBuf, output: PathBuf, filter: Filter)`, write the files of the given CAS
+///   file kept by the given filter into the given output file, as they are
+/// * `Remove(path: PathBuf, names: Vec<String>, output: Option<PathBuf>, yes: bool)`, remove
+///   the files with the given names from the given CAS file, optionally writing the result
+///   into another file
+/// * `Rename(path: PathBuf, name: String, new_name: String, output: Option<PathBuf>)`, rename
+///   the file with the given name in the given CAS file, optionally writing the result into
+///   another file
+/// * `RenameBulk(path: PathBuf, rules: PathBuf, output: Option<PathBuf>)`, rename the files of
+///   the given CAS file with the rules of the given file, optionally writing the result into
+///   another file
+/// * `Repair(path: PathBuf, fixes: Fixes, output: Option<PathBuf>, syncs: Vec<[u8; 8]>,
+///   yes: bool)`, rewrite the given CAS file keeping only its blocks, and optionally applying
+///   the given fixes to it
+/// * `Serve(dir: PathBuf, port: u16)`, serve the CAS files of the given directory through an
+///   HTTP API on the given local port
+/// * `AddLoader(path: PathBuf, name: String, output: Option<PathBuf>)`, add a BASIC program
+///   loading the binary files of the given CAS file at its front, optionally writing the
+///   result into another file
+/// * `AddVerifier(path: PathBuf, output: Option<PathBuf>)`, add a binary file at the end of the
+///   given CAS file that checks the memory of its binary files once loaded, optionally writing
+///   the result into another file
+/// * `Inlay(path: PathBuf, output: Option<PathBuf>)`, print the content listing of the given
+///   CAS file for the J-card of a cassette, or write it into a text or PDF file
+/// * `Embed(path: PathBuf, wav: Option<Preset>, uri: bool, wrap: Option<usize>,
+///   output: Option<PathBuf>)`, print the given CAS file, or the WAV file exported from it with
+///   the given preset, in base64 or as a data URI, optionally writing it into a file
+/// * `Manifest(path: PathBuf)`, print the manifest that reproduces the given CAS file from its
+///   extracted files
+/// * `Optimize(path: PathBuf, join_custom: bool, output: Option<PathBuf>, yes: bool)`, repack
+///   the given CAS file to waste less bytes and audio time, trimming the binary files past their
+///   END address and optionally joining the runs of custom blocks
+/// * `Defrag(path: PathBuf, output: Option<PathBuf>, yes: bool)`, rewrite the given CAS file
+///   with every block starting at an 8-byte boundary and without the bytes out of its files,
+///   optionally writing the result into another file
+/// * `Init(dir: PathBuf)`, create a new project in the given directory, with the sources of
+///   a program and a makefile building it into a tape and exporting it into audio files
+/// * `GenDocs(format: DocFormat)`, print the manual of `mcp` in the given format
+/// * `Rpc`, serve JSON-RPC requests from the standard input
+///
+/// The `yes` argument of the commands that remove or overwrite tapes tells not to ask the
+/// user for confirmation.
+///
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Version(bool),
+    List(PathBuf, Option<Format>, Vec<u8>, Vec<[u8; 8]>, bool, Option<String>, Style, Option<u32>, Option<PathBuf>),
+    Add(PathBuf, Vec<PathBuf>, Option<PathBuf>, Option<String>, Vec<[u8; 8]>, Policy, Layout),
+    Extract(PathBuf, Option<Format>, Vec<u8>, Vec<[u8; 8]>, Option<usize>, Extraction),
+    Export(PathBuf, Option<Format>, PathBuf, Option<Preview>, Option<PathBuf>, Preset, Vec<Gap>),
+    Verify(PathBuf, PathBuf),
+    Compare(PathBuf, PathBuf),
+    ExportAll(PathBuf, PathBuf, Option<usize>),
+    SetList(PathBuf),
+    SetExport(PathBuf, PathBuf, Preset),
+    Import(PathBuf, PathBuf, Option<PathBuf>, Option<Channel>, DecoderKind),
+    ImportAll(PathBuf, PathBuf, Option<usize>, Option<Channel>, DecoderKind),
+    Roundtrip(PathBuf, PathBuf, Preset, Vec<Gap>, DecoderKind),
+    MetaShow(PathBuf),
+    MetaGet(PathBuf, String),
+    MetaSet(PathBuf, String, String),
+    NoteGet(PathBuf, String),
+    NoteSet(PathBuf, String, String),
+    DedupLibrary(PathBuf, Option<usize>, Duplicates, bool),
+    Check(PathBuf, Option<PathBuf>, Severity),
+    Map(PathBuf, Option<Format>, usize),
+    Undo(PathBuf, String, bool),
+    Analyze(PathBuf, Option<Format>, usize, Option<PathBuf>),
+    Disasm(PathBuf, Option<Format>, String, Option<PathBuf>),
+    Dump(PathBuf, Option<Format>, DumpTarget),
+    Cat(PathBuf, Option<Format>, String),
+    Filter(PathBuf, PathBuf, Filter),
+    Remove(PathBuf, Vec<String>, Option<PathBuf>, bool),
+    Rename(PathBuf, String, String, Option<PathBuf>),
+    RenameBulk(PathBuf, PathBuf, Option<PathBuf>),
+    Repair(PathBuf, Fixes, Option<PathBuf>, Vec<[u8; 8]>, bool),
+    Serve(PathBuf, u16),
+    AddLoader(PathBuf, String, Option<PathBuf>),
+    AddVerifier(PathBuf, Option<PathBuf>),
+    Inlay(PathBuf, Option<PathBuf>),
+    Embed(PathBuf, Option<Preset>, bool, Option<usize>, Option<PathBuf>),
+    Manifest(PathBuf),
+    Optimize(PathBuf, bool, Option<PathBuf>, bool),
+    Defrag(PathBuf, Option<PathBuf>, bool),
+    Init(PathBuf),
+    GenDocs(DocFormat),
+    Rpc,
+}
+
+/// Where and what to render when previewing an exported signal
+#[derive(Debug, PartialEq)]
+pub struct Preview {
+    pub path: PathBuf,
+    pub block: Option<usize>,
+}
+
+/// The fixes applied by `repair` to a tape, besides keeping only its blocks
+#[derive(Debug, Default, PartialEq)]
+pub struct Fixes {
+    /// Remove the trailing garbage of the tape
+    pub trim: bool,
+    /// Turn the tape into its canonical form
+    pub normalize: bool,
+    /// Join the custom blocks in this range into one block
+    pub join: Option<RangeInclusive<usize>>,
+    /// Replace the implausible addresses of binary files by the inferred ones
+    pub recover_bin: bool,
+}
+
+/// How the added files are laid out in blocks
+#[derive(Debug, Default, PartialEq)]
+pub struct Layout {
+    /// Add every file as a custom file, with no header, whatever its extension
+    pub headerless: bool,
+    /// Split the custom files into blocks of this size
+    pub block_size: Option<usize>,
+    /// Drop the position prefixes of extracted files (e.g., `01_`) from the names of the files
+    pub numbered: bool,
+    /// Tokenize the Basic files given as plain-text listings
+    pub tokenize: bool,
+    /// End the lines of the ASCII files with a carriage return and a line feed
+    pub crlf: bool,
+    /// Convert the ASCII files from UTF-8 into this character set
+    pub charset: Charset,
+}
+
+/// The file types `extract` may be restricted to, named after the extension of their files
+pub const TYPES: &[&str] = &["bin", "bas", "asc", "custom"];
+
+/// Which files are extracted and how
+#[derive(Debug, Default, PartialEq)]
+pub struct Extraction {
+    /// Extract every block as a custom file, ignoring any header
+    pub headerless: bool,
+    /// Prefix the extracted files with their position in the tape (e.g., `01_`)
+    pub numbered: bool,
+    /// Extract only the files of these types (see `TYPES`), or every file if empty
+    pub types: Vec<String>,
+    /// Write the files into this directory instead of the current one
+    pub output_dir: Option<PathBuf>,
+    /// Write only the file with this name, into the standard output
+    pub stdout: Option<String>,
+    /// Convert the ASCII files from this character set into UTF-8
+    pub charset: Charset,
+}
+
+/// How to print each listed file
+#[derive(Debug, PartialEq)]
+pub enum Style {
+    /// The size of the file in human units and its load time
+    Human,
+    /// The exact size of the file in bytes and its load time
+    Bytes,
+    /// The size of the file in human units and its load time, followed by where it is in the
+    /// CAS file: its offset, the blocks it spans and its size with its headers
+    Verbose,
+    /// The fields of the file given by a template (see `template::FILE_FIELDS`)
+    Format(Template),
+    /// An array of objects with the fields of the files, in JSON
+    Json,
+    /// A row with the fields of each file, in CSV (see `CSV_HEADER`)
+    Csv,
+}
+
+/// What to dump of a tape
+#[derive(Debug, PartialEq)]
+pub enum DumpTarget {
+    /// The data of the block at the given position, counted from 0
+    Block(usize),
+    /// The file with the given name, as stored in the tape or as extracted
+    File(String),
+}
+
+/// The format of the manual of `mcp`
+#[derive(Debug, PartialEq)]
+pub enum DocFormat {
+    Man,
+    Markdown,
+}
+
+/// What to do with duplicated tapes
+#[derive(Debug, PartialEq)]
+pub enum Duplicates {
+    Report,
+    Link,
+    Remove,
+}
+
+/// A raw description of the arguments processed by DCOPT
+///
+/// This is not public. Use `Command` instead.
+///
+#[derive(Debug, Deserialize)]
+struct Args {
+    // flag_help: bool,
+    flag_version: bool,
+    flag_json: bool,
+    flag_csv: bool,
+    flag_annotate: bool,
+    flag_only: Option<String>,
+    flag_bytes: bool,
+    flag_verbose: bool,
+    flag_format: Option<String>,
+    flag_times: bool,
+    flag_bauds: u32,
+    flag_output: Option<String>,
+    flag_backup: bool,
+    flag_validate: Vec<String>,
+    flag_suffix: String,
+    flag_headerless: bool,
+    flag_numbered: bool,
+    flag_block_size: Option<usize>,
+    flag_tokenize: bool,
+    flag_crlf: bool,
+    flag_input_format: Option<Format>,
+    flag_header_id: Vec<String>,
+    flag_sync: Vec<String>,
+    flag_type: Vec<String>,
+    flag_output_dir: Option<String>,
+    flag_stdout: bool,
+    flag_charset: Charset,
+    flag_preset: Preset,
+    flag_gap: Vec<String>,
+    flag_cache: Option<String>,
+    flag_preview: Option<String>,
+    flag_preview_png: Option<String>,
+    flag_preview_block: Option<usize>,
+    flag_block: Option<usize>,
+    flag_file: Option<String>,
+    flag_dat: String,
+    flag_jobs: Option<usize>,
+    flag_channel: Option<Channel>,
+    flag_decoder: DecoderKind,
+    flag_link: bool,
+    flag_remove: bool,
+    flag_yes: bool,
+    flag_report: Option<String>,
+    flag_sym: Option<String>,
+    flag_fail_on: Severity,
+    flag_width: usize,
+    flag_trim: bool,
+    flag_normalize: bool,
+    flag_join: Option<String>,
+    flag_join_custom: bool,
+    flag_recover_bin: bool,
+    flag_keep: Vec<String>,
+    flag_drop: Vec<String>,
+    flag_drop_custom: bool,
+    flag_wav: bool,
+    flag_uri: bool,
+    flag_wrap: Option<usize>,
+    flag_port: u16,
+    flag_name: String,
+    flag_rpc: bool,
+    cmd_add: bool,
+    cmd_extract: bool,
+    cmd_verify: bool,
+    cmd_compare: bool,
+    cmd_export_all: bool,
+    cmd_import: bool,
+    cmd_import_all: bool,
+    cmd_roundtrip: bool,
+    cmd_meta: bool,
+    cmd_note: bool,
+    cmd_show: bool,
+    cmd_get: bool,
+    cmd_set: bool,
+    cmd_list: bool,
+    cmd_export: bool,
+    cmd_dedup_library: bool,
+    cmd_check: bool,
+    cmd_map: bool,
+    cmd_undo: bool,
+    cmd_analyze: bool,
+    cmd_disasm: bool,
+    cmd_dump: bool,
+    cmd_cat: bool,
+    cmd_filter: bool,
+    cmd_remove: bool,
+    cmd_rename: bool,
+    cmd_rename_bulk: bool,
+    cmd_repair: bool,
+    cmd_serve: bool,
+    cmd_add_loader: bool,
+    cmd_add_verifier: bool,
+    cmd_inlay: bool,
+    cmd_embed: bool,
+    cmd_manifest: bool,
+    cmd_optimize: bool,
+    cmd_defrag: bool,
+    cmd_init: bool,
+    cmd_gen_docs: bool,
+    cmd_man: bool,
+    arg_cas_file: String,
+    arg_file: Vec<String>,
+    arg_wav_file: String,
+    arg_cas_dir: String,
+    arg_wav_dir: String,
+    arg_set_file: String,
+    arg_project_dir: String,
+    arg_key: String,
+    arg_value: String,
+    arg_name: String,
+    arg_note: String,
+    arg_out_file: String,
+    arg_rules_file: String,
+    arg_file_name: Vec<String>,
+    arg_old_name: String,
+    arg_new_name: String,
+    arg_other_file: String,
+}
+
+impl Args {
+
+    /// Parse the
+    pub fn cmd(self) -> Command {
+        if self.flag_version {
+            Command::Version(self.flag_json)
+        } else if self.cmd_list && !self.cmd_set {
+            let (header_ids, syncs, style, times) = (self.header_ids(), self.syncs(), self.style(), self.times());
+            Command::List(
+                PathBuf::from(self.arg_cas_file),
+                self.flag_input_format,
+                header_ids,
+                syncs,
+                self.flag_annotate,
+                self.flag_only,
+                style,
+                times,
+                self.flag_preview_png.map(PathBuf::from),
+            )
+        } else if self.cmd_add {
+            let (syncs, policy, layout) = (self.syncs(), self.policy(), self.layout());
+            Command::Add(
+                PathBuf::from(self.arg_cas_file),
+                self.arg_file.iter().map(PathBuf::from).collect(),
+                self.flag_output.map(PathBuf::from),
+                if self.flag_backup { Some(self.flag_suffix) } else { None },
+                syncs,
+                policy,
+                layout)
+        } else if self.cmd_extract {
+            let (header_ids, syncs, extraction) = (self.header_ids(), self.syncs(), self.extraction());
+            Command::Extract(
+                PathBuf::from(self.arg_cas_file),
+                self.flag_input_format,
+                header_ids,
+                syncs,
+                self.flag_jobs,
+                extraction)
+        } else if self.cmd_export && !self.cmd_set {
+            let (block, gaps) = (self.flag_preview_block, self.gaps());
+            Command::Export(
+                PathBuf::from(self.arg_cas_file),
+                self.flag_input_format,
+                PathBuf::from(self.arg_wav_file),
+                self.flag_preview.map(|p| Preview { path: PathBuf::from(p), block }),
+                self.flag_cache.map(PathBuf::from),
+                self.flag_preset,
+                gaps)
+        } else if self.cmd_verify {
+            Command::Verify(PathBuf::from(self.arg_cas_file), PathBuf::from(self.flag_dat))
+        } else if self.cmd_compare {
+            Command::Compare(PathBuf::from(self.arg_cas_file), PathBuf::from(self.arg_other_file))
+        } else if self.cmd_export_all {
+            Command::ExportAll(
+                PathBuf::from(self.arg_cas_dir),
+                PathBuf::from(self.arg_wav_dir),
+                self.flag_jobs)
+        } else if self.cmd_import {
+            Command::Import(
+                PathBuf::from(self.arg_wav_file),
+                PathBuf::from(self.arg_cas_file),
+                self.flag_report.map(PathBuf::from),
+                self.flag_channel,
+                self.flag_decoder)
+        } else if self.cmd_import_all {
+            Command::ImportAll(
+                PathBuf::from(self.arg_wav_dir),
+                PathBuf::from(self.arg_cas_dir),
+                self.flag_jobs,
+                self.flag_channel,
+                self.flag_decoder)
+        } else if self.cmd_roundtrip {
+            let gaps = self.gaps();
+            Command::Roundtrip(
+                PathBuf::from(self.arg_cas_file),
+                PathBuf::from(self.arg_wav_file),
+                self.flag_preset,
+                gaps,
+                self.flag_decoder)
+        } else if self.cmd_meta && self.cmd_show {
+            Command::MetaShow(PathBuf::from(self.arg_cas_file))
+        } else if self.cmd_meta && self.cmd_get {
+            Command::MetaGet(PathBuf::from(self.arg_cas_file), self.arg_key)
+        } else if self.cmd_meta && self.cmd_set {
+            Command::MetaSet(PathBuf::from(self.arg_cas_file), self.arg_key, self.arg_value)
+        } else if self.cmd_note && self.cmd_get {
+            Command::NoteGet(PathBuf::from(self.arg_cas_file), self.arg_name)
+        } else if self.cmd_note && self.cmd_set {
+            Command::NoteSet(PathBuf::from(self.arg_cas_file), self.arg_name, self.arg_note)
+        } else if self.cmd_set && self.cmd_list {
+            Command::SetList(PathBuf::from(self.arg_set_file))
+        } else if self.cmd_set && self.cmd_export {
+            Command::SetExport(PathBuf::from(self.arg_set_file), PathBuf::from(self.arg_wav_dir), self.flag_preset)
+        } else if self.cmd_dedup_library {
+            let action = if self.flag_link {
+                Duplicates::Link
+            } else if self.flag_remove {
+                Duplicates::Remove
+            } else {
+                Duplicates::Report
+            };
+            Command::DedupLibrary(PathBuf::from(self.arg_cas_dir), self.flag_jobs, action, self.flag_yes)
+        } else if self.cmd_check {
+            Command::Check(
+                PathBuf::from(self.arg_cas_file),
+                self.flag_report.map(PathBuf::from),
+                self.flag_fail_on)
+        } else if self.cmd_map {
+            Command::Map(PathBuf::from(self.arg_cas_file), self.flag_input_format, self.flag_width)
+        } else if self.cmd_analyze {
+            Command::Analyze(
+                PathBuf::from(self.arg_cas_file),
+                self.flag_input_format,
+                self.flag_width,
+                self.flag_sym.map(PathBuf::from))
+        } else if self.cmd_disasm {
+            Command::Disasm(
+                PathBuf::from(self.arg_cas_file),
+                self.flag_input_format,
+                self.arg_name,
+                self.flag_sym.map(PathBuf::from))
+        } else if self.cmd_dump {
+            let target = match (self.flag_block, self.flag_file) {
+                (Some(block), _) => DumpTarget::Block(block),
+                (None, name) => DumpTarget::File(name.unwrap_or_default()),
+            };
+            Command::Dump(PathBuf::from(self.arg_cas_file), self.flag_input_format, target)
+        } else if self.cmd_cat {
+            Command::Cat(PathBuf::from(self.arg_cas_file), self.flag_input_format, self.arg_name)
+        } else if self.cmd_filter {
+            let filter = self.filter();
+            Command::Filter(PathBuf::from(self.arg_cas_file), PathBuf::from(self.arg_out_file), filter)
+        } else if self.cmd_remove {
+            Command::Remove(
+                PathBuf::from(self.arg_cas_file),
+                self.arg_file_name,
+                self.flag_output.map(PathBuf::from),
+                self.flag_yes)
+        } else if self.cmd_rename {
+            Command::Rename(
+                PathBuf::from(self.arg_cas_file),
+                self.arg_old_name,
+                self.arg_new_name,
+                self.flag_output.map(PathBuf::from))
+        } else if self.cmd_rename_bulk {
+            Command::RenameBulk(
+                PathBuf::from(self.arg_cas_file),
+                PathBuf::from(self.arg_rules_file),
+                self.flag_output.map(PathBuf::from))
+        } else if self.cmd_undo {
+            Command::Undo(PathBuf::from(self.arg_cas_file), self.flag_suffix, self.flag_yes)
+        } else if self.cmd_repair {
+            let fixes = Fixes {
+                trim: self.flag_trim,
+                normalize: self.flag_normalize,
+                join: self.join(),
+                recover_bin: self.flag_recover_bin,
+            };
+            let syncs = self.syncs();
+            Command::Repair(PathBuf::from(self.arg_cas_file), fixes, self.flag_output.map(PathBuf::from), syncs, self.flag_yes)
+        } else if self.cmd_serve {
+            Command::Serve(PathBuf::from(self.arg_cas_dir), self.flag_port)
+        } else if self.cmd_add_loader {
+            Command::AddLoader(PathBuf::from(self.arg_cas_file), self.flag_name, self.flag_output.map(PathBuf::from))
+        } else if self.cmd_add_verifier {
+            Command::AddVerifier(PathBuf::from(self.arg_cas_file), self.flag_output.map(PathBuf::from))
+        } else if self.cmd_inlay {
+            Command::Inlay(PathBuf::from(self.arg_cas_file), self.flag_output.map(PathBuf::from))
+        } else if self.cmd_embed {
+            let wav = if self.flag_wav { Some(self.flag_preset) } else { None };
+            Command::Embed(
+                PathBuf::from(self.arg_cas_file),
+                wav,
+                self.flag_uri,
+                self.flag_wrap,
+                self.flag_output.map(PathBuf::from))
+        } else if self.cmd_manifest {
+            Command::Manifest(PathBuf::from(self.arg_cas_file))
+        } else if self.cmd_optimize {
+            Command::Optimize(
+                PathBuf::from(self.arg_cas_file),
+                self.flag_join_custom,
+                self.flag_output.map(PathBuf::from),
+                self.flag_yes)
+        } else if self.cmd_defrag {
+            Command::Defrag(PathBuf::from(self.arg_cas_file), self.flag_output.map(PathBuf::from), self.flag_yes)
+        } else if self.cmd_init {
+            Command::Init(PathBuf::from(self.arg_project_dir))
+        } else if self.cmd_gen_docs {
+            Command::GenDocs(if self.cmd_man { DocFormat::Man } else { DocFormat::Markdown })
+        } else if self.flag_rpc {
+            Command::Rpc
+        } else {
+            panic!("args are parsed in a inconsistent state")
+        }
+    }
+}
+
+impl Args {
+
+    fn header_ids(&self) -> Vec<u8> {
+        self.flag_header_id
+            .iter()
+            .map(|id| {
+                let hex = id.trim_start_matches("0x").trim_start_matches("0X");
+                u8::from_str_radix(hex, 16).unwrap_or_else(|_| {
+                    docopt::Error::Argv(format!("invalid header ID byte: {}", id)).exit()
+                })
+            })
+            .collect()
+    }
+
+    fn syncs(&self) -> Vec<[u8; 8]> {
+        self.flag_sync
+            .iter()
+            .map(|sync| {
+                let hex = sync.trim_start_matches("0x").trim_start_matches("0X");
+                let mut bytes = [0; 8];
+                let valid = hex.len() == 16
+                    && bytes.iter_mut().enumerate().all(|(i, byte)| {
+                        hex.get(2 * i..2 * i + 2)
+                            .and_then(|b| u8::from_str_radix(b, 16).ok())
+                            .map(|b| *byte = b)
+                            .is_some()
+                    });
+                if !valid {
+                    docopt::Error::Argv(format!("invalid sync sequence: {}", sync)).exit()
+                }
+                bytes
+            })
+            .collect()
+    }
+
+    fn policy(&self) -> Policy {
+        let mut policy = Policy::default();
+        for rule in &self.flag_validate {
+            if !policy.apply(rule) {
+                docopt::Error::Argv(format!("invalid validation rule: {}", rule)).exit()
+            }
+        }
+        policy
+    }
+
+    fn style(&self) -> Style {
+        match &self.flag_format {
+            Some(format) => Style::Format(
+                Template::parse(format, FILE_FIELDS)
+                    .unwrap_or_else(|e| docopt::Error::Argv(format!("invalid format: {}", e)).exit()),
+            ),
+            None if self.flag_json => Style::Json,
+            None if self.flag_csv => Style::Csv,
+            None if self.flag_bytes => Style::Bytes,
+            None if self.flag_verbose => Style::Verbose,
+            None => Style::Human,
+        }
+    }
+
+    fn times(&self) -> Option<u32> {
+        if self.flag_bauds == 0 {
+            docopt::Error::Argv("bauds must be above zero".to_string()).exit();
+        }
+        Some(self.flag_bauds).filter(|_| self.flag_times)
+    }
+
+    fn layout(&self) -> Layout {
+        if let Some(size) = self.flag_block_size.filter(|size| *size == 0 || !size.is_multiple_of(8)) {
+            docopt::Error::Argv(format!("invalid block size {}: it must be a multiple of 8", size)).exit();
+        }
+        Layout {
+            headerless: self.flag_headerless,
+            block_size: self.flag_block_size,
+            numbered: self.flag_numbered,
+            tokenize: self.flag_tokenize,
+            crlf: self.flag_crlf,
+            charset: self.flag_charset,
+        }
+    }
+
+    fn extraction(&self) -> Extraction {
+        if let Some(kind) = self.flag_type.iter().find(|kind| !TYPES.contains(&kind.as_str())) {
+            docopt::Error::Argv(format!("invalid file type {}: it must be bin, bas, asc or custom", kind)).exit();
+        }
+        Extraction {
+            headerless: self.flag_headerless,
+            numbered: self.flag_numbered,
+            types: self.flag_type.clone(),
+            output_dir: self.flag_output_dir.as_ref().map(PathBuf::from),
+            stdout: Some(self.arg_name.clone()).filter(|_| self.flag_stdout),
+            charset: self.flag_charset,
+        }
+    }
+
+    fn join(&self) -> Option<RangeInclusive<usize>> {
+        self.flag_join.as_ref().map(|join| {
+            join.split_once("..")
+                .and_then(|(first, last)| Some(first.parse().ok()?..=last.parse().ok()?))
+                .unwrap_or_else(|| docopt::Error::Argv(format!("invalid block range: {}", join)).exit())
+        })
+    }
+
+    fn filter(&self) -> Filter {
+        let rules = |rules: &[String]| -> Vec<Rule> {
+            rules
+                .iter()
+                .map(|rule| Rule::parse(rule).unwrap_or_else(|| docopt::Error::Argv(format!("invalid filter rule: {}", rule)).exit()))
+                .collect()
+        };
+        let mut filter = Filter { keep: rules(&self.flag_keep), drop: rules(&self.flag_drop) };
+        if self.flag_drop_custom {
+            filter.drop.push(Rule::Kind("custom".to_string()));
+        }
+        filter
+    }
+
+    fn gaps(&self) -> Vec<Gap> {
+        self.flag_gap
+            .iter()
+            .map(|gap| Gap::parse(gap).unwrap_or_else(|| docopt::Error::Argv(format!("invalid gap: {}", gap)).exit()))
+            .collect()
+    }
+}
+
+/// Parse the arguments passed to `mcp`
+///
+/// Same as `parse_args(std::env::args())`, once the language of the messages is selected.
+///
+#[allow(dead_code)]
+pub fn parse() -> Command {
+    let mut args: Vec<String> = args().collect();
+    i18n::select(take_lang(&mut args).as_deref());
+    parse_args(args.into_iter())
+}
+
+/// Remove the `--lang` option given before the command from the given arguments, and
+/// return its value.
+fn take_lang(args: &mut Vec<String>) -> Option<String> {
+    match args.get(1).map(String::as_str) {
+        Some("--lang") if args.len() > 2 => {
+            let lang = args.remove(2);
+            args.remove(1);
+            Some(lang)
+        }
+        Some(arg) if arg.starts_with("--lang=") => Some(args.remove(1)["--lang=".len()..].to_string()),
+        _ => None,
+    }
+}
+
+/// Parse the given arguments and return the corresponding `Command` object
+pub fn parse_args<I, S>(args: I) -> Command
+where S: AsRef<str>, I: Iterator<Item=S>, S: Into<String> {
+    let args = expand_output_dir(expand_command(args.map(Into::into).collect()));
+    let parsed: Args = Docopt::new(USAGE)
+        .and_then(|d| d.argv(args).deserialize())
+        .unwrap_or_else(|e| e.exit());
+    parsed.cmd()
+}
+
+/// The subcommands of `mcp`, which may be abbreviated to any unambiguous prefix
+static SUBCOMMANDS: &[&str] = &[
+    "list", "add", "extract", "export", "verify", "compare", "export-all", "import", "import-all", "roundtrip", "meta", "note", "set", "dedup-library", "check", "map", "analyze", "disasm", "dump", "cat", "filter", "remove", "rename", "rename-bulk", "undo", "repair",
+    "serve", "add-loader", "add-verifier", "inlay", "embed", "manifest", "optimize", "defrag", "init", "gen-docs",
+];
+
+/// Conventional aliases of the commands of `mcp`, as found in shells
+static ALIASES: &[(&str, &str)] = &[("ls", "list"), ("x", "extract"), ("rm", "remove"), ("mv", "rename"), ("cp", "filter")];
+
+/// The flags that gave the first commands of `mcp`, still accepted in place of them
+static FLAG_COMMANDS: &[(&str, &str)] = &[
+    ("-l", "list"), ("--list", "list"),
+    ("-a", "add"), ("--add", "add"),
+    ("-x", "extract"), ("--extract", "extract"),
+    ("-e", "export"), ("--export", "export"),
+];
+
+/// Replace the command given in the arguments by its full name, if it is an alias or an
+/// unambiguous prefix of a subcommand.
+fn expand_command(mut args: Vec<String>) -> Vec<String> {
+    // Flags may be given anywhere, as options are
+    let flag = args.iter().skip(1).position(|arg| FLAG_COMMANDS.iter().any(|(flag, _)| flag == arg));
+    if let Some(i) = flag {
+        let flag = args.remove(i + 1);
+        let name = FLAG_COMMANDS.iter().find(|(f, _)| *f == flag).map(|(_, name)| *name).unwrap_or_default();
+        args.insert(1, name.to_string());
+        return args;
+    }
+    let command = match args.get(1) {
+        Some(command) if !command.starts_with('-') && !SUBCOMMANDS.contains(&command.as_str()) => command,
+        _ => return args,
+    };
+    let expanded = match ALIASES.iter().find(|(alias, _)| alias == command) {
+        Some((_, name)) => Some(*name),
+        None => {
+            let mut candidates = SUBCOMMANDS.iter().filter(|name| name.starts_with(command.as_str()));
+            match (candidates.next(), candidates.next()) {
+                (Some(name), None) => Some(*name),
+                _ => None,
+            }
+        }
+    };
+    if let Some(name) = expanded {
+        args[1] = name.to_string();
+    }
+    args
+}
+
+/// Take `-o` and `--output` given to `extract` as `--output-dir`, as it writes files into a
+/// directory rather than a tape into a file.
+fn expand_output_dir(mut args: Vec<String>) -> Vec<String> {
+    if args.get(1).map(String::as_str) != Some("extract") {
+        return args;
+    }
+    for arg in args.iter_mut().skip(2).take_while(|arg| *arg != "--") {
+        let dir = match arg.as_str() {
+            "-o" | "--output" => None,
+            other => match other.strip_prefix("--output=").or_else(|| other.strip_prefix("-o")) {
+                Some(dir) if !dir.is_empty() => Some(dir.to_string()),
+                _ => continue,
+            },
+        };
+        *arg = match dir {
+            Some(dir) => format!("--output-dir={}", dir),
+            None => "--output-dir".to_string(),
+        };
+    }
+    args
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn should_parse_version() {
+        let argv = ["mcp", "--version"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Version(false), cmd);
+
+        let argv = ["mcp", "--version", "--json"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Version(true), cmd);
+    }
+
+    #[test]
+    fn should_parse_list() {
+        let argv = ["mcp", "--list", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::List(PathBuf::from("foobar.cas"), None, vec![], vec![], false, None, Style::Human, None, None), cmd);
+
+        let argv = ["mcp", "--list", "--input-format", "tsx", "foobar.dat"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::List(PathBuf::from("foobar.dat"), Some(Format::Tsx), vec![], vec![], false, None, Style::Human, None, None), cmd);
+
+        let argv = ["mcp", "--list", "--header-id", "C5", "--header-id", "0x01", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::List(PathBuf::from("foobar.cas"), None, vec![0xc5, 0x01], vec![], false, None, Style::Human, None, None), cmd);
+
+        let argv = ["mcp", "--list", "--sync", "1FA6DEBACC137D75", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::List(
+                PathBuf::from("foobar.cas"),
+                None,
+                vec![],
+                vec![[0x1f, 0xa6, 0xde, 0xba, 0xcc, 0x13, 0x7d, 0x75]],
+                false,
+                None,
+                Style::Human,
+                None,
+                None),
+            cmd);
+
+        let argv = ["mcp", "--list", "--annotate", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::List(PathBuf::from("foobar.cas"), None, vec![], vec![], true, None, Style::Human, None, None), cmd);
+
+        let argv = ["mcp", "--list", "--only", "game.bin", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::List(PathBuf::from("foobar.cas"), None, vec![], vec![], false, Some("game.bin".to_string()), Style::Human, None, None), cmd);
+
+        let argv = ["mcp", "--list", "--bytes", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::List(PathBuf::from("foobar.cas"), None, vec![], vec![], false, None, Style::Bytes, None, None), cmd);
+
+        let argv = ["mcp", "--list", "--format", "{name}\\t{crc32}", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        let template = Template::parse("{name}\\t{crc32}", FILE_FIELDS).unwrap();
+        assert_eq!(Command::List(PathBuf::from("foobar.cas"), None, vec![], vec![], false, None, Style::Format(template), None, None), cmd);
+
+        let argv = ["mcp", "list", "--verbose", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::List(PathBuf::from("foobar.cas"), None, vec![], vec![], false, None, Style::Verbose, None, None), cmd);
+
+        let argv = ["mcp", "list", "--json", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::List(PathBuf::from("foobar.cas"), None, vec![], vec![], false, None, Style::Json, None, None), cmd);
+
+        let argv = ["mcp", "list", "--csv", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::List(PathBuf::from("foobar.cas"), None, vec![], vec![], false, None, Style::Csv, None, None), cmd);
+
+        let argv = ["mcp", "--list", "--times", "--bauds", "2400", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::List(PathBuf::from("foobar.cas"), None, vec![], vec![], false, None, Style::Human, Some(2400), None), cmd);
+    }
+
+    #[test]
+    fn should_parse_add() {
+        let argv = ["mcp", "--add", "foobar.cas", "f1.bin"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Add(PathBuf::from("foobar.cas"), vec![ PathBuf::from("f1.bin")], None, None, vec![], Policy::default(), Layout::default()), cmd);
+
+        let argv = ["mcp", "--add", "--output", "copy.cas", "foobar.cas", "f1.bin"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Add(
+                PathBuf::from("foobar.cas"),
+                vec![ PathBuf::from("f1.bin")],
+                Some(PathBuf::from("copy.cas")),
+                None,
+                vec![], Policy::default(), Layout::default()),
+            cmd);
+
+        let argv = ["mcp", "--add", "--backup", "foobar.cas", "f1.bin"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Add(PathBuf::from("foobar.cas"), vec![ PathBuf::from("f1.bin")], None, Some(".bak".to_string()), vec![], Policy::default(), Layout::default()),
+            cmd);
+
+        let argv = ["mcp", "--add", "--backup", "--suffix", ".orig", "foobar.cas", "f1.bin"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Add(PathBuf::from("foobar.cas"), vec![ PathBuf::from("f1.bin")], None, Some(".orig".to_string()), vec![], Policy::default(), Layout::default()),
+            cmd);
+
+        let argv = ["mcp", "--add", "--validate", "warn", "--validate", "name=off", "foobar.cas", "f1.bin"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        let mut policy = Policy::default();
+        policy.apply("warn");
+        policy.apply("name=off");
+        assert_eq!(Command::Add(PathBuf::from("foobar.cas"), vec![ PathBuf::from("f1.bin")], None, None, vec![], policy, Layout::default()), cmd);
+
+        let argv = ["mcp", "--add", "--headerless", "foobar.cas", "f1.bin"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        let layout = Layout { headerless: true, block_size: None, numbered: false, ..Layout::default() };
+        assert_eq!(Command::Add(PathBuf::from("foobar.cas"), vec![ PathBuf::from("f1.bin")], None, None, vec![], Policy::default(), layout), cmd);
+
+        let argv = ["mcp", "--add", "--block-size", "256", "foobar.cas", "f1.dat"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        let layout = Layout { headerless: false, block_size: Some(256), numbered: false, ..Layout::default() };
+        assert_eq!(Command::Add(PathBuf::from("foobar.cas"), vec![ PathBuf::from("f1.dat")], None, None, vec![], Policy::default(), layout), cmd);
+
+        let argv = ["mcp", "--add", "--numbered", "foobar.cas", "01_f1.bin"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        let layout = Layout { headerless: false, block_size: None, numbered: true, ..Layout::default() };
+        assert_eq!(Command::Add(PathBuf::from("foobar.cas"), vec![ PathBuf::from("01_f1.bin")], None, None, vec![], Policy::default(), layout), cmd);
+
+        let argv = ["mcp", "--add", "--tokenize", "foobar.cas", "f1.bas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        let layout = Layout { tokenize: true, ..Layout::default() };
+        assert_eq!(Command::Add(PathBuf::from("foobar.cas"), vec![ PathBuf::from("f1.bas")], None, None, vec![], Policy::default(), layout), cmd);
+
+        let argv = ["mcp", "--add", "--crlf", "foobar.cas", "f1.asc"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        let layout = Layout { crlf: true, ..Layout::default() };
+        assert_eq!(Command::Add(PathBuf::from("foobar.cas"), vec![ PathBuf::from("f1.asc")], None, None, vec![], Policy::default(), layout), cmd);
+
+        let argv = ["mcp", "--add", "--charset=msx-jp", "foobar.cas", "f1.asc"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        let layout = Layout { charset: Charset::MsxJp, ..Layout::default() };
+        assert_eq!(Command::Add(PathBuf::from("foobar.cas"), vec![ PathBuf::from("f1.asc")], None, None, vec![], Policy::default(), layout), cmd);
+    }
+
+    #[test]
+    fn should_parse_extract() {
+        let argv = ["mcp", "--extract", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Extract(PathBuf::from("foobar.cas"), None, vec![], vec![], None, Extraction::default()), cmd);
+
+        let argv = ["mcp", "--extract", "--jobs", "4", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Extract(PathBuf::from("foobar.cas"), None, vec![], vec![], Some(4), Extraction::default()), cmd);
+
+        let argv = ["mcp", "--extract", "--headerless", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        let extraction = Extraction { headerless: true, ..Extraction::default() };
+        assert_eq!(Command::Extract(PathBuf::from("foobar.cas"), None, vec![], vec![], None, extraction), cmd);
+
+        let argv = ["mcp", "--extract", "--numbered", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        let extraction = Extraction { numbered: true, ..Extraction::default() };
+        assert_eq!(Command::Extract(PathBuf::from("foobar.cas"), None, vec![], vec![], None, extraction), cmd);
+
+        let argv = ["mcp", "extract", "--type", "bin", "--type=bas", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        let extraction = Extraction { types: vec!["bin".to_string(), "bas".to_string()], ..Extraction::default() };
+        assert_eq!(Command::Extract(PathBuf::from("foobar.cas"), None, vec![], vec![], None, extraction), cmd);
+
+        for argv in [
+            ["mcp", "extract", "--output-dir", "out", "foobar.cas"],
+            ["mcp", "extract", "-o", "out", "foobar.cas"],
+            ["mcp", "x", "foobar.cas", "--output", "out"],
+            ["mcp", "extract", "-oout", "foobar.cas", "--"],
+        ] {
+            let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+            let extraction = Extraction { output_dir: Some(PathBuf::from("out")), ..Extraction::default() };
+            assert_eq!(Command::Extract(PathBuf::from("foobar.cas"), None, vec![], vec![], None, extraction), cmd);
+        }
+
+        let argv = ["mcp", "extract", "foobar.cas", "game.bin", "--stdout"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        let extraction = Extraction { stdout: Some("game.bin".to_string()), ..Extraction::default() };
+        assert_eq!(Command::Extract(PathBuf::from("foobar.cas"), None, vec![], vec![], None, extraction), cmd);
+
+        let argv = ["mcp", "extract", "--charset", "msx-jp", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        let extraction = Extraction { charset: Charset::MsxJp, ..Extraction::default() };
+        assert_eq!(Command::Extract(PathBuf::from("foobar.cas"), None, vec![], vec![], None, extraction), cmd);
+    }
+
+    #[test]
+    fn should_parse_export() {
+        let argv = ["mcp", "--export", "foobar.cas", "foobar.wav"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Export(PathBuf::from("foobar.cas"), None, PathBuf::from("foobar.wav"), None, None, Preset::Default, vec![]),
+            cmd);
+
+        let argv = ["mcp", "--export", "--preview", "foobar.png", "--preview-block", "3", "foobar.cas", "foobar.wav"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Export(
+                PathBuf::from("foobar.cas"),
+                None,
+                PathBuf::from("foobar.wav"),
+                Some(Preview { path: PathBuf::from("foobar.png"), block: Some(3) }),
+                None,
+                Preset::Default,
+                vec![]),
+            cmd);
+
+        let argv = ["mcp", "--export", "--cache", ".cache", "foobar.cas", "foobar.wav"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Export(
+                PathBuf::from("foobar.cas"),
+                None,
+                PathBuf::from("foobar.wav"),
+                None,
+                Some(PathBuf::from(".cache")),
+                Preset::Default,
+                vec![]),
+            cmd);
+
+        let argv = ["mcp", "--export", "--preset", "tzxduino", "foobar.cas", "foobar.wav"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Export(PathBuf::from("foobar.cas"), None, PathBuf::from("foobar.wav"), None, None, Preset::Tzxduino, vec![]),
+            cmd);
+
+        let argv = ["mcp", "--export", "--gap", "3:1500", "--gap", "custom:500", "foobar.cas", "foobar.wav"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Export(
+                PathBuf::from("foobar.cas"),
+                None,
+                PathBuf::from("foobar.wav"),
+                None,
+                None,
+                Preset::Default,
+                vec![Gap::parse("3:1500").unwrap(), Gap::parse("custom:500").unwrap()]),
+            cmd);
+    }
+
+    #[test]
+    fn should_parse_verify() {
+        let argv = ["mcp", "verify", "--dat", "msx.dat", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Verify(PathBuf::from("foobar.cas"), PathBuf::from("msx.dat")), cmd);
+    }
+
+    #[test]
+    fn should_parse_export_all() {
+        let argv = ["mcp", "export-all", "tapes", "wavs"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::ExportAll(PathBuf::from("tapes"), PathBuf::from("wavs"), None), cmd);
+
+        let argv = ["mcp", "export-all", "--jobs", "8", "tapes", "wavs"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::ExportAll(PathBuf::from("tapes"), PathBuf::from("wavs"), Some(8)), cmd);
+    }
+
+    #[test]
+    fn should_parse_set() {
+        let argv = ["mcp", "set", "list", "game.set.json"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::SetList(PathBuf::from("game.set.json")), cmd);
+
+        let argv = ["mcp", "set", "export", "--preset", "tzxduino", "game.set.json", "wavs"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::SetExport(PathBuf::from("game.set.json"), PathBuf::from("wavs"), Preset::Tzxduino), cmd);
+    }
+
+    #[test]
+    fn should_parse_import() {
+        let argv = ["mcp", "import", "foobar.wav", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Import(PathBuf::from("foobar.wav"), PathBuf::from("foobar.cas"), None, None, DecoderKind::ZeroCrossing),
+            cmd);
+
+        let argv = [
+            "mcp", "import", "--report", "report.json", "--channel", "right", "--decoder", "fsk", "foobar.wav", "foobar.cas",
+        ];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Import(
+                PathBuf::from("foobar.wav"),
+                PathBuf::from("foobar.cas"),
+                Some(PathBuf::from("report.json")),
+                Some(Channel::Right),
+                DecoderKind::Fsk),
+            cmd);
+    }
+
+    #[test]
+    fn should_parse_import_all() {
+        let argv = ["mcp", "import-all", "-j", "2", "wavs", "tapes"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::ImportAll(PathBuf::from("wavs"), PathBuf::from("tapes"), Some(2), None, DecoderKind::ZeroCrossing),
+            cmd);
+    }
+
+    #[test]
+    fn should_parse_meta() {
+        let argv = ["mcp", "meta", "show", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::MetaShow(PathBuf::from("foobar.cas")), cmd);
+
+        let argv = ["mcp", "meta", "get", "foobar.cas", "title"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::MetaGet(PathBuf::from("foobar.cas"), "title".to_string()), cmd);
+
+        let argv = ["mcp", "meta", "set", "foobar.cas", "title", "Foo Bar"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::MetaSet(PathBuf::from("foobar.cas"), "title".to_string(), "Foo Bar".to_string()),
+            cmd);
+    }
+
+    #[test]
+    fn should_parse_note() {
+        let argv = ["mcp", "note", "get", "foobar.cas", "STAGE2"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::NoteGet(PathBuf::from("foobar.cas"), "STAGE2".to_string()), cmd);
+
+        let argv = ["mcp", "note", "set", "foobar.cas", "STAGE2", "loads at 0x9000"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::NoteSet(PathBuf::from("foobar.cas"), "STAGE2".to_string(), "loads at 0x9000".to_string()),
+            cmd);
+    }
+
+    #[test]
+    fn should_parse_dedup_library() {
+        let argv = ["mcp", "dedup-library", "tapes"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::DedupLibrary(PathBuf::from("tapes"), None, Duplicates::Report, false), cmd);
+
+        let argv = ["mcp", "dedup-library", "--link", "tapes"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::DedupLibrary(PathBuf::from("tapes"), None, Duplicates::Link, false), cmd);
+
+        let argv = ["mcp", "dedup-library", "--remove", "tapes"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::DedupLibrary(PathBuf::from("tapes"), None, Duplicates::Remove, false), cmd);
+
+        let argv = ["mcp", "dedup-library", "--remove", "--yes", "tapes"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::DedupLibrary(PathBuf::from("tapes"), None, Duplicates::Remove, true), cmd);
+    }
+
+    #[test]
+    fn should_parse_check() {
+        let argv = ["mcp", "check", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Check(PathBuf::from("foobar.cas"), None, Severity::Error), cmd);
+
+        let argv = ["mcp", "check", "--report", "report.json", "--fail-on", "warning", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Check(PathBuf::from("foobar.cas"), Some(PathBuf::from("report.json")), Severity::Warning),
+            cmd);
+    }
+
+    #[test]
+    fn should_parse_map() {
+        let argv = ["mcp", "map", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Map(PathBuf::from("foobar.cas"), None, 64), cmd);
+
+        let argv = ["mcp", "map", "--width", "32", "--input-format", "csw", "-"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Map(PathBuf::from("-"), Some(Format::Csw), 32), cmd);
+    }
+
+    #[test]
+    fn should_parse_undo() {
+        let argv = ["mcp", "undo", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Undo(PathBuf::from("foobar.cas"), ".bak".to_string(), false), cmd);
+
+        let argv = ["mcp", "undo", "-y", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Undo(PathBuf::from("foobar.cas"), ".bak".to_string(), true), cmd);
+    }
+
+    #[test]
+    fn should_parse_repair() {
+        let argv = ["mcp", "repair", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Repair(PathBuf::from("foobar.cas"), Fixes::default(), None, vec![], false), cmd);
+
+        let argv = ["mcp", "repair", "--trim", "-o", "fixed.cas", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Repair(
+                PathBuf::from("foobar.cas"),
+                Fixes { trim: true, ..Fixes::default() },
+                Some(PathBuf::from("fixed.cas")),
+                vec![],
+                false),
+            cmd);
+
+        let argv = ["mcp", "repair", "--normalize", "--yes", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Repair(PathBuf::from("foobar.cas"), Fixes { normalize: true, ..Fixes::default() }, None, vec![], true),
+            cmd);
+
+        let argv = ["mcp", "repair", "--join", "3..7", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Repair(PathBuf::from("foobar.cas"), Fixes { join: Some(3..=7), ..Fixes::default() }, None, vec![], false),
+            cmd);
+
+        let argv = ["mcp", "repair", "--recover-bin", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Repair(PathBuf::from("foobar.cas"), Fixes { recover_bin: true, ..Fixes::default() }, None, vec![], false),
+            cmd);
+    }
+
+    #[test]
+    fn should_parse_serve() {
+        let argv = ["mcp", "serve", "tapes"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Serve(PathBuf::from("tapes"), 8080), cmd);
+
+        let argv = ["mcp", "serve", "--port", "9000", "tapes"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Serve(PathBuf::from("tapes"), 9000), cmd);
+    }
+
+    #[test]
+    fn should_parse_rpc() {
+        let argv = ["mcp", "--rpc"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Rpc, cmd);
+    }
+
+    #[test]
+    fn should_parse_aliases_and_prefixes() {
+        let argv = ["mcp", "ls", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::List(PathBuf::from("foobar.cas"), None, vec![], vec![], false, None, Style::Human, None, None), cmd);
+
+        let argv = ["mcp", "x", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Extract(PathBuf::from("foobar.cas"), None, vec![], vec![], None, Extraction::default()), cmd);
+
+        let argv = ["mcp", "rep", "--trim", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Repair(PathBuf::from("foobar.cas"), Fixes { trim: true, ..Fixes::default() }, None, vec![], false),
+            cmd);
+
+        // Full names win over longer names they are a prefix of
+        let argv = ["mcp", "import", "foobar.wav", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert!(matches!(cmd, Command::Import(..)));
+
+        let expand = |args: &[&str]| expand_command(args.iter().map(|a| a.to_string()).collect());
+        assert_eq!(vec!["mcp", "imp"], expand(&["mcp", "imp"]));
+        assert_eq!(vec!["mcp", "dedup-library"], expand(&["mcp", "dedup"]));
+        assert_eq!(vec!["mcp", "list"], expand(&["mcp", "-l"]));
+        assert_eq!(vec!["mcp", "list", "--bytes", "foobar.cas"], expand(&["mcp", "--bytes", "--list", "foobar.cas"]));
+        assert_eq!(vec!["mcp", "--version"], expand(&["mcp", "--version"]));
+        assert_eq!(vec!["mcp", "remove", "foobar.cas"], expand(&["mcp", "rm", "foobar.cas"]));
+        assert_eq!(vec!["mcp", "rename", "foobar.cas", "a", "b"], expand(&["mcp", "mv", "foobar.cas", "a", "b"]));
+        assert_eq!(vec!["mcp", "filter", "foobar.cas", "copy.cas"], expand(&["mcp", "cp", "foobar.cas", "copy.cas"]));
+    }
+
+    #[test]
+    fn should_parse_subcommands() {
+        let argv = ["mcp", "list", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::List(PathBuf::from("foobar.cas"), None, vec![], vec![], false, None, Style::Human, None, None), cmd);
+
+        let argv = ["mcp", "add", "foobar.cas", "f1.bin"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Add(PathBuf::from("foobar.cas"), vec![ PathBuf::from("f1.bin")], None, None, vec![], Policy::default(), Layout::default()), cmd);
+
+        let argv = ["mcp", "extract", "--jobs", "4", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Extract(PathBuf::from("foobar.cas"), None, vec![], vec![], Some(4), Extraction::default()), cmd);
+
+        let argv = ["mcp", "export", "foobar.cas", "foobar.wav"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Export(PathBuf::from("foobar.cas"), None, PathBuf::from("foobar.wav"), None, None, Preset::Default, vec![]), cmd);
+    }
+
+    #[test]
+    fn should_take_lang() {
+        let mut argv: Vec<String> = ["mcp", "--lang", "es", "-l", "foobar.cas"].iter().map(|a| a.to_string()).collect();
+        assert_eq!(Some("es".to_string()), take_lang(&mut argv));
+        assert_eq!(vec!["mcp", "-l", "foobar.cas"], argv);
+
+        let mut argv: Vec<String> = ["mcp", "--lang=ja", "-l", "foobar.cas"].iter().map(|a| a.to_string()).collect();
+        assert_eq!(Some("ja".to_string()), take_lang(&mut argv));
+        assert_eq!(vec!["mcp", "-l", "foobar.cas"], argv);
+
+        let mut argv: Vec<String> = ["mcp", "-l", "foobar.cas"].iter().map(|a| a.to_string()).collect();
+        assert_eq!(None, take_lang(&mut argv));
+        assert_eq!(3, argv.len());
+    }
+
+    #[test]
+    fn should_parse_gen_docs() {
+        let argv = ["mcp", "gen-docs", "man"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::GenDocs(DocFormat::Man), cmd);
+
+        let argv = ["mcp", "gen-docs", "markdown"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::GenDocs(DocFormat::Markdown), cmd);
+    }
+
+    #[test]
+    fn should_parse_add_loader() {
+        let argv = ["mcp", "add-loader", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::AddLoader(PathBuf::from("foobar.cas"), "LOADER".to_string(), None), cmd);
+
+        let argv = ["mcp", "add-loader", "--name", "RUNME", "-o", "game.cas", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::AddLoader(PathBuf::from("foobar.cas"), "RUNME".to_string(), Some(PathBuf::from("game.cas"))),
+            cmd);
+    }
+
+    #[test]
+    fn should_parse_inlay() {
+        let argv = ["mcp", "inlay", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Inlay(PathBuf::from("foobar.cas"), None), cmd);
+
+        let argv = ["mcp", "inlay", "-o", "inlay.pdf", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Inlay(PathBuf::from("foobar.cas"), Some(PathBuf::from("inlay.pdf"))), cmd);
+    }
+
+    #[test]
+    fn should_parse_manifest() {
+        let argv = ["mcp", "manifest", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Manifest(PathBuf::from("foobar.cas")), cmd);
+    }
+
+    #[test]
+    fn should_parse_optimize() {
+        let argv = ["mcp", "optimize", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Optimize(PathBuf::from("foobar.cas"), false, None, false), cmd);
+
+        let argv = ["mcp", "optimize", "--join-custom", "-o", "small.cas", "--yes", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Optimize(PathBuf::from("foobar.cas"), true, Some(PathBuf::from("small.cas")), true), cmd);
+    }
+
+    #[test]
+    fn should_parse_defrag() {
+        let argv = ["mcp", "defrag", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Defrag(PathBuf::from("foobar.cas"), None, false), cmd);
+
+        let argv = ["mcp", "defrag", "-o", "clean.cas", "--yes", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Defrag(PathBuf::from("foobar.cas"), Some(PathBuf::from("clean.cas")), true), cmd);
+    }
+
+    #[test]
+    fn should_parse_init() {
+        let argv = ["mcp", "init", "race"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Init(PathBuf::from("race")), cmd);
+    }
+
+    #[test]
+    fn should_parse_analyze() {
+        let argv = ["mcp", "analyze", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Analyze(PathBuf::from("foobar.cas"), None, 64, None), cmd);
+
+        let argv = ["mcp", "analyze", "--sym", "foobar.sym", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Analyze(PathBuf::from("foobar.cas"), None, 64, Some(PathBuf::from("foobar.sym"))), cmd);
+    }
+
+    #[test]
+    fn should_parse_filter() {
+        let argv = ["mcp", "filter", "in.cas", "out.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Filter(PathBuf::from("in.cas"), PathBuf::from("out.cas"), Filter::default()), cmd);
+
+        let argv = ["mcp", "filter", "--keep", "STAGE*", "--keep", "0..1", "--drop-custom", "in.cas", "out.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        let filter = Filter {
+            keep: vec![Rule::Name("STAGE*".to_string()), Rule::Range(0..=1)],
+            drop: vec![Rule::Kind("custom".to_string())],
+        };
+        assert_eq!(Command::Filter(PathBuf::from("in.cas"), PathBuf::from("out.cas"), filter), cmd);
+    }
+
+    #[test]
+    fn should_parse_remove() {
+        let argv = ["mcp", "remove", "foobar.cas", "loader", "game.bin"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Remove(PathBuf::from("foobar.cas"), vec!["loader".to_string(), "game.bin".to_string()], None, false),
+            cmd);
+
+        let argv = ["mcp", "rm", "--yes", "foobar.cas", "loader"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Remove(PathBuf::from("foobar.cas"), vec!["loader".to_string()], None, true), cmd);
+    }
+
+    #[test]
+    fn should_parse_rename() {
+        let argv = ["mcp", "rename", "-o", "fixed.cas", "foobar.cas", "game.bin", "GAME"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Rename(
+                PathBuf::from("foobar.cas"),
+                "game.bin".to_string(),
+                "GAME".to_string(),
+                Some(PathBuf::from("fixed.cas"))),
+            cmd);
+    }
+
+    #[test]
+    fn should_parse_rename_bulk() {
+        let argv = ["mcp", "rename-bulk", "-o", "clean.cas", "foobar.cas", "rules.txt"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::RenameBulk(PathBuf::from("foobar.cas"), PathBuf::from("rules.txt"), Some(PathBuf::from("clean.cas"))),
+            cmd);
+    }
+
+    #[test]
+    fn should_parse_embed() {
+        let argv = ["mcp", "embed", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Embed(PathBuf::from("foobar.cas"), None, false, None, None), cmd);
+
+        let argv = ["mcp", "embed", "--wav", "--uri", "-o", "foobar.txt", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Embed(PathBuf::from("foobar.cas"), Some(Preset::Default), true, None, Some(PathBuf::from("foobar.txt"))),
+            cmd);
+
+        let argv = ["mcp", "embed", "--wrap", "76", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Embed(PathBuf::from("foobar.cas"), None, false, Some(76), None), cmd);
+    }
+
+    #[test]
+    fn should_parse_add_verifier() {
+        let argv = ["mcp", "add-verifier", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::AddVerifier(PathBuf::from("foobar.cas"), None), cmd);
+    }
+
+    #[test]
+    fn should_parse_roundtrip() {
+        let argv = ["mcp", "roundtrip", "foobar.cas", "foobar.wav"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Roundtrip(PathBuf::from("foobar.cas"), PathBuf::from("foobar.wav"), Preset::Default, vec![], DecoderKind::ZeroCrossing),
+            cmd);
+
+        let argv = ["mcp", "roundtrip", "--preset", "tzxduino", "--decoder", "fsk", "foobar.cas", "foobar.wav"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Roundtrip(PathBuf::from("foobar.cas"), PathBuf::from("foobar.wav"), Preset::Tzxduino, vec![], DecoderKind::Fsk),
+            cmd);
+    }
+
+    #[test]
+    fn should_parse_compare() {
+        let argv = ["mcp", "compare", "foobar.cas", "foobar.tsx"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Compare(PathBuf::from("foobar.cas"), PathBuf::from("foobar.tsx")), cmd);
+    }
+
+    #[test]
+    fn should_parse_disasm() {
+        let argv = ["mcp", "disasm", "foobar.cas", "LOADER"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Disasm(PathBuf::from("foobar.cas"), None, "LOADER".to_string(), None), cmd);
+
+        let argv = ["mcp", "disasm", "--sym", "foobar.sym", "foobar.cas", "LOADER"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::Disasm(PathBuf::from("foobar.cas"), None, "LOADER".to_string(), Some(PathBuf::from("foobar.sym"))),
+            cmd);
+    }
+
+    #[test]
+    fn should_parse_dump() {
+        let argv = ["mcp", "dump", "--block", "3", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Dump(PathBuf::from("foobar.cas"), None, DumpTarget::Block(3)), cmd);
+
+        let argv = ["mcp", "dump", "--file", "loader.bin", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Dump(PathBuf::from("foobar.cas"), None, DumpTarget::File("loader.bin".to_string())), cmd);
+    }
+
+    #[test]
+    fn should_parse_cat() {
+        let argv = ["mcp", "cat", "foobar.cas", "game.bas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(Command::Cat(PathBuf::from("foobar.cas"), None, "game.bas".to_string()), cmd);
+    }
+
+    #[test]
+    fn should_parse_list_with_preview_png() {
+        let argv = ["mcp", "-l", "--preview-png", "screen.png", "foobar.cas"];
+        let cmd = parse_args(argv.iter().map(|a| a.to_string()));
+        assert_eq!(
+            Command::List(
+                PathBuf::from("foobar.cas"),
+                None,
+                vec![],
+                vec![],
+                false,
+                None,
+                Style::Human,
+                None,
+                Some(PathBuf::from("screen.png"))),
+            cmd);
+    }
+}
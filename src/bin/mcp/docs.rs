@@ -0,0 +1,256 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// The description of `mcp` as a whole
+static DESCRIPTION: &str = "\
+MCP is a tool to manage CAS files, the tape images of MSX computers. It lists, adds and \
+extracts the files of a tape, exports tapes into WAV files that can be played into a real \
+MSX, and imports tapes back from recordings. It also keeps tape libraries healthy: it \
+checks, repairs and verifies tapes, and finds the duplicated ones.";
+
+/// The long help of each command, by the words that name it in the usage
+static COMMANDS: &[(&str, &str)] = &[
+    ("list", "List the files of a tape, with their type, name, size, load time and, for binary files, \
+        their BEGIN, END and START addresses."),
+    ("add", "Add files to a tape, creating it if it does not exist. The type of each file is \
+        detected from its extension: .bin, .bas, .asc, or custom data otherwise."),
+    ("extract", "Extract the files of a tape into the current directory, or the one given, renaming \
+        the files whose names clash."),
+    ("export", "Export a tape into a WAV file that can be played into a real MSX."),
+    ("verify", "Verify a tape and its files against a DAT or CSV file of known-good dumps."),
+    ("compare", "Compare the blocks of two tapes in any input format, such as a CAS file and a TSX \
+        file of the same tape, ignoring their timing and the padding of their files."),
+    ("export-all", "Export every CAS file of a directory into a WAV file of another directory."),
+    ("set list", "List the files of each side of a tape set, described by a JSON file, along with \
+        the totals of the set and the files found in more than one side."),
+    ("set export", "Export each side of a tape set into a WAV file named after its label."),
+    ("import", "Decode a recording of a tape into a CAS file."),
+    ("import-all", "Decode every WAV file of a directory into a CAS file of another directory, \
+        reporting the quality of each recording."),
+    ("roundtrip", "Export a tape into a WAV file and decode it back, failing if the decoded blocks \
+        are not the blocks of the tape, to validate the audio settings before recording."),
+    ("meta show", "Show the metadata of a tape, stored in its .meta.json sidecar file."),
+    ("meta get", "Print a field of the metadata of a tape."),
+    ("meta set", "Set a field of the metadata of a tape."),
+    ("note get", "Print the note attached to a file of a tape, stored in its .notes.json sidecar file."),
+    ("note set", "Attach a free-form note to a file of a tape, or remove it if the note is empty."),
+    ("dedup-library", "Find the duplicated tapes of a library, and the tapes whose files are \
+        all contained in another tape."),
+    ("check", "Check the health of a tape, reporting the issues found in its blocks and files."),
+    ("map", "Draw where each file of a tape sits in the tape and in the audio timeline."),
+    ("analyze", "Report the memory taken by the binary files of a tape, how they overlap and where \
+        their START addresses point to, with a memory map, as to understand chained loaders."),
+    ("disasm", "Print the Z80 disassembly of a binary file of a tape, from its START address on, \
+        naming the BIOS routines it calls. Only available when built with the disasm feature."),
+    ("dump", "Print the hex and ASCII dump of a block or a file of a tape, as hexdump -C does."),
+    ("cat", "Print an ASCII file of a tape as text, or list a tokenized Basic file, without \
+        extracting it."),
+    ("filter", "Write a new tape with some of the files of a tape, selected by name, type or \
+        position, keeping their bytes and their order."),
+    ("remove", "Remove files from a tape, given by their name as stored in the tape (e.g., game) or \
+        as extracted (e.g., game.bin)."),
+    ("rename", "Rename a file of a tape, writing the new name, up to six printable ASCII characters, \
+        into its header."),
+    ("rename-bulk", "Rename the files of a tape in one pass, with the rules of a file: a rule per \
+        line, as <old> -> <new> or /<regex>/ -> <replacement>."),
+    ("undo", "Restore a tape from the backup made when it was modified."),
+    ("repair", "Rewrite a tape keeping only its blocks, optionally trimming the garbage at its end, \
+        turning it into its canonical form or joining a run of custom blocks."),
+    ("serve", "Serve the tapes of a directory through a local HTTP API."),
+    ("add-loader", "Add a BASIC program at the front of a tape that loads its binary files in turn \
+        with BLOAD and runs the last one, so the tape is loaded with a single RUN\"CAS:\"."),
+    ("add-verifier", "Add a binary file at the end of a tape that, run after loading the binary \
+        files of the tape, checks their memory against their checksums and reports the corrupted \
+        ones, to test the audio chain."),
+    ("inlay", "Print the content listing of a tape for the J-card of a cassette, with the files laid \
+        out on its sides and their load times, or write it into a text or PDF file."),
+    ("embed", "Print a tape, or the WAV file exported from it, in base64 or as a data: URI, to \
+        embed it into web pages and web launchers."),
+    ("manifest", "Print a TOML manifest of a tape that, together with its extracted files, \
+        reproduces the tape byte for byte."),
+    ("optimize", "Repack a tape to waste less bytes and audio time, reporting the padding of its \
+        files, trimming the binary files past their END address and, optionally, joining the runs \
+        of custom blocks."),
+    ("defrag", "Rewrite a tape with every block starting at an 8-byte boundary, removing the bytes \
+        before the first block, the trailing garbage and the bytes past the END address of binary \
+        files, and reporting each change."),
+    ("init", "Create a new homebrew project, with the sources of a program, a directory for the \
+        data files of the tape and a makefile building the tape and exporting it into WAV and TSX \
+        files."),
+    ("gen-docs", "Print the manual of mcp as a man page or in Markdown."),
+    ("--rpc", "Serve JSON-RPC requests, one per line, from the standard input."),
+    ("--help", "Print the help message."),
+    ("--version", "Print the version of mcp."),
+];
+
+/// The environment variables read by `mcp`
+static ENVIRONMENT: &[(&str, &str)] = &[
+    ("MCP_LANG", "Language of the messages: en, es or ja. The locale (LC_ALL, LC_MESSAGES or LANG) \
+        is used if it is not set."),
+    (mcp::plugin::PLUGINS_VAR, "Directory with the JSON manifests of the plugins that handle the \
+        custom blocks of some loaders."),
+    (mcp::hook::Hook::PreAdd.var(), "Command run before adding each file to a tape, with the path of \
+        the file as its last argument."),
+    (mcp::hook::Hook::PostExport.var(), "Command run after exporting a tape, with the path of the \
+        exported file as its last argument."),
+    (mcp::hook::CONTEXT_VAR, "The context of the running hook in JSON: its name, the tape and \
+        the file added or exported."),
+];
+
+/// The parts of a docopt usage message
+struct Usage<'a> {
+    /// The usage patterns, without the leading `mcp`
+    patterns: Vec<&'a str>,
+    /// The options, with their names and their descriptions
+    options: Vec<(&'a str, String)>,
+}
+
+impl<'a> Usage<'a> {
+    fn parse(usage: &'a str) -> Usage<'a> {
+        let mut patterns = vec![];
+        let mut options: Vec<(&str, String)> = vec![];
+        let mut in_options = false;
+        for line in usage.lines() {
+            let line = line.trim();
+            if line == "Options:" {
+                in_options = true;
+            } else if !in_options {
+                let pattern = line.strip_prefix("Usage:").unwrap_or(line).trim();
+                patterns.extend(pattern.strip_prefix("mcp "));
+            } else if line.starts_with('-') {
+                // Names and descriptions are separated by two spaces at least
+                let (name, description) = line.split_once("  ").unwrap_or((line, ""));
+                options.push((name, description.trim().to_string()));
+            } else if let (false, Some((_, description))) = (line.is_empty(), options.last_mut()) {
+                description.push(' ');
+                description.push_str(line);
+            }
+        }
+        Usage { patterns, options }
+    }
+}
+
+/// Returns the words naming the command of the given usage pattern, as `meta show` or `list`.
+fn command_of(pattern: &str) -> String {
+    let mut words = vec![];
+    for word in pattern.split_whitespace() {
+        if word.starts_with(['[', '<', '(']) || (!words.is_empty() && word.starts_with('-')) {
+            break;
+        }
+        words.push(word);
+    }
+    words.join(" ")
+}
+
+fn long_help(pattern: &str) -> &'static str {
+    let command = command_of(pattern);
+    COMMANDS.iter().find(|(name, _)| *name == command).map(|(_, help)| *help).unwrap_or_default()
+}
+
+/// Escape the given text for roff, so its dashes and backslashes are printed as they are.
+fn roff(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    // Lines starting with a dot or a quote would be taken as requests
+    if text.starts_with(['.', '\'']) { format!("\\&{}", text) } else { text }
+}
+
+/// Render the given docopt usage message into a man page.
+pub fn man(usage: &str, version: &str) -> String {
+    let usage = Usage::parse(usage);
+    let mut page = format!(".TH MCP 1 \"\" \"mcp {}\" \"User Commands\"\n", version);
+    page += ".SH NAME\nmcp \\- MSX CAS Packager\n.SH SYNOPSIS\n";
+    for pattern in &usage.patterns {
+        page += &format!(".B mcp\n{}\n.br\n", roff(pattern));
+    }
+    page += &format!(".SH DESCRIPTION\n{}\n.SH COMMANDS\n", roff(DESCRIPTION));
+    for pattern in &usage.patterns {
+        page += &format!(".TP\n.B mcp {}\n{}\n", roff(pattern), roff(long_help(pattern)));
+    }
+    page += ".SH OPTIONS\n";
+    for (name, description) in &usage.options {
+        page += &format!(".TP\n.B {}\n{}\n", roff(name), roff(description));
+    }
+    page += ".SH ENVIRONMENT\n";
+    for (name, description) in ENVIRONMENT {
+        page += &format!(".TP\n.B {}\n{}\n", name, roff(description));
+    }
+    page
+}
+
+/// Render the given docopt usage message into a Markdown document.
+pub fn markdown(usage: &str, version: &str) -> String {
+    let usage = Usage::parse(usage);
+    let mut doc = format!("# mcp {}\n\nMSX CAS Packager\n\n## Synopsis\n\n", version);
+    for pattern in &usage.patterns {
+        doc += &format!("    mcp {}\n", pattern);
+    }
+    doc += &format!("\n## Description\n\n{}\n\n## Commands\n", DESCRIPTION);
+    for pattern in &usage.patterns {
+        doc += &format!("\n### `mcp {}`\n\n{}\n", command_of(pattern), long_help(pattern));
+        doc += &format!("\n    mcp {}\n", pattern);
+    }
+    doc += "\n## Options\n\n";
+    for (name, description) in &usage.options {
+        doc += &format!("* `{}`: {}\n", name, description);
+    }
+    doc += "\n## Environment\n\n";
+    for (name, description) in ENVIRONMENT {
+        doc += &format!("* `{}`: {}\n", name, description);
+    }
+    doc
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    static SAMPLE: &str = "
+Usage: mcp list [--bytes] <cas-file>
+       mcp meta show <cas-file>
+       mcp --version
+
+Options:
+    -v, --version               Print the mcp version
+    --bytes                     List the exact size of the files in bytes,
+                                instead of in human units
+";
+
+    #[test]
+    fn should_parse_usage() {
+        let usage = Usage::parse(SAMPLE);
+        assert_eq!(vec!["list [--bytes] <cas-file>", "meta show <cas-file>", "--version"], usage.patterns);
+        assert_eq!(
+            vec![
+                ("-v, --version", "Print the mcp version".to_string()),
+                ("--bytes", "List the exact size of the files in bytes, instead of in human units".to_string()),
+            ],
+            usage.options);
+        assert_eq!("list", command_of(usage.patterns[0]));
+        assert_eq!("meta show", command_of(usage.patterns[1]));
+    }
+
+    #[test]
+    fn should_render_docs() {
+        let page = man(SAMPLE, "1.0");
+        assert!(page.starts_with(".TH MCP 1"));
+        assert!(page.contains(".TP\n.B mcp meta show <cas\\-file>\nShow the metadata"));
+        assert!(page.contains(".B \\-\\-bytes\nList the exact size"));
+
+        let doc = markdown(SAMPLE, "1.0");
+        assert!(doc.contains("### `mcp list`\n\nList the files of a tape"));
+        assert!(doc.contains("* `--bytes`: List the exact size of the files in bytes, instead of in human units\n"));
+    }
+
+    #[test]
+    fn should_describe_every_command() {
+        for pattern in Usage::parse(crate::args::USAGE).patterns {
+            assert!(!long_help(pattern).is_empty(), "{}", pattern);
+        }
+    }
+}
@@ -0,0 +1,2001 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+extern crate docopt;
+#[macro_use]
+extern crate mcp;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+mod args;
+mod docs;
+
+#[cfg(feature = "disasm")]
+use mcp::disasm;
+use mcp::{
+    analyze, asm, basic, batch, cache, check, confirm, dat, defrag, dump, embed, file, filter, gap, hash, hook,
+    import, inlay, input, library, loader, manifest, map, meta, notes, plugin, policy, preview,
+    progress, rename, rpc, scaffold, screen, serve, speed, sym, tape, tapeset, template, tsx,
+    verifier, wav,
+};
+
+use std::convert::From;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process;
+
+use mcp::charset::Charset;
+use mcp::tape::Tape;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The git commit `mcp` was built from, if any (see `build.rs`)
+const GIT_HASH: &str = env!("MCP_GIT_HASH");
+
+const PREVIEW_WIDTH: usize = 2048;
+const PREVIEW_HEIGHT: usize = 256;
+
+/// Samples of the header shown before the data of a block in its close-up preview
+const PREVIEW_LEAD_SAMPLES: usize = 512;
+
+/// Samples shown in the close-up preview of a block
+const PREVIEW_BLOCK_SAMPLES: usize = 8192;
+
+/// The length of a side of a C60 tape, in seconds
+const C60_SIDE_SECONDS: f64 = 30.0 * 60.0;
+
+#[derive(Debug)]
+enum Error {
+    Io(io::Error),
+    Failed(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Failed(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[allow(dead_code)]
+fn main() {
+    let cmd = args::parse();
+    let result = match cmd {
+        args::Command::Version(json) => print_version(json),
+        args::Command::List(path, format, header_ids, syncs, annotate, only, style, times, screens) => {
+            input::load(&path, format, &syncs).map_err(Error::from).and_then(|tape| {
+                list_files(&path, &tape, &header_ids, annotate, only.as_deref(), &style, times)?;
+                match screens {
+                    Some(png_path) => save_screens(&tape, &header_ids, &png_path),
+                    None => Ok(()),
+                }
+            })
+        }
+        args::Command::Add(path, files, output, backup, syncs, policy, layout) => {
+            let input_files: Vec<&Path> = files.iter().map(|f| f.as_path()).collect();
+            add_files(&path, &input_files, output.as_deref(), backup.as_deref(), &syncs, &policy, &layout)
+        }
+        args::Command::Extract(path, format, header_ids, syncs, jobs, extraction) => {
+            extract_all(&path, format, &header_ids, &syncs, jobs, &extraction)
+        }
+        args::Command::Export(path, format, output, preview, cache, preset, gaps) => {
+            export(&path, format, &output, preview.as_ref(), cache.as_deref(), preset, &gaps)
+        }
+        args::Command::Verify(path, dat) => verify(&path, &dat),
+        args::Command::Compare(path, other) => compare(&path, &other),
+        args::Command::ExportAll(cas_dir, wav_dir, jobs) => export_all(&cas_dir, &wav_dir, jobs),
+        args::Command::SetList(path) => list_set(&path),
+        args::Command::SetExport(path, wav_dir, preset) => export_set(&path, &wav_dir, preset),
+        args::Command::Import(wav_path, path, report, channel, decoder) => {
+            import(&wav_path, &path, report.as_deref(), channel, decoder)
+        }
+        args::Command::ImportAll(wav_dir, cas_dir, jobs, channel, decoder) => {
+            import_all(&wav_dir, &cas_dir, jobs, channel, decoder)
+        }
+        args::Command::Roundtrip(path, output, preset, gaps, decoder) => {
+            roundtrip(&path, &output, preset, &gaps, decoder)
+        }
+        args::Command::MetaShow(path) => meta_show(&path),
+        args::Command::MetaGet(path, key) => meta_get(&path, &key),
+        args::Command::MetaSet(path, key, value) => meta_set(&path, &key, &value),
+        args::Command::NoteGet(path, name) => note_get(&path, &name),
+        args::Command::NoteSet(path, name, note) => note_set(&path, &name, &note),
+        args::Command::DedupLibrary(dir, jobs, action, yes) => dedup_library(&dir, jobs, &action, yes),
+        args::Command::Check(path, report, fail_on) => check_tape(&path, report.as_deref(), fail_on),
+        args::Command::Map(path, format, width) => print_map(&path, format, width),
+        args::Command::Analyze(path, format, width, sym) => print_analysis(&path, format, width, sym.as_deref()),
+        args::Command::Disasm(path, format, name, sym) => print_disasm(&path, format, &name, sym.as_deref()),
+        args::Command::Dump(path, format, target) => print_dump(&path, format, &target),
+        args::Command::Cat(path, format, name) => print_text(&path, format, &name),
+        args::Command::Filter(path, output, filter) => filter_tape(&path, &output, &filter),
+        args::Command::Remove(path, names, output, yes) => remove_files(&path, &names, output.as_deref(), yes),
+        args::Command::Rename(path, name, new_name, output) => rename_file(&path, &name, &new_name, output.as_deref()),
+        args::Command::RenameBulk(path, rules, output) => rename_bulk(&path, &rules, output.as_deref()),
+        args::Command::Undo(path, suffix, yes) => undo(&path, &suffix, yes),
+        args::Command::Repair(path, fixes, output, syncs, yes) => repair(&path, fixes, output.as_deref(), &syncs, yes),
+        args::Command::Serve(dir, port) => serve(&dir, port),
+        args::Command::AddLoader(path, name, output) => add_loader(&path, &name, output.as_deref()),
+        args::Command::AddVerifier(path, output) => add_verifier(&path, output.as_deref()),
+        args::Command::Inlay(path, output) => print_inlay(&path, output.as_deref()),
+        args::Command::Embed(path, wav, uri, wrap, output) => embed(&path, wav, uri, wrap, output.as_deref()),
+        args::Command::Manifest(path) => print_manifest(&path),
+        args::Command::Optimize(path, join_custom, output, yes) => optimize(&path, join_custom, output.as_deref(), yes),
+        args::Command::Defrag(path, output, yes) => defrag(&path, output.as_deref(), yes),
+        args::Command::Init(dir) => init(&dir),
+        args::Command::GenDocs(format) => gen_docs(format),
+        args::Command::Rpc => rpc(),
+    };
+    if let Err(e) = result {
+        match e {
+            Error::Io(e) => eprintln!("{}", tr!("Error: IO operation failed: {}", e)),
+            Error::Failed(msg) => eprintln!("{}", tr!("Error: {}", msg)),
+        }
+        process::exit(1);
+    }
+}
+
+fn print_version(json: bool) -> Result<()> {
+    if json {
+        let features: Vec<&str> = [("net", cfg!(feature = "net")), ("disasm", cfg!(feature = "disasm"))].iter().filter(|f| f.1).map(|f| f.0).collect();
+        let info = serde_json::json!({
+            "version": VERSION,
+            "git_hash": Some(GIT_HASH).filter(|h| !h.is_empty()),
+            "features": features,
+            "input_formats": input::Format::ALL,
+            "export_formats": ["wav", "tsx"],
+            "languages": ["en", "es", "ja"],
+        });
+        println!("{}", serde_json::to_string_pretty(&info).map_err(io::Error::from)?);
+        return Ok(());
+    }
+    println!("MSX CAS Packager (MCP) v{}", VERSION);
+    println!("Copyright (C) 2015 Alvaro Polo");
+    println!();
+    println!("{}", tr!("This program is subject to the terms of the Mozilla Public License v2.0."));
+    println!();
+    Ok(())
+}
+
+fn list_files(
+    path: &Path,
+    tape: &Tape,
+    header_ids: &[u8],
+    annotate: bool,
+    only: Option<&str>,
+    style: &args::Style,
+    times: Option<u32>,
+) -> Result<()> {
+    // Only the files that are listed are read
+    let entries: Vec<_> = tape.entries_with(header_ids).filter(|e| only.is_none_or(|name| e.is_named(name))).collect();
+    if let (Some(name), true) = (only, entries.is_empty()) {
+        return Err(Error::Failed(tape.no_file_named(name, header_ids).to_string()));
+    }
+    let plugins = plugin::Plugins::from_env()?;
+    let mut exporter = wav::Exporter::new();
+    if let Some(bauds) = times {
+        exporter.set_bauds(bauds);
+    }
+    if let args::Style::Format(template) = style {
+        for entry in &entries {
+            println!("{}", format_file(template, entry, &exporter));
+        }
+        return Ok(());
+    }
+    if *style == args::Style::Csv {
+        println!("{}", CSV_HEADER);
+        for entry in &entries {
+            println!("{}", file_csv(entry));
+        }
+        return Ok(());
+    }
+    if *style == args::Style::Json {
+        let files: Vec<serde_json::Value> = entries.iter().map(file_json).collect();
+        println!("{}", serde_json::to_string_pretty(&files).map_err(io::Error::from)?);
+        return Ok(());
+    }
+    let notes = notes::Notes::load(path)?;
+    let metadata = meta::Metadata::load(path)?;
+    if !metadata.is_empty() {
+        print_metadata(&metadata);
+        println!();
+    }
+    for entry in &entries {
+        // Exact byte counts on demand, human units and load times otherwise
+        let columns = |nbytes: usize| {
+            let size = if *style == args::Style::Bytes { format!("{:5} bytes", nbytes) } else { format!("{:>8}", format_size(nbytes)) };
+            format!("{} | {}", size, format_duration(exporter.seconds_of(entry.blocks())))
+        };
+        match entry.file() {
+            tape::File::Bin(name, begin, end, start, data) => {
+                print!("bin    | {:6} | {} | [0x{:x},0x{:x}]:0x{:x}", name, columns(data.len()), begin, end, start);
+                // Multi-stage loaders may jump outside of the loaded program
+                if annotate && (start < begin || start > end) {
+                    let side = if start < begin { tr!("before BEGIN") } else { tr!("after END") };
+                    print!("{}", tr!(" ! START {}, in {}", side, tr!(tape::memory_region(start))));
+                }
+                println!();
+            }
+            tape::File::Basic(name, data) => {
+                println!("basic  | {:6} | {} |", name, columns(data.len()));
+            }
+            tape::File::Ascii(name, data) => {
+                let nbytes = data.iter().fold(0, |size, chunk| size + chunk.len());
+                println!("ascii  | {:6} | {} |", name, columns(nbytes));
+            }
+            tape::File::Other(id, name, data) => {
+                println!("other  | {:6} | {} | id 0x{:02x}", name, columns(data.len()), id);
+            }
+            tape::File::Custom(data) => match plugins.for_block(data) {
+                Some(plugin) => {
+                    let name = plugin.name_of(data)?;
+                    println!("custom | {:6} | {} | {}", name, columns(data.len()), plugin.name);
+                }
+                None if screen::Screen::detect(data).is_some() => {
+                    println!("custom |        | {} | {}", columns(data.len()), tr!("SCREEN 2 image"))
+                }
+                None => println!("custom |        | {} |", columns(data.len())),
+            },
+        };
+        if let Some(note) = entry.header_name().and_then(|name| notes.get(name)) {
+            println!("       | {}", note);
+        }
+        if *style == args::Style::Verbose {
+            let blocks = entry.block_range();
+            let offset: usize = tape.blocks()[..blocks.start].iter().map(|b| b.data().len()).sum();
+            let tape_bytes: usize = entry.blocks().iter().map(|b| b.data().len()).sum();
+            println!("       | {}", tr!(
+                "offset {}, {} blocks ({}), {} bytes with headers",
+                format!("0x{:06x}", offset),
+                blocks.len(),
+                format!("{}-{}", blocks.start, blocks.end - 1),
+                tape_bytes,
+            ));
+        }
+    }
+    let seconds: f64 = entries.iter().map(|e| exporter.seconds_of(e.blocks())).sum();
+    let bytes: usize = entries.iter().map(|e| file_fields(&e.file()).2).sum();
+    let tape_bytes: usize = entries.iter().flat_map(|e| e.blocks()).map(|b| b.data().len()).sum();
+    let size = |nbytes: usize| if *style == args::Style::Bytes { format!("{} bytes", nbytes) } else { format_size(nbytes) };
+    println!();
+    println!("{}", tr!(
+        "Total: {} files, {} blocks, {} of data, {} in the CAS file, {} at {} bauds",
+        entries.len(),
+        entries.iter().map(|e| e.blocks().len()).sum::<usize>(),
+        size(bytes),
+        size(tape_bytes),
+        format_duration(seconds),
+        exporter.bauds(),
+    ));
+    if times.is_some() {
+        if seconds <= C60_SIDE_SECONDS {
+            println!("{}", tr!("It fits in a side of a C60 tape ({})", format_duration(C60_SIDE_SECONDS)));
+        } else {
+            println!("{}", tr!("It does not fit in a side of a C60 tape ({})", format_duration(C60_SIDE_SECONDS)));
+        }
+    }
+    let garbage = tape.trailing_garbage().len();
+    if garbage > 0 {
+        println!();
+        println!("{}", tr!("Warning: {} bytes of trailing garbage found after the last file", garbage));
+    }
+    Ok(())
+}
+
+/// Render the SCREEN 2 images found in the custom files of the given tape into PNG files
+/// named after the given one, with the position of each file (e.g., `screen-3.png`).
+fn save_screens(tape: &Tape, header_ids: &[u8], png_path: &Path) -> Result<()> {
+    let stem = png_path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = png_path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut saved = 0;
+    for (i, entry) in tape.entries_with(header_ids).enumerate() {
+        let screen = match entry.file() {
+            tape::File::Custom(data) => screen::Screen::detect(data).map(|s| s.render()),
+            _ => None,
+        };
+        if let Some(image) = screen {
+            let path = png_path.with_file_name(format!("{}-{}{}", stem, i, ext));
+            let mut png = vec![];
+            image.write_png(&mut png)?;
+            file::write_content(&path, &png)?;
+            println!("{}", tr!("SCREEN 2 image of file {} rendered into {}", i, quoted(&path)));
+            saved += 1;
+        }
+    }
+    if saved == 0 {
+        return Err(Error::Failed(tr!("no SCREEN 2 images found in the custom files of the tape")));
+    }
+    Ok(())
+}
+
+/// Format the given file of a tape with the given template (see `template::FILE_FIELDS`).
+fn format_file(template: &template::Template, entry: &tape::Entry, exporter: &wav::Exporter) -> String {
+    let file = entry.file();
+    let content = file.content();
+    let (kind, name, bytes, addresses) = file_fields(&file);
+    let address = |pick: fn((usize, usize, usize)) -> usize| {
+        addresses.map(|a| format!("0x{:04x}", pick(a))).unwrap_or_default()
+    };
+    template.render(|field| match field {
+        "type" => kind.to_string(),
+        "name" => name.to_string(),
+        "bytes" => bytes.to_string(),
+        "size" => format_size(bytes),
+        "time" => format_duration(exporter.seconds_of(entry.blocks())),
+        "begin" => address(|(begin, _, _)| begin),
+        "end" => address(|(_, end, _)| end),
+        "start" => address(|(_, _, start)| start),
+        "crc32" => format!("{:08x}", hash::crc32(&content)),
+        "sha1" => hash::sha1(&content),
+        _ => String::new(),
+    })
+}
+
+/// Describe the given file of a tape in JSON, with its addresses if it is a binary file and
+/// the positions of its first and last blocks.
+fn file_json(entry: &tape::Entry) -> serde_json::Value {
+    let file = entry.file();
+    let (kind, name, bytes, addresses) = file_fields(&file);
+    let blocks = entry.block_range();
+    let mut json = serde_json::json!({
+        "type": kind,
+        "name": Some(name).filter(|_| entry.kind().is_some()),
+        "bytes": bytes,
+        "tape_bytes": entry.blocks().iter().map(|b| b.data().len()).sum::<usize>(),
+        "blocks": [blocks.start, blocks.end - 1],
+    });
+    if let Some((begin, end, start)) = addresses {
+        json["begin"] = begin.into();
+        json["end"] = end.into();
+        json["start"] = start.into();
+    }
+    json
+}
+
+/// The header row of the files listed in CSV, whose columns are kept as they are so scripts
+/// may rely on them
+const CSV_HEADER: &str = "type,name,bytes,begin,end,start,blocks";
+
+/// Describe the given file of a tape as a CSV row (see `CSV_HEADER`), with hex addresses
+/// that are empty unless it is a binary file.
+fn file_csv(entry: &tape::Entry) -> String {
+    let file = entry.file();
+    let (kind, name, bytes, addresses) = file_fields(&file);
+    let address = |pick: fn((usize, usize, usize)) -> usize| {
+        addresses.map(|a| format!("0x{:04x}", pick(a))).unwrap_or_default()
+    };
+    // Names are quoted if needed, as they may have commas or quotes
+    let name = if name.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    } else {
+        name.to_string()
+    };
+    format!(
+        "{},{},{},{},{},{},{}",
+        kind,
+        name,
+        bytes,
+        address(|(begin, _, _)| begin),
+        address(|(_, end, _)| end),
+        address(|(_, _, start)| start),
+        entry.blocks().len(),
+    )
+}
+
+/// Returns the type, the name, the size in bytes and the addresses, if it is a binary file,
+/// of the given file of a tape.
+fn file_fields<'a>(file: &'a tape::File) -> (&'static str, &'a str, usize, Option<(usize, usize, usize)>) {
+    match file {
+        tape::File::Bin(name, begin, end, start, data) => ("bin", name.as_str(), data.len(), Some((*begin, *end, *start))),
+        tape::File::Basic(name, data) => ("basic", name.as_str(), data.len(), None),
+        tape::File::Ascii(name, chunks) => ("ascii", name.as_str(), chunks.iter().map(|c| c.len()).sum(), None),
+        tape::File::Other(_, name, data) => ("other", name.as_str(), data.len(), None),
+        tape::File::Custom(data) => ("custom", "", data.len(), None),
+    }
+}
+
+fn meta_show(path: &Path) -> Result<()> {
+    print_metadata(&meta::Metadata::load(path)?);
+    Ok(())
+}
+
+fn meta_get(path: &Path, key: &str) -> Result<()> {
+    let metadata = meta::Metadata::load(path)?;
+    if let Some(value) = metadata.get(key)? {
+        println!("{}", value);
+    }
+    Ok(())
+}
+
+fn meta_set(path: &Path, key: &str, value: &str) -> Result<()> {
+    let _lock = file::lock(path)?;
+    let mut metadata = meta::Metadata::load(path)?;
+    metadata.set(key, value)?;
+    metadata.save(path)?;
+    Ok(())
+}
+
+fn note_get(path: &Path, name: &str) -> Result<()> {
+    let name = header_name_of(&Tape::from_file(path)?, name)?;
+    if let Some(note) = notes::Notes::load(path)?.get(&name) {
+        println!("{}", note);
+    }
+    Ok(())
+}
+
+fn note_set(path: &Path, name: &str, note: &str) -> Result<()> {
+    let _lock = file::lock(path)?;
+    let name = header_name_of(&Tape::from_file(path)?, name)?;
+    let mut notes = notes::Notes::load(path)?;
+    notes.set(&name, note);
+    notes.save(path)?;
+    Ok(())
+}
+
+/// Returns the name stored in the header of the file with the given name, either as stored
+/// or as extracted, so `game.bin` is noted as `game`.
+fn header_name_of(tape: &Tape, name: &str) -> Result<String> {
+    match tape.entries_with(&[]).find(|e| e.is_named(name)).and_then(|e| e.header_name()) {
+        Some(name) => Ok(name.to_string()),
+        None => Err(Error::Failed(tape.no_file_named(name, &[]).to_string())),
+    }
+}
+
+fn print_metadata(metadata: &meta::Metadata) {
+    for (key, value) in metadata.fields() {
+        println!("{:10} {}", format!("{}:", key), value);
+    }
+}
+
+fn extract_all(
+    path: &Path,
+    format: Option<input::Format>,
+    header_ids: &[u8],
+    syncs: &[[u8; 8]],
+    jobs: Option<usize>,
+    extraction: &args::Extraction,
+) -> Result<()> {
+    let (headerless, numbered) = (extraction.headerless, extraction.numbered);
+    let tape = input::load(path, format, syncs)?;
+    if let Some(name) = &extraction.stdout {
+        return extract_to_stdout(&tape, header_ids, name, extraction.charset);
+    }
+    let out_dir = extraction.output_dir.as_deref().unwrap_or_else(|| Path::new(""));
+    if !out_dir.as_os_str().is_empty() {
+        fs::create_dir_all(out_dir)?;
+    }
+    let plugins = plugin::Plugins::from_env()?;
+    // Header-less streams are extracted block by block, as custom files, ignoring any header
+    let tape_files: Vec<tape::File> = if headerless {
+        tape.blocks().iter().map(|b| tape::File::Custom(b.data_without_prefix())).collect()
+    } else {
+        tape.files_with(header_ids).collect()
+    };
+    let jobs = jobs.unwrap_or_else(batch::default_jobs);
+
+    // Output file names are chosen beforehand, so files with the same name do not clash
+    let mut next_custom = 0;
+    let mut reserved = vec![];
+    let mut files = vec![];
+    let count = tape_files.len();
+    for (i, file) in tape_files.into_iter().enumerate() {
+        let plugin = match &file {
+            tape::File::Custom(data) if !headerless => plugins.for_block(data),
+            _ => None,
+        };
+        let out_path = match (&file, plugin) {
+            (tape::File::Custom(data), Some(plugin)) => format!("{}.{}", plugin.name_of(data)?, plugin.extension),
+            // The position already tells numbered custom files apart
+            (tape::File::Custom(_), None) if numbered => "custom.dat".to_string(),
+            _ => file.name().map(|n| n.to_string()).unwrap_or_else(|| {
+                format!("custom.{:03}", {
+                    next_custom += 1;
+                    next_custom
+                })
+            }),
+        };
+        let out_path = if numbered { file::numbered(i + 1, count, &out_path) } else { out_path };
+        // Files of other types are skipped once named, so the rest keep the names they get
+        // when the whole tape is extracted
+        if !extracts(extraction, &file) {
+            continue;
+        }
+        let (out_filename, clash) = file::unique_filename(&out_dir.join(&out_path), &reserved)?;
+        reserved.push(out_filename.clone());
+        files.push((file, plugin, out_path, out_filename, clash));
+    }
+
+    let results = batch::run(&files, jobs, |(file, plugin, _, out_filename, _)| {
+        extract_file(file, *plugin, extraction.charset, out_filename)
+    });
+    for ((_, _, out_path, out_filename, clash), result) in files.iter().zip(results) {
+        print!("{}", tr!("Extracting {}... ", out_path));
+        if *clash {
+            let warning = tr!(
+                "filename {} already exists, writing output to {}",
+                format!("{:?}", out_path),
+                format!("{:?}", out_filename));
+            print!("{}", tr!("Warning: {}... ", warning));
+        }
+        result?;
+        println!("{}", tr!("Done"));
+    }
+    Ok(())
+}
+
+/// Write the file with the given name into the standard output, so it can be piped into
+/// other programs, printing the messages into the standard error instead.
+fn extract_to_stdout(tape: &Tape, header_ids: &[u8], name: &str, charset: Charset) -> Result<()> {
+    let file = match tape.entries_with(header_ids).find(|e| e.is_named(name)) {
+        Some(entry) => entry.file(),
+        None => return Err(Error::Failed(tape.no_file_named(name, header_ids).to_string())),
+    };
+    eprint!("{}", tr!("Extracting {}... ", name));
+    let mut stdout = io::stdout().lock();
+    match file {
+        tape::File::Ascii(..) if charset != Charset::Raw => stdout.write_all(&charset.decode(&file.content()))?,
+        _ => file.write_to(&mut stdout)?,
+    }
+    stdout.flush()?;
+    eprintln!("{}", tr!("Done"));
+    Ok(())
+}
+
+/// Returns `true` if the given file is of one of the types to extract, if any.
+fn extracts(extraction: &args::Extraction, file: &tape::File) -> bool {
+    let kind = match file {
+        tape::File::Bin(..) => "bin",
+        tape::File::Basic(..) => "bas",
+        tape::File::Ascii(..) => "asc",
+        tape::File::Custom(_) => "custom",
+        tape::File::Other(..) => "other",
+    };
+    extraction.types.is_empty() || extraction.types.iter().any(|t| t == kind)
+}
+
+fn extract_file(
+    file: &tape::File,
+    plugin: Option<&plugin::Plugin>,
+    charset: Charset,
+    out_filename: &Path,
+) -> Result<()> {
+    if let (tape::File::Custom(data), Some(plugin)) = (file, plugin) {
+        file::write_content(out_filename, &plugin.extract(data)?)?;
+        return Ok(());
+    }
+    if matches!(file, tape::File::Ascii(..)) && charset != Charset::Raw {
+        file::write_content(out_filename, &charset.decode(&file.content()))?;
+        return Ok(());
+    }
+    let mut ofile = File::create(out_filename)?;
+    file.write_to(&mut ofile)?;
+    Ok(())
+}
+
+fn add_files(
+    path: &Path,
+    files: &[&Path],
+    output: Option<&Path>,
+    backup: Option<&str>,
+    syncs: &[[u8; 8]],
+    policy: &policy::Policy,
+    layout: &args::Layout,
+) -> Result<()> {
+    let target = output.unwrap_or(path);
+    let _lock = file::lock(target)?;
+    let mut padding = 0;
+    let mut tape = file::read_content(path)
+        .map(|bytes| Tape::from_bytes_with(&bytes, syncs))
+        .unwrap_or_else(|_| Tape::new());
+    let plugins = plugin::Plugins::from_env()?;
+    for file in files {
+        hook::Hook::PreAdd.run(file, serde_json::json!({ "tape": target, "file": file }))?;
+        // Files extracted with their position are named in the tape without it
+        let name = if layout.numbered { file::without_number(file) } else { file.to_path_buf() };
+        if layout.headerless {
+            padding += add_custom_file(&mut tape, file, policy, layout.block_size)?;
+        } else if let Some(plugin) = plugins.for_file(file) {
+            padding += add_plugin_file(&mut tape, file, plugin)?;
+        } else if file::is_bin_file(file) {
+            padding += add_bin_file(&mut tape, file, &name, policy)?;
+        } else if file::is_ascii_file(file) {
+            add_ascii_file(&mut tape, file, &name, policy, layout.crlf, layout.charset)?;
+        } else if file::is_basic_file(file) {
+            padding += add_basic_file(&mut tape, file, &name, policy, layout.tokenize)?;
+        } else {
+            padding += add_custom_file(&mut tape, file, policy, layout.block_size)?;
+        };
+    }
+    if let Some(suffix) = backup {
+        backup_tape(target, suffix)?;
+    }
+    save_tape(&tape, target)?;
+
+    if padding > 0 && policy.level(policy::Check::Alignment) != policy::Level::Off {
+        println!();
+        println!(
+            "{}",
+            tr!("Warning: some files had lengths that required padding with zeroes to be aligned
+to 8-byte boundaries. This is a constraint of CAS file format: every data block
+must start in an offset divisible by 8.
+
+For binary files, this means the total length of the file excluding the
+0x1F prefix must be 8-byte aligned.
+
+For ASCII files, this does not affect you. ASCII files are always aligned to
+256-byte boundaries and padded with EOF values (0x1A) needed by MSX BIOS to
+detect the end of the file.
+
+For custom files, the effect is unknown. These files are loaded using custom
+code. And if padding zeroes affect or not depends on that code.
+
+Using the right file sizes is highly recommended to prevent problems. However
+this is not considered as an error, and your CAS package has been successfully
+generated.")
+        );
+    }
+    Ok(())
+}
+
+fn add_bin_file(tape: &mut tape::Tape, file: &Path, name: &Path, policy: &policy::Policy) -> Result<usize> {
+    print!("{}", tr!("Adding binary file {}... ", quoted(file)));
+
+    let mut data = file::read_content(file)?;
+    // The addresses of assembled programs are taken from the listing and symbols files
+    if let Some(build) = asm::Build::load(file)? {
+        let (headed, (begin, end, start)) = build.apply(&data).map_err(|e| Error::Failed(format!("{}: {}", quoted(file), e)))?;
+        print!("{}", tr!("BEGIN 0x{}, END 0x{}, START 0x{} from the assembler... ",
+            format!("{:04x}", begin), format!("{:04x}", end), format!("{:04x}", start)));
+        data = headed;
+    }
+    validate(policy, policy::check_file(name, &data)?)?;
+
+    let (fname, _) = file::file_name_of(name)?;
+    let padding = tape.append_bin(&fname, &data)?;
+    if padding == 0 {
+        println!("{}", tr!("Done"));
+    } else {
+        println!("{}", tr!("Done (padded with {} bytes!)", padding));
+    }
+    Ok(padding)
+}
+
+fn add_basic_file(
+    tape: &mut tape::Tape,
+    file: &Path,
+    name: &Path,
+    policy: &policy::Policy,
+    tokenize: bool,
+) -> Result<usize> {
+    print!("{}", tr!("Adding basic file {}... ", quoted(file)));
+
+    let mut data = file::read_content(file)?;
+    // Listings are told apart from tokenized programs by the lack of the Basic ID byte
+    if tokenize && data.first() != Some(&0xff) {
+        data = basic::tokenize(&data).map_err(|e| Error::Failed(tr!("cannot tokenize {}: {}", quoted(file), e)))?;
+    }
+    validate(policy, policy::check_file(name, &data)?)?;
+
+    let (fname, _) = file::file_name_of(name)?;
+    let padding = tape.append_basic(&fname, &data)?;
+
+    if padding == 0 {
+        println!("{}", tr!("Done"));
+    } else {
+        println!("{}", tr!("Done (padded with {} bytes!)", padding));
+    }
+    Ok(padding)
+}
+
+fn add_ascii_file(
+    tape: &mut tape::Tape,
+    file: &Path,
+    name: &Path,
+    policy: &policy::Policy,
+    crlf: bool,
+    charset: Charset,
+) -> Result<usize> {
+    print!("{}", tr!("Adding ascii file {}... ", quoted(file)));
+
+    let data = file::read_content(file)?;
+    let data = charset.encode(&data).map_err(|e| Error::Failed(tr!("cannot convert {}: {}", quoted(file), e)))?;
+    let data = if crlf { file::with_crlf(&data) } else { data };
+    validate(policy, policy::check_file(name, &data)?)?;
+
+    let (fname, _) = file::file_name_of(name)?;
+    let padding = tape.append_ascii(&fname, &data)?;
+    println!("{}", tr!("Done"));
+    Ok(padding)
+}
+
+fn add_custom_file(
+    tape: &mut tape::Tape,
+    file: &Path,
+    policy: &policy::Policy,
+    block_size: Option<usize>,
+) -> Result<usize> {
+    print!("{}", tr!("Adding custom file {}... ", quoted(file)));
+
+    // Raw data has no header to check, whatever its extension
+    let data = file::read_content(file)?;
+    validate(policy, policy::check_alignment(data.len()))?;
+    let append = match block_size {
+        Some(size) => tape.append_custom_blocks(&data, size)?,
+        None => tape.append_custom(&data)?,
+    };
+
+    if append == 0 {
+        println!("{}", tr!("Done"));
+    } else {
+        println!("{}", tr!("Done (padded with {} bytes!)", append));
+    }
+
+    Ok(append)
+}
+
+fn add_plugin_file(tape: &mut tape::Tape, file: &Path, plugin: &plugin::Plugin) -> Result<usize> {
+    print!("{}", tr!("Adding {} file {}... ", plugin.name, quoted(file)));
+
+    let data = plugin.pack(&file::read_content(file)?)?;
+    let append = tape.append_custom(&data)?;
+
+    if append == 0 {
+        println!("{}", tr!("Done"));
+    } else {
+        println!("{}", tr!("Done (padded with {} bytes!)", append));
+    }
+
+    Ok(append)
+}
+
+/// Enforce the given policy on the violations of the checks of a file, printing a warning
+/// for each violation that does not make it fail.
+fn validate(policy: &policy::Policy, violations: Vec<policy::Violation>) -> Result<()> {
+    for warning in policy.enforce(violations)? {
+        print!("{}", tr!("Warning: {}... ", warning.message));
+    }
+    Ok(())
+}
+
+fn save_tape(tape: &tape::Tape, file: &Path) -> Result<()> {
+    file::write_content(file, &tape.to_bytes())?;
+    Ok(())
+}
+
+fn backup_tape(path: &Path, suffix: &str) -> Result<()> {
+    if let Some(backup) = file::backup(path, suffix)? {
+        println!("{}", tr!("Backup of {} saved into {}", quoted(path), quoted(&backup)));
+    }
+    Ok(())
+}
+
+/// Ask the user to confirm a destructive operation with the given question, unless `yes` is
+/// given, failing if it is not confirmed.
+fn confirm(question: &str, yes: bool) -> Result<()> {
+    if yes || confirm::confirm(question)? {
+        Ok(())
+    } else {
+        Err(Error::Failed(tr!("cancelled, nothing was changed")))
+    }
+}
+
+fn undo(path: &Path, suffix: &str, yes: bool) -> Result<()> {
+    let _lock = file::lock(path)?;
+    if file::exists(&file::backup_path(path, suffix)?) {
+        confirm(&tr!("Overwrite {} with its backup?", quoted(path)), yes)?;
+    }
+    let backup = file::restore(path, suffix)?;
+    println!("{}", tr!("{} restored from {}", quoted(path), quoted(&backup)));
+    Ok(())
+}
+
+fn repair(path: &Path, fixes: args::Fixes, output: Option<&Path>, syncs: &[[u8; 8]], yes: bool) -> Result<()> {
+    let target = output.unwrap_or(path);
+    let _lock = file::lock(target)?;
+    let mut tape = Tape::from_bytes_with(&file::read_content(path)?, syncs);
+    let lossy = apply_fixes(&mut tape, &fixes)?;
+    let recovered = if fixes.recover_bin { tape.recover_bin_headers() } else { vec![] };
+    if fixes.recover_bin {
+        for change in &recovered {
+            println!("{}", change);
+        }
+        println!("{}", tr!("Recovered the addresses of {} binary files", recovered.len()));
+    }
+    if (lossy || !recovered.is_empty()) && output.is_none() {
+        confirm(&tr!("Overwrite {} with the repaired tape?", quoted(path)), yes)?;
+    } else if !recovered.is_empty() {
+        // The addresses are guessed, so they are not written without asking
+        confirm(&tr!("Write the recovered addresses into {}?", quoted(target)), yes)?;
+    }
+    save_tape(&tape, target)?;
+    println!("{}", tr!("{} blocks written into {}", tape.blocks().len(), quoted(target)));
+    if !recovered.is_empty() {
+        flag_recovered(&tape, &recovered, target)?;
+    }
+    Ok(())
+}
+
+/// Join, normalize and trim the given tape as the given fixes say, and tell whether any of
+/// them changed the tape.
+fn apply_fixes(tape: &mut Tape, fixes: &args::Fixes) -> Result<bool> {
+    let garbage = tape.trailing_garbage().len();
+    let mut lossy = false;
+    if let Some(join) = fixes.join.clone() {
+        let (first, last) = (*join.start(), *join.end());
+        let padding = tape.join_blocks(join)?;
+        println!("{}", tr!("Joined blocks {} to {} into one block, padded with {} bytes", first, last, padding));
+        lossy = true;
+    }
+    if fixes.normalize {
+        let changes = tape.normalize();
+        for change in &changes {
+            println!("{}", change);
+        }
+        println!("{}", tr!("{} changes made to normalize the tape", changes.len()));
+        lossy |= !changes.is_empty();
+    } else if garbage > 0 && fixes.trim {
+        tape.trim_trailing_garbage();
+        println!("{}", tr!("Removed {} bytes of trailing garbage", garbage));
+        lossy = true;
+    } else if garbage > 0 {
+        println!("{}", tr!("Kept {} bytes of trailing garbage (use --trim to remove them)", garbage));
+    }
+    Ok(lossy)
+}
+
+/// Note the binary files whose addresses were recovered, along with their original ones, so
+/// they are known to be guessed.
+fn flag_recovered(tape: &Tape, recovered: &[tape::Change], cas_path: &Path) -> Result<()> {
+    let mut notes = notes::Notes::load(cas_path)?;
+    for change in recovered {
+        if let tape::Change::Addresses { block, from: (begin, end, start), .. } = change {
+            let name = tape.blocks()[block - 1].file_name().unwrap_or_default().to_string();
+            let flag = format!("addresses recovered by mcp repair, stored as [0x{:x},0x{:x}]:0x{:x}", begin, end, start);
+            let note = match notes.get(&name) {
+                Some(note) if note.contains(&flag) => continue,
+                Some(note) => format!("{}\n{}", note, flag),
+                None => flag,
+            };
+            notes.set(&name, &note);
+        }
+    }
+    notes.save(cas_path)?;
+    Ok(())
+}
+
+fn optimize(path: &Path, join_custom: bool, output: Option<&Path>, yes: bool) -> Result<()> {
+    let target = output.unwrap_or(path);
+    let _lock = file::lock(target)?;
+    let mut tape = Tape::from_bytes(&file::read_content(path)?);
+    let exporter = wav::Exporter::new();
+    let (bytes, seconds) = (tape.to_bytes().len(), exporter.seconds_of(tape.blocks()));
+    for entry in tape.entries_with(&[]) {
+        let padding = entry.padding();
+        if padding > 0 {
+            let kind = entry.kind().map(|kind| kind.name()).unwrap_or("custom");
+            let name = entry.header_name().unwrap_or_default();
+            println!("{:6} | {:6} | {}", kind, name, tr!("{} bytes of padding", format!("{:5}", padding)));
+        }
+    }
+
+    let trimmed = tape.trim_bin_blocks();
+    if trimmed > 0 {
+        println!("{}", tr!("Trimmed {} bytes past the END address of binary files", trimmed));
+    }
+    if join_custom {
+        // Joining a run shifts the blocks after it, so the last runs are joined first
+        for run in tape.custom_runs().into_iter().rev() {
+            let (first, last) = (*run.start(), *run.end());
+            let padding = tape.join_blocks(run)?;
+            println!("{}", tr!("Joined blocks {} to {} into one block, padded with {} bytes", first, last, padding));
+        }
+    }
+    let (new_bytes, new_seconds) = (tape.to_bytes().len(), exporter.seconds_of(tape.blocks()));
+    if new_bytes == bytes {
+        println!("{}", tr!("{} cannot be optimized any further", quoted(path)));
+        return Ok(());
+    }
+    println!("{}", tr!("Saved {} bytes ({} to {}) and {} of audio ({} to {})",
+        bytes - new_bytes, bytes, new_bytes, format_duration(seconds - new_seconds),
+        format_duration(seconds), format_duration(new_seconds)));
+    if output.is_none() {
+        confirm(&tr!("Overwrite {} with the optimized tape?", quoted(path)), yes)?;
+    }
+    save_tape(&tape, target)?;
+    println!("{}", tr!("{} blocks written into {}", tape.blocks().len(), quoted(target)));
+    Ok(())
+}
+
+fn defrag(path: &Path, output: Option<&Path>, yes: bool) -> Result<()> {
+    let target = output.unwrap_or(path);
+    let _lock = file::lock(target)?;
+    let (tape, changes) = defrag::defrag(&file::read_content(path)?);
+    if tape.blocks().is_empty() {
+        return Err(Error::Failed(tr!("no blocks found in {}", quoted(path))));
+    }
+    for change in &changes {
+        println!("{}", change);
+    }
+    if changes.is_empty() {
+        println!("{}", tr!("{} is already defragmented", quoted(path)));
+        return Ok(());
+    }
+    println!("{}", tr!("{} changes made to defragment the tape", changes.len()));
+    if output.is_none() {
+        confirm(&tr!("Overwrite {} with the defragmented tape?", quoted(path)), yes)?;
+    }
+    save_tape(&tape, target)?;
+    println!("{}", tr!("{} blocks written into {}", tape.blocks().len(), quoted(target)));
+    Ok(())
+}
+
+fn init(dir: &Path) -> Result<()> {
+    let dir_name = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let name = scaffold::project_name(&dir_name.file_name().unwrap_or_default().to_string_lossy());
+    let files = scaffold::files(&name);
+    // Nothing is written if any file of the project is already there
+    if let Some((path, _)) = files.iter().find(|(path, _)| file::exists(&dir.join(path))) {
+        return Err(Error::Failed(tr!("{} already exists", quoted(&dir.join(path)))));
+    }
+    for sub in scaffold::DIRS {
+        fs::create_dir_all(dir.join(sub))?;
+    }
+    for (path, content) in &files {
+        file::write_content(&dir.join(path), content)?;
+        println!("{}", tr!("Created {}", quoted(&dir.join(path))));
+    }
+    println!();
+    println!("{}", tr!("Project {} created: run make in {} to build {}.cas, {}.wav and {}.tsx",
+        name, quoted(dir), name, name, name));
+    Ok(())
+}
+
+fn print_dump(path: &Path, format: Option<input::Format>, target: &args::DumpTarget) -> Result<()> {
+    let tape = input::load(path, format, &[])?;
+    let data = match target {
+        args::DumpTarget::Block(i) => match tape.blocks().get(*i) {
+            Some(block) => block.data_without_prefix().to_vec(),
+            None => return Err(Error::Failed(tr!("there is no block {} in the tape", i))),
+        },
+        args::DumpTarget::File(name) => match tape.entries_with(&[]).find(|e| e.is_named(name)) {
+            Some(entry) => entry.file().content(),
+            None => return Err(Error::Failed(tape.no_file_named(name, &[]).to_string())),
+        },
+    };
+    for line in dump::hexdump(&data) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Print the ASCII or Basic file with the given name as text, listing Basic programs as
+/// `LIST` does.
+fn print_text(path: &Path, format: Option<input::Format>, name: &str) -> Result<()> {
+    let tape = input::load(path, format, &[])?;
+    let text = match tape.entries_with(&[]).find(|e| e.is_named(name)).map(|e| e.file()) {
+        Some(file @ tape::File::Ascii(..)) => file.content(),
+        Some(tape::File::Basic(_, program)) => basic::detokenize(program),
+        Some(_) => return Err(Error::Failed(tr!("{} is not an ASCII or Basic file", name))),
+        None => return Err(Error::Failed(tape.no_file_named(name, &[]).to_string())),
+    };
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(&text)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+#[cfg(feature = "disasm")]
+fn print_disasm(path: &Path, format: Option<input::Format>, name: &str, sym: Option<&Path>) -> Result<()> {
+    let tape = input::load(path, format, &[])?;
+    let symbols = sym.map(sym::Symbols::load).transpose()?.unwrap_or_default();
+    let entry = tape.entries_with(&[]).find(|e| e.is_named(name));
+    let (begin, end, start, content) = match entry.map(|e| e.file()) {
+        Some(tape::File::Bin(_, begin, end, start, content)) => (begin, end, start, content),
+        Some(_) => return Err(Error::Failed(tr!("{} is not a binary file", name))),
+        None => return Err(Error::Failed(tape.no_file_named(name, &[]).to_string())),
+    };
+    if start < begin || start > end {
+        return Err(Error::Failed(tr!("the START address of {} is outside its BEGIN-END range", name)));
+    }
+    let code = content.get(6..).unwrap_or_default();
+    let code = &code[..code.len().min(end - begin + 1)];
+    let instructions = disasm::disassemble(begin as u16, code, start - begin);
+
+    println!("; {}: BEGIN 0x{:04x}, END 0x{:04x}, START 0x{:04x}", name, begin, end, start);
+    // Label the instructions jumped to from the code
+    let targets: std::collections::BTreeSet<u16> = instructions.iter().filter_map(|i| i.target).collect();
+    for instruction in &instructions {
+        let addr = instruction.addr;
+        if let Some(symbol) = symbols.name_of(addr) {
+            println!("{}:", symbol);
+        } else if addr as usize == start {
+            println!("START:");
+        } else if targets.contains(&addr) {
+            println!("L{:04x}:", addr);
+        }
+        let bytes: Vec<String> = instruction.bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let routine = instruction.target.and_then(|t| symbols.name_of(t).or_else(|| disasm::bios_routine(t)));
+        match routine {
+            Some(routine) => println!("{:04x}  {:<12} {:<20} ; {}", addr, bytes.join(" "), instruction.text, routine),
+            None => println!("{:04x}  {:<12} {}", addr, bytes.join(" "), instruction.text),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "disasm"))]
+fn print_disasm(_path: &Path, _format: Option<input::Format>, _name: &str, _sym: Option<&Path>) -> Result<()> {
+    Err(Error::Failed(tr!("mcp was built without the disassembler (`disasm` feature)")))
+}
+
+fn filter_tape(path: &Path, output: &Path, filter: &filter::Filter) -> Result<()> {
+    let _lock = file::lock(output)?;
+    let tape = Tape::from_bytes(&file::read_content(path)?);
+    let mut bytes = vec![];
+    let (mut kept, mut total) = (0, 0);
+    for (i, entry) in tape.entries_with(&[]).enumerate() {
+        total += 1;
+        if filter.keeps(i, &entry) {
+            kept += 1;
+            for block in entry.blocks() {
+                bytes.extend_from_slice(block.data());
+            }
+        }
+    }
+    file::write_content(output, &bytes)?;
+    println!("{}", tr!("{} of {} files written into {}", kept, total, quoted(output)));
+    Ok(())
+}
+
+fn remove_files(path: &Path, names: &[String], output: Option<&Path>, yes: bool) -> Result<()> {
+    let target = output.unwrap_or(path);
+    let _lock = file::lock(target)?;
+    let mut tape = Tape::from_bytes(&file::read_content(path)?);
+    // Nothing is written unless every file is found
+    for name in names {
+        let blocks = tape.remove_file(name)?;
+        println!("{}", tr!("Removed {} ({} blocks)", name, blocks));
+    }
+    if output.is_none() {
+        confirm(&tr!("Overwrite {} without the removed files?", quoted(path)), yes)?;
+    }
+    save_tape(&tape, target)?;
+    println!("{}", tr!("{} blocks written into {}", tape.blocks().len(), quoted(target)));
+    Ok(())
+}
+
+fn rename_file(path: &Path, name: &str, new_name: &str, output: Option<&Path>) -> Result<()> {
+    let target = output.unwrap_or(path);
+    let _lock = file::lock(target)?;
+    let mut tape = Tape::from_bytes(&file::read_content(path)?);
+    tape.rename_file(name, new_name).map_err(Error::Failed)?;
+    save_tape(&tape, target)?;
+    println!("{:6} -> {}", name, new_name);
+    println!("{}", tr!("File renamed in {}", quoted(target)));
+    Ok(())
+}
+
+fn rename_bulk(path: &Path, rules_path: &Path, output: Option<&Path>) -> Result<()> {
+    let target = output.unwrap_or(path);
+    let _lock = file::lock(target)?;
+    let rules = rename::Rules::parse(&String::from_utf8_lossy(&file::read_content(rules_path)?))?;
+    let mut tape = Tape::from_bytes(&file::read_content(path)?);
+    let mut renames = vec![];
+    let mut block = 0;
+    for entry in tape.entries_with(&[]) {
+        if let Some(name) = entry.header_name() {
+            if let Some(new) = rules.apply(name).filter(|new| new != name) {
+                renames.push((block, name.to_string(), new));
+            }
+        }
+        block += entry.blocks().len();
+    }
+    // Nothing is written unless every file can be renamed
+    for (block, old, new) in &renames {
+        tape.set_header_name(*block, new).map_err(Error::Failed)?;
+        println!("{:6} -> {}", old, new);
+    }
+    save_tape(&tape, target)?;
+    println!("{}", tr!("{} files renamed in {}", renames.len(), quoted(target)));
+    Ok(())
+}
+
+fn add_loader(path: &Path, name: &str, output: Option<&Path>) -> Result<()> {
+    let target = output.unwrap_or(path);
+    let _lock = file::lock(target)?;
+    let mut tape = Tape::from_bytes(&file::read_content(path)?);
+    let bins: Vec<loader::Addresses> = tape
+        .files()
+        .filter_map(|file| match file {
+            tape::File::Bin(_, begin, end, start, _) => Some((begin, end, start)),
+            _ => None,
+        })
+        .collect();
+    if bins.is_empty() {
+        return Err(Error::Failed(tr!("there are no binary files in {}", quoted(path))));
+    }
+
+    let program = loader::bload_program(&bins);
+    print!("{}", String::from_utf8_lossy(&program));
+    let mut loader = Tape::new();
+    loader.append_ascii(&tape::file_name(name).0, &program)?;
+    tape.prepend(loader);
+    save_tape(&tape, target)?;
+    println!("{}", tr!("Loader of {} binary files added to {}, load it with RUN\"CAS:\"", bins.len(), quoted(target)));
+    Ok(())
+}
+
+fn add_verifier(path: &Path, output: Option<&Path>) -> Result<()> {
+    let target = output.unwrap_or(path);
+    let _lock = file::lock(target)?;
+    let mut tape = Tape::from_bytes(&file::read_content(path)?);
+    let bins: Vec<(String, usize, usize, Vec<u8>)> = tape
+        .files()
+        .filter_map(|file| match file {
+            tape::File::Bin(name, begin, end, _, data) if begin <= end && data.len() > 6 + end - begin => {
+                Some((name, begin, end, data[6..=6 + end - begin].to_vec()))
+            }
+            _ => None,
+        })
+        .collect();
+    // The files loaded over by later files cannot be checked once every file is loaded
+    let mut checked = vec![];
+    for (i, (name, begin, end, data)) in bins.iter().enumerate() {
+        match bins[i + 1..].iter().find(|(_, b, e, _)| b <= end && begin <= e) {
+            Some((later, _, _, _)) => println!("{}", tr!("{} is not checked, {} is loaded over it", name, later)),
+            None => checked.push((name.as_str(), *begin, &data[..])),
+        }
+    }
+    if checked.is_empty() {
+        return Err(Error::Failed(tr!("there are no binary files in {}", quoted(path))));
+    }
+    let taken: Vec<(usize, usize)> = bins.iter().map(|(_, begin, end, _)| (*begin, *end)).collect();
+    let org = verifier::place(checked.len(), &taken)
+        .ok_or_else(|| Error::Failed(tr!("there is no room in memory for the verifier")))?;
+
+    tape.append_bin(&tape::file_name("VERIFY").0, &verifier::program(org, &checked))?;
+    save_tape(&tape, target)?;
+    println!("{}", tr!("Verifier of {} binary files added to {} at {}", checked.len(), quoted(target), format!("0x{:04x}", org)));
+    println!("{}", tr!("Load the files with BLOAD\"CAS:\" and then run the verifier with BLOAD\"CAS:\",R"));
+    Ok(())
+}
+
+fn print_inlay(path: &Path, output: Option<&Path>) -> Result<()> {
+    let tape = input::load(path, None, &[])?;
+    let exporter = wav::Exporter::new();
+    let items = tape
+        .entries_with(&[])
+        .map(|entry| inlay::Item {
+            name: entry.header_name().unwrap_or_default().to_string(),
+            kind: entry.kind().map(|kind| kind.name()).unwrap_or("custom"),
+            seconds: exporter.seconds_of(entry.blocks()),
+        })
+        .collect();
+    let metadata = meta::Metadata::load(path)?;
+    let title = match (&metadata.title, &metadata.year) {
+        (Some(title), Some(year)) => format!("{} ({})", title, year),
+        (Some(title), None) => title.clone(),
+        _ => file::stem_of(path)?.to_string(),
+    };
+    let inlay = inlay::Inlay::new(&title, items, C60_SIDE_SECONDS);
+    match output {
+        Some(output) if output.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf")) => {
+            file::write_content(output, &inlay.pdf())?;
+        }
+        Some(output) => file::write_content(output, inlay.text().as_bytes())?,
+        None => print!("{}", inlay.text()),
+    }
+    Ok(())
+}
+
+fn embed(path: &Path, wav: Option<wav::Preset>, uri: bool, wrap: Option<usize>, output: Option<&Path>) -> Result<()> {
+    let tape = input::load(path, None, &[])?;
+    let (bytes, mime) = match wav {
+        Some(preset) => {
+            let mut exporter = wav::Exporter::with_preset(preset);
+            for block in tape.blocks() {
+                exporter.write_block(block)?;
+            }
+            let mut bytes = vec![];
+            exporter.export(&mut bytes)?;
+            (bytes, embed::WAV_MIME)
+        }
+        None => (tape.to_bytes().into_owned(), embed::CAS_MIME),
+    };
+    let text = match (uri, wrap) {
+        (true, _) => format!("{}\n", embed::data_uri(mime, &bytes)),
+        (false, Some(width)) => embed::wrap(&embed::base64(&bytes), width),
+        (false, None) => format!("{}\n", embed::base64(&bytes)),
+    };
+    match output {
+        Some(output) => {
+            file::write_content(output, text.as_bytes())?;
+            println!("{}", tr!("{} bytes embedded into {} characters in {}", bytes.len(), text.len(), quoted(output)));
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+fn print_manifest(path: &Path) -> Result<()> {
+    let bytes = file::read_content(path)?;
+    let tape = Tape::from_bytes(&bytes);
+    // The bytes before the first block are dropped by the tape
+    let leading = &bytes[..bytes.len() - tape.to_bytes().len()];
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    print!("{}", manifest::generate(&tape, leading, &name, VERSION));
+    Ok(())
+}
+
+fn export(
+    cas_path: &Path,
+    format: Option<input::Format>,
+    wav_path: &Path,
+    preview: Option<&args::Preview>,
+    cache_dir: Option<&Path>,
+    preset: wav::Preset,
+    gaps: &[gap::Gap],
+) -> Result<()> {
+    let bytes = file::fetch_content(cas_path)?;
+    let format = format.unwrap_or_else(|| input::Format::detect(&bytes));
+    let tape = input::decode(&bytes, format, &[])?;
+    // Keep the speeds and pauses of the original blocks when converting from TSX
+    let (speeds, pauses) = match format {
+        input::Format::Tsx => {
+            let tsx = tsx::Tsx::from_bytes(&bytes)?;
+            let bauds = tsx.blocks.iter().map(|b| b.bauds()).collect();
+            (speed::Speeds { bauds }, Some(tsx.blocks.iter().map(|b| b.pause).collect::<Vec<u16>>()))
+        }
+        _ => (speed::Speeds::load(cas_path)?, None),
+    };
+    let extra = gap::extra_silences(&tape, gaps);
+    let silences = gap::silences(&tape, gaps);
+    if wav_path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("tsx")) {
+        if preview.is_some() {
+            return Err(Error::Failed(tr!("previews can only be rendered when exporting to WAV")));
+        }
+        export_tsx(&tape, &speeds, pauses.as_deref(), &silences, &extra, wav_path)?;
+        return post_export(cas_path, wav_path);
+    }
+    let mut exporter = wav::Exporter::with_preset(preset);
+    let total: usize = tape
+        .blocks()
+        .iter()
+        .enumerate()
+        .map(|(i, block)| {
+            exporter.set_bauds(speeds.of(i));
+            exporter.set_silence(silences[i]);
+            let (silence, header, data) = exporter.block_samples(block.is_file_header(), block.data_without_prefix().len());
+            exporter.pause_samples(extra[i]) + silence + header + data
+        })
+        .sum();
+    let seconds = total as f64 / exporter.sample_rate() as f64;
+    add_tags(&mut exporter, cas_path, &bytes, &tape, seconds)?;
+    // The path `-` stands for the standard output, where the WAV stream is written with
+    // its final length in the header, as it cannot be updated afterwards, and the
+    // messages go to the standard error
+    let stream = wav_path == Path::new("-");
+    let mut log: Box<dyn Write> = if stream { Box::new(io::stderr()) } else { Box::new(io::stdout()) };
+    let mut header = vec![];
+    exporter.write_wave_header(&mut header, if stream { total } else { 0 })?;
+    let bauds: Vec<u32> = (0..tape.blocks().len()).map(|i| speeds.of(i)).collect();
+    let key = hash::sha1(format!("{} {:?} {:?} {:?} {:?} {}", hash::sha1(&bytes), preset, bauds, silences, extra, hash::sha1(&header)).as_bytes());
+    // An interrupted export of the same tape with the same options is resumed, unless the
+    // preview is rendered, which needs the whole signal
+    let wav_len = fs::metadata(wav_path).map(|m| m.len()).unwrap_or(0);
+    let progress = if stream { None } else { progress::Progress::load(wav_path)? }
+        .filter(|progress| preview.is_none() && progress.key == key && progress.len <= wav_len);
+    let (mut wav_file, first, mut data_len) = match progress {
+        _ if stream => {
+            io::stdout().write_all(&header)?;
+            (None, 0, 0)
+        }
+        Some(progress) => {
+            writeln!(log, "{}", tr!("Resuming from block {}", progress.blocks))?;
+            let mut wav_file = fs::OpenOptions::new().write(true).open(wav_path)?;
+            wav_file.set_len(progress.len)?;
+            wav_file.seek(SeekFrom::End(0))?;
+            (Some(wav_file), progress.blocks, progress.len as usize - header.len())
+        }
+        None => {
+            let mut wav_file = File::create(wav_path)?;
+            wav_file.write_all(&header)?;
+            (Some(wav_file), 0, 0)
+        }
+    };
+    let cache = cache_dir.map(cache::BlockCache::open).transpose()?;
+
+    let mut starts = vec![];
+    let mut written = 0;
+    for (i, block) in tape.blocks().iter().enumerate().skip(first) {
+        write!(log, "{}", tr!("Encoding block {}... ", i))?;
+        exporter.set_bauds(speeds.of(i));
+        exporter.set_silence(silences[i]);
+        exporter.write_pause(extra[i])?;
+        let start = exporter.samples().len();
+        starts.push(start);
+        match &cache {
+            Some(cache) => {
+                let key = cache::BlockCache::key(&exporter.settings(), block);
+                if let Some(samples) = cache.get(&key) {
+                    let nbytes = exporter.write_samples(&samples)?;
+                    writeln!(log, "{}", tr!("{} KiB (cached)", nbytes / 1024))?;
+                } else {
+                    let nbytes = exporter.write_block(block)?;
+                    cache.put(&key, &exporter.samples()[start..])?;
+                    writeln!(log, "{} KiB", nbytes / 1024)?;
+                }
+            }
+            None => {
+                let nbytes = exporter.write_block(block)?;
+                writeln!(log, "{} KiB", nbytes / 1024)?;
+            }
+        }
+        let samples = &exporter.samples()[written..];
+        match &mut wav_file {
+            // Write the block and update the header, so the file is playable as it is and
+            // the export may be resumed from the next block
+            Some(wav_file) => {
+                wav_file.write_all(samples)?;
+                data_len += samples.len();
+                wav_file.seek(SeekFrom::Start(0))?;
+                exporter.write_wave_header(wav_file, data_len)?;
+                let len = wav_file.seek(SeekFrom::End(0))?;
+                progress::Progress { key: key.clone(), blocks: i + 1, len }.save(wav_path)?;
+            }
+            None => io::stdout().write_all(samples)?,
+        }
+        // The preview is rendered from the whole signal
+        if preview.is_some() {
+            written = exporter.samples().len();
+        } else {
+            exporter.clear_samples();
+        }
+    }
+    if !stream {
+        progress::Progress::remove(wav_path)?;
+    }
+
+    if let Some(preview) = preview {
+        let image = match preview.block {
+            Some(i) if i >= tape.blocks().len() => {
+                return Err(Error::Failed(tr!("there is no block {} in the tape", i)));
+            }
+            Some(i) => {
+                let block = &tape.blocks()[i];
+                exporter.set_bauds(speeds.of(i));
+                exporter.set_silence(silences[i]);
+                let (silence, header, _) = exporter.block_samples(block.is_file_header(), 0);
+                let data = starts[i] + silence + header;
+                let from = data - PREVIEW_LEAD_SAMPLES;
+                let to = (from + PREVIEW_BLOCK_SAMPLES).min(exporter.samples().len());
+                preview::render(&exporter.samples()[from..to], &[data - from], PREVIEW_WIDTH, PREVIEW_HEIGHT)
+            }
+            None => preview::render(exporter.samples(), &starts, PREVIEW_WIDTH, PREVIEW_HEIGHT),
+        };
+        let mut png = vec![];
+        image.write_png(&mut png)?;
+        file::write_content(&preview.path, &png)?;
+        writeln!(log, "{}", tr!("Preview rendered into {}", quoted(&preview.path)))?;
+    }
+    post_export(cas_path, wav_path)
+}
+
+/// Run the post-export hook, if any, for the given tape exported into the given file.
+fn post_export(cas_path: &Path, out_path: &Path) -> Result<()> {
+    hook::Hook::PostExport.run(out_path, serde_json::json!({ "tape": cas_path, "output": out_path }))?;
+    Ok(())
+}
+
+fn roundtrip(
+    cas_path: &Path,
+    wav_path: &Path,
+    preset: wav::Preset,
+    gaps: &[gap::Gap],
+    decoder: import::DecoderKind,
+) -> Result<()> {
+    export(cas_path, None, wav_path, None, None, preset, gaps)?;
+    let tape = input::load(cas_path, None, &[])?;
+    let recording = import::Recording::from_bytes(&file::read_content(wav_path)?)?;
+    let blocks = import::Importer::with_decoder(decoder).decode(&recording);
+
+    let expected: Vec<&[u8]> = tape.blocks().iter().map(|b| b.data_without_prefix()).collect();
+    let decoded: Vec<&[u8]> = blocks.iter().map(|b| &b.data[..]).collect();
+    let mismatches = import::compare(&expected, &decoded);
+    for mismatch in &mismatches {
+        println!("{}", mismatch);
+    }
+    if !mismatches.is_empty() {
+        return Err(Error::Failed(tr!("{} does not decode back to {}", quoted(wav_path), quoted(cas_path))));
+    }
+    println!("{}", tr!("{} decodes back to the {} blocks of {}", quoted(wav_path), blocks.len(), quoted(cas_path)));
+    Ok(())
+}
+
+fn compare(path: &Path, other_path: &Path) -> Result<()> {
+    let tape = input::load(path, None, &[])?;
+    let other = input::load(other_path, None, &[])?;
+    let mismatches = import::compare(&tape.logical_blocks(), &other.logical_blocks());
+    for mismatch in &mismatches {
+        println!("{}", mismatch);
+    }
+    if !mismatches.is_empty() {
+        return Err(Error::Failed(tr!("{} and {} do not contain the same blocks", quoted(path), quoted(other_path))));
+    }
+    println!("{}", tr!("{} and {} contain the same {} blocks", quoted(path), quoted(other_path), tape.blocks().len()));
+    Ok(())
+}
+
+fn export_all(cas_dir: &Path, wav_dir: &Path, jobs: Option<usize>) -> Result<()> {
+    let tapes = file::list_dir(cas_dir, file::is_cas_file)?;
+    let jobs = jobs.unwrap_or_else(batch::default_jobs);
+    fs::create_dir_all(wav_dir)?;
+
+    println!("{}", tr!("Exporting {} tapes using {} jobs...", tapes.len(), jobs));
+    let results = batch::run(&tapes, jobs, |cas_path| {
+        let stem = cas_path.file_stem().unwrap_or_default().to_string_lossy();
+        let wav_path = wav_dir.join(format!("{}.wav", stem));
+        export_silently(cas_path, &wav_path)
+            .and_then(|nbytes| post_export(cas_path, &wav_path).map(|_| nbytes))
+            .map(|nbytes| (wav_path, nbytes))
+    });
+
+    let mut failed = 0;
+    for (cas_path, result) in tapes.iter().zip(results) {
+        match result {
+            Ok((wav_path, nbytes)) => println!(
+                "{}",
+                tr!(
+                    "{} -> {}... {} KiB",
+                    format!("{:?}", cas_path.as_os_str()),
+                    format!("{:?}", wav_path.as_os_str()),
+                    nbytes / 1024
+                )
+            ),
+            Err(e) => {
+                failed += 1;
+                println!("{}", tr!("{}... Error: {}", quoted(cas_path), e));
+            }
+        }
+    }
+    println!();
+    println!("{}", tr!("{} tapes exported, {} failed", tapes.len() - failed, failed));
+    Ok(())
+}
+
+fn list_set(path: &Path) -> Result<()> {
+    let set = tapeset::TapeSet::load(path)?;
+    let exporter = wav::Exporter::new();
+    let mut tapes = vec![];
+    if let Some(title) = &set.title {
+        println!("{}", title);
+        println!();
+    }
+    for side in &set.sides {
+        let tape = input::load(&side.path, None, &[])?;
+        println!("{} ({})", side.label, quoted(&side.path));
+        list_files(&side.path, &tape, &[], false, None, &args::Style::Human, None)?;
+        println!();
+        tapes.push(tape);
+    }
+
+    let files: usize = tapes.iter().map(|t| t.entries_with(&[]).count()).sum();
+    let seconds: f64 = tapes.iter().flat_map(|t| t.entries_with(&[])).map(|e| exporter.seconds_of(e.blocks())).sum();
+    println!("{}", tr!("{} sides, {} files, {} of load time", set.sides.len(), files, format_duration(seconds)));
+    for duplicate in tapeset::duplicates(&tapes) {
+        let copies: Vec<String> = duplicate.iter().map(|(side, name)| format!("{} ({})", name, set.sides[*side].label)).collect();
+        println!("{}", tr!("Warning: the same file is found in more than one side: {}", copies.join(", ")));
+    }
+    Ok(())
+}
+
+fn export_set(path: &Path, wav_dir: &Path, preset: wav::Preset) -> Result<()> {
+    let set = tapeset::TapeSet::load(path)?;
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    // Descriptors are usually named after the set, as `game.set.json`
+    let stem = name.trim_end_matches(".set");
+    fs::create_dir_all(wav_dir)?;
+    for (i, side) in set.sides.iter().enumerate() {
+        let wav_path = wav_dir.join(set.wav_name(stem, i));
+        println!("{}", tr!("Exporting {} into {}", side.label, quoted(&wav_path)));
+        export(&side.path, None, &wav_path, None, None, preset, &[])?;
+        println!();
+    }
+    println!("{}", tr!("{} sides exported into {}", set.sides.len(), quoted(wav_dir)));
+    Ok(())
+}
+
+fn export_silently(cas_path: &Path, wav_path: &Path) -> Result<usize> {
+    let bytes = file::read_content(cas_path)?;
+    let tape = Tape::from_bytes(&bytes);
+    if tape.blocks().is_empty() {
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no blocks found in tape",
+        )));
+    }
+    let mut exporter = wav::Exporter::new();
+    let mut nbytes = 0;
+    for block in tape.blocks() {
+        nbytes += exporter.write_block(block)?;
+    }
+    let seconds = exporter.samples().len() as f64 / exporter.sample_rate() as f64;
+    add_tags(&mut exporter, cas_path, &bytes, &tape, seconds)?;
+    let mut wav_file = File::create(wav_path)?;
+    exporter.export(&mut wav_file)?;
+    Ok(nbytes)
+}
+
+/// Tag the WAV file exported from the given tape with its title, the files it contains, its
+/// duration and the hash of the tape file, so it can be traced back to the tape.
+fn add_tags(exporter: &mut wav::Exporter, cas_path: &Path, bytes: &[u8], tape: &Tape, seconds: f64) -> Result<()> {
+    let metadata = meta::Metadata::load(cas_path)?;
+    let name = cas_path.file_name().unwrap_or_default().to_string_lossy();
+    exporter.add_tag(b"INAM", metadata.title.as_deref().unwrap_or(&name));
+    if let Some(year) = &metadata.year {
+        exporter.add_tag(b"ICRD", year);
+    }
+    let files: Vec<String> = tape
+        .entries_with(&[])
+        .map(|entry| match (entry.kind(), entry.header_name()) {
+            (Some(kind), Some(name)) => format!("{} {}", kind.name(), name.trim_end()),
+            _ => "custom".to_string(),
+        })
+        .collect();
+    exporter.add_tag(b"ICMT", &format!("{} ({}, SHA-1 {}): {}", name, format_duration(seconds), hash::sha1(bytes), files.join(", ")));
+    exporter.add_tag(b"ISFT", &format!("MSX CAS Packager (MCP) v{}", VERSION));
+    Ok(())
+}
+
+fn export_tsx(
+    tape: &Tape,
+    speeds: &speed::Speeds,
+    pauses: Option<&[u16]>,
+    silences: &[Option<u32>],
+    extra: &[u32],
+    tsx_path: &Path,
+) -> Result<()> {
+    let mut tsx = tsx::Tsx::of(tape, speeds, pauses);
+    // The silence before a block is the pause after the previous one
+    for (block, (silence, extra)) in tsx.blocks.iter_mut().zip(silences.iter().zip(extra).skip(1)) {
+        if let Some(silence) = silence {
+            block.pause = (*silence).min(u16::MAX as u32) as u16;
+        }
+        block.pause = block.pause.saturating_add((*extra).min(u16::MAX as u32) as u16);
+    }
+    file::write_content(tsx_path, &tsx.to_bytes())?;
+    println!("{}", tr!("{} blocks written into {}", tsx.blocks.len(), quoted(tsx_path)));
+    Ok(())
+}
+
+fn import(
+    wav_path: &Path,
+    cas_path: &Path,
+    report_path: Option<&Path>,
+    channel: Option<import::Channel>,
+    decoder: import::DecoderKind,
+) -> Result<()> {
+    let bytes = file::read_content(wav_path)?;
+    if input::Format::detect(&bytes) == input::Format::Tsx {
+        return import_tsx(&bytes, cas_path);
+    }
+    let recording = import::Recording::from_bytes_with(&bytes, channel)?;
+    let blocks = import::Importer::with_decoder(decoder).decode(&recording);
+
+    let report = import::Report::of(&wav_path.to_string_lossy(), &blocks);
+    println!("{}", tr!("block | bauds | bytes | expected | errors | level | quality"));
+    for block in &report.blocks {
+        println!(
+            "{:5} | {:5} | {:5} | {:>8} | {:6} | {:4.0}% | {}",
+            block.block,
+            block.bauds,
+            block.bytes,
+            block.expected.map(|n| n.to_string()).unwrap_or_default(),
+            block.errors,
+            block.level * 100.0,
+            block.quality
+        );
+    }
+    println!(
+        "{}",
+        tr!(
+            "{} blocks decoded: {} good, {} salvaged, {} damaged",
+            report.blocks.len(),
+            report.count(import::Quality::Good),
+            report.count(import::Quality::Salvaged),
+            report.count(import::Quality::Damaged)
+        )
+    );
+    if let Some(report_path) = report_path {
+        let mut json = serde_json::to_vec_pretty(&report).map_err(io::Error::from)?;
+        json.push(b'\n');
+        file::write_content(report_path, &json)?;
+    }
+
+    let mut tape = Tape::new();
+    for block in &blocks {
+        tape.append_raw_block(&block.data);
+    }
+    save_tape(&tape, cas_path)?;
+    let speeds = speed::Speeds { bauds: blocks.iter().map(|b| b.bauds).collect() };
+    if save_speeds(&speeds, cas_path)? {
+        println!("{}", tr!("Speeds of turbo blocks saved into {}", quoted(&speed::Speeds::sidecar_path(cas_path))));
+    }
+    Ok(())
+}
+
+fn import_tsx(bytes: &[u8], cas_path: &Path) -> Result<()> {
+    let tsx = tsx::Tsx::from_bytes(bytes)?;
+    let speeds = speed::Speeds { bauds: tsx.blocks.iter().map(|b| b.bauds()).collect() };
+    let mut tape = Tape::new();
+    for (i, block) in tsx.blocks.iter().enumerate() {
+        println!("{}", tr!("Reading block {}... {} bytes at {} bauds", i, block.data.len(), block.bauds()));
+        tape.append_raw_block(&block.data);
+    }
+    save_tape(&tape, cas_path)?;
+    if save_speeds(&speeds, cas_path)? {
+        println!("{}", tr!("Speeds of turbo blocks saved into {}", quoted(&speed::Speeds::sidecar_path(cas_path))));
+    }
+    Ok(())
+}
+
+/// Save the speeds of the blocks of the given CAS file if any of them is a turbo block,
+/// removing any stale speed file otherwise. Returns whether the speeds were saved.
+fn save_speeds(speeds: &speed::Speeds, cas_path: &Path) -> Result<bool> {
+    let sidecar = speed::Speeds::sidecar_path(cas_path);
+    if !speeds.is_default() {
+        speeds.save(cas_path)?;
+        Ok(true)
+    } else {
+        if file::exists(&sidecar) {
+            fs::remove_file(&sidecar)?;
+        }
+        Ok(false)
+    }
+}
+
+fn import_all(
+    wav_dir: &Path,
+    cas_dir: &Path,
+    jobs: Option<usize>,
+    channel: Option<import::Channel>,
+    decoder: import::DecoderKind,
+) -> Result<()> {
+    let recordings = file::list_dir(wav_dir, file::is_wav_file)?;
+    let jobs = jobs.unwrap_or_else(batch::default_jobs);
+    fs::create_dir_all(cas_dir)?;
+
+    println!("{}", tr!("Importing {} recordings using {} jobs...", recordings.len(), jobs));
+    let results = batch::run(&recordings, jobs, |wav_path| {
+        let stem = wav_path.file_stem().unwrap_or_default().to_string_lossy();
+        let cas_path = cas_dir.join(format!("{}.cas", stem));
+        import_silently(wav_path, &cas_path, channel, decoder).map(|blocks| (cas_path, blocks))
+    });
+
+    let (mut clean, mut damaged, mut failed) = (0, 0, 0);
+    for (wav_path, result) in recordings.iter().zip(results) {
+        match result {
+            Ok((cas_path, blocks)) => {
+                let nbytes = blocks.iter().fold(0, |n, b| n + b.data.len());
+                let errors = blocks.iter().filter(|b| b.errors > 0).count();
+                let quality = if blocks.is_empty() {
+                    damaged += 1;
+                    tr!("NO DATA")
+                } else if errors > 0 {
+                    damaged += 1;
+                    tr!("DAMAGED")
+                } else {
+                    clean += 1;
+                    tr!("OK")
+                };
+                println!(
+                    "{}",
+                    tr!(
+                        "{} -> {}... {} blocks, {} bytes, {} damaged blocks: {}",
+                        format!("{:?}", wav_path.as_os_str()),
+                        format!("{:?}", cas_path.as_os_str()),
+                        blocks.len(),
+                        nbytes,
+                        errors,
+                        quality
+                    )
+                );
+            }
+            Err(e) => {
+                failed += 1;
+                println!("{}", tr!("{}... Error: {}", quoted(wav_path), e));
+            }
+        }
+    }
+    println!();
+    println!("{}", tr!("{} recordings imported, {} damaged, {} failed", clean, damaged, failed));
+    if damaged > 0 {
+        println!("{}", tr!("Damaged recordings could not be fully decoded and should be re-recorded."));
+    }
+    Ok(())
+}
+
+fn import_silently(
+    wav_path: &Path,
+    cas_path: &Path,
+    channel: Option<import::Channel>,
+    decoder: import::DecoderKind,
+) -> Result<Vec<import::DecodedBlock>> {
+    let recording = import::Recording::from_bytes_with(&file::read_content(wav_path)?, channel)?;
+    let blocks = import::Importer::with_decoder(decoder).decode(&recording);
+    let mut tape = Tape::new();
+    for block in &blocks {
+        tape.append_raw_block(&block.data);
+    }
+    save_tape(&tape, cas_path)?;
+    save_speeds(&speed::Speeds { bauds: blocks.iter().map(|b| b.bauds).collect() }, cas_path)?;
+    Ok(blocks)
+}
+
+fn dedup_library(dir: &Path, jobs: Option<usize>, action: &args::Duplicates, yes: bool) -> Result<()> {
+    let paths = file::walk_dir(dir, file::is_cas_file)?;
+    let jobs = jobs.unwrap_or_else(batch::default_jobs);
+    println!("{}", tr!("Scanning {} tapes using {} jobs...", paths.len(), jobs));
+
+    let mut tapes = vec![];
+    for (path, result) in paths.iter().zip(batch::run(&paths, jobs, |p| library::TapeInfo::scan(p))) {
+        match result {
+            Ok(tape) => tapes.push(tape),
+            Err(e) => println!("{}", tr!("{}... Error: {}", quoted(path), e)),
+        }
+    }
+    let redundancies = library::find_redundancies(&tapes);
+    let duplicates: usize = redundancies.duplicates.iter().map(|group| group.len() - 1).sum();
+    if duplicates > 0 {
+        match action {
+            args::Duplicates::Report => {}
+            args::Duplicates::Link => confirm(&tr!("Replace {} duplicated tapes by links?", duplicates), yes)?,
+            args::Duplicates::Remove => confirm(&tr!("Remove {} duplicated tapes?", duplicates), yes)?,
+        }
+    }
+
+    println!();
+    println!("{}", tr!("{} groups of duplicated tapes", redundancies.duplicates.len()));
+    for group in &redundancies.duplicates {
+        let original = &tapes[group[0]].path;
+        println!();
+        println!("  {:?}", original.as_os_str());
+        for i in &group[1..] {
+            let duplicate = &tapes[*i].path;
+            print!("  {:?}", duplicate.as_os_str());
+            match action {
+                args::Duplicates::Report => {}
+                args::Duplicates::Link => {
+                    file::replace_with_link(duplicate, original)?;
+                    print!("{}", tr!(" (linked)"));
+                }
+                args::Duplicates::Remove => {
+                    file::remove(duplicate)?;
+                    print!("{}", tr!(" (removed)"));
+                }
+            }
+            println!();
+        }
+    }
+
+    println!();
+    println!("{}", tr!("{} tapes contained in others", redundancies.supersets.len()));
+    for (a, b) in &redundancies.supersets {
+        println!();
+        println!("  {:?}", tapes[*a].path.as_os_str());
+        println!("{}", tr!("  contains all files of {}", quoted(&tapes[*b].path)));
+    }
+    Ok(())
+}
+
+fn check_tape(path: &Path, report_path: Option<&Path>, fail_on: check::Severity) -> Result<()> {
+    let bytes = file::fetch_content(path)?;
+    let report = check::check(&path.to_string_lossy(), &bytes);
+    for issue in &report.issues {
+        let block = issue.block.map(|b| tr!("block {}", b)).unwrap_or_default();
+        println!("{:7} | {:9} | {:9} | {}", issue.severity, block, issue.check, issue.message);
+    }
+    println!(
+        "{}",
+        tr!(
+            "{} errors, {} warnings, {} infos in {} blocks",
+            report.count(check::Severity::Error),
+            report.count(check::Severity::Warning),
+            report.count(check::Severity::Info),
+            report.blocks
+        )
+    );
+
+    if let Some(report_path) = report_path {
+        let mut json = serde_json::to_vec_pretty(&report).map_err(io::Error::from)?;
+        json.push(b'\n');
+        file::write_content(report_path, &json)?;
+    }
+
+    let failures = report.count_at_least(fail_on);
+    if failures > 0 {
+        return Err(Error::Failed(tr!("{} issues of {} severity or worse found", failures, fail_on)));
+    }
+    Ok(())
+}
+
+fn serve(dir: &Path, port: u16) -> Result<()> {
+    println!("{}", tr!("Serving the tapes of {} on {}", quoted(dir), format!("http://127.0.0.1:{}/tapes", port)));
+    serve::serve(dir, port)?;
+    Ok(())
+}
+
+fn gen_docs(format: args::DocFormat) -> Result<()> {
+    match format {
+        args::DocFormat::Man => print!("{}", docs::man(args::USAGE, VERSION)),
+        args::DocFormat::Markdown => print!("{}", docs::markdown(args::USAGE, VERSION)),
+    }
+    Ok(())
+}
+
+fn rpc() -> Result<()> {
+    let stdin = io::stdin();
+    rpc::Session::new(io::stdout()).run(stdin.lock())?;
+    Ok(())
+}
+
+fn print_map(path: &Path, format: Option<input::Format>, width: usize) -> Result<()> {
+    let tape = input::load(path, format, &[])?;
+    let map = map::Map::of(&tape, &wav::Exporter::new());
+    println!("{:6}|{}| {}", tr!("Tape"), map.tape_bar(width), tr!("{} bytes", map.bytes()));
+    println!("{:6}|{}| {}", tr!("Audio"), map.audio_bar(width), format_time(map.seconds(map.samples())));
+    println!();
+    for s in &map.segments {
+        println!(
+            "{} | {} | {:6} | {:6} | {} | {}",
+            map::label(s.file),
+            tr!("block {}", format!("{:3}", s.block)),
+            s.kind,
+            s.name.as_deref().unwrap_or(""),
+            tr!("{} bytes at {}", format!("{:6}", s.size), format!("{:6}", s.offset)),
+            tr!("{} at {}", format!("{:>8}", format_time(map.seconds(s.len()))), format!("{:>8}", format_time(map.seconds(s.start))))
+        );
+    }
+    Ok(())
+}
+
+fn print_analysis(path: &Path, format: Option<input::Format>, width: usize, sym: Option<&Path>) -> Result<()> {
+    let tape = input::load(path, format, &[])?;
+    let symbols = sym.map(sym::Symbols::load).transpose()?.unwrap_or_default();
+    let analysis = analyze::Analysis::of(&tape);
+    if analysis.footprints.is_empty() {
+        return Err(Error::Failed(tr!("there are no binary files in {}", quoted(path))));
+    }
+    let names = |files: Vec<usize>| {
+        let names: Vec<String> = files.iter().map(|j| format!("{} ({})", map::label(*j), analysis.footprints[*j].name)).collect();
+        names.join(", ")
+    };
+    for (i, file) in analysis.footprints.iter().enumerate() {
+        println!(
+            "{} | {:6} | [0x{:04x},0x{:04x}]:0x{:04x} | {}",
+            map::label(i),
+            file.name,
+            file.begin,
+            file.end,
+            file.start,
+            tr!("{} bytes in {}", file.end - file.begin + 1, tr!(tape::memory_region(file.begin))));
+        let addresses = [("BEGIN", file.begin), ("END", file.end), ("START", file.start)];
+        let symbols: Vec<String> = addresses
+            .iter()
+            .filter_map(|(what, addr)| Some(format!("{} {}", what, symbols.name_of(*addr as u16)?)))
+            .collect();
+        if !symbols.is_empty() {
+            println!("  | {}", symbols.join(", "));
+        }
+        let overlaps = analysis.overlaps(i);
+        if !overlaps.is_empty() {
+            println!("  | {}", tr!("! loaded over {}", names(overlaps)));
+        }
+        let start_in = analysis.start_in(i);
+        if !start_in.is_empty() {
+            println!("  | {}", tr!("! START in {}", names(start_in)));
+        } else if !file.contains(file.start) {
+            println!("  | {}", tr!("! START outside of every file, in {}", tr!(tape::memory_region(file.start))));
+        }
+    }
+    println!();
+    let ruler: String = (0..width).map(|col| if (col * 4).is_multiple_of(width) { '|' } else { ' ' }).collect();
+    println!("         {}", ruler);
+    for (i, file) in analysis.footprints.iter().enumerate() {
+        println!("{} {:6} {}", map::label(i), file.name, analysis.memory_bar(i, width));
+    }
+    println!("  {:6} {}", "all", analysis.overall_bar(width));
+    println!("         0x0000{:>w$}", "0xffff", w = width.saturating_sub(6));
+    Ok(())
+}
+
+/// Returns the given path quoted, as in messages.
+fn quoted(path: &Path) -> String {
+    format!("{:?}", path.as_os_str())
+}
+
+fn format_time(seconds: f64) -> String {
+    format!("{}:{:04.1}", (seconds / 60.0) as u64, seconds % 60.0)
+}
+
+/// Format the given time as minutes and seconds, as in `01:05`.
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Format the given size in bytes, or in kilobytes if it takes more than one.
+fn format_size(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    }
+}
+
+fn verify(cas_path: &Path, dat_path: &Path) -> Result<()> {
+    let dat = dat::Dat::from_file(dat_path)?;
+    let bytes = file::fetch_content(cas_path)?;
+    let tape = Tape::from_bytes(&bytes);
+
+    let tape_name = cas_path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    print!("{}", tr!("Tape {}: ", quoted(cas_path)));
+    print_verdict(&dat.lookup(tape_name, &hash::Digest::of(&bytes)));
+
+    let mut next_custom = 0;
+    for file in tape.files() {
+        let name = file.name().unwrap_or_else(|| {
+            next_custom += 1;
+            format!("custom.{:03}", next_custom)
+        });
+        print!("  {:12} ", name);
+        print_verdict(&dat.lookup(&name, &hash::Digest::of(&file.content())));
+    }
+    Ok(())
+}
+
+fn print_verdict(verdict: &dat::Verdict) {
+    match verdict {
+        dat::Verdict::Match(entry) => println!("{:8} | {} / {}", tr!("OK"), entry.game, entry.name),
+        dat::Verdict::Mismatch(entry) => println!("{:8} | {} / {}", tr!("MISMATCH"), entry.game, entry.name),
+        dat::Verdict::Unknown => println!("{:8} |", tr!("UNKNOWN")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_tell_joined_blocks_apart_when_normalizing() {
+        let mut tape = Tape::new();
+        tape.append_custom(&[1; 8]).unwrap();
+        tape.append_custom(&[2; 8]).unwrap();
+        let normalize = args::Fixes { normalize: true, ..Default::default() };
+        assert!(!apply_fixes(&mut tape, &normalize).unwrap());
+
+        let fixes = args::Fixes { normalize: true, join: Some(0..=1), ..Default::default() };
+        assert!(apply_fixes(&mut tape, &fixes).unwrap());
+        assert_eq!(1, tape.blocks().len());
+    }
+}
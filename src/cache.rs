@@ -0,0 +1,89 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::file;
+use crate::hash;
+use crate::tape::Block;
+
+/// A cache of encoded blocks stored in a directory
+///
+/// Each entry is the audio encoding of a block, stored in a file named after a key that
+/// comprises the block contents and the encoder settings. Thus, a block is only encoded
+/// again if it changes, or if it is encoded differently.
+///
+pub struct BlockCache {
+    dir: PathBuf,
+}
+
+impl BlockCache {
+    /// Open the cache stored in the given directory, creating it if necessary.
+    pub fn open(dir: &Path) -> io::Result<BlockCache> {
+        fs::create_dir_all(dir)?;
+        Ok(BlockCache { dir: dir.to_path_buf() })
+    }
+
+    /// Returns the key of the given block encoded using the given encoder settings.
+    pub fn key(settings: &str, block: &Block) -> String {
+        let mut content = settings.as_bytes().to_vec();
+        content.push(0);
+        content.push(block.is_file_header() as u8);
+        content.extend_from_slice(block.data_without_prefix());
+        hash::sha1(&content)
+    }
+
+    /// Returns the encoded block for the given key, if it is cached.
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        file::read_content(&self.path_of(key)).ok()
+    }
+
+    /// Store the encoded block for the given key.
+    pub fn put(&self, key: &str, samples: &[u8]) -> io::Result<()> {
+        file::write_content(&self.path_of(key), samples)
+    }
+
+    fn path_of(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.block", key))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use tempdir::TempDir;
+
+    use crate::tape::Tape;
+
+    use super::*;
+
+    #[test]
+    fn should_cache_blocks() {
+        let temp = TempDir::new("mcp").unwrap();
+        let cache = BlockCache::open(&temp.path().join("cache")).unwrap();
+        let mut tape = Tape::new();
+        tape.append_custom(&[0x01, 0x02, 0x03]).unwrap();
+        let key = BlockCache::key("1200/43200", &tape.blocks()[0]);
+        assert_eq!(None, cache.get(&key));
+        cache.put(&key, &[0x80, 0x81]).unwrap();
+        assert_eq!(Some(vec![0x80, 0x81]), cache.get(&key));
+    }
+
+    #[test]
+    fn should_compute_keys() {
+        let mut tape = Tape::new();
+        tape.append_custom(&[0x01, 0x02, 0x03]).unwrap();
+        tape.append_custom(&[0x01, 0x02, 0x04]).unwrap();
+        let (a, b) = (&tape.blocks()[0], &tape.blocks()[1]);
+        assert_eq!(BlockCache::key("1200/43200", a), BlockCache::key("1200/43200", a));
+        assert_ne!(BlockCache::key("1200/43200", a), BlockCache::key("2400/43200", a));
+        assert_ne!(BlockCache::key("1200/43200", a), BlockCache::key("1200/43200", b));
+    }
+}
@@ -0,0 +1,178 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Character sets of MSX computers
+//!
+//! MSX computers share the ASCII characters from `0x20` to `0x7e`, but the rest of the
+//! characters depend on the machine: international machines have accented letters, Greek
+//! letters and graphics from `0x80` on, while Japanese ones have hiragana and katakana.
+//! Both have 32 more graphic characters, written as `0x01` followed by the character plus
+//! `0x40`, as control characters take the codes below `0x20`.
+
+/// The character set of the texts of a tape
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Charset {
+    /// The character set of international (European) machines
+    MsxInt,
+    /// The character set of Japanese machines
+    MsxJp,
+    /// The bytes as they are, with no conversion
+    #[default]
+    Raw,
+}
+
+/// The byte telling that the next one is a graphic character
+const GRAPHIC: u8 = 0x01;
+
+/// The graphic characters of international machines, written after `GRAPHIC`
+const INT_GRAPHICS: [char; 32] = [
+    ' ', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼',
+    '┿', '┴', '┬', '┤', '├', '┼', '│', '─', '┌', '┐', '└', '┘', '╳', '╱', '╲', '╂',
+];
+
+/// The characters of international machines from `0x80` on
+const INT_CHARS: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    'Ã', 'ã', 'Ĩ', 'ĩ', 'Õ', 'õ', 'Ũ', 'ũ', 'Ĳ', 'ĳ', '¾', '∽', '◇', '‰', '¶', '§',
+    '▂', '▚', '▆', '🮂', '▬', '🮅', '▎', '▞', '▊', '🮇', '🮊', '🮙', '🮘', '🭭', '🭯', '🭬',
+    '🭮', '🮚', '🮛', '▘', '▗', '▝', '▖', '🮖', 'Δ', '‡', 'ω', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', '∈', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '▯',
+];
+
+/// The graphic characters of Japanese machines, written after `GRAPHIC`
+const JP_GRAPHICS: [char; 32] = [
+    ' ', '月', '火', '水', '木', '金', '土', '日', '年', '円', '時', '分', '秒', '百', '千', '万',
+    'π', '┴', '┬', '┤', '├', '┼', '│', '─', '┌', '┐', '└', '┘', '╳', '大', '中', '小',
+];
+
+/// The characters of Japanese machines from `0x80` on, with the katakana as half-width
+/// characters as JIS X 0201 does
+const JP_CHARS: [char; 128] = [
+    '♠', '♥', '♣', '♦', '○', '●', 'を', 'ぁ', 'ぃ', 'ぅ', 'ぇ', 'ぉ', 'ゃ', 'ゅ', 'ょ', 'っ',
+    '\u{3000}', 'あ', 'い', 'う', 'え', 'お', 'か', 'き', 'く', 'け', 'こ', 'さ', 'し', 'す', 'せ', 'そ',
+    '\u{a0}', '｡', '｢', '｣', '､', '･', 'ｦ', 'ｧ', 'ｨ', 'ｩ', 'ｪ', 'ｫ', 'ｬ', 'ｭ', 'ｮ', 'ｯ',
+    'ｰ', 'ｱ', 'ｲ', 'ｳ', 'ｴ', 'ｵ', 'ｶ', 'ｷ', 'ｸ', 'ｹ', 'ｺ', 'ｻ', 'ｼ', 'ｽ', 'ｾ', 'ｿ',
+    'ﾀ', 'ﾁ', 'ﾂ', 'ﾃ', 'ﾄ', 'ﾅ', 'ﾆ', 'ﾇ', 'ﾈ', 'ﾉ', 'ﾊ', 'ﾋ', 'ﾌ', 'ﾍ', 'ﾎ', 'ﾏ',
+    'ﾐ', 'ﾑ', 'ﾒ', 'ﾓ', 'ﾔ', 'ﾕ', 'ﾖ', 'ﾗ', 'ﾘ', 'ﾙ', 'ﾚ', 'ﾛ', 'ﾜ', 'ﾝ', 'ﾞ', 'ﾟ',
+    'た', 'ち', 'つ', 'て', 'と', 'な', 'に', 'ぬ', 'ね', 'の', 'は', 'ひ', 'ふ', 'へ', 'ほ', 'ま',
+    'み', 'む', 'め', 'も', 'や', 'ゆ', 'よ', 'ら', 'り', 'る', 'れ', 'ろ', 'わ', 'ん', '█', '▯',
+];
+
+impl Charset {
+    /// Returns the given text of this character set as UTF-8.
+    ///
+    /// The bytes below `0x80` but for the graphic characters are kept as they are, so
+    /// line breaks and tabs are not changed. Raw texts are returned as they are.
+    pub fn decode(self, text: &[u8]) -> Vec<u8> {
+        let (graphics, chars) = match self.tables() {
+            Some(tables) => tables,
+            None => return text.to_vec(),
+        };
+        let mut decoded = String::with_capacity(text.len());
+        let mut bytes = text.iter();
+        while let Some(&byte) = bytes.next() {
+            match byte {
+                GRAPHIC => match bytes.as_slice().first() {
+                    Some(next @ 0x40..=0x5f) => {
+                        decoded.push(graphics[(next - 0x40) as usize]);
+                        bytes.next();
+                    }
+                    _ => decoded.push(GRAPHIC as char),
+                },
+                0x80..=0xff => decoded.push(chars[(byte - 0x80) as usize]),
+                _ => decoded.push(byte as char),
+            }
+        }
+        decoded.into_bytes()
+    }
+
+    /// Returns the given UTF-8 text in this character set, the other way around than
+    /// `decode()` does, or an error if the text is not UTF-8 or has characters out of this
+    /// character set. Raw texts are returned as they are.
+    pub fn encode(self, text: &[u8]) -> Result<Vec<u8>, String> {
+        let (graphics, chars) = match self.tables() {
+            Some(tables) => tables,
+            None => return Ok(text.to_vec()),
+        };
+        let text = std::str::from_utf8(text).map_err(|_| tr!("the text is not UTF-8"))?;
+        let mut encoded = Vec::with_capacity(text.len());
+        for c in text.chars() {
+            if c.is_ascii() {
+                encoded.push(c as u8);
+            } else if let Some(i) = chars.iter().position(|x| *x == c) {
+                encoded.push(0x80 + i as u8);
+            } else if let Some(i) = graphics.iter().skip(1).position(|x| *x == c) {
+                encoded.extend_from_slice(&[GRAPHIC, 0x41 + i as u8]);
+            } else {
+                return Err(tr!("character {} is not in the character set", c));
+            }
+        }
+        Ok(encoded)
+    }
+
+    /// Returns the graphic characters and the characters from `0x80` on of this character
+    /// set, if it is not raw.
+    fn tables(self) -> Option<(&'static [char; 32], &'static [char; 128])> {
+        match self {
+            Charset::MsxInt => Some((&INT_GRAPHICS, &INT_CHARS)),
+            Charset::MsxJp => Some((&JP_GRAPHICS, &JP_CHARS)),
+            Charset::Raw => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::tape::{file_name, Tape};
+
+    #[test]
+    fn should_decode_texts() {
+        let text = b"10 PRINT \"Espa\xa4a \x01\x43\"\r\n\x01";
+        assert_eq!("10 PRINT \"España ♥\"\r\n\u{1}", String::from_utf8_lossy(&Charset::MsxInt.decode(text)));
+        let text = b"\xb1\xb2\xb3 \x91\x92\x93 \x01\x47";
+        assert_eq!("ｱｲｳ あいう 日", String::from_utf8_lossy(&Charset::MsxJp.decode(text)));
+        assert_eq!(text.to_vec(), Charset::Raw.decode(text));
+    }
+
+    #[test]
+    fn should_encode_texts() {
+        assert_eq!(Ok(b"\xb1\xb2\xb3 \x91\x92\x93 \x01\x47".to_vec()), Charset::MsxJp.encode("ｱｲｳ あいう 日".as_bytes()));
+        assert_eq!(Ok(b"Espa\xa4a".to_vec()), Charset::MsxInt.encode("España".as_bytes()));
+        assert!(Charset::MsxInt.encode("日本".as_bytes()).is_err());
+        assert!(Charset::MsxJp.encode(b"\xa4").is_err());
+        assert_eq!(Ok(b"\xa4".to_vec()), Charset::Raw.encode(b"\xa4"));
+    }
+
+    #[test]
+    fn should_round_trip_texts() {
+        let mut text: Vec<u8> = (0x80..=0xff).collect();
+        text.extend((0x41..0x60).flat_map(|c| vec![GRAPHIC, c]));
+        text.extend_from_slice(b"10 PRINT\r\n");
+        for charset in [Charset::MsxInt, Charset::MsxJp] {
+            assert_eq!(Ok(text.clone()), charset.encode(&charset.decode(&text)));
+        }
+    }
+
+    #[test]
+    fn should_round_trip_texts_through_tapes() {
+        let listing: String = (1..=40).map(|i| format!("{} PRINT \"ｶﾀｶﾅ ひらかな {}年\"\r\n", i * 10, i)).collect();
+        let text = Charset::MsxJp.encode(listing.as_bytes()).unwrap();
+        assert!(text.len() > 256);
+        let mut tape = Tape::new();
+        tape.append_ascii(&file_name("japan").0, &text).unwrap();
+        let files: Vec<_> = tape.files().collect();
+        assert_eq!(text, files[0].content());
+        assert_eq!(listing.as_bytes(), &Charset::MsxJp.decode(&files[0].content())[..]);
+    }
+}
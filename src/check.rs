@@ -0,0 +1,397 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::tape::{Block, Tape, BLOCK_PREFIX};
+
+/// The severity of an issue found in a tape
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Something worth knowing, but harmless
+    Info,
+    /// Something that may prevent the tape from loading in some cases
+    Warning,
+    /// Something that prevents the tape from loading
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        f.pad(name)
+    }
+}
+
+/// An issue found in a tape
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    /// The kind of check that found the issue: `structure`, `bin`, `basic`, `ascii`,
+    /// `padding` or `name`
+    pub check: &'static str,
+    /// The index of the block the issue refers to, if any
+    pub block: Option<usize>,
+    pub message: String,
+}
+
+/// The result of checking a tape
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub tape: String,
+    pub blocks: usize,
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    /// Returns the number of issues of the given severity.
+    pub fn count(&self, severity: Severity) -> usize {
+        self.issues.iter().filter(|i| i.severity == severity).count()
+    }
+
+    /// Returns the number of issues of the given severity or worse.
+    pub fn count_at_least(&self, severity: Severity) -> usize {
+        self.issues.iter().filter(|i| i.severity >= severity).count()
+    }
+}
+
+/// Check the health of the tape stored in the given bytes.
+///
+/// The structure of the tape is validated, as well as the contents of the files (BIN
+/// headers, Basic programs and ASCII files), the padding of the blocks and the names of
+/// the files.
+pub fn check(name: &str, bytes: &[u8]) -> Report {
+    let tape = Tape::from_bytes(bytes);
+    let mut checker = Checker { issues: vec![] };
+    checker.check_structure(bytes, &tape);
+    checker.check_files(tape.blocks());
+    checker.check_names(tape.blocks());
+    Report {
+        tape: name.to_string(),
+        blocks: tape.blocks().len(),
+        issues: checker.issues,
+    }
+}
+
+struct Checker {
+    issues: Vec<Issue>,
+}
+
+impl Checker {
+    fn report(&mut self, severity: Severity, check: &'static str, block: Option<usize>, message: String) {
+        self.issues.push(Issue { severity, check, block, message });
+    }
+
+    fn check_structure(&mut self, bytes: &[u8], tape: &Tape) {
+        if tape.blocks().is_empty() {
+            self.report(Severity::Error, "structure", None, tr!("no blocks found"));
+            return;
+        }
+
+        let first = bytes.chunks(8).position(|c| c == BLOCK_PREFIX).unwrap_or(0) * 8;
+        if first > 0 {
+            self.report(
+                Severity::Error,
+                "structure",
+                None,
+                tr!("{} bytes before the first block are ignored", first),
+            );
+        }
+
+        let misaligned = bytes
+            .windows(8)
+            .enumerate()
+            .filter(|(i, w)| i % 8 != 0 && *w == BLOCK_PREFIX)
+            .map(|(i, _)| i);
+        for offset in misaligned {
+            self.report(
+                Severity::Warning,
+                "structure",
+                None,
+                tr!("block prefix at offset {} is not 8-byte aligned and is ignored", offset),
+            );
+        }
+
+        if !bytes.len().is_multiple_of(8) {
+            self.report(
+                Severity::Warning,
+                "padding",
+                Some(tape.blocks().len() - 1),
+                tr!("tape size ({} bytes) is not a multiple of 8", bytes.len()),
+            );
+        }
+
+        let garbage = tape.trailing_garbage().len();
+        if garbage > 0 {
+            self.report(
+                Severity::Warning,
+                "structure",
+                Some(tape.blocks().len() - 1),
+                tr!("{} bytes of trailing garbage after the last file", garbage),
+            );
+        }
+
+        for (i, block) in tape.blocks().iter().enumerate() {
+            if block.data_without_prefix().is_empty() {
+                self.report(Severity::Warning, "structure", Some(i), tr!("empty block"));
+            }
+        }
+    }
+
+    fn check_files(&mut self, blocks: &[Block]) {
+        let mut i = 0;
+        while i < blocks.len() {
+            let header = &blocks[i];
+            if !header.is_file_header() {
+                i += 1;
+                continue;
+            }
+
+            let extra = header.data_without_prefix().len().saturating_sub(16);
+            if extra > 0 {
+                self.report(
+                    Severity::Info,
+                    "structure",
+                    Some(i),
+                    tr!("file header has {} extra bytes", extra),
+                );
+            }
+
+            let data = match blocks.get(i + 1) {
+                Some(block) if !block.is_file_header() => block.data_without_prefix(),
+                _ => {
+                    self.report(
+                        Severity::Error,
+                        "structure",
+                        Some(i),
+                        tr!("file header is not followed by a data block"),
+                    );
+                    i += 1;
+                    continue;
+                }
+            };
+
+            if header.is_bin_header() {
+                self.check_bin(i + 1, data);
+                i += 2;
+            } else if header.is_basic_header() {
+                self.check_basic(i + 1, data);
+                i += 2;
+            } else {
+                i = self.check_ascii(blocks, i + 1);
+            }
+        }
+    }
+
+    fn check_bin(&mut self, i: usize, data: &[u8]) {
+        if data.len() < 6 {
+            self.report(
+                Severity::Error,
+                "bin",
+                Some(i),
+                tr!("data block is too short ({} bytes) to have a header", data.len()),
+            );
+            return;
+        }
+        let begin = LittleEndian::read_u16(&data[0..2]) as usize;
+        let end = LittleEndian::read_u16(&data[2..4]) as usize;
+        let start = LittleEndian::read_u16(&data[4..6]) as usize;
+        if begin > end {
+            self.report(
+                Severity::Error,
+                "bin",
+                Some(i),
+                tr!("BEGIN address {} is above END address {}", format!("{:X}", begin), format!("{:X}", end)),
+            );
+            return;
+        }
+        let len = end - begin + 1;
+        let size = data.len() - 6;
+        if len > size {
+            self.report(
+                Severity::Error,
+                "bin",
+                Some(i),
+                tr!("program is truncated: {} bytes expected, {} bytes found", len, size),
+            );
+        } else if len < size {
+            self.report(
+                Severity::Info,
+                "padding",
+                Some(i),
+                tr!("program is followed by {} padding bytes", size - len),
+            );
+        }
+        if start < begin || start > end {
+            self.report(
+                Severity::Warning,
+                "bin",
+                Some(i),
+                tr!(
+                    "START address {} is not between BEGIN address {} and END address {}",
+                    format!("{:X}", start),
+                    format!("{:X}", begin),
+                    format!("{:X}", end)
+                ),
+            );
+        }
+    }
+
+    fn check_basic(&mut self, i: usize, data: &[u8]) {
+        if data.len() < 2 {
+            self.report(
+                Severity::Error,
+                "basic",
+                Some(i),
+                tr!("data block is too short ({} bytes) to have a program", data.len()),
+            );
+        }
+    }
+
+    fn check_ascii(&mut self, blocks: &[Block], from: usize) -> usize {
+        let mut i = from;
+        while i < blocks.len() && !blocks[i].is_file_header() {
+            let data = blocks[i].data_without_prefix();
+            if data.contains(&0x1a) {
+                return i + 1;
+            }
+            i += 1;
+        }
+        self.report(
+            Severity::Error,
+            "ascii",
+            Some(i - 1),
+            tr!("file is truncated: no EOF byte found"),
+        );
+        i
+    }
+
+    fn check_names(&mut self, blocks: &[Block]) {
+        let mut seen: HashMap<Vec<u8>, usize> = HashMap::new();
+        for (i, block) in blocks.iter().enumerate() {
+            if !block.is_file_header() || block.data_without_prefix().len() < 16 {
+                continue;
+            }
+            let name = &block.data_without_prefix()[10..16];
+            let printable = String::from_utf8_lossy(name);
+            if name.iter().any(|c| *c < 0x20 || *c > 0x7e) {
+                self.report(
+                    Severity::Warning,
+                    "name",
+                    Some(i),
+                    tr!("file name {} has non-printable characters", format!("{:?}", printable)),
+                );
+            } else if name.iter().all(|c| *c == b' ') {
+                self.report(Severity::Warning, "name", Some(i), tr!("file name is blank"));
+            } else if let Some(first) = seen.insert(name.to_vec(), i) {
+                self.report(
+                    Severity::Warning,
+                    "name",
+                    Some(i),
+                    tr!("file name {} is also used by block {}", format!("{:?}", printable), first),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::tape::file_name;
+
+    use super::*;
+
+    fn issues_of(tape: &Tape) -> Vec<(Severity, &'static str, Option<usize>)> {
+        let mut bytes = vec![];
+        for block in tape.blocks() {
+            bytes.extend_from_slice(block.data());
+        }
+        check("test.cas", &bytes)
+            .issues
+            .into_iter()
+            .map(|i| (i.severity, i.check, i.block))
+            .collect()
+    }
+
+    #[test]
+    fn should_report_empty_tape() {
+        let report = check("test.cas", &[]);
+        assert_eq!(1, report.count(Severity::Error));
+    }
+
+    #[test]
+    fn should_accept_healthy_tape() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("foo").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_ascii(&file_name("bar").0, b"10 PRINT\r\n").unwrap();
+        assert_eq!(Vec::<(Severity, &str, Option<usize>)>::new(), issues_of(&tape));
+    }
+
+    #[test]
+    fn should_report_bin_issues() {
+        let mut tape = Tape::new();
+        tape.append_raw_block(&[0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, b'f', b'o', b'o', b' ', b' ', b' ']);
+        tape.append_raw_block(&[0x00, 0x80, 0x08, 0x80, 0x00, 0x90, 0x00, 0x00]);
+        assert_eq!(
+            vec![(Severity::Error, "bin", Some(1)), (Severity::Warning, "bin", Some(1))],
+            issues_of(&tape));
+    }
+
+    #[test]
+    fn should_report_missing_data_block() {
+        let mut tape = Tape::new();
+        tape.append_raw_block(&[0xd3, 0xd3, 0xd3, 0xd3, 0xd3, 0xd3, 0xd3, 0xd3, 0xd3, 0xd3, b'f', b'o', b'o', b' ', b' ', b' ']);
+        assert_eq!(vec![(Severity::Error, "structure", Some(0))], issues_of(&tape));
+    }
+
+    #[test]
+    fn should_report_truncated_ascii_file() {
+        let mut tape = Tape::new();
+        tape.append_raw_block(&[0xea, 0xea, 0xea, 0xea, 0xea, 0xea, 0xea, 0xea, 0xea, 0xea, b'f', b'o', b'o', b' ', b' ', b' ']);
+        tape.append_raw_block(b"10 PRINT");
+        assert_eq!(vec![(Severity::Error, "ascii", Some(1))], issues_of(&tape));
+    }
+
+    #[test]
+    fn should_report_name_issues() {
+        let mut tape = Tape::new();
+        tape.append_basic(&file_name("foo").0, &[0x00, 0x00]).unwrap();
+        tape.append_basic(&file_name("foo").0, &[0x00, 0x00]).unwrap();
+        tape.append_basic(&file_name("").0, &[0x00, 0x00]).unwrap();
+        assert_eq!(
+            vec![(Severity::Warning, "name", Some(2)), (Severity::Warning, "name", Some(4))],
+            issues_of(&tape));
+    }
+
+    #[test]
+    fn should_report_garbage_and_misalignment() {
+        let mut bytes = vec![0x00; 8];
+        bytes.extend_from_slice(&BLOCK_PREFIX);
+        bytes.extend_from_slice(&[0x01, 0x02, 0x03]);
+        bytes.extend_from_slice(&BLOCK_PREFIX);
+        let report = check("test.cas", &bytes);
+        let checks: Vec<(Severity, &str)> = report.issues.iter().map(|i| (i.severity, i.check)).collect();
+        assert_eq!(
+            vec![
+                (Severity::Error, "structure"),
+                (Severity::Warning, "structure"),
+                (Severity::Warning, "padding"),
+                (Severity::Warning, "structure"),
+            ],
+            checks);
+    }
+}
@@ -0,0 +1,52 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io;
+use std::io::{BufRead, IsTerminal, Write};
+
+/// Ask the user to confirm a destructive operation, with the given question.
+///
+/// The user is only asked if the standard input is a terminal. Otherwise, as when `mcp`
+/// runs from a script, the operation is confirmed.
+pub fn confirm(question: &str) -> io::Result<bool> {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        return Ok(true);
+    }
+    ask(question, stdin.lock(), io::stdout())
+}
+
+/// Ask the given question into the given output, and read the answer from the given input.
+///
+/// Only `y` and `yes` confirm, in any case. An empty answer or the end of the input do not.
+pub fn ask<R: BufRead, W: Write>(question: &str, mut input: R, mut output: W) -> io::Result<bool> {
+    write!(output, "{} [y/N] ", question)?;
+    output.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    let answer = answer.trim().to_lowercase();
+    Ok(answer == "y" || answer == "yes")
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_ask_for_confirmation() {
+        let mut output = vec![];
+        assert!(ask("Remove 2 tapes?", &b"y\n"[..], &mut output).unwrap());
+        assert_eq!(b"Remove 2 tapes? [y/N] ", &output[..]);
+
+        assert!(ask("Remove 2 tapes?", &b" YES \n"[..], io::sink()).unwrap());
+        assert!(!ask("Remove 2 tapes?", &b"\n"[..], io::sink()).unwrap());
+        assert!(!ask("Remove 2 tapes?", &b"no\n"[..], io::sink()).unwrap());
+        assert!(!ask("Remove 2 tapes?", &b""[..], io::sink()).unwrap());
+    }
+}
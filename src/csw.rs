@@ -0,0 +1,148 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io;
+use std::io::Read;
+
+use byteorder::{ByteOrder, LittleEndian};
+use flate2::read::ZlibDecoder;
+
+use crate::import::Recording;
+
+/// The signature every CSW file starts with
+pub const MAGIC: &[u8] = b"Compressed Square Wave\x1a";
+
+/// A recording in CSW (Compressed Square Wave) format
+///
+/// A CSW file describes a square wave as the length of each of its pulses (in samples).
+/// Versions 1 (RLE compression) and 2 (RLE and Z-RLE compression) are supported.
+///
+#[derive(Debug, PartialEq)]
+pub struct Csw {
+    pub sample_rate: u32,
+    /// Whether the first pulse is high
+    pub high: bool,
+    pub pulses: Vec<u32>,
+}
+
+impl Csw {
+    /// Read a CSW file from the given bytes.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Csw> {
+        if !bytes.starts_with(MAGIC) || bytes.len() < 0x20 {
+            return Err(invalid_csw(&tr!("missing CSW header")));
+        }
+        let (sample_rate, compression, flags, data) = match bytes[0x17] {
+            1 => (
+                LittleEndian::read_u16(&bytes[0x19..0x1b]) as u32,
+                bytes[0x1b],
+                bytes[0x1c],
+                &bytes[0x20..],
+            ),
+            2 => {
+                if bytes.len() < 0x34 {
+                    return Err(invalid_csw(&tr!("header is too short")));
+                }
+                let data = 0x34 + bytes[0x23] as usize;
+                (
+                    LittleEndian::read_u32(&bytes[0x19..0x1d]),
+                    bytes[0x21],
+                    bytes[0x22],
+                    bytes.get(data..).unwrap_or(&[]),
+                )
+            }
+            v => return Err(invalid_csw(&tr!("unsupported version {}", v))),
+        };
+        if sample_rate == 0 {
+            return Err(invalid_csw(&tr!("sample rate is zero")));
+        }
+        let pulses = match compression {
+            1 => rle_pulses(data)?,
+            2 => {
+                let mut rle = vec![];
+                ZlibDecoder::new(data).read_to_end(&mut rle)?;
+                rle_pulses(&rle)?
+            }
+            c => return Err(invalid_csw(&tr!("unsupported compression type {}", c))),
+        };
+        Ok(Csw { sample_rate, high: flags & 0x01 != 0, pulses })
+    }
+
+    /// Returns the recording described by this CSW file.
+    pub fn recording(&self) -> Recording {
+        Recording::from_pulses(self.sample_rate, &self.pulses, self.high)
+    }
+}
+
+/// Decode RLE pulses: every byte is the length of a pulse, unless it is zero, in which
+/// case the length is the next 32-bit word.
+fn rle_pulses(data: &[u8]) -> io::Result<Vec<u32>> {
+    let mut pulses = vec![];
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] != 0 {
+            pulses.push(data[i] as u32);
+            i += 1;
+        } else if i + 5 <= data.len() {
+            pulses.push(LittleEndian::read_u32(&data[i + 1..i + 5]));
+            i += 5;
+        } else {
+            return Err(invalid_csw(&tr!("truncated pulse length")));
+        }
+    }
+    Ok(pulses)
+}
+
+fn invalid_csw(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, tr!("invalid CSW file: {}", msg))
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Write;
+
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    const RLE: &[u8] = &[0x12, 0x24, 0x00, 0x00, 0x01, 0x00, 0x00];
+
+    #[test]
+    fn should_read_csw1() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[0x01, 0x01, 0x44, 0xac, 0x01, 0x01, 0x00, 0x00, 0x00]);
+        bytes.extend_from_slice(RLE);
+        assert_eq!(
+            Csw { sample_rate: 44100, high: true, pulses: vec![0x12, 0x24, 0x100] },
+            Csw::from_bytes(&bytes).unwrap());
+    }
+
+    #[test]
+    fn should_read_csw2() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[0x02, 0x00, 0x44, 0xac, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00]);
+        bytes.extend_from_slice(&[0x02, 0x00, 0x02]);
+        bytes.extend_from_slice(&[0x00; 16]);
+        bytes.extend_from_slice(&[0xaa, 0xbb]);
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(RLE).unwrap();
+        bytes.extend_from_slice(&encoder.finish().unwrap());
+        assert_eq!(
+            Csw { sample_rate: 44100, high: false, pulses: vec![0x12, 0x24, 0x100] },
+            Csw::from_bytes(&bytes).unwrap());
+    }
+
+    #[test]
+    fn should_reject_truncated_pulses() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[0x01, 0x01, 0x44, 0xac, 0x01, 0x01, 0x00, 0x00, 0x00]);
+        bytes.extend_from_slice(&[0x12, 0x00, 0x01]);
+        assert!(Csw::from_bytes(&bytes).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::hash::Digest;
+
+/// An entry of a DAT file describing a known-good dump
+///
+/// Each entry belongs to a game (the collection item) and describes one of its
+/// files (the "rom" in DAT jargon). The size and hashes are optional, since not
+/// every DAT file provides all of them.
+///
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub game: String,
+    pub name: String,
+    pub size: Option<u64>,
+    pub crc32: Option<u32>,
+    pub sha1: Option<String>,
+}
+
+impl Entry {
+    /// Returns `true` if the given digest corresponds to this entry.
+    ///
+    /// The SHA1 is used if the entry provides it. Otherwise, the CRC32 is used. The size
+    /// must match in any case if the entry provides it. An entry without any hash never
+    /// matches.
+    pub fn matches(&self, digest: &Digest) -> bool {
+        if let Some(size) = self.size {
+            if size != digest.size {
+                return false;
+            }
+        }
+        match (&self.sha1, self.crc32) {
+            (Some(sha1), _) => sha1.eq_ignore_ascii_case(&digest.sha1),
+            (None, Some(crc32)) => crc32 == digest.crc32,
+            (None, None) => false,
+        }
+    }
+}
+
+/// The result of looking up a dump in a DAT file
+#[derive(Debug, PartialEq)]
+pub enum Verdict<'a> {
+    /// The dump matches a known-good entry.
+    Match(&'a Entry),
+    /// There is an entry with the same name, but the dump does not match it.
+    Mismatch(&'a Entry),
+    /// The dump is not known by the DAT file.
+    Unknown,
+}
+
+/// A collection of known-good dumps
+///
+/// Two formats are supported:
+/// * Logiqx XML DAT files, as used by TOSEC, No-Intro and most ROM managers. Every
+///   `<rom>` element of every `<game>` (or `<machine>`) is loaded as an entry.
+/// * CSV files. If the first line is a header, it is used to determine the columns
+///   (`game`, `name`, `size`, `crc32` or `crc` and `sha1`). Otherwise the columns are
+///   expected to be `name,size,crc32,sha1`. Empty lines and lines starting with `#`
+///   are ignored.
+///
+#[derive(Debug)]
+pub struct Dat {
+    entries: Vec<Entry>,
+}
+
+impl Dat {
+    /// Load a DAT file from the given path.
+    pub fn from_file(path: &Path) -> io::Result<Dat> {
+        let text = fs::read_to_string(path)?;
+        Dat::parse(&text)
+    }
+
+    /// Parse the given DAT file contents, detecting whether it is XML or CSV.
+    pub fn parse(text: &str) -> io::Result<Dat> {
+        if text.trim_start().starts_with('<') {
+            Dat::parse_xml(text)
+        } else {
+            Dat::parse_csv(text)
+        }
+    }
+
+    /// Returns the entries of this DAT file.
+    #[allow(dead_code)]
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries[..]
+    }
+
+    /// Look up the dump with the given name and digest.
+    ///
+    /// Any entry whose hashes match the digest is a match, regardless of its name. If
+    /// none matches, but there is an entry with the same name (case insensitive), the
+    /// dump is considered a mismatch.
+    pub fn lookup(&self, name: &str, digest: &Digest) -> Verdict<'_> {
+        if let Some(entry) = self.entries.iter().find(|e| e.matches(digest)) {
+            return Verdict::Match(entry);
+        }
+        match self.entries.iter().find(|e| e.name.eq_ignore_ascii_case(name)) {
+            Some(entry) => Verdict::Mismatch(entry),
+            None => Verdict::Unknown,
+        }
+    }
+
+    fn parse_xml(text: &str) -> io::Result<Dat> {
+        let mut entries = vec![];
+        let mut game = String::new();
+        let mut rest = text;
+        while let Some(from) = rest.find('<') {
+            let to = rest[from..].find('>').ok_or_else(|| invalid_dat(&tr!("unterminated XML tag")))?;
+            let tag = &rest[from + 1..from + to];
+            rest = &rest[from + to + 1..];
+
+            let tag_name = tag.split_whitespace().next().unwrap_or("");
+            match tag_name {
+                "game" | "machine" => {
+                    game = xml_attribute(tag, "name").unwrap_or_default();
+                }
+                "rom" => {
+                    let name = xml_attribute(tag, "name")
+                        .ok_or_else(|| invalid_dat(&tr!("rom element without name")))?;
+                    entries.push(Entry {
+                        game: game.clone(),
+                        name,
+                        size: parse_size(xml_attribute(tag, "size"))?,
+                        crc32: parse_crc32(xml_attribute(tag, "crc"))?,
+                        sha1: parse_sha1(xml_attribute(tag, "sha1")),
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(Dat { entries })
+    }
+
+    fn parse_csv(text: &str) -> io::Result<Dat> {
+        let mut entries = vec![];
+        let mut columns = vec!["name", "size", "crc32", "sha1"];
+        let lines = text
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'));
+        for (i, line) in lines.enumerate() {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim().trim_matches('"')).collect();
+            if i == 0 && fields.iter().any(|f| f.eq_ignore_ascii_case("name")) {
+                columns = fields.iter().map(|f| csv_column(f)).collect();
+                continue;
+            }
+            let field = |col: &str| {
+                columns
+                    .iter()
+                    .position(|c| *c == col)
+                    .and_then(|i| fields.get(i))
+                    .filter(|f| !f.is_empty())
+                    .map(|f| f.to_string())
+            };
+            let name = field("name").ok_or_else(|| invalid_dat(&tr!("CSV line without name")))?;
+            entries.push(Entry {
+                game: field("game").unwrap_or_else(|| name.clone()),
+                name,
+                size: parse_size(field("size"))?,
+                crc32: parse_crc32(field("crc32"))?,
+                sha1: parse_sha1(field("sha1")),
+            });
+        }
+        Ok(Dat { entries })
+    }
+}
+
+fn csv_column(header: &str) -> &'static str {
+    match header.to_lowercase().as_str() {
+        "game" => "game",
+        "name" => "name",
+        "size" => "size",
+        "crc" | "crc32" => "crc32",
+        "sha1" => "sha1",
+        _ => "",
+    }
+}
+
+fn xml_attribute(tag: &str, attr: &str) -> Option<String> {
+    let pattern = format!(" {}=\"", attr);
+    let from = tag.find(&pattern)? + pattern.len();
+    let len = tag[from..].find('"')?;
+    Some(
+        tag[from..from + len]
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&"),
+    )
+}
+
+fn parse_size(value: Option<String>) -> io::Result<Option<u64>> {
+    value
+        .map(|v| v.parse().map_err(|_| invalid_dat(&tr!("invalid size {}", format!("{:?}", v)))))
+        .transpose()
+}
+
+fn parse_crc32(value: Option<String>) -> io::Result<Option<u32>> {
+    value
+        .map(|v| u32::from_str_radix(&v, 16).map_err(|_| invalid_dat(&tr!("invalid CRC32 {}", format!("{:?}", v)))))
+        .transpose()
+}
+
+fn parse_sha1(value: Option<String>) -> Option<String> {
+    value.map(|v| v.to_lowercase())
+}
+
+fn invalid_dat(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, tr!("invalid DAT file: {}", msg))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_parse_xml_dat() {
+        let dat = Dat::parse(
+            r#"<?xml version="1.0"?>
+            <datafile>
+                <header><name>MSX Tapes</name></header>
+                <game name="Foo &amp; Bar (1985)">
+                    <description>Foo &amp; Bar</description>
+                    <rom name="foobar.cas" size="12" crc="1C291CA3" sha1="2EF7BDE608CE5404E97D5F042F95F89F1C232871"/>
+                </game>
+            </datafile>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            &[Entry {
+                game: "Foo & Bar (1985)".to_string(),
+                name: "foobar.cas".to_string(),
+                size: Some(12),
+                crc32: Some(0x1c291ca3),
+                sha1: Some("2ef7bde608ce5404e97d5f042f95f89f1c232871".to_string()),
+            }],
+            dat.entries()
+        );
+    }
+
+    #[test]
+    fn should_parse_csv_dat_without_header() {
+        let dat = Dat::parse("# Known dumps\nfoobar.cas,12,1c291ca3,\n").unwrap();
+        assert_eq!(
+            &[Entry {
+                game: "foobar.cas".to_string(),
+                name: "foobar.cas".to_string(),
+                size: Some(12),
+                crc32: Some(0x1c291ca3),
+                sha1: None,
+            }],
+            dat.entries()
+        );
+    }
+
+    #[test]
+    fn should_parse_csv_dat_with_header() {
+        let dat = Dat::parse("sha1,name,game\n2EF7BDE608CE5404E97D5F042F95F89F1C232871,foobar.cas,Foobar\n").unwrap();
+        assert_eq!(
+            &[Entry {
+                game: "Foobar".to_string(),
+                name: "foobar.cas".to_string(),
+                size: None,
+                crc32: None,
+                sha1: Some("2ef7bde608ce5404e97d5f042f95f89f1c232871".to_string()),
+            }],
+            dat.entries()
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_crc32() {
+        assert!(Dat::parse("foobar.cas,12,xyz,\n").is_err());
+    }
+
+    #[test]
+    fn should_lookup_dumps() {
+        let dat = Dat::parse("name,size,crc32\nfoobar.cas,12,1c291ca3\n").unwrap();
+        let good = Digest::of(b"Hello World!");
+        let bad = Digest::of(b"Hello World?");
+        assert_eq!(Verdict::Match(&dat.entries()[0]), dat.lookup("other.cas", &good));
+        assert_eq!(Verdict::Mismatch(&dat.entries()[0]), dat.lookup("FOOBAR.CAS", &bad));
+        assert_eq!(Verdict::Unknown, dat.lookup("other.cas", &bad));
+    }
+}
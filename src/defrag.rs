@@ -0,0 +1,118 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use memchr::memmem;
+
+use crate::tape::{Change, Tape, BLOCK_PREFIX};
+
+/// Defragment the tape stored in the given bytes, returning it along with the changes made.
+///
+/// Unlike reading the tape, which only finds the blocks that start at an 8-byte boundary,
+/// every block prefix found in the bytes starts a block. The blocks are written one after
+/// another, so every block starts at an 8-byte boundary, and the bytes that do not belong
+/// to any file are removed:
+///
+/// * The bytes before the first block.
+/// * The trailing garbage of the tape (see `Tape::trailing_garbage()`).
+/// * The bytes past the END address of binary files, which `BLOAD` never reads (see
+///   `Tape::trim_bin_blocks()`).
+///
+/// The blocks that are moved to an 8-byte boundary are padded with zeroes, as when they are
+/// added to a tape, and no other byte is changed.
+///
+pub fn defrag(bytes: &[u8]) -> (Tape, Vec<Change>) {
+    let offsets: Vec<usize> = memmem::find_iter(bytes, &BLOCK_PREFIX).collect();
+    let mut changes = vec![];
+    match offsets.first() {
+        Some(first) if *first > 0 => changes.push(Change::Leading { bytes: *first }),
+        Some(_) => {}
+        None => return (Tape::new(), changes),
+    }
+
+    let mut aligned = vec![];
+    for (i, from) in offsets.iter().enumerate() {
+        if i > 0 {
+            let padding = aligned.len().next_multiple_of(8) - aligned.len();
+            aligned.resize(aligned.len() + padding, 0x00);
+        }
+        if !from.is_multiple_of(8) {
+            changes.push(Change::Aligned { block: i, from: *from, to: aligned.len() });
+        }
+        let to = offsets.get(i + 1).copied().unwrap_or(bytes.len());
+        aligned.extend_from_slice(&bytes[*from..to]);
+    }
+
+    let mut tape = Tape::from_bytes(&aligned);
+    let garbage = tape.trim_trailing_garbage();
+    if garbage > 0 {
+        changes.push(Change::Trimmed { block: tape.blocks().len() - 1, bytes: garbage });
+    }
+    let lens: Vec<usize> = tape.blocks().iter().map(|b| b.data().len()).collect();
+    tape.trim_bin_blocks();
+    for (i, (block, len)) in tape.blocks().iter().zip(lens).enumerate() {
+        if block.data().len() < len {
+            changes.push(Change::Trimmed { block: i, bytes: len - block.data().len() });
+        }
+    }
+    (tape, changes)
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::tape::file_name;
+
+    use super::*;
+
+    #[test]
+    fn should_keep_defragmented_tapes() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("game").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_custom(&[0x01; 16]).unwrap();
+        let bytes = tape.to_bytes();
+        let (defragged, changes) = defrag(&bytes);
+        assert_eq!(Vec::<Change>::new(), changes);
+        assert_eq!(bytes, defragged.to_bytes());
+    }
+
+    #[test]
+    fn should_defrag_tapes() {
+        let mut bytes = vec![0xff; 3];
+        bytes.extend_from_slice(&BLOCK_PREFIX);
+        bytes.extend_from_slice(&[0x01, 0x02, 0x03]);
+        bytes.extend_from_slice(&BLOCK_PREFIX);
+        bytes.extend_from_slice(&[0x04; 8]);
+        bytes.extend_from_slice(&[0xee; 3]);
+
+        let (tape, changes) = defrag(&bytes);
+        assert_eq!(
+            vec![
+                Change::Leading { bytes: 3 },
+                Change::Aligned { block: 0, from: 3, to: 0 },
+                Change::Aligned { block: 1, from: 14, to: 16 },
+                Change::Trimmed { block: 1, bytes: 3 },
+            ],
+            changes);
+        assert_eq!(2, tape.blocks().len());
+        assert_eq!(&[0x01, 0x02, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00], tape.blocks()[0].data_without_prefix());
+        assert_eq!(&[0x04; 8], tape.blocks()[1].data_without_prefix());
+        assert!(defrag(&[0x00; 16]).0.blocks().is_empty());
+    }
+
+    #[test]
+    fn should_trim_binary_files() {
+        let mut data = vec![0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9];
+        data.extend_from_slice(&[0x00; 16]);
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("game").0, &data).unwrap();
+        tape.append_custom(&[0x01; 8]).unwrap();
+        let (defragged, changes) = defrag(&tape.to_bytes());
+        assert_eq!(vec![Change::Trimmed { block: 1, bytes: 16 }], changes);
+        assert_eq!(&data[..8], defragged.blocks()[1].data_without_prefix());
+    }
+}
@@ -0,0 +1,344 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Opcodes are decoded following the structure described in "Decoding Z80 Opcodes" by
+// Cristian Dinu, including the undocumented ones (e.g., `SLL` and `IXH`).
+
+const R: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+const RP: [&str; 4] = ["BC", "DE", "HL", "SP"];
+const RP2: [&str; 4] = ["BC", "DE", "HL", "AF"];
+const CC: [&str; 8] = ["NZ", "Z", "NC", "C", "PO", "PE", "P", "M"];
+const ALU: [&str; 8] = ["ADD A,", "ADC A,", "SUB ", "SBC A,", "AND ", "XOR ", "OR ", "CP "];
+const ROT: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SLL", "SRL"];
+const IM: [&str; 8] = ["0", "0/1", "1", "2", "0", "0/1", "1", "2"];
+const BLOCK: [[&str; 4]; 4] = [
+    ["LDI", "CPI", "INI", "OUTI"],
+    ["LDD", "CPD", "IND", "OUTD"],
+    ["LDIR", "CPIR", "INIR", "OTIR"],
+    ["LDDR", "CPDR", "INDR", "OTDR"],
+];
+
+/// The entry points of the BIOS of MSX computers, as called by programs
+const BIOS: &[(u16, &str)] = &[
+    (0x0000, "CHKRAM"), (0x0008, "SYNCHR"), (0x000c, "RDSLT"), (0x0010, "CHRGTR"),
+    (0x0014, "WRSLT"), (0x0018, "OUTDO"), (0x001c, "CALSLT"), (0x0020, "DCOMPR"),
+    (0x0024, "ENASLT"), (0x0028, "GETYPR"), (0x0030, "CALLF"), (0x0038, "KEYINT"),
+    (0x003b, "INITIO"), (0x003e, "INIFNK"), (0x0041, "DISSCR"), (0x0044, "ENASCR"),
+    (0x0047, "WRTVDP"), (0x004a, "RDVRM"), (0x004d, "WRTVRM"), (0x0050, "SETRD"),
+    (0x0053, "SETWRT"), (0x0056, "FILVRM"), (0x0059, "LDIRMV"), (0x005c, "LDIRVM"),
+    (0x005f, "CHGMOD"), (0x0062, "CHGCLR"), (0x0066, "NMI"), (0x0069, "CLRSPR"),
+    (0x006c, "INITXT"), (0x006f, "INIT32"), (0x0072, "INIGRP"), (0x0075, "INIMLT"),
+    (0x0078, "SETTXT"), (0x007b, "SETT32"), (0x007e, "SETGRP"), (0x0081, "SETMLT"),
+    (0x0084, "CALPAT"), (0x0087, "CALATR"), (0x008a, "GSPSIZ"), (0x008d, "GRPPRT"),
+    (0x0090, "GICINI"), (0x0093, "WRTPSG"), (0x0096, "RDPSG"), (0x0099, "STRTMS"),
+    (0x009c, "CHSNS"), (0x009f, "CHGET"), (0x00a2, "CHPUT"), (0x00a5, "LPTOUT"),
+    (0x00a8, "LPTSTT"), (0x00ab, "CNVCHR"), (0x00ae, "PINLIN"), (0x00b1, "INLIN"),
+    (0x00b4, "QINLIN"), (0x00b7, "BREAKX"), (0x00ba, "ISCNTC"), (0x00bd, "CKCNTC"),
+    (0x00c0, "BEEP"), (0x00c3, "CLS"), (0x00c6, "POSIT"), (0x00c9, "FNKSB"),
+    (0x00cc, "ERAFNK"), (0x00cf, "DSPFNK"), (0x00d2, "TOTEXT"), (0x00d5, "GTSTCK"),
+    (0x00d8, "GTTRIG"), (0x00db, "GTPAD"), (0x00de, "GTPDL"), (0x00e1, "TAPION"),
+    (0x00e4, "TAPIN"), (0x00e7, "TAPIOF"), (0x00ea, "TAPOON"), (0x00ed, "TAPOUT"),
+    (0x00f0, "TAPOOF"), (0x00f3, "STMOTR"), (0x0132, "CHGCAP"), (0x0135, "CHGSND"),
+    (0x0138, "RSLREG"), (0x013b, "WSLREG"), (0x013e, "RDVDP"), (0x0141, "SNSMAT"),
+    (0x0156, "KILBUF"), (0x015f, "EXTROM"),
+];
+
+/// Returns the name of the BIOS routine at the given address, if any.
+pub fn bios_routine(addr: u16) -> Option<&'static str> {
+    BIOS.iter().find(|(a, _)| *a == addr).map(|(_, name)| *name)
+}
+
+/// A decoded instruction
+#[derive(Debug, PartialEq)]
+pub struct Instruction {
+    /// The address of the instruction
+    pub addr: u16,
+    /// The bytes of the instruction
+    pub bytes: Vec<u8>,
+    /// The instruction in assembly language, as `LD HL,0xc000`
+    pub text: String,
+    /// The address the instruction jumps to or calls, if any
+    pub target: Option<u16>,
+}
+
+/// Decode the instructions of the given code, loaded at the given address, from the given
+/// offset on.
+///
+/// The bytes of an instruction cut at the end of the code, and the prefixes that have no
+/// effect on the opcode that follows them, are shown as data bytes.
+pub fn disassemble(org: u16, code: &[u8], from: usize) -> Vec<Instruction> {
+    let mut instructions = vec![];
+    let mut pos = from;
+    while pos < code.len() {
+        let addr = org.wrapping_add(pos as u16);
+        let instruction = Decoder::new(&code[pos..], addr).decode().unwrap_or_else(|| Instruction {
+            addr,
+            bytes: vec![code[pos]],
+            text: format!("DB 0x{:02x}", code[pos]),
+            target: None,
+        });
+        pos += instruction.bytes.len();
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+/// The decoder of a single instruction
+struct Decoder<'a> {
+    code: &'a [u8],
+    addr: u16,
+    pos: usize,
+    /// The index register replacing HL, if the instruction has a DD or FD prefix
+    index: Option<&'static str>,
+    /// The displacement of the indexed operand, once read
+    disp: Option<i8>,
+    target: Option<u16>,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(code: &'a [u8], addr: u16) -> Decoder<'a> {
+        Decoder { code, addr, pos: 0, index: None, disp: None, target: None }
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        let byte = *self.code.get(self.pos)?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    fn word(&mut self) -> Option<u16> {
+        Some(self.byte()? as u16 | (self.byte()? as u16) << 8)
+    }
+
+    fn n(&mut self) -> Option<String> {
+        Some(format!("0x{:02x}", self.byte()?))
+    }
+
+    fn nn(&mut self) -> Option<String> {
+        Some(format!("0x{:04x}", self.word()?))
+    }
+
+    /// Read an absolute address jumped to or called.
+    fn jump(&mut self) -> Option<String> {
+        let target = self.word()?;
+        self.target = Some(target);
+        Some(format!("0x{:04x}", target))
+    }
+
+    /// Read a relative jump, resolved to its absolute address.
+    fn relative(&mut self) -> Option<String> {
+        let offset = self.byte()? as i8;
+        let target = self.addr.wrapping_add(self.pos as u16).wrapping_add(offset as u16);
+        self.target = Some(target);
+        Some(format!("0x{:04x}", target))
+    }
+
+    fn hl(&self) -> &'static str {
+        self.index.unwrap_or("HL")
+    }
+
+    /// Returns the given 8-bit register, where H and L are the halves of the index register
+    /// unless the instruction also refers to `(HL)`.
+    fn r(&mut self, i: u8, has_memory: bool) -> Option<String> {
+        match (i, self.index) {
+            (6, Some(index)) => {
+                let disp = match self.disp {
+                    Some(disp) => disp,
+                    None => self.byte()? as i8,
+                };
+                self.disp = Some(disp);
+                let sign = if disp < 0 { '-' } else { '+' };
+                Some(format!("({}{}0x{:02x})", index, sign, disp.unsigned_abs()))
+            }
+            (4, Some(index)) if !has_memory => Some(format!("{}H", index)),
+            (5, Some(index)) if !has_memory => Some(format!("{}L", index)),
+            _ => Some(R[i as usize].to_string()),
+        }
+    }
+
+    fn rp(&self, p: u8) -> &'static str {
+        if p == 2 { self.hl() } else { RP[p as usize] }
+    }
+
+    fn rp2(&self, p: u8) -> &'static str {
+        if p == 2 { self.hl() } else { RP2[p as usize] }
+    }
+
+    fn decode(mut self) -> Option<Instruction> {
+        let mut opcode = self.byte()?;
+        if opcode == 0xdd || opcode == 0xfd {
+            self.index = Some(if opcode == 0xdd { "IX" } else { "IY" });
+            opcode = self.byte()?;
+            // A prefix followed by another one is ignored
+            if opcode == 0xdd || opcode == 0xfd || opcode == 0xed {
+                return None;
+            }
+        }
+        let text = match opcode {
+            0xcb => self.decode_cb()?,
+            0xed => self.decode_ed()?,
+            _ => self.decode_main(opcode)?,
+        };
+        Some(Instruction { addr: self.addr, bytes: self.code[..self.pos].to_vec(), text, target: self.target })
+    }
+
+    fn decode_main(&mut self, opcode: u8) -> Option<String> {
+        let (x, y, z) = (opcode >> 6, (opcode >> 3) & 7, opcode & 7);
+        let (p, q) = (y >> 1, y & 1);
+        let text = match (x, z) {
+            (0, 0) => match y {
+                0 => "NOP".to_string(),
+                1 => "EX AF,AF'".to_string(),
+                2 => format!("DJNZ {}", self.relative()?),
+                3 => format!("JR {}", self.relative()?),
+                _ => format!("JR {},{}", CC[y as usize - 4], self.relative()?),
+            },
+            (0, 1) if q == 0 => format!("LD {},{}", self.rp(p), self.nn()?),
+            (0, 1) => format!("ADD {},{}", self.hl(), self.rp(p)),
+            (0, 2) => match (p, q) {
+                (0, 0) => "LD (BC),A".to_string(),
+                (1, 0) => "LD (DE),A".to_string(),
+                (2, 0) => format!("LD ({}),{}", self.nn()?, self.hl()),
+                (3, 0) => format!("LD ({}),A", self.nn()?),
+                (0, _) => "LD A,(BC)".to_string(),
+                (1, _) => "LD A,(DE)".to_string(),
+                (2, _) => format!("LD {},({})", self.hl(), self.nn()?),
+                _ => format!("LD A,({})", self.nn()?),
+            },
+            (0, 3) => format!("{} {}", if q == 0 { "INC" } else { "DEC" }, self.rp(p)),
+            (0, 4) => format!("INC {}", self.r(y, false)?),
+            (0, 5) => format!("DEC {}", self.r(y, false)?),
+            (0, 6) => {
+                let r = self.r(y, false)?;
+                format!("LD {},{}", r, self.n()?)
+            }
+            (0, _) => ["RLCA", "RRCA", "RLA", "RRA", "DAA", "CPL", "SCF", "CCF"][y as usize].to_string(),
+            (1, 6) if y == 6 => "HALT".to_string(),
+            (1, _) => {
+                let has_memory = y == 6 || z == 6;
+                let dst = self.r(y, has_memory)?;
+                format!("LD {},{}", dst, self.r(z, has_memory)?)
+            }
+            (2, _) => format!("{}{}", ALU[y as usize], self.r(z, false)?),
+            (_, 0) => format!("RET {}", CC[y as usize]),
+            (_, 1) => match (q, p) {
+                (0, _) => format!("POP {}", self.rp2(p)),
+                (_, 0) => "RET".to_string(),
+                (_, 1) => "EXX".to_string(),
+                (_, 2) => format!("JP ({})", self.hl()),
+                _ => format!("LD SP,{}", self.hl()),
+            },
+            (_, 2) => format!("JP {},{}", CC[y as usize], self.jump()?),
+            (_, 3) => match y {
+                0 => format!("JP {}", self.jump()?),
+                2 => format!("OUT ({}),A", self.n()?),
+                3 => format!("IN A,({})", self.n()?),
+                4 => format!("EX (SP),{}", self.hl()),
+                5 => "EX DE,HL".to_string(),
+                6 => "DI".to_string(),
+                _ => "EI".to_string(),
+            },
+            (_, 4) => format!("CALL {},{}", CC[y as usize], self.jump()?),
+            (_, 5) if q == 0 => format!("PUSH {}", self.rp2(p)),
+            (_, 5) => format!("CALL {}", self.jump()?),
+            (_, 6) => format!("{}{}", ALU[y as usize], self.n()?),
+            _ => {
+                self.target = Some(y as u16 * 8);
+                format!("RST 0x{:02x}", y * 8)
+            }
+        };
+        Some(text)
+    }
+
+    fn decode_cb(&mut self) -> Option<String> {
+        // The displacement of indexed instructions comes before the opcode
+        if self.index.is_some() {
+            self.r(6, true)?;
+        }
+        let opcode = self.byte()?;
+        let (x, y, z) = (opcode >> 6, (opcode >> 3) & 7, opcode & 7);
+        let operand = match self.index {
+            // Undocumented: the result is also copied into the given register
+            Some(_) if z != 6 && x != 1 => format!("{},{}", self.r(6, true)?, R[z as usize]),
+            Some(_) => self.r(6, true)?,
+            None => R[z as usize].to_string(),
+        };
+        let text = match x {
+            0 => format!("{} {}", ROT[y as usize], operand),
+            1 => format!("BIT {},{}", y, operand),
+            2 => format!("RES {},{}", y, operand),
+            _ => format!("SET {},{}", y, operand),
+        };
+        Some(text)
+    }
+
+    fn decode_ed(&mut self) -> Option<String> {
+        let opcode = self.byte()?;
+        let (x, y, z) = (opcode >> 6, (opcode >> 3) & 7, opcode & 7);
+        let (p, q) = (y >> 1, y & 1);
+        let text = match (x, z) {
+            (1, 0) if y == 6 => "IN (C)".to_string(),
+            (1, 0) => format!("IN {},(C)", R[y as usize]),
+            (1, 1) if y == 6 => "OUT (C),0".to_string(),
+            (1, 1) => format!("OUT (C),{}", R[y as usize]),
+            (1, 2) => format!("{} HL,{}", if q == 0 { "SBC" } else { "ADC" }, RP[p as usize]),
+            (1, 3) if q == 0 => format!("LD ({}),{}", self.nn()?, RP[p as usize]),
+            (1, 3) => format!("LD {},({})", RP[p as usize], self.nn()?),
+            (1, 4) => "NEG".to_string(),
+            (1, 5) => if y == 1 { "RETI" } else { "RETN" }.to_string(),
+            (1, 6) => format!("IM {}", IM[y as usize]),
+            (1, _) => ["LD I,A", "LD R,A", "LD A,I", "LD A,R", "RRD", "RLD", "NOP", "NOP"][y as usize].to_string(),
+            (2, 0..=3) if y >= 4 => BLOCK[y as usize - 4][z as usize].to_string(),
+            _ => return None,
+        };
+        Some(text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn text(code: &[u8]) -> Vec<String> {
+        disassemble(0x9000, code, 0).into_iter().map(|i| i.text).collect()
+    }
+
+    #[test]
+    fn should_decode_unprefixed_instructions() {
+        assert_eq!(
+            vec!["LD HL,0xc05a", "LD B,0x01", "PUSH BC", "LD E,(HL)", "ADD A,L", "JR NZ,0x9007", "CALL 0x00a2", "RET"],
+            text(&[0x21, 0x5a, 0xc0, 0x06, 0x01, 0xc5, 0x5e, 0x85, 0x20, 0xfd, 0xcd, 0xa2, 0x00, 0xc9]));
+        assert_eq!(vec!["HALT", "EX AF,AF'", "RST 0x38", "CP 0x1a", "LD (0xf3ae),A"], text(&[0x76, 0x08, 0xff, 0xfe, 0x1a, 0x32, 0xae, 0xf3]));
+    }
+
+    #[test]
+    fn should_decode_prefixed_instructions() {
+        assert_eq!(vec!["BIT 7,A", "SRL (HL)"], text(&[0xcb, 0x7f, 0xcb, 0x3e]));
+        assert_eq!(vec!["LDIR", "IM 1", "LD (0xc000),DE", "SBC HL,BC"], text(&[0xed, 0xb0, 0xed, 0x56, 0xed, 0x53, 0x00, 0xc0, 0xed, 0x42]));
+        assert_eq!(
+            vec!["LD IX,0x1234", "LD (IX+0x05),0x20", "LD H,(IX-0x02)", "LD IYL,A", "SET 3,(IY+0x01)", "JP (IX)"],
+            text(&[0xdd, 0x21, 0x34, 0x12, 0xdd, 0x36, 0x05, 0x20, 0xdd, 0x66, 0xfe, 0xfd, 0x6f, 0xfd, 0xcb, 0x01, 0xde, 0xdd, 0xe9]));
+    }
+
+    #[test]
+    fn should_decode_jump_targets() {
+        let instructions = disassemble(0x9000, &[0x18, 0xfe, 0xc3, 0x00, 0xc0, 0x3e, 0x00], 0);
+        let targets: Vec<Option<u16>> = instructions.iter().map(|i| i.target).collect();
+        assert_eq!(vec![Some(0x9000), Some(0xc000), None], targets);
+        assert_eq!(Some("CHPUT"), bios_routine(0x00a2));
+    }
+
+    #[test]
+    fn should_show_incomplete_instructions_as_data() {
+        let instructions = disassemble(0x9000, &[0x00, 0x00, 0x21, 0x00], 2);
+        assert_eq!(vec!["DB 0x21", "NOP"], instructions.iter().map(|i| i.text.as_str()).collect::<Vec<_>>());
+        assert_eq!(0x9003, instructions[1].addr);
+        assert_eq!(vec!["DB 0xdd", "NOP"], text(&[0xdd, 0xdd, 0x00]));
+    }
+}
@@ -0,0 +1,61 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// The number of bytes dumped per line
+const LINE_LEN: usize = 16;
+
+/// Returns the canonical hex+ASCII dump of the given data, as `hexdump -C` prints it.
+///
+/// Each line gives the offset of its first byte, up to 16 bytes in hex, split in two
+/// groups of 8, and the same bytes as ASCII characters, with a dot for the non-printable
+/// ones. The last line gives the size of the data.
+///
+pub fn hexdump(data: &[u8]) -> Vec<String> {
+    let mut lines: Vec<String> = data
+        .chunks(LINE_LEN)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut hex = String::new();
+            for j in 0..LINE_LEN {
+                if j == LINE_LEN / 2 {
+                    hex.push(' ');
+                }
+                match chunk.get(j) {
+                    Some(byte) => hex += &format!("{:02x} ", byte),
+                    None => hex += "   ",
+                }
+            }
+            let ascii: String = chunk
+                .iter()
+                .map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' })
+                .collect();
+            format!("{:08x}  {} |{}|", i * LINE_LEN, hex, ascii)
+        })
+        .collect();
+    lines.push(format!("{:08x}", data.len()));
+    lines
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_dump_data() {
+        let data: Vec<u8> = b"10 PRINT \"HELLO\"\r\n\x1a".to_vec();
+        assert_eq!(
+            vec![
+                "00000000  31 30 20 50 52 49 4e 54  20 22 48 45 4c 4c 4f 22  |10 PRINT \"HELLO\"|",
+                "00000010  0d 0a 1a                                          |...|",
+                "00000013",
+            ],
+            hexdump(&data));
+        assert_eq!(vec!["00000000"], hexdump(&[]));
+    }
+}
@@ -0,0 +1,69 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// The MIME type of embedded tapes, which have no registered type of their own
+pub const CAS_MIME: &str = "application/octet-stream";
+
+/// The MIME type of embedded WAV files
+pub const WAV_MIME: &str = "audio/wav";
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode the given bytes in base64, with padding.
+pub fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Returns the `data:` URI with the given bytes of the given MIME type.
+pub fn data_uri(mime: &str, bytes: &[u8]) -> String {
+    format!("data:{};base64,{}", mime, base64(bytes))
+}
+
+/// Break the given text into lines of the given width, each one ended with a newline.
+pub fn wrap(text: &str, width: usize) -> String {
+    text.as_bytes()
+        .chunks(width.max(1))
+        .map(|line| format!("{}\n", String::from_utf8_lossy(line)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_encode_base64() {
+        assert_eq!("", base64(b""));
+        assert_eq!("Zg==", base64(b"f"));
+        assert_eq!("Zm8=", base64(b"fo"));
+        assert_eq!("Zm9v", base64(b"foo"));
+        assert_eq!("Zm9vYmFy", base64(b"foobar"));
+        assert_eq!("H6beuswTfXQ=", base64(&[0x1f, 0xa6, 0xde, 0xba, 0xcc, 0x13, 0x7d, 0x74]));
+        assert_eq!("data:audio/wav;base64,Zm9v", data_uri(WAV_MIME, b"foo"));
+    }
+
+    #[test]
+    fn should_wrap_lines() {
+        assert_eq!("Zm9v\nYmFy\n", wrap("Zm9vYmFy", 4));
+        assert_eq!("Zm9v\nYg==\n", wrap("Zm9vYg==", 4));
+    }
+}
@@ -15,7 +15,7 @@ use std::path::{Path, PathBuf};
 use crate::tape;
 
 pub fn exists(file: &Path) -> bool {
-    if let Ok(_) = fs::File::open(file) { true } else { false }
+    fs::File::open(file).is_ok()
 }
 
 pub fn remove(file: &Path) -> io::Result<()> {
@@ -28,7 +28,7 @@ pub fn temporary(file: &Path) -> io::Result<PathBuf> {
         .map(|fname| file.with_file_name(format!("{}.temp", fname)))
         .ok_or_else(|| io::Error::new(
             io::ErrorKind::InvalidInput,
-            format!("no temporary available for path {:?}", file)))
+            tr!("no temporary available for path {}", format!("{:?}", file))))
 }
 
 pub fn read_content(file: &Path) -> io::Result<Vec<u8>> {
@@ -38,6 +38,44 @@ pub fn read_content(file: &Path) -> io::Result<Vec<u8>> {
         .map(|_| data)
 }
 
+/// Returns `true` if the given path is actually an HTTP or HTTPS URL.
+pub fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .map(|p| p.starts_with("http://") || p.starts_with("https://"))
+        .unwrap_or(false)
+}
+
+/// Read the content of the given file, or download it if the path is an HTTP(S) URL.
+///
+/// The path `-` stands for the standard input.
+pub fn fetch_content(path: &Path) -> io::Result<Vec<u8>> {
+    if path == Path::new("-") {
+        let mut data = vec![];
+        io::stdin().read_to_end(&mut data)?;
+        Ok(data)
+    } else if is_url(path) {
+        download(path.to_str().unwrap_or_default())
+    } else {
+        read_content(path)
+    }
+}
+
+#[cfg(feature = "net")]
+fn download(url: &str) -> io::Result<Vec<u8>> {
+    let response = ureq::get(url).call()
+        .map_err(|e| io::Error::other(tr!("download failed: {}", e)))?;
+    let mut data: Vec<u8> = Vec::with_capacity(64*1024);
+    response.into_reader().read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[cfg(not(feature = "net"))]
+fn download(url: &str) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        tr!("cannot download {}: mcp was built without network support (`net` feature)", url)))
+}
+
 pub fn write_content(path: &Path, content: &[u8]) -> io::Result<()> {
     let temp_path = temporary(path)?;
     let mut file = fs::File::create(&temp_path)?;
@@ -50,13 +88,48 @@ pub fn write_content(path: &Path, content: &[u8]) -> io::Result<()> {
 }
 
 pub fn file_name_of(path: &Path) -> io::Result<([u8;6], bool)> {
-    let path_str = path
+    Ok(tape::file_name(stem_of(path)?))
+}
+
+pub fn stem_of(path: &Path) -> io::Result<&str> {
+    path
         .file_stem()
         .and_then(|f| f.to_str())
         .ok_or_else(|| io::Error::new(
             io::ErrorKind::InvalidInput,
-            format!("cannot convert path {:?} into string", path)))?;
-    Ok(tape::file_name(path_str))
+            tr!("cannot convert path {} into string", format!("{:?}", path))))
+}
+
+/// Returns the given name prefixed with the given position in a tape of the given number of
+/// files, with as many digits as needed to sort them in order (e.g., `01_LOADER.bin`).
+pub fn numbered(position: usize, count: usize, name: &str) -> String {
+    let width = count.to_string().len().max(2);
+    format!("{:0width$}_{}", position, name, width = width)
+}
+
+/// Returns the given path without the position prefix of numbered files (e.g.,
+/// `LOADER.bin` for `01_LOADER.bin`), if it has one.
+pub fn without_number(path: &Path) -> PathBuf {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    match name.split_once('_') {
+        Some((number, rest)) if !number.is_empty() && !rest.is_empty() && number.chars().all(|c| c.is_ascii_digit()) => {
+            path.with_file_name(rest)
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Returns the given text with its bare line feeds turned into a carriage return and a line
+/// feed, as MSX-BASIC expects the lines of ASCII files to end.
+pub fn with_crlf(text: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(text.len());
+    for (i, byte) in text.iter().enumerate() {
+        if *byte == b'\n' && (i == 0 || text[i - 1] != b'\r') {
+            converted.push(b'\r');
+        }
+        converted.push(*byte);
+    }
+    converted
 }
 
 pub fn is_bin_file(path: &Path) -> bool {
@@ -71,31 +144,152 @@ pub fn is_basic_file(path: &Path) -> bool {
     has_extension(path, "bas")
 }
 
+pub fn is_cas_file(path: &Path) -> bool {
+    has_extension(path, "cas")
+}
+
+pub fn is_wav_file(path: &Path) -> bool {
+    has_extension(path, "wav")
+}
+
+/// Returns the files of the given directory accepted by the given predicate, sorted by name.
+pub fn list_dir<F>(dir: &Path, accept: F) -> io::Result<Vec<PathBuf>>
+where F: Fn(&Path) -> bool {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && accept(&path) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Returns the files of the given directory and its subdirectories accepted by the given
+/// predicate, sorted by path.
+pub fn walk_dir<F>(dir: &Path, accept: F) -> io::Result<Vec<PathBuf>>
+where F: Fn(&Path) -> bool {
+    let mut files = vec![];
+    walk_dir_into(dir, &accept, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn walk_dir_into<F>(dir: &Path, accept: &F, files: &mut Vec<PathBuf>) -> io::Result<()>
+where F: Fn(&Path) -> bool {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk_dir_into(&path, accept, files)?;
+        } else if path.is_file() && accept(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// An advisory lock on a file, released when dropped
+///
+/// The lock is taken on a companion file (the file name plus `.lock`), so it survives
+/// the file being replaced by `write_content()`. The companion file is left in place,
+/// since removing it could let two processes take the lock at the same time.
+///
+#[derive(Debug)]
+pub struct Lock {
+    _file: fs::File,
+}
+
+/// Take an exclusive advisory lock on the given file, to be held during a
+/// read-modify-write cycle.
+///
+/// This function does not wait: if another process holds the lock, an error of
+/// `WouldBlock` kind is returned.
+pub fn lock(file: &Path) -> io::Result<Lock> {
+    let lock_path = backup_path(file, ".lock")?;
+    let lock_file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)?;
+    match lock_file.try_lock() {
+        Ok(()) => Ok(Lock { _file: lock_file }),
+        Err(fs::TryLockError::WouldBlock) => Err(io::Error::new(
+            io::ErrorKind::WouldBlock,
+            tr!("tape {} is busy: it is being modified by another process", format!("{:?}", file)))),
+        Err(fs::TryLockError::Error(e)) => Err(e),
+    }
+}
+
+/// Returns the path of the backup of the given file using the given suffix.
+pub fn backup_path(file: &Path, suffix: &str) -> io::Result<PathBuf> {
+    file.file_name()
+        .and_then(|fname| fname.to_str())
+        .map(|fname| file.with_file_name(format!("{}{}", fname, suffix)))
+        .ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            tr!("no backup available for path {}", format!("{:?}", file))))
+}
+
+/// Copy the given file into its backup, replacing any previous backup.
+///
+/// Returns the path of the backup, or `None` if the file does not exist (so there is
+/// nothing to back up).
+pub fn backup(file: &Path, suffix: &str) -> io::Result<Option<PathBuf>> {
+    if !exists(file) {
+        return Ok(None);
+    }
+    let backup = backup_path(file, suffix)?;
+    fs::copy(file, &backup)?;
+    Ok(Some(backup))
+}
+
+/// Restore the given file from its backup, consuming the backup.
+pub fn restore(file: &Path, suffix: &str) -> io::Result<PathBuf> {
+    let backup = backup_path(file, suffix)?;
+    if !exists(&backup) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            tr!("no backup found for {}", format!("{:?}", file))));
+    }
+    fs::rename(&backup, file)?;
+    Ok(backup)
+}
+
+/// Replace the given file by a hard link to the given original file.
+pub fn replace_with_link(file: &Path, original: &Path) -> io::Result<()> {
+    let temp_path = temporary(file)?;
+    fs::hard_link(original, &temp_path)?;
+    fs::rename(&temp_path, file)
+}
+
 fn has_extension(path: &Path, ext: &str) -> bool {
     path.extension()
         .and_then(|e| e.to_str())
         .map(|e| e.to_lowercase() == ext).unwrap_or(false)
 }
 
-pub fn unique_filename(path: &Path) -> io::Result<(PathBuf, bool)> {
-    if !exists(path) {
+/// Returns a file name like the given one that is neither taken by an existing file nor
+/// among the given reserved names, and whether it is different from the given one.
+pub fn unique_filename(path: &Path, reserved: &[PathBuf]) -> io::Result<(PathBuf, bool)> {
+    if !exists(path) && !reserved.iter().any(|r| r == path) {
         Ok((path.to_path_buf(), false))
     } else {
-        unique_filename_for_suffix(path, 1).map(|f| (f, true))
+        unique_filename_for_suffix(path, reserved, 1).map(|f| (f, true))
     }
 }
 
-fn unique_filename_for_suffix(path: &Path, suffix: usize) -> io::Result<PathBuf> {
+fn unique_filename_for_suffix(path: &Path, reserved: &[PathBuf], suffix: usize) -> io::Result<PathBuf> {
     let stem = extract_from_path(path, |p| p.file_stem())?;
     let ext = path.extension()
         .and_then(|e| e.to_str())
         .map(|e| format!(".{}", e))
         .unwrap_or("".to_string());
     let target = path.with_file_name(format!("{}-{}{}", stem, suffix, ext));
-    if !exists(&target) {
+    if !exists(&target) && !reserved.contains(&target) {
         Ok(target)
     } else {
-        unique_filename_for_suffix(path, suffix + 1)
+        unique_filename_for_suffix(path, reserved, suffix + 1)
     }
 }
 
@@ -103,7 +297,7 @@ fn extract_from_path<F>(path: &Path, f: F) -> io::Result<&str>
 where F: FnOnce(&Path) -> Option<&OsStr> {
     f(path).and_then(|s| s.to_str()).ok_or_else(|| io::Error::new(
         io::ErrorKind::InvalidInput,
-        format!("cannot extract path element from {:?}", path)))
+        tr!("cannot extract path element from {}", format!("{:?}", path))))
 }
 
 #[cfg(test)]
@@ -126,6 +320,48 @@ mod tests {
         });
     }
 
+    #[test]
+    fn should_compute_is_url() {
+        assert!(is_url(Path::new("http://example.com/foobar.cas")));
+        assert!(is_url(Path::new("https://example.com/foobar.cas")));
+        assert!(!is_url(Path::new("foobar.cas")));
+        assert!(!is_url(Path::new("/path/to/http://foobar.cas")));
+    }
+
+    #[test]
+    fn should_fetch_local_content() {
+        with_unexisting_file("fetch", |f| {
+            write_content(f, b"Hello World!").unwrap();
+            assert_eq!(fetch_content(f).unwrap(), b"Hello World!");
+        });
+    }
+
+    #[test]
+    fn should_lock_files() {
+        with_unexisting_file("locked.cas", |f| {
+            let lock1 = lock(f).unwrap();
+            let busy = lock(f).unwrap_err();
+            assert_eq!(io::ErrorKind::WouldBlock, busy.kind());
+            drop(lock1);
+            assert!(lock(f).is_ok());
+        });
+    }
+
+    #[test]
+    fn should_backup_and_restore() {
+        with_unexisting_file("backup", |f| {
+            assert_eq!(None, backup(f, ".bak").unwrap());
+            write_content(f, b"original").unwrap();
+            let b = backup(f, ".bak").unwrap().unwrap();
+            assert_eq!(backup_path(f, ".bak").unwrap(), b);
+            write_content(f, b"modified").unwrap();
+            restore(f, ".bak").unwrap();
+            assert_eq!(read_content(f).unwrap(), b"original");
+            assert!(!exists(&b));
+            assert!(restore(f, ".bak").is_err());
+        });
+    }
+
     #[test]
     fn should_compute_temporary_file_name() {
         assert_eq!(
@@ -155,6 +391,22 @@ mod tests {
         assert_eq!(&fname, b"foobar");
     }
 
+    #[test]
+    fn should_number_files() {
+        assert_eq!("01_LOADER.bin", numbered(1, 3, "LOADER.bin"));
+        assert_eq!("007_custom.dat", numbered(7, 120, "custom.dat"));
+        assert_eq!(Path::new("tapes/LOADER.bin"), without_number(Path::new("tapes/01_LOADER.bin")));
+        assert_eq!(Path::new("MY_GAME.bin"), without_number(Path::new("MY_GAME.bin")));
+        assert_eq!(Path::new("01_"), without_number(Path::new("01_")));
+    }
+
+    #[test]
+    fn should_convert_line_feeds() {
+        assert_eq!(b"10 CLS\r\n20 END\r\n".to_vec(), with_crlf(b"10 CLS\n20 END\r\n"));
+        assert_eq!(b"\r\n\r\n".to_vec(), with_crlf(b"\n\n"));
+        assert_eq!(b"10 CLS".to_vec(), with_crlf(b"10 CLS"));
+    }
+
     #[test]
     fn should_compute_is_bin_file() {
         assert!(is_bin_file(Path::new("foobar.bin")));
@@ -183,30 +435,87 @@ mod tests {
     }
 
 
+    #[test]
+    fn should_compute_is_cas_file() {
+        assert!(is_cas_file(Path::new("foobar.cas")));
+        assert!(is_cas_file(Path::new("foobar.CAS")));
+        assert!(!is_cas_file(Path::new("foobar")));
+        assert!(!is_cas_file(Path::new("foobar.wav")));
+    }
+
+    #[test]
+    fn should_list_dir() {
+        with_existing_file("b.cas", |b| {
+            with_existing_file_from(b, "a.cas", |a| {
+                with_existing_file_from(b, "c.wav", |_| {
+                    let dir = b.parent().unwrap();
+                    assert_eq!(
+                        list_dir(dir, is_cas_file).unwrap(),
+                        vec![a.to_path_buf(), b.to_path_buf()]);
+                })
+            })
+        });
+    }
+
+    #[test]
+    fn should_walk_dir() {
+        with_existing_file("a.cas", |a| {
+            let dir = a.parent().unwrap();
+            fs::create_dir(dir.join("sub")).unwrap();
+            File::create(dir.join("sub").join("b.cas")).unwrap();
+            File::create(dir.join("sub").join("c.wav")).unwrap();
+            assert_eq!(
+                walk_dir(dir, is_cas_file).unwrap(),
+                vec![a.to_path_buf(), dir.join("sub").join("b.cas")]);
+        });
+    }
+
+    #[test]
+    fn should_replace_with_link() {
+        with_existing_file("a.cas", |a| {
+            write_content(a, b"original").unwrap();
+            with_existing_file_from(a, "b.cas", |b| {
+                replace_with_link(b, a).unwrap();
+                assert_eq!(read_content(b).unwrap(), b"original");
+            })
+        });
+    }
+
     #[test]
     fn should_compute_unique_filename() {
         with_unexisting_file("foobar", |f| {
-            let (alt, clash) = unique_filename(f).unwrap();
+            let (alt, clash) = unique_filename(f, &[]).unwrap();
             assert!(!clash);
             assert_eq!(alt, f);
         });
         with_existing_file("foobar", |f| {
-            let (alt, clash) = unique_filename(f).unwrap();
+            let (alt, clash) = unique_filename(f, &[]).unwrap();
             assert!(clash);
             assert_eq!(alt, f.with_file_name("foobar-1"));
         });
         with_existing_file("foobar.bin", |f| {
-            let (alt, clash) = unique_filename(f).unwrap();
+            let (alt, clash) = unique_filename(f, &[]).unwrap();
             assert!(clash);
             assert_eq!(alt, f.with_file_name("foobar-1.bin"));
         });
         with_existing_file("foobar.bin", |f1| {
             with_existing_file_from(f1, "foobar-1.bin", |_| {
-                let (alt, clash) = unique_filename(f1).unwrap();
+                let (alt, clash) = unique_filename(f1, &[]).unwrap();
                 assert!(clash);
                 assert_eq!(alt, f1.with_file_name("foobar-2.bin"));
             })
         });
+        with_existing_file("foobar.bin", |f| {
+            let reserved = vec![f.with_file_name("foobar-1.bin")];
+            let (alt, clash) = unique_filename(f, &reserved).unwrap();
+            assert!(clash);
+            assert_eq!(alt, f.with_file_name("foobar-2.bin"));
+        });
+        with_unexisting_file("foobar.bin", |f| {
+            let (alt, clash) = unique_filename(f, &[f.to_path_buf()]).unwrap();
+            assert!(clash);
+            assert_eq!(alt, f.with_file_name("foobar-1.bin"));
+        });
     }
 
     fn with_unexisting_file<P, F>(filename: P, f: F) where P: AsRef<Path>, F: FnOnce(&Path) {
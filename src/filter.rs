@@ -0,0 +1,152 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::ops::RangeInclusive;
+
+use crate::gap::KINDS;
+use crate::tape::Entry;
+
+/// A rule selecting some of the files of a tape
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rule {
+    /// The files with a name matching the given glob pattern, with `*` and `?` wildcards
+    Name(String),
+    /// The files of the given type, as named by `map`
+    Kind(String),
+    /// The files in the given range of positions in the tape, counted from 0
+    Range(RangeInclusive<usize>),
+}
+
+impl Rule {
+    /// Parse a rule given as `<first>..<last>`, `type:<type>` or a name pattern (e.g., `2..5`,
+    /// `type:custom` or `STAGE*`).
+    pub fn parse(s: &str) -> Option<Rule> {
+        if let Some(kind) = s.strip_prefix("type:") {
+            return if KINDS.contains(&kind) { Some(Rule::Kind(kind.to_string())) } else { None };
+        }
+        match s.split_once("..") {
+            Some((first, last)) => Some(Rule::Range(first.parse().ok()?..=last.parse().ok()?)),
+            None if !s.is_empty() => Some(Rule::Name(s.to_string())),
+            None => None,
+        }
+    }
+
+    /// Returns `true` if the given file, found at the given position of its tape, is selected
+    /// by this rule.
+    ///
+    /// Names are matched as stored in the header of the file (e.g., `game`) or as it is
+    /// extracted (e.g., `game.bin`).
+    pub fn matches(&self, index: usize, entry: &Entry) -> bool {
+        match self {
+            Rule::Name(pattern) => {
+                entry.header_name().is_some_and(|name| glob(pattern, name))
+                    || entry.file().name().is_some_and(|name| glob(pattern, &name))
+            }
+            Rule::Kind(kind) => entry.kind().map(|k| k.name()).unwrap_or("custom") == kind,
+            Rule::Range(range) => range.contains(&index),
+        }
+    }
+}
+
+/// The files of a tape to keep when filtering it
+///
+/// A file is kept if it is selected by any of the `keep` rules, or there are none, and it is
+/// not selected by any of the `drop` rules.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    pub keep: Vec<Rule>,
+    pub drop: Vec<Rule>,
+}
+
+impl Filter {
+    /// Returns `true` if the given file, found at the given position of its tape, is kept.
+    pub fn keeps(&self, index: usize, entry: &Entry) -> bool {
+        (self.keep.is_empty() || self.keep.iter().any(|rule| rule.matches(index, entry)))
+            && !self.drop.iter().any(|rule| rule.matches(index, entry))
+    }
+}
+
+/// Returns `true` if the given text matches the given pattern, where `*` matches any
+/// sequence of characters and `?` any single character.
+pub fn glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // The positions after the last `*` seen in the pattern and the text, to backtrack to
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((after, from)) => {
+                    p = after;
+                    t = from + 1;
+                    star = Some((after, from + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::tape::{file_name, Tape};
+
+    #[test]
+    fn should_parse_rules() {
+        assert_eq!(Some(Rule::Range(2..=5)), Rule::parse("2..5"));
+        assert_eq!(Some(Rule::Kind("custom".to_string())), Rule::parse("type:custom"));
+        assert_eq!(Some(Rule::Name("STAGE*".to_string())), Rule::parse("STAGE*"));
+        assert_eq!(None, Rule::parse("type:game"));
+        assert_eq!(None, Rule::parse("2..last"));
+        assert_eq!(None, Rule::parse(""));
+    }
+
+    #[test]
+    fn should_match_globs() {
+        assert!(glob("STAGE*", "STAGE1"));
+        assert!(glob("STAGE*", "STAGE"));
+        assert!(glob("*.bin", "game.bin"));
+        assert!(glob("ST?GE", "STAGE"));
+        assert!(glob("*A*E*", "STAGE2"));
+        assert!(!glob("STAGE*", "LOADER"));
+        assert!(!glob("ST?GE", "STGE"));
+        assert!(!glob("*.bin", "game.asc"));
+    }
+
+    #[test]
+    fn should_keep_selected_files() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("loader").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_bin(&file_name("STAGE1").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_custom(&[0x01; 8]).unwrap();
+        tape.append_bin(&file_name("STAGE2").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        let kept = |filter: &Filter| -> Vec<usize> {
+            tape.entries_with(&[]).enumerate().filter(|(i, e)| filter.keeps(*i, e)).map(|(i, _)| i).collect()
+        };
+        assert_eq!(vec![0, 1, 2, 3], kept(&Filter::default()));
+        let filter = Filter { keep: vec![Rule::Name("STAGE*".to_string())], drop: vec![] };
+        assert_eq!(vec![1, 3], kept(&filter));
+        let filter = Filter { keep: vec![Rule::Range(1..=3)], drop: vec![Rule::Kind("custom".to_string())] };
+        assert_eq!(vec![1, 3], kept(&filter));
+        let filter = Filter { keep: vec![], drop: vec![Rule::Name("loader.bin".to_string())] };
+        assert_eq!(vec![1, 2, 3], kept(&filter));
+    }
+}
@@ -0,0 +1,135 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::map;
+use crate::tape::Tape;
+
+/// The types of file a gap may precede, as named by `map`
+pub const KINDS: [&str; 5] = ["bin", "basic", "ascii", "other", "custom"];
+
+/// The blocks preceded by an extra gap
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    /// The block with the given index
+    Block(usize),
+    /// The first block of every file of the given type
+    Kind(String),
+}
+
+/// An extra silence before some blocks of a tape, on top of the silence that precedes
+/// every block when it is exported, or a silence replacing it
+///
+/// Games that switch loading routines between blocks may need more time to do it than
+/// the silence before regular blocks, while blocks read by the same routine may do with
+/// less.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gap {
+    pub target: Target,
+    /// The length of the silence, in milliseconds
+    pub millis: u32,
+    /// Whether the silence replaces the one that precedes the blocks instead of adding to it
+    pub replace: bool,
+}
+
+impl Gap {
+    /// Parse a gap given as `<block>:<millis>` or `<type>:<millis>` (e.g., `3:1500` or
+    /// `custom:1500`), or as `<block>=<millis>` or `<type>=<millis>` to replace the silence
+    /// (e.g., `custom=300`).
+    pub fn parse(s: &str) -> Option<Gap> {
+        let (target, millis, replace) = match s.split_once(':') {
+            Some((target, millis)) => (target, millis, false),
+            None => s.split_once('=').map(|(target, millis)| (target, millis, true))?,
+        };
+        let millis = millis.parse().ok()?;
+        let target = match target.parse() {
+            Ok(block) => Target::Block(block),
+            Err(_) if KINDS.contains(&target) => Target::Kind(target.to_string()),
+            Err(_) => return None,
+        };
+        Some(Gap { target, millis, replace })
+    }
+}
+
+/// Returns the extra silence (in milliseconds) before each block of the given tape.
+///
+/// The silences of all the gaps that precede a block are added up.
+pub fn extra_silences(tape: &Tape, gaps: &[Gap]) -> Vec<u32> {
+    let extra: Vec<Gap> = gaps.iter().filter(|gap| !gap.replace).cloned().collect();
+    preceding(tape, &extra).into_iter().map(|gaps| gaps.iter().map(|gap| gap.millis).sum()).collect()
+}
+
+/// Returns the silence (in milliseconds) replacing the one that precedes each block of the
+/// given tape, if any.
+///
+/// When several gaps replace the silence before a block, the last one given is taken.
+pub fn silences(tape: &Tape, gaps: &[Gap]) -> Vec<Option<u32>> {
+    let replacing: Vec<Gap> = gaps.iter().filter(|gap| gap.replace).cloned().collect();
+    preceding(tape, &replacing).into_iter().map(|gaps| gaps.last().map(|gap| gap.millis)).collect()
+}
+
+/// Returns the given gaps that precede each block of the given tape.
+fn preceding<'a>(tape: &Tape, gaps: &'a [Gap]) -> Vec<Vec<&'a Gap>> {
+    let files = map::file_of_blocks(tape.blocks());
+    (0..files.len())
+        .map(|i| {
+            let first = i == 0 || files[i - 1].0 != files[i].0;
+            gaps.iter()
+                .filter(|gap| match &gap.target {
+                    Target::Block(block) => *block == i,
+                    Target::Kind(kind) => first && *kind == files[i].1,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::tape::file_name;
+
+    use super::*;
+
+    #[test]
+    fn should_parse_gaps() {
+        assert_eq!(Some(Gap { target: Target::Block(3), millis: 1500, replace: false }), Gap::parse("3:1500"));
+        assert_eq!(
+            Some(Gap { target: Target::Kind("custom".to_string()), millis: 500, replace: false }),
+            Gap::parse("custom:500"));
+        assert_eq!(
+            Some(Gap { target: Target::Kind("basic".to_string()), millis: 3000, replace: true }),
+            Gap::parse("basic=3000"));
+        assert_eq!(None, Gap::parse("3"));
+        assert_eq!(None, Gap::parse("3:long"));
+        assert_eq!(None, Gap::parse("game:1500"));
+        assert_eq!(None, Gap::parse("custom=short"));
+    }
+
+    #[test]
+    fn should_compute_extra_silences() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("game").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_custom(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        tape.append_custom(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        let gaps = [Gap::parse("custom:500").unwrap(), Gap::parse("3:1000").unwrap(), Gap::parse("1:200").unwrap()];
+        assert_eq!(vec![0, 200, 500, 1500], extra_silences(&tape, &gaps));
+        assert_eq!(vec![0, 0, 0, 0], extra_silences(&tape, &[]));
+    }
+
+    #[test]
+    fn should_compute_replacing_silences() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("game").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_custom(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        tape.append_custom(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        let gaps = [Gap::parse("custom=300").unwrap(), Gap::parse("3=200").unwrap(), Gap::parse("3:1000").unwrap()];
+        assert_eq!(vec![None, None, Some(300), Some(200)], silences(&tape, &gaps));
+        assert_eq!(vec![0, 0, 0, 1000], extra_silences(&tape, &gaps));
+    }
+}
@@ -0,0 +1,68 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crc32fast::Hasher;
+use sha1_smol::Sha1;
+
+/// The fingerprint of a chunk of data
+///
+/// A digest comprises the size, the CRC32 and the SHA1 of the data. These are the
+/// values typically found in the DAT files used by preservation communities to
+/// describe known-good dumps.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Digest {
+    pub size: u64,
+    pub crc32: u32,
+    pub sha1: String,
+}
+
+impl Digest {
+    /// Compute the digest of the given data.
+    pub fn of(data: &[u8]) -> Digest {
+        Digest {
+            size: data.len() as u64,
+            crc32: crc32(data),
+            sha1: sha1(data),
+        }
+    }
+}
+
+/// Compute the CRC32 of the given data.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut hasher = Hasher::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Compute the SHA1 of the given data as a lowercase hexadecimal string.
+pub fn sha1(data: &[u8]) -> String {
+    Sha1::from(data).digest().to_string()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_compute_digest() {
+        let digest = Digest::of(b"Hello World!");
+        assert_eq!(12, digest.size);
+        assert_eq!(0x1c291ca3, digest.crc32);
+        assert_eq!("2ef7bde608ce5404e97d5f042f95f89f1c232871", digest.sha1);
+    }
+
+    #[test]
+    fn should_compute_digest_of_empty_data() {
+        let digest = Digest::of(b"");
+        assert_eq!(0, digest.size);
+        assert_eq!(0, digest.crc32);
+        assert_eq!("da39a3ee5e6b4b0d3255bfef95601890afd80709", digest.sha1);
+    }
+}
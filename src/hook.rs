@@ -0,0 +1,96 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::env;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// The variable with the JSON context passed to the hooks
+pub const CONTEXT_VAR: &str = "MCP_HOOK_CONTEXT";
+
+/// A point where a user command can be hooked
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hook {
+    /// Before a file is added to a tape, as to assemble it
+    PreAdd,
+    /// After a tape is exported, as to upload the exported file
+    PostExport,
+}
+
+impl Hook {
+    /// Returns the name of the hook, as given in its context.
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PreAdd => "pre-add",
+            Hook::PostExport => "post-export",
+        }
+    }
+
+    /// Returns the variable with the command of the hook.
+    pub const fn var(self) -> &'static str {
+        match self {
+            Hook::PreAdd => "MCP_PRE_ADD",
+            Hook::PostExport => "MCP_POST_EXPORT",
+        }
+    }
+
+    /// Run the command of the hook, if any, with the given file and context.
+    ///
+    /// The context is a JSON object with the name of the hook and the given fields.
+    pub fn run(self, path: &Path, context: serde_json::Value) -> io::Result<()> {
+        match env::var(self.var()) {
+            Ok(command) if !command.trim().is_empty() => {
+                let mut context = context;
+                context["hook"] = self.name().into();
+                run_command(&command, path, &context)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Run the given command through the shell, with the given file as its last argument and
+/// the given context in the `MCP_HOOK_CONTEXT` variable.
+pub fn run_command(command: &str, path: &Path, context: &serde_json::Value) -> io::Result<()> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command).arg(path);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(format!("{} \"$@\"", command)).arg("mcp").arg(path);
+        shell
+    };
+    let status = shell.env(CONTEXT_VAR, context.to_string()).status()?;
+    if !status.success() {
+        return Err(io::Error::other(tr!("hook {} failed ({})", format!("{:?}", command), status)));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod test {
+
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::file;
+
+    #[test]
+    fn should_run_hooks() {
+        let temp = TempDir::new("mcp").unwrap();
+        let out = temp.path().join("out.txt");
+        let command = format!("printf '%s %s' \"$MCP_HOOK_CONTEXT\" > '{}'", out.display());
+        let context = serde_json::json!({"hook": "pre-add"});
+        run_command(&command, Path::new("game.bin"), &context).unwrap();
+        assert_eq!(b"{\"hook\":\"pre-add\"} game.bin".to_vec(), file::read_content(&out).unwrap());
+
+        assert!(run_command("exit 3", Path::new("game.bin"), &context).is_err());
+    }
+}
@@ -0,0 +1,663 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::env;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// A language the messages of `mcp` are translated into
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lang {
+    En,
+    Es,
+    Ja,
+}
+
+impl Lang {
+    /// Returns the language of the given locale (as `es`, `es_ES.UTF-8` or `ja-JP`), if it is
+    /// one of the supported languages.
+    pub fn from_locale(locale: &str) -> Option<Lang> {
+        let code = locale.split(['_', '-', '.', '@']).next().unwrap_or_default();
+        match code.to_lowercase().as_str() {
+            "en" | "c" | "posix" => Some(Lang::En),
+            "es" => Some(Lang::Es),
+            "ja" => Some(Lang::Ja),
+            _ => None,
+        }
+    }
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Select the language of the messages: the given one if any, or otherwise the one given
+/// by the `MCP_LANG` variable or the locale of the environment, or English.
+///
+/// Only the first selection takes effect.
+pub fn select(lang: Option<&str>) {
+    let from_env = || {
+        ["MCP_LANG", "LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+    };
+    let lang = lang.map(str::to_string).or_else(from_env).and_then(|l| Lang::from_locale(&l));
+    let _ = LANG.set(lang.unwrap_or(Lang::En));
+}
+
+/// Returns the selected language of the messages, English if none was selected.
+pub fn lang() -> Lang {
+    LANG.get().copied().unwrap_or(Lang::En)
+}
+
+/// Returns the translation of the given message into the selected language.
+///
+/// Messages without a translation are returned as they are.
+pub fn tr(message: &'static str) -> &'static str {
+    translate(message, lang())
+}
+
+fn translate(message: &'static str, lang: Lang) -> &'static str {
+    let translation = MESSAGES.iter().find(|(en, _, _)| *en == message).map(|(_, es, ja)| match lang {
+        Lang::En => message,
+        Lang::Es => es,
+        Lang::Ja => ja,
+    });
+    translation.unwrap_or(message)
+}
+
+/// Replace each `{}` of the given message by the next of the given arguments, and each
+/// `{N}` by the argument at position `N`, so translations may reorder them.
+pub fn fill(message: &str, args: &[&dyn Display]) -> String {
+    let mut result = String::new();
+    let mut rest = message;
+    let mut next = 0;
+    while let Some(begin) = rest.find('{') {
+        result.push_str(&rest[..begin]);
+        let end = match rest[begin..].find('}') {
+            Some(end) => begin + end,
+            None => break,
+        };
+        let index = match &rest[begin + 1..end] {
+            "" => {
+                next += 1;
+                next - 1
+            }
+            index => index.parse().unwrap_or(usize::MAX),
+        };
+        if let Some(arg) = args.get(index) {
+            result.push_str(&arg.to_string());
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Translate a message into the selected language, replacing its `{}` placeholders by the
+/// given arguments, as `format!()` does.
+#[macro_export]
+macro_rules! tr {
+    ($message:expr) => {
+        $crate::i18n::tr($message).to_string()
+    };
+    ($message:expr, $($arg:expr),+ $(,)?) => {
+        $crate::i18n::fill($crate::i18n::tr($message), &[$(&$arg as &dyn std::fmt::Display),+])
+    };
+}
+
+/// The translations of the messages, as (English, Spanish, Japanese)
+static MESSAGES: &[(&str, &str, &str)] = &[
+    (
+        "This program is subject to the terms of the Mozilla Public License v2.0.",
+        "Este programa está sujeto a los términos de la Mozilla Public License v2.0.",
+        "このプログラムは Mozilla Public License v2.0 の条件に従います。",
+    ),
+    ("Error: IO operation failed: {}", "Error: falló una operación de E/S: {}", "エラー: 入出力操作に失敗しました: {}"),
+    ("Error: {}", "Error: {}", "エラー: {}"),
+    ("Warning: {}... ", "Aviso: {}... ", "警告: {}... "),
+    (
+        "Warning: {} bytes of trailing garbage found after the last file",
+        "Aviso: se encontraron {} bytes de basura tras el último fichero",
+        "警告: 最後のファイルの後に {} バイトの不要なデータがあります",
+    ),
+    ("Extracting {}... ", "Extrayendo {}... ", "{} を展開中... "),
+    ("Done", "Hecho", "完了"),
+    ("Done (padded with {} bytes!)", "Hecho (¡rellenado con {} bytes!)", "完了 ({} バイトを埋めました!)"),
+    ("Adding binary file {}... ", "Añadiendo el fichero binario {}... ", "バイナリファイル {} を追加中... "),
+    ("Adding basic file {}... ", "Añadiendo el fichero Basic {}... ", "BASIC ファイル {} を追加中... "),
+    ("Adding ascii file {}... ", "Añadiendo el fichero ASCII {}... ", "ASCII ファイル {} を追加中... "),
+    ("Adding {} file {}... ", "Añadiendo el fichero {} {}... ", "{} ファイル {1} を追加中... "),
+    ("Adding custom file {}... ", "Añadiendo el fichero personalizado {}... ", "カスタムファイル {} を追加中... "),
+    (
+        "Warning: some files had lengths that required padding with zeroes to be aligned
+to 8-byte boundaries. This is a constraint of CAS file format: every data block
+must start in an offset divisible by 8.
+
+For binary files, this means the total length of the file excluding the
+0x1F prefix must be 8-byte aligned.
+
+For ASCII files, this does not affect you. ASCII files are always aligned to
+256-byte boundaries and padded with EOF values (0x1A) needed by MSX BIOS to
+detect the end of the file.
+
+For custom files, the effect is unknown. These files are loaded using custom
+code. And if padding zeroes affect or not depends on that code.
+
+Using the right file sizes is highly recommended to prevent problems. However
+this is not considered as an error, and your CAS package has been successfully
+generated.",
+        "Aviso: algunos ficheros tenían longitudes que han requerido rellenarlos con ceros
+para alinearlos a 8 bytes. Es una restricción del formato CAS: cada bloque de
+datos debe empezar en una posición divisible por 8.
+
+Para los ficheros binarios, esto significa que la longitud total del fichero sin
+el prefijo 0x1F debe estar alineada a 8 bytes.
+
+A los ficheros ASCII no les afecta. Siempre se alinean a 256 bytes y se rellenan
+con valores EOF (0x1A), que la BIOS del MSX necesita para detectar su final.
+
+Para los ficheros personalizados, el efecto es desconocido. Se cargan con código
+propio, y que les afecten o no los ceros de relleno depende de ese código.
+
+Es muy recomendable usar los tamaños de fichero correctos para evitar problemas.
+Aun así, no se considera un error, y el paquete CAS se ha generado con éxito.",
+        "警告: 一部のファイルは 8 バイト境界に揃えるためにゼロで埋められました。
+これは CAS 形式の制約で、すべてのデータブロックは 8 で割り切れる位置から
+始まる必要があります。
+
+バイナリファイルの場合、0x1F プレフィックスを除いたファイルの長さが
+8 バイト境界に揃っている必要があります。
+
+ASCII ファイルには影響しません。ASCII ファイルは常に 256 バイト境界に揃えられ、
+MSX BIOS がファイルの終わりを検出するための EOF 値 (0x1A) で埋められます。
+
+カスタムファイルへの影響は不明です。これらのファイルは独自のコードで読み込まれ、
+埋められたゼロが影響するかどうかはそのコード次第です。
+
+問題を避けるため、正しいファイルサイズを使うことを強くお勧めします。
+ただし、これはエラーではなく、CAS パッケージは正常に生成されました。",
+    ),
+    ("Backup of {} saved into {}", "Copia de seguridad de {} guardada en {}", "{} のバックアップを {} に保存しました"),
+    ("{} restored from {}", "{} restaurado desde {}", "{} を {} から復元しました"),
+    ("cancelled, nothing was changed", "cancelado, no se ha cambiado nada", "キャンセルしました。何も変更されていません"),
+    ("Overwrite {} with its backup?", "¿Sobrescribir {} con su copia de seguridad?", "{} をバックアップで上書きしますか?"),
+    ("Overwrite {} with the repaired tape?", "¿Sobrescribir {} con la cinta reparada?", "{} を修復したテープで上書きしますか?"),
+    (
+        "Replace {} duplicated tapes by links?",
+        "¿Sustituir {} cintas duplicadas por enlaces?",
+        "重複した {} 本のテープをリンクに置き換えますか?",
+    ),
+    ("Remove {} duplicated tapes?", "¿Eliminar {} cintas duplicadas?", "重複した {} 本のテープを削除しますか?"),
+    ("{} changes made to normalize the tape", "{} cambios hechos para normalizar la cinta", "テープを正規化するために {} 件の変更を行いました"),
+    ("Removed {} bytes of trailing garbage", "Eliminados {} bytes de basura al final", "末尾の不要なデータ {} バイトを削除しました"),
+    (
+        "Kept {} bytes of trailing garbage (use --trim to remove them)",
+        "Conservados {} bytes de basura al final (usa --trim para eliminarlos)",
+        "末尾の不要なデータ {} バイトを残しました (削除するには --trim を使ってください)",
+    ),
+    ("{} blocks written into {}", "{} bloques escritos en {}", "{} 個のブロックを {} に書き込みました"),
+    ("previews can only be rendered when exporting to WAV", "las vistas previas solo se generan al exportar a WAV", "プレビューは WAV へのエクスポート時のみ作成できます"),
+    ("Encoding block {}... ", "Codificando el bloque {}... ", "ブロック {} をエンコード中... "),
+    ("{} KiB (cached)", "{} KiB (en caché)", "{} KiB (キャッシュ)"),
+    ("Total: {} files, {} blocks, {} of data, {} in the CAS file, {} at {} bauds",
+        "Total: {} ficheros, {} bloques, {} de datos, {} en el fichero CAS, {} a {} baudios",
+        "合計: {} ファイル、{} ブロック、データ {}、CAS ファイル {}、{} ({} ボー)"),
+    ("It fits in a side of a C60 tape ({})",
+        "Cabe en una cara de una cinta C60 ({})",
+        "C60 テープの片面 ({}) に収まります"),
+    ("It does not fit in a side of a C60 tape ({})",
+        "No cabe en una cara de una cinta C60 ({})",
+        "C60 テープの片面 ({}) に収まりません"),
+    ("Joined blocks {} to {} into one block, padded with {} bytes",
+        "Bloques {} a {} unidos en un bloque, rellenado con {} bytes",
+        "ブロック {} から {} を 1 つのブロックに結合しました ({} バイトのパディング)"),
+    ("Trimmed {} bytes past the END address of binary files",
+        "Recortados {} bytes tras la dirección END de los ficheros binarios",
+        "バイナリファイルの END アドレス以降の {} バイトを削除しました"),
+    ("{} cannot be optimized any further", "{} no se puede optimizar más", "{} はこれ以上最適化できません"),
+    ("Saved {} bytes ({} to {}) and {} of audio ({} to {})",
+        "Ahorrados {} bytes (de {} a {}) y {} de audio (de {} a {})",
+        "{} バイト ({} → {}) と音声 {} ({} → {}) を削減しました"),
+    ("Overwrite {} with the optimized tape?", "¿Sobrescribir {} con la cinta optimizada?", "{} を最適化したテープで上書きしますか?"),
+    ("{} of {} files written into {}", "{} de {} ficheros escritos en {}", "{1} 個中 {0} 個のファイルを {2} に書き込みました"),
+    ("File renamed in {}", "Fichero renombrado en {}", "{} でファイルの名前を変更しました"),
+    ("{} files renamed in {}", "{} ficheros renombrados en {}", "{} 個のファイルの名前を {} で変更しました"),
+    ("{} bytes embedded into {} characters in {}", "{} bytes incrustados en {} caracteres en {}", "{} バイトを {2} に {1} 文字で埋め込みました"),
+    ("{} is not checked, {} is loaded over it", "{} no se comprueba, {} se carga encima", "{} は検査されません ({1} が上に読み込まれます)"),
+    ("there is no room in memory for the verifier", "no hay sitio en memoria para el verificador", "検証プログラムを置くメモリの空きがありません"),
+    ("Verifier of {} binary files added to {} at {}",
+        "Verificador de {} ficheros binarios añadido a {} en {}",
+        "{} 個のバイナリファイルの検証プログラムを {} の {} に追加しました"),
+    ("Load the files with BLOAD\"CAS:\" and then run the verifier with BLOAD\"CAS:\",R",
+        "Carga los ficheros con BLOAD\"CAS:\" y ejecuta después el verificador con BLOAD\"CAS:\",R",
+        "BLOAD\"CAS:\" でファイルを読み込んでから、BLOAD\"CAS:\",R で検証プログラムを実行してください"),
+    ("{} does not decode back to {}", "{} no se decodifica de vuelta a {}", "{0} をデコードしても {1} に戻りません"),
+    ("{} decodes back to the {} blocks of {}", "{} se decodifica de vuelta a los {} bloques de {}", "{0} をデコードすると {2} の {1} ブロックに戻ります"),
+    ("{} and {} do not contain the same blocks", "{} y {} no contienen los mismos bloques", "{} と {} のブロックは一致しません"),
+    ("{} and {} contain the same {} blocks", "{} y {} contienen los mismos {} bloques", "{} と {} は同じ {} 個のブロックを含みます"),
+    ("{} is not a binary file", "{} no es un fichero binario", "{} はバイナリファイルではありません"),
+    ("the START address of {} is outside its BEGIN-END range", "la dirección START de {} está fuera de su rango BEGIN-END",
+        "{} の START アドレスが BEGIN-END の範囲外です"),
+    ("mcp was built without the disassembler (`disasm` feature)", "mcp se compiló sin el desensamblador (característica `disasm`)",
+        "mcp は逆アセンブラなし (`disasm` フィーチャー) でビルドされています"),
+    ("SCREEN 2 image of file {} rendered into {}", "Imagen de SCREEN 2 del fichero {} dibujada en {}",
+        "ファイル {} の SCREEN 2 画像を {} に描画しました"),
+    ("no SCREEN 2 images found in the custom files of the tape", "no se encontraron imágenes de SCREEN 2 en los ficheros personalizados de la cinta",
+        "テープのカスタムファイルに SCREEN 2 画像が見つかりません"),
+    ("Resuming from block {}", "Reanudando desde el bloque {}", "ブロック {} から再開します"),
+    ("no blocks found in {}", "no se encontraron bloques en {}", "{} にブロックが見つかりません"),
+    ("{} is already defragmented", "{} ya está desfragmentada", "{} はすでにデフラグされています"),
+    ("{} changes made to defragment the tape", "{} cambios hechos para desfragmentar la cinta",
+        "テープをデフラグするために {} 件の変更を行いました"),
+    ("Overwrite {} with the defragmented tape?", "¿Sobrescribir {} con la cinta desfragmentada?",
+        "{} をデフラグしたテープで上書きしますか?"),
+    ("Recovered the addresses of {} binary files", "Recuperadas las direcciones de {} ficheros binarios",
+        "{} 個のバイナリファイルのアドレスを復元しました"),
+    ("Write the recovered addresses into {}?", "¿Escribir las direcciones recuperadas en {}?",
+        "復元したアドレスを {} に書き込みますか?"),
+    ("{} sides, {} files, {} of load time", "{} caras, {} ficheros, {} de tiempo de carga", "{} 面、{} ファイル、ロード時間 {}"),
+    ("Warning: the same file is found in more than one side: {}", "Aviso: el mismo fichero está en más de una cara: {}", "警告: 同じファイルが複数の面にあります: {}"),
+    ("Exporting {} into {}", "Exportando {} a {}", "{} を {} にエクスポートしています"),
+    ("{} sides exported into {}", "{} caras exportadas a {}", "{} 面を {} にエクスポートしました"),
+    ("{} already exists", "{} ya existe", "{} はすでに存在します"),
+    ("Created {}", "Creado {}", "{} を作成しました"),
+    ("Project {} created: run make in {} to build {}.cas, {}.wav and {}.tsx",
+        "Proyecto {} creado: ejecuta make en {} para construir {}.cas, {}.wav y {}.tsx",
+        "プロジェクト {} を作成しました: {} で make を実行すると {}.cas、{}.wav、{}.tsx が作られます"),
+    ("BEGIN 0x{}, END 0x{}, START 0x{} from the assembler... ",
+        "BEGIN 0x{}, END 0x{}, START 0x{} del ensamblador... ",
+        "アセンブラから BEGIN 0x{}、END 0x{}、START 0x{}... "),
+    ("START address 0x{} does not match the entry point 0x{} of the program",
+        "la dirección START 0x{} no coincide con el punto de entrada 0x{} del programa",
+        "START アドレス 0x{} がプログラムのエントリポイント 0x{} と一致しません"),
+    ("the program does not fit in memory from its ORG 0x{}",
+        "el programa no cabe en memoria desde su ORG 0x{}",
+        "プログラムが ORG 0x{} からメモリに収まりません"),
+    ("the entry point 0x{} of the program is out of its code",
+        "el punto de entrada 0x{} del programa está fuera de su código",
+        "プログラムのエントリポイント 0x{} がコードの外にあります"),
+    ("the ORG of the program is unknown, as there is no listing file",
+        "el ORG del programa es desconocido, ya que no hay fichero de listado",
+        "リスティングファイルがないため、プログラムの ORG が不明です"),
+    ("Removed {} ({} blocks)", "Eliminado {} ({} bloques)", "{} を削除しました ({} ブロック)"),
+    ("offset {}, {} blocks ({}), {} bytes with headers", "posición {}, {} bloques ({}), {} bytes con cabeceras", "オフセット {}、{} ブロック ({})、ヘッダー込みで {} バイト"),
+    ("{} is not an ASCII or Basic file", "{} no es un fichero ASCII o Basic", "{} は ASCII ファイルでも Basic ファイルでもありません"),
+    ("cannot tokenize {}: {}", "no se puede tokenizar {}: {}", "{} をトークン化できません: {}"),
+    ("line {} of the listing has no line number", "la línea {} del listado no tiene número de línea", "リストの行 {} に行番号がありません"),
+    ("invalid line number {}", "número de línea {} no válido", "無効な行番号 {}"),
+    ("line {} is not after line {}", "la línea {} no va después de la línea {}", "行 {} が行 {} の後にありません"),
+    ("the program does not fit in memory", "el programa no cabe en memoria", "プログラムがメモリに収まりません"),
+    ("number {} out of range", "número {} fuera de rango", "数値 {} が範囲外です"),
+    ("cannot convert {}: {}", "no se puede convertir {}: {}", "{} を変換できません: {}"),
+    ("the text is not UTF-8", "el texto no es UTF-8", "テキストが UTF-8 ではありません"),
+    ("character {} is not in the character set", "el carácter {} no está en el juego de caracteres", "文字 {} は文字セットにありません"),
+    ("Overwrite {} without the removed files?", "¿Sobrescribir {} sin los ficheros eliminados?",
+        "削除したファイルなしで {} を上書きしますか?"),
+    (" ! START {}, in {}", " ! START {}, en {}", " ! START {}、{}"),
+    ("! START in {}", "! START en {}", "! START は {} の中"),
+    (
+        "! START outside of every file, in {}",
+        "! START fuera de todos los ficheros, en {}",
+        "! START はどのファイルの外にもあり、{} にあります",
+    ),
+    ("! loaded over {}", "! cargado sobre {}", "! {} の上に読み込まれます"),
+    ("before BEGIN", "antes de BEGIN", "BEGIN より前"),
+    ("after END", "después de END", "END より後"),
+    ("BIOS ROM, page 0", "ROM de la BIOS, página 0", "BIOS ROM、ページ 0"),
+    ("BASIC ROM, page 1", "ROM de BASIC, página 1", "BASIC ROM、ページ 1"),
+    ("RAM, page 2", "RAM, página 2", "RAM、ページ 2"),
+    ("RAM, page 3", "RAM, página 3", "RAM、ページ 3"),
+    ("system area, page 3", "área del sistema, página 3", "システム領域、ページ 3"),
+    ("SCREEN 2 image", "imagen de SCREEN 2", "SCREEN 2 画像"),
+    ("{} bytes", "{} bytes", "{} バイト"),
+    ("{} bytes in {}", "{} bytes en {}", "{} バイト、{}"),
+    ("{} bytes of padding", "{} bytes de relleno", "{} バイトのパディング"),
+    (
+        "filename {} already exists, writing output to {}",
+        "el fichero {} ya existe, escribiendo la salida en {}",
+        "ファイル {} は既に存在するため、{} に出力します",
+    ),
+    (
+        "block | bauds | bytes | expected | errors | level | quality",
+        "bloque | baudios | bytes | esperados | errores | nivel | calidad",
+        "ブロック | ボー | バイト | 予想 | エラー | レベル | 品質",
+    ),
+    (
+        "{} blocks decoded: {} good, {} salvaged, {} damaged",
+        "{} bloques decodificados: {} buenos, {} recuperados, {} dañados",
+        "{} ブロックをデコードしました: 良好 {}、回復 {}、破損 {}",
+    ),
+    ("block {}", "bloque {}", "ブロック {}"),
+    (
+        "{} errors, {} warnings, {} infos in {} blocks",
+        "{} errores, {} avisos, {} informaciones en {} bloques",
+        "{} 件のエラー、{} 件の警告、{} 件の情報 ({} ブロック中)",
+    ),
+    ("no blocks found", "no se encontraron bloques", "ブロックが見つかりません"),
+    (
+        "{} bytes before the first block are ignored",
+        "se ignoran {} bytes antes del primer bloque",
+        "最初のブロックの前の {} バイトは無視されます",
+    ),
+    (
+        "block prefix at offset {} is not 8-byte aligned and is ignored",
+        "el prefijo de bloque en la posición {} no está alineado a 8 bytes y se ignora",
+        "オフセット {} のブロック接頭辞は 8 バイト境界になく、無視されます",
+    ),
+    (
+        "tape size ({} bytes) is not a multiple of 8",
+        "el tamaño de la cinta ({} bytes) no es múltiplo de 8",
+        "テープのサイズ ({} バイト) が 8 の倍数ではありません",
+    ),
+    (
+        "{} bytes of trailing garbage after the last file",
+        "{} bytes de basura tras el último fichero",
+        "最後のファイルの後に {} バイトの不要なデータがあります",
+    ),
+    ("empty block", "bloque vacío", "空のブロック"),
+    ("file header has {} extra bytes", "la cabecera del fichero tiene {} bytes de más", "ファイルヘッダーに余分な {} バイトがあります"),
+    (
+        "file header is not followed by a data block",
+        "la cabecera del fichero no va seguida de un bloque de datos",
+        "ファイルヘッダーの後にデータブロックがありません",
+    ),
+    (
+        "data block is too short ({} bytes) to have a header",
+        "el bloque de datos es demasiado corto ({} bytes) para tener cabecera",
+        "データブロックが短すぎて ({} バイト) ヘッダーを持てません",
+    ),
+    (
+        "BEGIN address {} is above END address {}",
+        "la dirección BEGIN {} es mayor que la dirección END {}",
+        "BEGIN アドレス {} が END アドレス {} より大きいです",
+    ),
+    (
+        "program is truncated: {} bytes expected, {} bytes found",
+        "el programa está truncado: se esperaban {} bytes y hay {} bytes",
+        "プログラムが途切れています: {} バイトのはずが {} バイトしかありません",
+    ),
+    (
+        "program is followed by {} padding bytes",
+        "el programa va seguido de {} bytes de relleno",
+        "プログラムの後に {} バイトのパディングがあります",
+    ),
+    (
+        "START address {} is not between BEGIN address {} and END address {}",
+        "la dirección START {} no está entre la dirección BEGIN {} y la dirección END {}",
+        "START アドレス {} が BEGIN アドレス {} と END アドレス {} の間にありません",
+    ),
+    (
+        "data block is too short ({} bytes) to have a program",
+        "el bloque de datos es demasiado corto ({} bytes) para tener un programa",
+        "データブロックが短すぎて ({} バイト) プログラムを持てません",
+    ),
+    (
+        "file is truncated: no EOF byte found",
+        "el fichero está truncado: no se encontró el byte EOF",
+        "ファイルが途切れています: EOF バイトが見つかりません",
+    ),
+    (
+        "file name {} has non-printable characters",
+        "el nombre de fichero {} tiene caracteres no imprimibles",
+        "ファイル名 {} に印字できない文字があります",
+    ),
+    ("file name is blank", "el nombre de fichero está en blanco", "ファイル名が空白です"),
+    (
+        "file name {} is also used by block {}",
+        "el nombre de fichero {} también lo usa el bloque {}",
+        "ファイル名 {} はブロック {} でも使われています",
+    ),
+    ("invalid CSW file: {}", "fichero CSW no válido: {}", "無効な CSW ファイル: {}"),
+    ("invalid DAT file: {}", "fichero DAT no válido: {}", "無効な DAT ファイル: {}"),
+    ("invalid WAV file: {}", "fichero WAV no válido: {}", "無効な WAV ファイル: {}"),
+    ("invalid TSX file: {}", "fichero TSX no válido: {}", "無効な TSX ファイル: {}"),
+    (
+        "invalid rename rule at line {}: {}",
+        "regla de renombrado no válida en la línea {}: {}",
+        "行 {} の名前変更ルールが無効です: {}",
+    ),
+    ("expected <old> -> <new>", "se esperaba <old> -> <new>", "<old> -> <new> が必要です"),
+    (
+        "invalid symbol at line {}: expected <name>: EQU <value>",
+        "símbolo no válido en la línea {}: se esperaba <name>: EQU <value>",
+        "行 {} のシンボルが無効です: <name>: EQU <value> が必要です",
+    ),
+    ("invalid metadata file {}: {}", "fichero de metadatos {} no válido: {}", "無効なメタデータファイル {}: {}"),
+    (
+        "unknown metadata key {} (expected one of {})",
+        "clave de metadatos {} desconocida (se esperaba una de {})",
+        "不明なメタデータキー {} ({} のいずれかが必要です)",
+    ),
+    ("invalid notes file {}: {}", "fichero de notas {} no válido: {}", "無効なノートファイル {}: {}"),
+    (
+        "unsupported version {}, upgrade mcp to read it",
+        "versión {} no soportada, actualiza mcp para leerla",
+        "バージョン {} はサポートされていません。読むには mcp を更新してください",
+    ),
+    ("invalid speed file {}: {}", "fichero de velocidades {} no válido: {}", "無効な速度ファイル {}: {}"),
+    ("speeds must be above zero", "las velocidades deben ser mayores que cero", "速度は 0 より大きくなければなりません"),
+    ("invalid progress file {}: {}", "fichero de progreso {} no válido: {}", "無効な進捗ファイル {}: {}"),
+    ("invalid tape set {}: {}", "conjunto de cintas {} no válido: {}", "無効なテープセット {}: {}"),
+    ("no tape named {}", "no hay ninguna cinta llamada {}", "{} という名前のテープはありません"),
+    ("hook {} failed ({})", "el hook {} falló ({})", "フック {} が失敗しました ({})"),
+    ("plugin {}: {}", "plugin {}: {}", "プラグイン {}: {}"),
+    ("invalid signature {}", "firma {} no válida", "無効なシグネチャ {}"),
+    ("missing command", "falta el comando", "コマンドがありません"),
+    ("plugin {} failed to {} ({})", "el plugin {} falló al {} ({})", "プラグイン {} の {} に失敗しました ({})"),
+    ("missing CSW header", "falta la cabecera CSW", "CSW ヘッダーがありません"),
+    ("header is too short", "la cabecera es demasiado corta", "ヘッダーが短すぎます"),
+    ("sample rate is zero", "la frecuencia de muestreo es cero", "サンプリングレートが 0 です"),
+    ("truncated pulse length", "longitud de pulso truncada", "パルス長が途切れています"),
+    ("unsupported version {}", "versión {} no soportada", "バージョン {} はサポートされていません"),
+    ("unsupported compression type {}", "tipo de compresión {} no soportado", "圧縮形式 {} はサポートされていません"),
+    ("unterminated XML tag", "etiqueta XML sin terminar", "XML タグが閉じられていません"),
+    ("rom element without name", "elemento rom sin nombre", "名前のない rom 要素"),
+    ("CSV line without name", "línea CSV sin nombre", "名前のない CSV 行"),
+    ("invalid size {}", "tamaño {} no válido", "無効なサイズ {}"),
+    ("invalid CRC32 {}", "CRC32 {} no válido", "無効な CRC32 {}"),
+    ("missing RIFF/WAVE header", "falta la cabecera RIFF/WAVE", "RIFF/WAVE ヘッダーがありません"),
+    ("format chunk is too short", "el bloque de formato es demasiado corto", "フォーマットチャンクが短すぎます"),
+    ("missing format chunk", "falta el bloque de formato", "フォーマットチャンクがありません"),
+    ("missing data chunk", "falta el bloque de datos", "データチャンクがありません"),
+    ("unsupported audio format {}", "formato de audio {} no soportado", "オーディオ形式 {} はサポートされていません"),
+    ("unsupported number of channels {}", "número de canales {} no soportado", "チャンネル数 {} はサポートされていません"),
+    ("unsupported bits per sample {}", "bits por muestra {} no soportados", "サンプルあたり {} ビットはサポートされていません"),
+    ("missing TSX header", "falta la cabecera TSX", "TSX ヘッダーがありません"),
+    (
+        "block {} at offset {} is truncated",
+        "el bloque {} en la posición {} está truncado",
+        "オフセット {1} のブロック {0} が途切れています",
+    ),
+    ("unknown block ID {}", "ID de bloque {} desconocido", "不明なブロック ID {}"),
+    ("block {} is truncated", "el bloque {} está truncado", "ブロック {} が途切れています"),
+    ("no temporary available for path {}", "no hay fichero temporal para la ruta {}", "パス {} の一時ファイルがありません"),
+    ("download failed: {}", "falló la descarga: {}", "ダウンロードに失敗しました: {}"),
+    (
+        "cannot download {}: mcp was built without network support (`net` feature)",
+        "no se puede descargar {}: mcp se compiló sin soporte de red (característica `net`)",
+        "{} をダウンロードできません: mcp はネットワーク対応 (`net` 機能) なしでビルドされています",
+    ),
+    ("cannot convert path {} into string", "no se puede convertir la ruta {} en texto", "パス {} を文字列に変換できません"),
+    (
+        "tape {} is busy: it is being modified by another process",
+        "la cinta {} está ocupada: otro proceso la está modificando",
+        "テープ {} は使用中です: 別のプロセスが変更しています",
+    ),
+    ("no backup available for path {}", "no hay copia de seguridad para la ruta {}", "パス {} のバックアップはありません"),
+    ("no backup found for {}", "no se encontró copia de seguridad de {}", "{} のバックアップが見つかりません"),
+    ("cannot extract path element from {}", "no se puede extraer el elemento de la ruta {}", "{} からパスの要素を取り出せません"),
+    (
+        "invalid range {}..{} of a tape with {} blocks",
+        "rango {}..{} no válido en una cinta de {} bloques",
+        "{} ブロックのテープで範囲 {}..{} は無効です",
+    ),
+    (
+        "block {} is not a custom block, only custom blocks can be joined",
+        "el bloque {} no es un bloque personalizado, solo se pueden unir bloques personalizados",
+        "ブロック {} はカスタムブロックではありません。結合できるのはカスタムブロックだけです",
+    ),
+    (
+        "invalid file name {}, up to six printable ASCII characters are allowed",
+        "nombre de fichero {} no válido, se admiten hasta seis caracteres ASCII imprimibles",
+        "無効なファイル名 {}: 印字可能な ASCII 文字 6 文字までです",
+    ),
+    ("block {} is not a file header", "el bloque {} no es una cabecera de fichero", "ブロック {} はファイルヘッダーではありません"),
+    (
+        "block {}: replaced sync sequence {} by the standard one",
+        "bloque {}: sustituida la secuencia de sincronismo {} por la estándar",
+        "ブロック {}: 同期シーケンス {} を標準のものに置き換えました",
+    ),
+    ("block {}: removed {} bytes at the end", "bloque {}: eliminados {} bytes al final", "ブロック {}: 末尾の {} バイトを削除しました"),
+    (
+        "block {}: set {} padding bytes to {}",
+        "bloque {}: {} bytes de relleno puestos a {}",
+        "ブロック {}: {} バイトのパディングを {} にしました",
+    ),
+    (
+        "removed {} bytes before the first block",
+        "eliminados {} bytes antes del primer bloque",
+        "最初のブロックの前の {} バイトを削除しました",
+    ),
+    (
+        "block {}: moved from offset {} to offset {}, at an 8-byte boundary",
+        "bloque {}: movido de la posición {} a la posición {}, en un límite de 8 bytes",
+        "ブロック {}: オフセット {} から 8 バイト境界のオフセット {} に移動しました",
+    ),
+    (
+        "block {}: replaced the addresses {} by {}",
+        "bloque {}: sustituidas las direcciones {} por {}",
+        "ブロック {}: アドレス {} を {} に置き換えました",
+    ),
+    (
+        "unknown placeholder {}, expected one of {}",
+        "marcador {} desconocido, se esperaba uno de {}",
+        "不明なプレースホルダー {}。{} のいずれかが必要です",
+    ),
+    ("unmatched {} in {}", "{} sin pareja en {}", "{1} に対応のない {0} があります"),
+    ("{} -> {}... {} KiB", "{} -> {}... {} KiB", "{} -> {}... {} KiB"),
+    (
+        "{} -> {}... {} blocks, {} bytes, {} damaged blocks: {}",
+        "{} -> {}... {} bloques, {} bytes, {} bloques dañados: {}",
+        "{} -> {}... {} ブロック、{} バイト、破損ブロック {}: {}",
+    ),
+    ("NO DATA", "SIN DATOS", "データなし"),
+    ("DAMAGED", "DAÑADA", "破損"),
+    ("OK", "OK", "OK"),
+    ("MISMATCH", "DISTINTO", "不一致"),
+    ("UNKNOWN", "DESCONOCIDO", "不明"),
+    ("Tape", "Cinta", "テープ"),
+    ("Audio", "Audio", "音声"),
+    ("{} bytes at {}", "{} bytes en {}", "{} バイト、位置 {}"),
+    ("{} at {}", "{} en {}", "{}、開始 {}"),
+    ("there are no binary files in {}", "no hay ficheros binarios en {}", "{} にバイナリファイルはありません"),
+    ("Loader of {} binary files added to {}, load it with RUN\"CAS:\"",
+        "Cargador de {} ficheros binarios añadido a {}, cárgalo con RUN\"CAS:\"",
+        "{} 個のバイナリファイルのローダーを {} に追加しました。RUN\"CAS:\" で読み込んでください"),
+    ("there is no block {} in the tape", "no hay ningún bloque {} en la cinta", "テープにブロック {} はありません"),
+    ("Preview rendered into {}", "Vista previa generada en {}", "プレビューを {} に作成しました"),
+    ("Exporting {} tapes using {} jobs...", "Exportando {} cintas con {} tareas...", "{} 本のテープを {} ジョブでエクスポート中..."),
+    ("{}... Error: {}", "{}... Error: {}", "{}... エラー: {}"),
+    ("{} tapes exported, {} failed", "{} cintas exportadas, {} fallidas", "{} 本のテープをエクスポート、{} 本が失敗"),
+    ("Speeds of turbo blocks saved into {}", "Velocidades de los bloques turbo guardadas en {}", "ターボブロックの速度を {} に保存しました"),
+    ("Reading block {}... {} bytes at {} bauds", "Leyendo el bloque {}... {} bytes a {} baudios", "ブロック {} を読み込み中... {} バイト、{} ボー"),
+    ("Importing {} recordings using {} jobs...", "Importando {} grabaciones con {} tareas...", "{} 件の録音を {} ジョブでインポート中..."),
+    (
+        "{} recordings imported, {} damaged, {} failed",
+        "{} grabaciones importadas, {} dañadas, {} fallidas",
+        "{} 件の録音をインポート、{} 件が破損、{} 件が失敗",
+    ),
+    (
+        "Damaged recordings could not be fully decoded and should be re-recorded.",
+        "Las grabaciones dañadas no se pudieron decodificar por completo y deberían grabarse de nuevo.",
+        "破損した録音は完全にはデコードできませんでした。録音し直してください。",
+    ),
+    ("Scanning {} tapes using {} jobs...", "Analizando {} cintas con {} tareas...", "{} 本のテープを {} ジョブでスキャン中..."),
+    ("{} groups of duplicated tapes", "{} grupos de cintas duplicadas", "重複したテープのグループ: {}"),
+    (" (linked)", " (enlazada)", " (リンク済み)"),
+    (" (removed)", " (eliminada)", " (削除済み)"),
+    ("{} tapes contained in others", "{} cintas contenidas en otras", "他のテープに含まれるテープ: {}"),
+    ("  contains all files of {}", "  contiene todos los ficheros de {}", "  {} のすべてのファイルを含みます"),
+    ("{} issues of {} severity or worse found", "{} problemas de gravedad {} o peor encontrados", "重大度 {1} 以上の問題が {0} 件見つかりました"),
+    ("no file named {} in the tape", "no hay ningún fichero llamado {} en la cinta", "テープに {} という名前のファイルはありません"),
+    (
+        "no file named {} in the tape, did you mean {}?",
+        "no hay ningún fichero llamado {} en la cinta, ¿quizás {}?",
+        "テープに {} という名前のファイルはありません。{} のことですか?",
+    ),
+    (" or ", " o ", " または "),
+    (
+        "invalid binary file: it is too short ({} bytes) to even have a header",
+        "fichero binario no válido: es demasiado corto ({} bytes) para tener cabecera",
+        "無効なバイナリファイル: 短すぎて ({} バイト) ヘッダーがありません",
+    ),
+    (
+        "invalid binary file header: BEGIN address {} must be below END address {}",
+        "cabecera de fichero binario no válida: la dirección BEGIN {} debe ser menor que la dirección END {}",
+        "無効なバイナリファイルヘッダー: BEGIN アドレス {} は END アドレス {} より小さい必要があります",
+    ),
+    (
+        "invalid binary file header: BEGIN and END addresses reveal a length ({} bytes) larger than program size ({} bytes)",
+        "cabecera de fichero binario no válida: las direcciones BEGIN y END indican una longitud ({} bytes) mayor que el programa ({} bytes)",
+        "無効なバイナリファイルヘッダー: BEGIN と END アドレスが示す長さ ({} バイト) がプログラムのサイズ ({} バイト) を超えています",
+    ),
+    (
+        "invalid binary file header: START address {} is not between BEGIN address {} and END address {}",
+        "cabecera de fichero binario no válida: la dirección START {} no está entre la dirección BEGIN {} y la dirección END {}",
+        "無効なバイナリファイルヘッダー: START アドレス {} が BEGIN アドレス {} と END アドレス {} の間にありません",
+    ),
+    (
+        "invalid basic file: it is too short ({} bytes) to contain a basic program",
+        "fichero Basic no válido: es demasiado corto ({} bytes) para contener un programa",
+        "無効な BASIC ファイル: 短すぎて ({} バイト) プログラムを含められません",
+    ),
+    (
+        "file name {} has characters other than printable ASCII",
+        "el nombre de fichero {} tiene caracteres que no son ASCII imprimibles",
+        "ファイル名 {} に印字可能な ASCII 以外の文字があります",
+    ),
+    ("file name truncated to {}", "nombre de fichero truncado a {}", "ファイル名を {} に切り詰めました"),
+    (
+        "length ({} bytes) is not a multiple of 8 and requires padding",
+        "la longitud ({} bytes) no es múltiplo de 8 y requiere relleno",
+        "長さ ({} バイト) が 8 の倍数ではないため、埋める必要があります",
+    ),
+    ("Serving the tapes of {} on {}", "Sirviendo las cintas de {} en {}", "{} のテープを {} で公開中"),
+    ("Tape {}: ", "Cinta {}: ", "テープ {}: "),
+];
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_select_language_from_locale() {
+        assert_eq!(Some(Lang::Es), Lang::from_locale("es_ES.UTF-8"));
+        assert_eq!(Some(Lang::Ja), Lang::from_locale("ja-JP"));
+        assert_eq!(Some(Lang::En), Lang::from_locale("C"));
+        assert_eq!(None, Lang::from_locale("fr_FR"));
+    }
+
+    #[test]
+    fn should_translate_messages() {
+        assert_eq!("Hecho", translate("Done", Lang::Es));
+        assert_eq!("完了", translate("Done", Lang::Ja));
+        assert_eq!("Done", translate("Done", Lang::En));
+        assert_eq!("Not translated", translate("Not translated", Lang::Es));
+        assert_eq!("3 bloques escritos en \"a.cas\"", fill(translate("{} blocks written into {}", Lang::Es), &[&3, &"\"a.cas\""]));
+    }
+
+    #[test]
+    fn should_translate_every_placeholder() {
+        for (en, es, ja) in MESSAGES {
+            assert_eq!(en.matches('{').count(), es.matches('{').count(), "{}", en);
+            assert_eq!(en.matches('{').count(), ja.matches('{').count(), "{}", en);
+        }
+        assert_eq!("2 of 1", fill("{1} of {0}", &[&1, &2]));
+    }
+}
@@ -0,0 +1,953 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::cmp::Reverse;
+use std::fmt;
+use std::io;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::tape::Block;
+
+/// Minimum number of consecutive short cycles recognized as a block header (pilot tone).
+const MIN_PILOT_CYCLES: usize = 200;
+
+/// Maximum number of short cycles accepted as stop bits before considering them a pilot tone.
+const MAX_STOP_CYCLES: usize = 8;
+
+/// The channel of a stereo recording to decode
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Left,
+    Right,
+    /// The average of both channels
+    Mix,
+}
+
+/// A recording of a tape, as read from a WAV file
+///
+/// The samples are normalized in the `[-1.0, 1.0]` range.
+#[derive(Clone)]
+pub struct Recording {
+    pub sample_rate: u32,
+    pub samples: Vec<f32>,
+}
+
+impl Recording {
+    /// Read a recording from the bytes of a WAV file.
+    ///
+    /// Only uncompressed PCM mono or stereo recordings of 8 or 16 bits per sample are
+    /// supported, at any sample rate. The channel with the strongest signal of stereo recordings is taken.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Recording> {
+        Recording::from_bytes_with(bytes, None)
+    }
+
+    /// Read a recording from the bytes of a WAV file, taking the given channel of stereo
+    /// recordings, or the one with the strongest signal if none is given.
+    ///
+    /// Data recorders are often wired to only one channel, leaving the other one silent.
+    pub fn from_bytes_with(bytes: &[u8], channel: Option<Channel>) -> io::Result<Recording> {
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err(invalid_wav(&tr!("missing RIFF/WAVE header")));
+        }
+
+        let mut format: Option<(u16, u16, u32, u16)> = None;
+        let mut data: Option<&[u8]> = None;
+        let mut i = 12;
+        while i + 8 <= bytes.len() {
+            let id = &bytes[i..i + 4];
+            let len = LittleEndian::read_u32(&bytes[i + 4..i + 8]) as usize;
+            let body = &bytes[i + 8..bytes.len().min(i + 8 + len)];
+            if id == b"fmt " {
+                if body.len() < 16 {
+                    return Err(invalid_wav(&tr!("format chunk is too short")));
+                }
+                format = Some((
+                    LittleEndian::read_u16(&body[0..2]),
+                    LittleEndian::read_u16(&body[2..4]),
+                    LittleEndian::read_u32(&body[4..8]),
+                    LittleEndian::read_u16(&body[14..16]),
+                ));
+            } else if id == b"data" {
+                data = Some(body);
+            }
+            // Chunks are aligned to 2-byte boundaries
+            i += 8 + len + len % 2;
+        }
+
+        let (audio_format, channels, sample_rate, bits) =
+            format.ok_or_else(|| invalid_wav(&tr!("missing format chunk")))?;
+        let data = data.ok_or_else(|| invalid_wav(&tr!("missing data chunk")))?;
+        if audio_format != 1 {
+            return Err(invalid_wav(&tr!("unsupported audio format {}", audio_format)));
+        }
+        if channels != 1 && channels != 2 {
+            return Err(invalid_wav(&tr!("unsupported number of channels {}", channels)));
+        }
+        if sample_rate == 0 {
+            return Err(invalid_wav(&tr!("sample rate is zero")));
+        }
+        let samples: Vec<f32> = match bits {
+            8 => data.iter().map(|s| (*s as f32 - 128.0) / 128.0).collect(),
+            16 => data
+                .chunks_exact(2)
+                .map(|s| LittleEndian::read_i16(s) as f32 / 32768.0)
+                .collect(),
+            _ => return Err(invalid_wav(&tr!("unsupported bits per sample {}", bits))),
+        };
+        if channels == 1 {
+            return Ok(Recording { sample_rate, samples });
+        }
+
+        let channel_samples = |c: usize| samples.iter().skip(c).step_by(2).cloned().collect::<Vec<f32>>();
+        let samples = match channel {
+            Some(Channel::Left) => channel_samples(0),
+            Some(Channel::Right) => channel_samples(1),
+            Some(Channel::Mix) => samples.chunks_exact(2).map(|f| (f[0] + f[1]) / 2.0).collect(),
+            None => {
+                let (left, right) = (channel_samples(0), channel_samples(1));
+                if energy(&right) > energy(&left) { right } else { left }
+            }
+        };
+        Ok(Recording { sample_rate, samples })
+    }
+
+    /// Build a recording from a square wave described by the length of its pulses.
+    ///
+    /// Each pulse is a run of samples at the same level, and the level alternates from
+    /// one pulse to the next, starting high or low as given.
+    pub fn from_pulses(sample_rate: u32, pulses: &[u32], high: bool) -> Recording {
+        let mut samples = Vec::with_capacity(pulses.iter().map(|p| *p as usize).sum());
+        let mut level = if high { 1.0 } else { -1.0 };
+        for pulse in pulses {
+            samples.extend((0..*pulse).map(|_| level));
+            level = -level;
+        }
+        Recording { sample_rate, samples }
+    }
+}
+
+/// A block of data decoded from a recording
+#[derive(Debug, PartialEq)]
+pub struct DecodedBlock {
+    /// The decoded bytes (without the block prefix bytes)
+    pub data: Vec<u8>,
+    /// The number of framing errors found while decoding the block
+    pub errors: usize,
+    /// The speed of the block, detected from its pilot tone
+    pub bauds: u32,
+    /// The peak level of the signal of the block, in the `[0.0, 1.0]` range
+    pub level: f32,
+    /// The number of glitches (cycles too short to be a cycle) ignored while decoding
+    pub glitches: usize,
+    /// Whether the block was decoded from the inverted signal
+    pub inverted: bool,
+}
+
+impl DecodedBlock {
+    fn empty() -> DecodedBlock {
+        DecodedBlock { data: vec![], errors: 0, bauds: 0, level: 0.0, glitches: 0, inverted: false }
+    }
+
+    /// Returns `true` if the block was decoded by ignoring glitches in its signal.
+    pub fn is_salvaged(&self) -> bool {
+        self.glitches > 0
+    }
+}
+
+/// A cycle of the recorded signal, classified by its length
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Cycle {
+    /// A cycle at twice the baud rate frequency (half of a `1` bit)
+    Short,
+    /// A cycle at the baud rate frequency (a `0` bit)
+    Long,
+    /// A silence or a distortion too long to be a cycle
+    Gap,
+}
+
+/// The decoding algorithm used by an importer
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DecoderKind {
+    /// Measure the length of each cycle between zero crossings (`ZeroCrossing`)
+    ZeroCrossing,
+    /// Correlate each bit with the tones of `0` and `1` bits (`Fsk`)
+    Fsk,
+}
+
+impl DecoderKind {
+    /// Returns a decoder implementing this algorithm.
+    pub fn decoder(self) -> Box<dyn Decoder> {
+        match self {
+            DecoderKind::ZeroCrossing => Box::new(ZeroCrossing),
+            DecoderKind::Fsk => Box::new(Fsk),
+        }
+    }
+}
+
+/// A recording prepared for decoding, along with the position of its edges
+pub struct Signal<'a> {
+    pub recording: &'a Recording,
+    /// The position (in samples) of each rising edge
+    pub rising: Vec<usize>,
+    /// The position (in samples) of each falling edge
+    pub falling: Vec<usize>,
+}
+
+impl<'a> Signal<'a> {
+    /// Prepare the given recording for decoding.
+    pub fn of(recording: &'a Recording) -> Signal<'a> {
+        Signal {
+            recording,
+            rising: Importer::edges(recording, false),
+            falling: Importer::edges(recording, true),
+        }
+    }
+}
+
+/// An algorithm to demodulate the data of a block
+///
+/// The importer finds the pilot tone of each block, and the decoder demodulates the bytes
+/// after it. Decoders handle the defects of the recordings differently, so the one that
+/// works best may depend on the tape.
+pub trait Decoder {
+    /// Demodulate the block whose data starts at the given sample, right after its pilot
+    /// tone, given the length of its short cycles (in samples). The block is returned along
+    /// with the sample where it ends.
+    fn demodulate(&self, signal: &Signal, start: usize, short: f32) -> (DecodedBlock, usize);
+}
+
+/// An object capable to decode the blocks of a tape recording
+///
+/// The importer is the counterpart of `wav::Exporter`. It finds the pilot tone of each
+/// block using the zero crossings of the signal, and detects the speed of the block from
+/// the length of its cycles. Then, the bytes of the block are demodulated by a `Decoder`.
+///
+pub struct Importer {
+    decoder: Box<dyn Decoder>,
+}
+
+impl Default for Importer {
+    fn default() -> Importer {
+        Importer::new()
+    }
+}
+
+impl Importer {
+    /// Create a new importer using default settings
+    pub fn new() -> Importer {
+        Importer::with_decoder(DecoderKind::ZeroCrossing)
+    }
+
+    /// Create a new importer using the given decoding algorithm
+    pub fn with_decoder(kind: DecoderKind) -> Importer {
+        Importer { decoder: kind.decoder() }
+    }
+
+    /// Decode the blocks contained in the given recording.
+    pub fn decode(&self, recording: &Recording) -> Vec<DecodedBlock> {
+        let signal = Signal::of(recording);
+        let rising = &signal.rising;
+        let mut blocks = vec![];
+        let mut i = 0;
+        while i + 1 < rising.len() {
+            let (pilot, short) = Importer::pilot(&rising[i..]);
+            if pilot >= MIN_PILOT_CYCLES {
+                let (mut block, end) = self.decoder.demodulate(&signal, rising[i + pilot], short);
+                block.bauds = nominal_bauds(recording.sample_rate as f32 / (2.0 * short));
+                block.level = peak(&recording.samples[rising[i]..end.min(recording.samples.len())]);
+                if !block.data.is_empty() {
+                    blocks.push(block);
+                }
+                i = rising.partition_point(|e| *e < end).max(i + pilot);
+            } else {
+                i += pilot.max(1);
+            }
+        }
+        blocks
+    }
+
+    /// Returns the number of cycles of the pilot tone starting at the first of the given
+    /// edges, and their average length. Cycles of a pilot tone are about the same length.
+    fn pilot(edges: &[usize]) -> (usize, f32) {
+        let mut total = 0.0;
+        let mut n = 0;
+        for len in edges.windows(2).map(|w| (w[1] - w[0]) as f32) {
+            let avg = if n == 0 { len } else { total / n as f32 };
+            if (len - avg).abs() > avg * 0.25 {
+                break;
+            }
+            total += len;
+            n += 1;
+        }
+        (n, if n == 0 { 0.0 } else { total / n as f32 })
+    }
+
+    /// Returns the position (in samples) of each rising edge of the given recording, or of
+    /// each falling edge if it is inverted.
+    fn edges(recording: &Recording, inverted: bool) -> Vec<usize> {
+        let sign = if inverted { -1.0 } else { 1.0 };
+        let threshold = peak(&recording.samples) * 0.1;
+
+        let mut edges = vec![];
+        let mut high = false;
+        for (i, sample) in recording.samples.iter().map(|s| s * sign).enumerate() {
+            if high && sample < -threshold {
+                high = false;
+            } else if !high && sample > threshold {
+                high = true;
+                edges.push(i);
+            }
+        }
+        edges
+    }
+}
+
+/// A decoder measuring the length of each cycle between zero crossings
+///
+/// Each cycle is classified as short or long on its own, so it copes well with the speed
+/// changes of worn tapes and decks (wow and flutter), but noise shifts the zero crossings.
+pub struct ZeroCrossing;
+
+impl Decoder for ZeroCrossing {
+    fn demodulate(&self, signal: &Signal, start: usize, short: f32) -> (DecodedBlock, usize) {
+        // Recordings may be phase-inverted, and cycles are only measured right from the
+        // edges of the right polarity: decode the block from both and keep the best
+        [(&signal.rising, false), (&signal.falling, true)]
+            .iter()
+            .map(|(edges, inverted)| (edges, edges.partition_point(|e| *e < start), *inverted))
+            .filter(|(edges, from, _)| *from < edges.len())
+            .map(|(edges, from, inverted)| ZeroCrossing::decode_at(edges, from, short, inverted))
+            .min_by_key(|(block, _)| (block.errors, Reverse(block.data.len())))
+            .unwrap_or_else(|| (DecodedBlock::empty(), start))
+    }
+}
+
+impl ZeroCrossing {
+    /// Decode the block whose data starts at the given edge. The block is returned along
+    /// with the sample where it ends.
+    fn decode_at(edges: &[usize], from: usize, short: f32, inverted: bool) -> (DecodedBlock, usize) {
+        let cycles = ZeroCrossing::cycles(&edges[from..], short);
+        let (mut block, next) = ZeroCrossing::decode_block(&cycles.kinds, 0);
+        let used = if next == 0 { 0 } else { cycles.ends[next.min(cycles.ends.len()) - 1] };
+        block.glitches = cycles.glitches;
+        block.inverted = inverted;
+        (block, edges[from + used])
+    }
+
+    fn decode_block(cycles: &[Cycle], from: usize) -> (DecodedBlock, usize) {
+        let cycle = |i: usize| cycles.get(i).cloned().unwrap_or(Cycle::Gap);
+        let mut block = DecodedBlock::empty();
+        let mut i = from;
+
+        // Every byte starts with a long cycle (start bit `0`)
+        while cycle(i) == Cycle::Long {
+            i += 1;
+            let mut byte = 0u8;
+            for bit in 0..8 {
+                match (cycle(i), cycle(i + 1)) {
+                    (Cycle::Long, _) => i += 1,
+                    (Cycle::Short, Cycle::Short) => {
+                        byte |= 1 << bit;
+                        i += 2;
+                    }
+                    _ => {
+                        block.errors += 1;
+                        return (block, i + 1);
+                    }
+                }
+            }
+            block.data.push(byte);
+
+            // Stop bits are two `1` bits (four short cycles). Many more are the pilot of
+            // the next block, that must not be consumed here.
+            let stop = cycles[i.min(cycles.len())..]
+                .iter()
+                .take_while(|c| **c == Cycle::Short)
+                .count();
+            if stop > MAX_STOP_CYCLES {
+                return (block, i);
+            }
+            i += stop;
+        }
+        (block, i)
+    }
+
+    /// Classify the cycles between the given edges given the length of a short cycle, up to
+    /// the first gap. Cycles too short to be a cycle are glitches, merged with the next one.
+    fn cycles(edges: &[usize], short: f32) -> Cycles {
+        let long = short * 2.0;
+        let mut cycles = Cycles { kinds: vec![], ends: vec![], glitches: 0 };
+        let mut len = 0.0;
+        for (i, w) in edges.windows(2).enumerate() {
+            len += (w[1] - w[0]) as f32;
+            if len < long * 0.25 {
+                cycles.glitches += 1;
+                continue;
+            }
+            let cycle = if len < long * 0.75 {
+                Cycle::Short
+            } else if len < long * 1.5 {
+                Cycle::Long
+            } else {
+                Cycle::Gap
+            };
+            cycles.kinds.push(cycle);
+            cycles.ends.push(i + 1);
+            len = 0.0;
+            if cycle == Cycle::Gap {
+                break;
+            }
+        }
+        cycles
+    }
+}
+
+/// The cycles of a block
+struct Cycles {
+    kinds: Vec<Cycle>,
+    /// The number of edges consumed up to each cycle (included)
+    ends: Vec<usize>,
+    glitches: usize,
+}
+
+/// A decoder correlating each bit of the signal with the tones of `0` and `1` bits
+///
+/// Each bit is classified by the tone with the strongest correlation over the whole bit,
+/// regardless of the phase, and the bits are synchronized again on the start bit of every
+/// byte. It copes well with noise and weak signals, but it expects a steady speed along
+/// each byte.
+pub struct Fsk;
+
+impl Decoder for Fsk {
+    fn demodulate(&self, signal: &Signal, start: usize, short: f32) -> (DecodedBlock, usize) {
+        let tones = Tones::new(&signal.recording.samples, short * 2.0);
+        let mut block = DecodedBlock::empty();
+        let mut expected = start as f32;
+
+        // Every byte starts with a `0` bit, found in the margin left by the stop bits. Many
+        // more `1` bits are the pilot of the next block, that must not be consumed here.
+        let margin = tones.bit * (MAX_STOP_CYCLES / 2) as f32;
+        while let Some(at) = tones.start_bit(expected - tones.bit / 2.0, expected + margin) {
+            let mut byte = 0u8;
+            for bit in 0..8 {
+                match tones.bit_at(at + tones.bit * (bit + 1) as f32) {
+                    Some(true) => byte |= 1 << bit,
+                    Some(false) => {}
+                    None => {
+                        block.errors += 1;
+                        return (block, (at + tones.bit * (bit + 1) as f32) as usize);
+                    }
+                }
+            }
+            block.data.push(byte);
+            expected = at + tones.bit * 11.0;
+        }
+        (block, (expected.max(0.0) as usize).min(signal.recording.samples.len()))
+    }
+}
+
+/// The correlation of the windows of a signal with the tones of `0` and `1` bits
+struct Tones<'a> {
+    samples: &'a [f32],
+    /// The length of a bit (in samples)
+    bit: f32,
+    /// The minimum energy of a window to not be a silence
+    silence: f32,
+    /// The cosine and sine of one cycle (`0` bit) and two cycles (`1` bit) per bit
+    zero: Vec<(f32, f32)>,
+    one: Vec<(f32, f32)>,
+}
+
+impl<'a> Tones<'a> {
+    fn new(samples: &'a [f32], bit: f32) -> Tones<'a> {
+        let len = bit.round().max(1.0) as usize;
+        let wave = |cycles: f32| -> Vec<(f32, f32)> {
+            (0..len)
+                .map(|k| 2.0 * std::f32::consts::PI * cycles * k as f32 / len as f32)
+                .map(|phase| (phase.cos(), phase.sin()))
+                .collect()
+        };
+        let threshold = peak(samples) * 0.1;
+        Tones { samples, bit, silence: threshold * threshold * len as f32, zero: wave(1.0), one: wave(2.0) }
+    }
+
+    /// Returns the correlation of the window starting at the given sample with the tones
+    /// of `0` and `1` bits, relative to the energy of the window, or `None` if the window is
+    /// a silence or it is out of the signal.
+    fn correlation(&self, at: f32) -> Option<(f32, f32)> {
+        let from = at.round();
+        if from < 0.0 || from as usize + self.zero.len() > self.samples.len() {
+            return None;
+        }
+        let window = &self.samples[from as usize..from as usize + self.zero.len()];
+        let energy: f32 = window.iter().map(|s| s * s).sum();
+        if energy < self.silence {
+            return None;
+        }
+        let correlate = |wave: &[(f32, f32)]| {
+            let (re, im) = window
+                .iter()
+                .zip(wave)
+                .fold((0.0, 0.0), |(re, im), (s, (c, s2))| (re + s * c, im + s * s2));
+            (re * re + im * im) / (energy * window.len() as f32 / 2.0)
+        };
+        Some((correlate(&self.zero), correlate(&self.one)))
+    }
+
+    /// Returns the value of the bit starting at the given sample, or `None` if there is no
+    /// tone clear enough.
+    fn bit_at(&self, at: f32) -> Option<bool> {
+        let (zero, one) = self.correlation(at)?;
+        if zero.max(one) < 0.25 {
+            None
+        } else {
+            Some(one > zero)
+        }
+    }
+
+    /// Returns the sample where the first start bit between the given samples begins.
+    fn start_bit(&self, from: f32, to: f32) -> Option<f32> {
+        let step = self.bit / 8.0;
+        let score = |at: f32| self.correlation(at).map(|(zero, one)| zero - one).unwrap_or(-1.0);
+        let mut at = from.max(0.0);
+        while at <= to {
+            if score(at) > 0.5 {
+                // The window matches the start bit best when aligned with it
+                return (0..=4)
+                    .map(|k| at + step * k as f32)
+                    .max_by(|a, b| score(*a).total_cmp(&score(*b)));
+            }
+            at += step;
+        }
+        None
+    }
+}
+
+/// The quality of a decoded block
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quality {
+    /// The block was decoded without trouble
+    Good,
+    /// The block was decoded, but its signal had glitches that were ignored
+    Salvaged,
+    /// The block could not be fully decoded
+    Damaged,
+}
+
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Quality::Good => "good",
+            Quality::Salvaged => "salvaged",
+            Quality::Damaged => "damaged",
+        };
+        f.pad(name)
+    }
+}
+
+/// The decoding statistics of a block
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BlockReport {
+    pub block: usize,
+    pub bauds: u32,
+    /// The number of decoded bytes
+    pub bytes: usize,
+    /// The number of bytes the block should have, if known from its file header
+    pub expected: Option<usize>,
+    pub errors: usize,
+    pub level: f32,
+    pub glitches: usize,
+    pub inverted: bool,
+    pub quality: Quality,
+}
+
+/// The decoding statistics of a recording
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub recording: String,
+    pub blocks: Vec<BlockReport>,
+}
+
+impl Report {
+    /// Build the report of the given blocks decoded from the recording with the given name.
+    pub fn of(name: &str, blocks: &[DecodedBlock]) -> Report {
+        let reports = blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| {
+                let expected = expected_len(blocks, i);
+                let quality = if block.errors > 0 || expected.is_some_and(|n| block.data.len() < n) {
+                    Quality::Damaged
+                } else if block.is_salvaged() {
+                    Quality::Salvaged
+                } else {
+                    Quality::Good
+                };
+                BlockReport {
+                    block: i,
+                    bauds: block.bauds,
+                    bytes: block.data.len(),
+                    expected,
+                    errors: block.errors,
+                    level: block.level,
+                    glitches: block.glitches,
+                    inverted: block.inverted,
+                    quality,
+                }
+            })
+            .collect();
+        Report { recording: name.to_string(), blocks: reports }
+    }
+
+    /// Returns the number of blocks of the given quality.
+    pub fn count(&self, quality: Quality) -> usize {
+        self.blocks.iter().filter(|b| b.quality == quality).count()
+    }
+}
+
+/// A difference between the blocks of a tape and the blocks decoded from its recording
+#[derive(Debug, PartialEq)]
+pub enum Mismatch {
+    /// The recording has a number of blocks other than the expected one
+    Blocks { expected: usize, decoded: usize },
+    /// The decoded block with the given index differs from the expected one from the given
+    /// byte on, or it is longer or shorter
+    Data { block: usize, offset: usize },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mismatch::Blocks { expected, decoded } => write!(f, "{} blocks found, {} expected", decoded, expected),
+            Mismatch::Data { block, offset } => write!(f, "block {}: differs from byte {} on", block, offset),
+        }
+    }
+}
+
+/// Compare the data of the given blocks of a tape with the data of the blocks decoded from
+/// its recording, or from another copy of the tape.
+///
+/// The blocks are compared in turn, so the blocks missing or left over at the end are
+/// reported as a different number of blocks.
+pub fn compare(expected: &[&[u8]], decoded: &[&[u8]]) -> Vec<Mismatch> {
+    let mut mismatches = vec![];
+    if expected.len() != decoded.len() {
+        mismatches.push(Mismatch::Blocks { expected: expected.len(), decoded: decoded.len() });
+    }
+    for (i, (a, b)) in expected.iter().zip(decoded).enumerate() {
+        if a != b {
+            let offset = a.iter().zip(b.iter()).position(|(x, y)| x != y).unwrap_or(a.len().min(b.len()));
+            mismatches.push(Mismatch::Data { block: i, offset });
+        }
+    }
+    mismatches
+}
+
+/// Returns the number of bytes the given block should have: 16 bytes for file headers,
+/// and the length given by the addresses of binary files for their data blocks.
+fn expected_len(blocks: &[DecodedBlock], i: usize) -> Option<usize> {
+    if Block::from_data(&blocks[i].data).is_file_header() {
+        return Some(16);
+    }
+    let header = Block::from_data(&blocks[i.checked_sub(1)?].data);
+    let data = &blocks[i].data;
+    if !header.is_bin_header() || data.len() < 4 {
+        return None;
+    }
+    let begin = LittleEndian::read_u16(&data[0..2]) as usize;
+    let end = LittleEndian::read_u16(&data[2..4]) as usize;
+    if begin > end {
+        return None;
+    }
+    Some(6 + end - begin + 1)
+}
+
+/// Returns the nominal speed of a block measured at the given bauds.
+///
+/// Tape decks do not play at the exact speed they recorded, so speeds close to the
+/// standard ones (1200 and 2400 bauds) are taken as such. Other speeds are custom turbo
+/// speeds, rounded to tens of bauds.
+fn nominal_bauds(measured: f32) -> u32 {
+    for standard in &[1200.0, 2400.0] {
+        if (measured - standard).abs() < standard * 0.1 {
+            return *standard as u32;
+        }
+    }
+    ((measured / 10.0).round() as u32 * 10).max(10)
+}
+
+/// Returns the energy of the given samples: the sum of their squares.
+fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0f32, |p, s| p.max(s.abs()))
+}
+
+fn energy(samples: &[f32]) -> f32 {
+    samples.iter().map(|s| s * s).sum()
+}
+
+fn invalid_wav(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, tr!("invalid WAV file: {}", msg))
+}
+
+#[cfg(test)]
+mod test {
+
+    use quickcheck::{quickcheck, TestResult};
+
+    use crate::wav::Exporter;
+
+    use super::*;
+
+    fn encode(blocks: &[&[u8]]) -> Vec<u8> {
+        let mut exporter = Exporter::new();
+        for (i, block) in blocks.iter().enumerate() {
+            if i == 0 {
+                exporter.write_long_silence().unwrap();
+                exporter.write_long_header().unwrap();
+            } else {
+                exporter.write_short_silence().unwrap();
+                exporter.write_short_header().unwrap();
+            }
+            exporter.write_data(block).unwrap();
+        }
+        let mut wav = vec![];
+        exporter.export(&mut wav).unwrap();
+        wav
+    }
+
+    #[test]
+    fn should_reject_non_wav_data() {
+        assert!(Recording::from_bytes(b"garbage").is_err());
+    }
+
+    #[test]
+    fn should_read_exported_wav() {
+        let recording = Recording::from_bytes(&encode(&[])).unwrap();
+        assert_eq!(43200, recording.sample_rate);
+        assert!(recording.samples.is_empty());
+    }
+
+    #[test]
+    fn should_decode_exported_blocks() {
+        let wav = encode(&[&[0x00, 0xff, 0x55, 0xaa], &[0x01, 0x02, 0x03]]);
+        let recording = Recording::from_bytes(&wav).unwrap();
+        let blocks = Importer::new().decode(&recording);
+        assert_eq!(
+            vec![
+                (vec![0x00, 0xff, 0x55, 0xaa], 0, 1200, 0),
+                (vec![0x01, 0x02, 0x03], 0, 1200, 0),
+            ],
+            blocks.into_iter().map(|b| (b.data, b.errors, b.bauds, b.glitches)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn should_detect_block_speeds() {
+        let mut exporter = Exporter::new();
+        for bauds in &[1200, 2400, 1200, 3600] {
+            exporter.set_bauds(*bauds);
+            exporter.write_short_silence().unwrap();
+            exporter.write_short_header().unwrap();
+            exporter.write_data(&[0x00, 0xff, 0x55, 0xaa]).unwrap();
+        }
+        let mut wav = vec![];
+        exporter.export(&mut wav).unwrap();
+        let blocks = Importer::new().decode(&Recording::from_bytes(&wav).unwrap());
+        assert_eq!(
+            vec![(1200, 0), (2400, 0), (1200, 0), (3600, 0)],
+            blocks.iter().map(|b| (b.bauds, b.errors)).collect::<Vec<_>>());
+        assert!(blocks.iter().all(|b| b.data == vec![0x00, 0xff, 0x55, 0xaa]));
+    }
+
+    #[test]
+    fn should_report_block_quality() {
+        let block = |data: &[u8], errors: usize, glitches: usize| DecodedBlock {
+            data: data.to_vec(),
+            errors,
+            bauds: 1200,
+            level: 0.5,
+            glitches,
+            inverted: false,
+        };
+        let blocks = vec![
+            block(&[0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, b'f', b'o', b'o', b' ', b' ', b' '], 0, 0),
+            block(&[0x00, 0x80, 0x07, 0x80, 0x00, 0x80, 0xc9, 0xc9], 0, 0),
+            block(&[0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, b'b', b'a', b'r', b' ', b' ', b' '], 0, 3),
+            block(&[0x01, 0x02], 1, 0),
+        ];
+        let report = Report::of("test.wav", &blocks);
+        let summary: Vec<(Option<usize>, Quality)> = report.blocks.iter().map(|b| (b.expected, b.quality)).collect();
+        assert_eq!(
+            vec![
+                (Some(16), Quality::Good),
+                (Some(14), Quality::Damaged),
+                (Some(16), Quality::Salvaged),
+                (None, Quality::Damaged),
+            ],
+            summary);
+        assert_eq!(2, report.count(Quality::Damaged));
+    }
+
+    #[test]
+    fn should_decode_inverted_recordings() {
+        let wav = encode(&[&[0x00, 0xff, 0x55, 0xaa], &[0x01, 0x02, 0x03]]);
+        let mut recording = Recording::from_bytes(&wav).unwrap();
+        for sample in recording.samples.iter_mut() {
+            *sample = -*sample;
+        }
+        let blocks = Importer::new().decode(&recording);
+        assert_eq!(
+            vec![(vec![0x00, 0xff, 0x55, 0xaa], 0, true), (vec![0x01, 0x02, 0x03], 0, true)],
+            blocks.into_iter().map(|b| (b.data, b.errors, b.inverted)).collect::<Vec<_>>()
+        );
+    }
+
+    /// Synthesize the recording of a block at 1200 bauds at the given sample rate, as
+    /// a sine wave sampled at exact times (unlike `Exporter`, whose cycles last a whole
+    /// number of samples).
+    fn synthesize(sample_rate: u32, data: &[u8]) -> Recording {
+        let mut cycles = vec![2400.0; 4000];
+        for byte in data {
+            cycles.push(1200.0);
+            for bit in 0..8 {
+                if byte & (1 << bit) != 0 {
+                    cycles.extend_from_slice(&[2400.0, 2400.0]);
+                } else {
+                    cycles.push(1200.0);
+                }
+            }
+            cycles.extend_from_slice(&[2400.0; 4]);
+        }
+        let mut samples = vec![0.0; sample_rate as usize / 10];
+        let mut t = 0.0;
+        for freq in cycles {
+            let end = t + sample_rate as f64 / freq;
+            while (samples.len() as f64) < end {
+                let x = samples.len() as f64 - t;
+                samples.push((2.0 * std::f64::consts::PI * x * freq / sample_rate as f64).sin() as f32 * 0.8);
+            }
+            t = end;
+        }
+        samples.extend(vec![0.0; sample_rate as usize / 10]);
+        Recording { sample_rate, samples }
+    }
+
+    #[test]
+    fn should_decode_any_sample_rate() {
+        let data = [0x00, 0xff, 0x55, 0xaa, 0x01, 0x80, 0x7e, 0x3c];
+        for sample_rate in &[8000, 11025, 22050, 32000, 44100, 48000, 96000, 192000] {
+            let blocks = Importer::new().decode(&synthesize(*sample_rate, &data));
+            assert_eq!(
+                vec![(data.to_vec(), 0, 1200)],
+                blocks.into_iter().map(|b| (b.data, b.errors, b.bauds)).collect::<Vec<_>>(),
+                "sample rate {}", sample_rate);
+        }
+    }
+
+    #[test]
+    fn should_decode_with_every_decoder() {
+        let data = [0x00, 0xff, 0x55, 0xaa, 0x01, 0x80, 0x7e, 0x3c];
+        let wav = encode(&[&data, &[0x01, 0x02, 0x03]]);
+        let mut inverted = Recording::from_bytes(&wav).unwrap();
+        for sample in inverted.samples.iter_mut() {
+            *sample = -*sample;
+        }
+        for kind in &[DecoderKind::ZeroCrossing, DecoderKind::Fsk] {
+            let importer = Importer::with_decoder(*kind);
+            for recording in &[Recording::from_bytes(&wav).unwrap(), inverted.clone(), synthesize(22050, &data)] {
+                let blocks = importer.decode(recording);
+                assert_eq!(data.to_vec(), blocks[0].data, "{:?}", kind);
+                assert!(blocks.iter().all(|b| b.errors == 0 && b.bauds == 1200), "{:?}", kind);
+            }
+        }
+    }
+
+    #[test]
+    fn should_decode_noisy_recordings_with_fsk() {
+        let data = [0x00, 0xff, 0x55, 0xaa, 0x01, 0x80, 0x7e, 0x3c];
+        let mut recording = synthesize(44100, &data);
+        let mut seed = 1u32;
+        for sample in recording.samples.iter_mut() {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            *sample += ((seed >> 16) as f32 / 32768.0 - 1.0) * 0.25;
+        }
+        let blocks = Importer::with_decoder(DecoderKind::Fsk).decode(&recording);
+        assert_eq!(
+            vec![(data.to_vec(), 0)],
+            blocks.into_iter().map(|b| (b.data, b.errors)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn should_round_speeds() {
+        assert_eq!(1200, nominal_bauds(1130.0));
+        assert_eq!(2400, nominal_bauds(2520.0));
+        assert_eq!(3600, nominal_bauds(3601.3));
+    }
+
+    fn stereo(left: &[i16], right: &[i16]) -> Vec<u8> {
+        let mut wav = b"RIFF\x00\x00\x00\x00WAVEfmt \x10\x00\x00\x00\x01\x00\x02\x00".to_vec();
+        wav.extend_from_slice(&[0x44, 0xac, 0x00, 0x00, 0x10, 0xb1, 0x02, 0x00, 0x04, 0x00, 0x10, 0x00]);
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(4 * left.len() as u32).to_le_bytes());
+        for (l, r) in left.iter().zip(right) {
+            wav.extend_from_slice(&l.to_le_bytes());
+            wav.extend_from_slice(&r.to_le_bytes());
+        }
+        wav
+    }
+
+    #[test]
+    fn should_read_stereo_channels() {
+        let wav = stereo(&[0, 0x4000, -0x4000], &[0x2000, 0x2000, 0x2000]);
+        let samples = |channel| Recording::from_bytes_with(&wav, channel).unwrap().samples;
+        assert_eq!(vec![0.0, 0.5, -0.5], samples(Some(Channel::Left)));
+        assert_eq!(vec![0.25, 0.25, 0.25], samples(Some(Channel::Right)));
+        assert_eq!(vec![0.125, 0.375, -0.125], samples(Some(Channel::Mix)));
+        assert_eq!(vec![0.0, 0.5, -0.5], samples(None));
+
+        let wav = stereo(&[0, 0, 0], &[0x2000, 0x2000, 0x2000]);
+        assert_eq!(vec![0.25, 0.25, 0.25], Recording::from_bytes(&wav).unwrap().samples);
+    }
+
+    #[test]
+    fn should_build_recording_from_pulses() {
+        let recording = Recording::from_pulses(44100, &[2, 1, 3], false);
+        assert_eq!(vec![-1.0, -1.0, 1.0, -1.0, -1.0, -1.0], recording.samples);
+    }
+
+    fn decode_exported_block_prop(bytes: Vec<u8>) -> TestResult {
+        if bytes.is_empty() {
+            return TestResult::discard();
+        }
+        let recording = Recording::from_bytes(&encode(&[&bytes])).unwrap();
+        let blocks = Importer::new().decode(&recording);
+        TestResult::from_bool(
+            blocks.len() == 1 && blocks[0].data == bytes && blocks[0].errors == 0 && blocks[0].bauds == 1200)
+    }
+
+    #[test]
+    fn should_decode_exported_block() {
+        quickcheck(decode_exported_block_prop as fn(Vec<u8>) -> TestResult);
+    }
+
+    #[test]
+    fn should_compare_decoded_blocks() {
+        let expected: [&[u8]; 2] = [&[1, 2, 3, 4], &[5, 6, 7, 8]];
+        assert_eq!(Vec::<Mismatch>::new(), compare(&expected, &[&[1, 2, 3, 4], &[5, 6, 7, 8]]));
+        assert_eq!(
+            vec![Mismatch::Data { block: 0, offset: 2 }, Mismatch::Data { block: 1, offset: 3 }],
+            compare(&expected, &[&[1, 2, 0, 4], &[5, 6, 7]]));
+        assert_eq!(
+            vec![Mismatch::Blocks { expected: 2, decoded: 1 }],
+            compare(&expected, &[&[1, 2, 3, 4]]));
+    }
+}
@@ -0,0 +1,187 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// The width in characters of the lines of an inlay
+const WIDTH: usize = 38;
+
+/// The size in points of the front panel of a standard cassette J-card (102 x 65 mm)
+const PAGE_SIZE: (f64, f64) = (289.0, 184.0);
+
+/// The margin in points around the text of the front panel
+const MARGIN: f64 = 10.0;
+
+/// A file listed in an inlay
+#[derive(Debug, PartialEq)]
+pub struct Item {
+    pub name: String,
+    pub kind: &'static str,
+    pub seconds: f64,
+}
+
+/// The content listing of a tape, to be printed on the J-card of a cassette
+///
+/// The files are laid out on the sides of the cassette in order, moving to the next side
+/// when the current one is full.
+///
+#[derive(Debug)]
+pub struct Inlay {
+    title: String,
+    sides: Vec<Vec<Item>>,
+}
+
+impl Inlay {
+    /// Lay out the given files on the sides of a cassette of the given length per side.
+    pub fn new(title: &str, items: Vec<Item>, side_seconds: f64) -> Inlay {
+        let mut sides: Vec<Vec<Item>> = vec![vec![]];
+        let mut used = 0.0;
+        for item in items {
+            let side = sides.last_mut().unwrap();
+            if !side.is_empty() && used + item.seconds > side_seconds {
+                sides.push(vec![]);
+                used = 0.0;
+            }
+            used += item.seconds;
+            sides.last_mut().unwrap().push(item);
+        }
+        Inlay { title: title.to_string(), sides }
+    }
+
+    /// Returns the lines of the inlay, `WIDTH` characters wide at most.
+    pub fn lines(&self) -> Vec<String> {
+        let rule = "-".repeat(WIDTH);
+        let mut lines = vec![truncate(&self.title, WIDTH), rule.clone()];
+        for (i, side) in self.sides.iter().enumerate() {
+            let label = side_label(i);
+            lines.push(format!("SIDE {}", label));
+            for (j, item) in side.iter().enumerate() {
+                let entry = format!("{:2}. {:6} {:6}", j + 1, truncate(&item.name, 6), item.kind);
+                lines.push(format!("{:w$}{}", entry, duration(item.seconds), w = WIDTH - 5));
+            }
+            let total: f64 = side.iter().map(|item| item.seconds).sum();
+            let footer = format!("Side {} total", label);
+            lines.push(format!("{:>w$} {}", footer, duration(total), w = WIDTH - 6));
+            lines.push(rule.clone());
+        }
+        lines
+    }
+
+    /// Render the inlay as plain text.
+    pub fn text(&self) -> String {
+        self.lines().iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    /// Render the inlay as a PDF document of the size of the front panel of a J-card.
+    ///
+    /// The text is set in Courier, as small as needed to fit the panel.
+    pub fn pdf(&self) -> Vec<u8> {
+        let lines = self.lines();
+        let (width, height) = PAGE_SIZE;
+        let leading = ((height - 2.0 * MARGIN) / lines.len() as f64).min(10.0);
+        // Courier characters are 0.6 times as wide as the font size
+        let size = (leading * 0.9).min((width - 2.0 * MARGIN) / (WIDTH as f64 * 0.6));
+        // Each line moves down before it is shown, so the text starts at the top margin
+        let mut content = format!("BT /F1 {:.2} Tf {:.2} TL {} {} Td\n", size, leading, MARGIN, height - MARGIN);
+        for line in &lines {
+            content += &format!("({}) '\n", pdf_string(line));
+        }
+        content += "ET\n";
+
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 4 0 R >> >> /Contents 5 0 R >>",
+                width, height),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>".to_string(),
+            format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content),
+        ];
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = vec![];
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+        }
+        let xref = pdf.len();
+        pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+        for offset in offsets {
+            pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        pdf.extend_from_slice(
+            format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).as_bytes());
+        pdf
+    }
+}
+
+/// Returns the label of the side with the given index: `A`, `B`, `C`...
+fn side_label(i: usize) -> char {
+    (b'A' + (i % 26) as u8) as char
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+fn duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Escape the given text as a PDF string, replacing the characters out of ASCII.
+fn pdf_string(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '(' | ')' | '\\' => format!("\\{}", c),
+            c if c.is_ascii() && !c.is_ascii_control() => c.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn item(name: &str, seconds: f64) -> Item {
+        Item { name: name.to_string(), kind: "bin", seconds }
+    }
+
+    #[test]
+    fn should_lay_out_files_on_sides() {
+        let inlay = Inlay::new("Arkanoid", vec![item("ARK", 600.0), item("LEVELS", 900.0), item("BONUS", 400.0)], 1800.0);
+        assert_eq!(
+            vec![
+                "Arkanoid",
+                "--------------------------------------",
+                "SIDE A",
+                " 1. ARK    bin                   10:00",
+                " 2. LEVELS bin                   15:00",
+                "                    Side A total 25:00",
+                "--------------------------------------",
+                "SIDE B",
+                " 1. BONUS  bin                   06:40",
+                "                    Side B total 06:40",
+                "--------------------------------------",
+            ],
+            inlay.lines());
+    }
+
+    #[test]
+    fn should_render_pdf() {
+        let inlay = Inlay::new("Arkanoid (1986)", vec![item("ARK", 600.0)], 1800.0);
+        let pdf = String::from_utf8(inlay.pdf()).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        assert!(pdf.contains("(Arkanoid \\(1986\\)) '\n"));
+        assert!(pdf.ends_with("%%EOF\n"));
+        // The cross-reference table points to every object
+        let xref = pdf.find("xref\n").unwrap();
+        assert!(pdf.contains(&format!("startxref\n{}\n", xref)));
+        let offset = pdf.find("3 0 obj").unwrap();
+        assert!(pdf.contains(&format!("{:010} 00000 n \n", offset)));
+    }
+}
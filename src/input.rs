@@ -0,0 +1,182 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::csw::{self, Csw};
+use crate::file;
+use crate::import::{Importer, Recording};
+use crate::mmap::Mapping;
+use crate::tape::Tape;
+use crate::tsx::{self, Tsx};
+
+/// The container format of a tape given as input
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// A CAS file, the native format of `mcp`
+    Cas,
+    /// A TSX file, storing each block in a Kansas City Standard block
+    Tsx,
+    /// A CSW (Compressed Square Wave) recording
+    Csw,
+    /// A WAV recording
+    Wav,
+}
+
+impl Format {
+    /// All the input formats
+    pub const ALL: [Format; 4] = [Format::Cas, Format::Tsx, Format::Csw, Format::Wav];
+
+    /// Detect the format of the given bytes from their magic bytes.
+    ///
+    /// Anything not recognized as other format is considered a CAS file.
+    pub fn detect(bytes: &[u8]) -> Format {
+        if bytes.starts_with(tsx::MAGIC) {
+            Format::Tsx
+        } else if bytes.starts_with(csw::MAGIC) {
+            Format::Csw
+        } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WAVE" {
+            Format::Wav
+        } else {
+            Format::Cas
+        }
+    }
+}
+
+/// Decode the tape stored in the given bytes using the given format.
+///
+/// Recordings (CSW and WAV) are decoded using the default settings of `Importer`. The
+/// given sync sequences are accepted as block prefixes of CAS files besides the standard one.
+pub fn decode(bytes: &[u8], format: Format, syncs: &[[u8; 8]]) -> io::Result<Tape> {
+    let blocks = match format {
+        Format::Cas => return Ok(Tape::from_bytes_with(bytes, syncs)),
+        Format::Tsx => Tsx::from_bytes(bytes)?.blocks.into_iter().map(|b| b.data).collect(),
+        Format::Csw => decode_recording(&Csw::from_bytes(bytes)?.recording()),
+        Format::Wav => decode_recording(&Recording::from_bytes(bytes)?),
+    };
+    let mut tape = Tape::new();
+    for block in blocks {
+        tape.append_raw_block(&block);
+    }
+    Ok(tape)
+}
+
+/// Load the tape stored in the given file or URL.
+///
+/// The format is detected from the content unless it is given. Local CAS files are mapped
+/// into memory instead of read, so the blocks of the tape refer to the file contents.
+pub fn load(path: &Path, format: Option<Format>, syncs: &[[u8; 8]]) -> io::Result<Tape> {
+    if path == Path::new("-") || file::is_url(path) {
+        let bytes = file::fetch_content(path)?;
+        return decode(&bytes, format.unwrap_or_else(|| Format::detect(&bytes)), syncs);
+    }
+    let mapping = Mapping::open(path)?;
+    match format.unwrap_or_else(|| Format::detect(&mapping)) {
+        Format::Cas => Ok(Tape::from_source(Arc::new(mapping), syncs)),
+        format => decode(&mapping, format, syncs),
+    }
+}
+
+fn decode_recording(recording: &Recording) -> Vec<Vec<u8>> {
+    Importer::new().decode(recording).into_iter().map(|b| b.data).collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use tempdir::TempDir;
+
+    use crate::tape::file_name;
+    use crate::wav::Exporter;
+
+    use super::*;
+
+    fn sample_tape() -> Tape {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("foo").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape
+    }
+
+    fn to_wav(tape: &Tape) -> Vec<u8> {
+        let mut exporter = Exporter::new();
+        for block in tape.blocks() {
+            exporter.write_short_silence().unwrap();
+            exporter.write_long_header().unwrap();
+            exporter.write_data(block.data_without_prefix()).unwrap();
+        }
+        let mut wav = vec![];
+        exporter.export(&mut wav).unwrap();
+        wav
+    }
+
+    fn blocks_of(tape: &Tape) -> Vec<Vec<u8>> {
+        tape.blocks().iter().map(|b| b.data().to_vec()).collect()
+    }
+
+    #[test]
+    fn should_detect_formats() {
+        assert_eq!(Format::Tsx, Format::detect(b"ZXTape!\x1a\x01\x15"));
+        assert_eq!(Format::Csw, Format::detect(b"Compressed Square Wave\x1a\x02\x00"));
+        assert_eq!(Format::Wav, Format::detect(b"RIFF\x00\x00\x00\x00WAVEfmt "));
+        assert_eq!(Format::Cas, Format::detect(&[0x1f, 0xa6, 0xde, 0xba, 0xcc, 0x13, 0x7d, 0x74]));
+        assert_eq!(Format::Cas, Format::detect(b""));
+    }
+
+    #[test]
+    fn should_load_local_files() {
+        let temp = TempDir::new("mcp").unwrap();
+        let tape = sample_tape();
+        let cas = temp.path().join("foo.cas");
+        file::write_content(&cas, &tape.to_bytes()).unwrap();
+        assert_eq!(blocks_of(&tape), blocks_of(&load(&cas, None, &[]).unwrap()));
+
+        let wav = temp.path().join("foo.wav");
+        file::write_content(&wav, &to_wav(&tape)).unwrap();
+        assert_eq!(blocks_of(&tape), blocks_of(&load(&wav, None, &[]).unwrap()));
+        assert!(load(&temp.path().join("missing.cas"), None, &[]).is_err());
+    }
+
+    #[test]
+    fn should_decode_wav() {
+        let tape = sample_tape();
+        let decoded = decode(&to_wav(&tape), Format::Wav, &[]).unwrap();
+        assert_eq!(blocks_of(&tape), blocks_of(&decoded));
+    }
+
+    #[test]
+    fn should_decode_csw() {
+        let tape = sample_tape();
+        // Turn the exported samples into pulses: runs of samples at the same side of silence
+        let wav = to_wav(&tape);
+        let mut pulses: Vec<u32> = vec![];
+        let mut high = true;
+        for sample in &wav[44..] {
+            let level = *sample >= 0x80;
+            match pulses.last_mut() {
+                Some(len) if level == high => *len += 1,
+                _ => pulses.push(1),
+            }
+            high = level;
+        }
+        let mut csw = csw::MAGIC.to_vec();
+        csw.extend_from_slice(&[0x01, 0x01, 0xc0, 0xa8, 0x01, 0x01, 0x00, 0x00, 0x00]);
+        for pulse in pulses {
+            if pulse < 0x100 {
+                csw.push(pulse as u8);
+            } else {
+                csw.push(0);
+                csw.extend_from_slice(&pulse.to_le_bytes());
+            }
+        }
+        let decoded = decode(&csw, Format::detect(&csw), &[]).unwrap();
+        assert_eq!(blocks_of(&tape), blocks_of(&decoded));
+    }
+}
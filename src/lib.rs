@@ -0,0 +1,106 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! MSX CAS Packager
+//!
+//! The library behind the `mcp` command, to read, write and convert the CAS tapes of MSX
+//! computers from other programs, such as emulators or build systems. The main types are:
+//!
+//! * `tape::Tape`, a tape made of blocks, with the files stored in them. Tapes are read from
+//!   and written into the bytes of CAS files, and files are added with `Tape::append_bin()`,
+//!   `Tape::append_basic()`, `Tape::append_ascii()` and `Tape::append_custom()`.
+//! * `wav::Exporter`, the encoder of tapes into WAV files that can be played into a real MSX.
+//! * `import::DecoderKind`, the decoders of recordings of tapes back into their blocks.
+//! * `input::load()`, reading a tape from a CAS, TSX, CSW or WAV file.
+//!
+//! ```no_run
+//! use mcp::tape::{file_name, Tape};
+//! use mcp::wav::Exporter;
+//!
+//! let mut tape = Tape::new();
+//! tape.append_bin(&file_name("game").0, &std::fs::read("game.bin").unwrap()).unwrap();
+//! std::fs::write("game.cas", tape.to_bytes()).unwrap();
+//!
+//! let mut exporter = Exporter::new();
+//! for block in tape.blocks() {
+//!     exporter.write_block(block).unwrap();
+//! }
+//! exporter.export(&mut std::fs::File::create("game.wav").unwrap()).unwrap();
+//! ```
+//!
+//! The rest of modules implement the commands of `mcp`, and are public so other programs
+//! may reuse them as well.
+//!
+
+extern crate byteorder;
+extern crate crc32fast;
+extern crate flate2;
+#[cfg(unix)]
+extern crate libc;
+extern crate memchr;
+extern crate regex;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate sha1_smol;
+
+#[cfg(test)]
+extern crate quickcheck;
+#[cfg(test)]
+extern crate tempdir;
+
+#[macro_use]
+pub mod i18n;
+
+pub mod analyze;
+pub mod asm;
+pub mod basic;
+pub mod batch;
+pub mod cache;
+pub mod charset;
+pub mod check;
+pub mod confirm;
+pub mod csw;
+pub mod dat;
+pub mod defrag;
+#[cfg(feature = "disasm")]
+pub mod disasm;
+pub mod dump;
+pub mod embed;
+pub mod file;
+pub mod filter;
+pub mod gap;
+pub mod hash;
+pub mod hook;
+pub mod import;
+pub mod inlay;
+pub mod input;
+pub mod loader;
+pub mod library;
+pub mod manifest;
+pub mod map;
+pub mod meta;
+pub mod mmap;
+pub mod notes;
+pub mod plugin;
+pub mod policy;
+pub mod preview;
+pub mod progress;
+pub mod rename;
+pub mod rpc;
+pub mod scaffold;
+pub mod screen;
+pub mod serve;
+pub mod speed;
+pub mod sym;
+pub mod tape;
+pub mod tapeset;
+pub mod template;
+pub mod tsx;
+pub mod verifier;
+pub mod wav;
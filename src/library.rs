@@ -0,0 +1,131 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::hash::Digest;
+use crate::mmap::Mapping;
+use crate::tape::Tape;
+
+/// The fingerprint of a tape of a library
+///
+/// It comprises the digest of the whole tape, and the SHA1 of each of its files.
+#[derive(Debug)]
+pub struct TapeInfo {
+    pub path: PathBuf,
+    pub digest: Digest,
+    pub files: Vec<String>,
+}
+
+impl TapeInfo {
+    /// Compute the fingerprint of the tape stored in the given file.
+    pub fn scan(path: &Path) -> io::Result<TapeInfo> {
+        let bytes = Mapping::open(path)?;
+        let tape = Tape::from_bytes(&bytes);
+        let mut files: Vec<String> = tape.files().map(|f| Digest::of(&f.content()).sha1).collect();
+        files.sort();
+        Ok(TapeInfo {
+            path: path.to_path_buf(),
+            digest: Digest::of(&bytes),
+            files,
+        })
+    }
+
+    /// Returns `true` if this tape contains all the files of the other one (and some more).
+    pub fn is_superset_of(&self, other: &TapeInfo) -> bool {
+        if other.files.is_empty() || self.files.len() <= other.files.len() {
+            return false;
+        }
+        // Both file lists are sorted, so a single pass suffices
+        let mut mine = self.files.iter();
+        other.files.iter().all(|f| mine.any(|m| m == f))
+    }
+}
+
+/// The redundancies found in a library of tapes
+#[derive(Debug, Default, PartialEq)]
+pub struct Redundancies {
+    /// Groups of tapes with identical contents, as indices of the scanned tapes
+    pub duplicates: Vec<Vec<usize>>,
+    /// Pairs of tapes `(a, b)` where `a` contains all the files of `b`, as indices of the
+    /// scanned tapes
+    pub supersets: Vec<(usize, usize)>,
+}
+
+/// Find the redundancies among the given tapes.
+///
+/// Tapes that are exact duplicates are grouped in the order they were given. Only the
+/// first tape of each duplicate group is considered to find supersets.
+pub fn find_redundancies(tapes: &[TapeInfo]) -> Redundancies {
+    let mut groups: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut firsts = vec![];
+    for (i, tape) in tapes.iter().enumerate() {
+        let group = groups.entry(&tape.digest.sha1).or_default();
+        if group.is_empty() {
+            firsts.push(i);
+        }
+        group.push(i);
+    }
+
+    let mut duplicates: Vec<Vec<usize>> = groups.into_values().filter(|g| g.len() > 1).collect();
+    duplicates.sort();
+
+    let mut supersets = vec![];
+    for a in &firsts {
+        for b in &firsts {
+            if tapes[*a].is_superset_of(&tapes[*b]) {
+                supersets.push((*a, *b));
+            }
+        }
+    }
+    Redundancies { duplicates, supersets }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn tape(sha1: &str, files: &[&str]) -> TapeInfo {
+        TapeInfo {
+            path: PathBuf::from(format!("{}.cas", sha1)),
+            digest: Digest { size: 0, crc32: 0, sha1: sha1.to_string() },
+            files: files.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn should_detect_supersets() {
+        assert!(tape("a", &["1", "2", "3"]).is_superset_of(&tape("b", &["1", "3"])));
+        assert!(tape("a", &["1", "1", "2"]).is_superset_of(&tape("b", &["1", "1"])));
+        assert!(!tape("a", &["1", "2"]).is_superset_of(&tape("b", &["1", "2"])));
+        assert!(!tape("a", &["1", "2", "3"]).is_superset_of(&tape("b", &["1", "4"])));
+        assert!(!tape("a", &["1", "2"]).is_superset_of(&tape("b", &["1", "1"])));
+        assert!(!tape("a", &["1", "2"]).is_superset_of(&tape("b", &[])));
+    }
+
+    #[test]
+    fn should_find_redundancies() {
+        let tapes = vec![
+            tape("a", &["1", "2", "3"]),
+            tape("b", &["1", "3"]),
+            tape("a", &["1", "2", "3"]),
+            tape("c", &["4"]),
+            tape("b", &["1", "3"]),
+        ];
+        assert_eq!(
+            Redundancies {
+                duplicates: vec![vec![0, 2], vec![1, 4]],
+                supersets: vec![(0, 1)],
+            },
+            find_redundancies(&tapes)
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// The lowest address the binary files must be loaded at to protect them with `CLEAR`,
+/// leaving room below for the loader itself and the stack of BASIC
+const CLEAR_FROM: usize = 0x8800;
+
+/// The first address of the work area of the system, that BASIC cannot give up
+const SYSTEM_AREA: usize = 0xf380;
+
+/// The addresses of a binary file: its BEGIN, END and START addresses
+pub type Addresses = (usize, usize, usize);
+
+/// Generate an ASCII BASIC program loading the binary files with the given addresses, in turn.
+///
+/// The files are loaded with `BLOAD"CAS:"`, but the last one, loaded with `BLOAD"CAS:",R` to
+/// run it from its START address. The earlier files whose START address lies within the
+/// memory they are loaded at, past their BEGIN address, are run as well, with `DEFUSR` and
+/// `USR`, so their code returns to the loader to load the next file. Those starting at their
+/// BEGIN address are usually data, as graphics are, so they are only loaded. If every file
+/// fits between the BASIC program and the system area, the memory they take is protected
+/// with `CLEAR` beforehand, so loading a file does not overwrite the variables of the program.
+///
+pub fn bload_program(files: &[Addresses]) -> Vec<u8> {
+    let mut lines = vec![];
+    let lowest = files.iter().map(|(begin, _, _)| *begin).min().unwrap_or(0);
+    let highest = files.iter().map(|(_, end, _)| *end).max().unwrap_or(0);
+    if lowest >= CLEAR_FROM && highest < SYSTEM_AREA {
+        lines.push(format!("CLEAR 200,&H{:X}", lowest - 1));
+    }
+    for (i, (begin, end, start)) in files.iter().enumerate() {
+        if i + 1 == files.len() {
+            lines.push("BLOAD\"CAS:\",R".to_string());
+        } else if start != begin && (begin..=end).contains(&start) {
+            lines.push(format!("BLOAD\"CAS:\":DEFUSR=&H{:X}:A=USR(0)", start));
+        } else {
+            lines.push("BLOAD\"CAS:\"".to_string());
+        }
+    }
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| format!("{} {}\r\n", (i + 1) * 10, line))
+        .collect::<String>()
+        .into_bytes()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::tape::{file_name, Tape};
+
+    #[test]
+    fn should_generate_bload_programs() {
+        let program = bload_program(&[(0x9000, 0x9fff, 0x9010), (0xc000, 0xd000, 0xc010)]);
+        assert_eq!(
+            "10 CLEAR 200,&H8FFF\r\n20 BLOAD\"CAS:\":DEFUSR=&H9010:A=USR(0)\r\n30 BLOAD\"CAS:\",R\r\n",
+            String::from_utf8(program).unwrap());
+
+        // Files overlapping the BASIC program cannot be protected
+        let program = bload_program(&[(0x8000, 0x8fff, 0x8000)]);
+        assert_eq!("10 BLOAD\"CAS:\",R\r\n", String::from_utf8(program).unwrap());
+    }
+
+    #[test]
+    fn should_run_the_stages_of_bload_programs() {
+        let program = bload_program(&[(0x9000, 0x90ff, 0x9004), (0xa000, 0xafff, 0), (0xc000, 0xd000, 0xc010)]);
+        assert_eq!(
+            "10 CLEAR 200,&H8FFF\r\n\
+             20 BLOAD\"CAS:\":DEFUSR=&H9004:A=USR(0)\r\n\
+             30 BLOAD\"CAS:\"\r\n\
+             40 BLOAD\"CAS:\",R\r\n",
+            String::from_utf8(program).unwrap());
+
+        // Loaders of many stages take several blocks of the tape
+        let stages: Vec<Addresses> = (0..40).map(|i| (0x9000 + i * 0x100, 0x90ff + i * 0x100, 0x9010 + i * 0x100)).collect();
+        let program = bload_program(&stages);
+        assert!(program.len() > 1024);
+        let mut tape = Tape::new();
+        tape.append_ascii(&file_name("loader").0, &program).unwrap();
+        let files: Vec<_> = tape.files().collect();
+        assert_eq!(program, files[0].content());
+        assert!(String::from_utf8(program).unwrap().ends_with("400 BLOAD\"CAS:\":DEFUSR=&HB610:A=USR(0)\r\n410 BLOAD\"CAS:\",R\r\n"));
+    }
+
+    #[test]
+    fn should_only_load_the_data_stages_of_bload_programs() {
+        let program = bload_program(&[(0x9000, 0x9fff, 0x9000), (0xa000, 0xbfff, 0xa000), (0xc000, 0xd000, 0xc010)]);
+        assert_eq!(
+            "10 CLEAR 200,&H8FFF\r\n\
+             20 BLOAD\"CAS:\"\r\n\
+             30 BLOAD\"CAS:\"\r\n\
+             40 BLOAD\"CAS:\",R\r\n",
+            String::from_utf8(program).unwrap());
+    }
+}
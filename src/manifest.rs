@@ -0,0 +1,150 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::path::PathBuf;
+
+use crate::hash;
+use crate::tape::{self, Entry, HeaderKind, Tape};
+
+/// The version of the format of the manifests written by `mcp`
+pub const VERSION: u32 = 1;
+
+/// Generate the TOML manifest of the given tape, named as given.
+///
+/// The manifest lists the files of the tape in order, with their type, the name stored in
+/// their header, and the path and SHA-1 hash of the file extracted by `mcp extract` into an empty
+/// directory. Together with the extracted files, it reproduces the tape byte for byte:
+///
+/// * The files whose blocks are not the ones `mcp add` writes for them (as headers longer
+///   than usual, unusual padding or trailing garbage) list their `blocks` as they are, in hex.
+/// * The bytes found before the first block are given as `leading`, in hex.
+///
+pub fn generate(tape: &Tape, leading: &[u8], cas_name: &str, version: &str) -> String {
+    let mut manifest = format!("# Manifest of {}, generated by mcp {}\n", string(cas_name), version);
+    manifest += &format!("# Its files are extracted with `mcp extract {}`\n", cas_name);
+    manifest += &format!("version = {}\n", VERSION);
+    if !leading.is_empty() {
+        manifest += &format!("leading = \"{}\"\n", hex(leading));
+    }
+
+    let mut next_custom = 0;
+    let mut reserved: Vec<PathBuf> = vec![];
+    for entry in tape.entries_with(&[]) {
+        let file = entry.file();
+        let path = file.name().unwrap_or_else(|| {
+            next_custom += 1;
+            format!("custom.{:03}", next_custom)
+        });
+        let path = unique_path(PathBuf::from(path), &reserved);
+        reserved.push(path.clone());
+
+        manifest += "\n[[file]]\n";
+        manifest += &format!("type = \"{}\"\n", entry.kind().map(|k| k.name()).unwrap_or("custom"));
+        if let Some(HeaderKind::Other(id)) = entry.kind() {
+            manifest += &format!("id = 0x{:02x}\n", id);
+        }
+        if let Some(name) = entry.header_name() {
+            manifest += &format!("name = {}\n", string(name));
+        }
+        manifest += &format!("path = {}\n", string(&path.to_string_lossy()));
+        manifest += &format!("sha1 = \"{}\"\n", hash::sha1(&file.content()));
+        if !is_rebuilt(&entry) {
+            let blocks: Vec<String> = entry.blocks().iter().map(|b| format!("\"{}\"", hex(b.data_without_prefix()))).collect();
+            manifest += &format!("blocks = [{}]\n", blocks.join(", "));
+        }
+    }
+    manifest
+}
+
+/// Returns `true` if adding the extracted file to a tape writes the same blocks of the entry.
+fn is_rebuilt(entry: &Entry) -> bool {
+    let content = entry.file().content();
+    let name = tape::file_name(entry.header_name().unwrap_or_default()).0;
+    let mut tape = Tape::new();
+    let appended = match entry.kind() {
+        Some(HeaderKind::Bin) => tape.append_bin(&name, &content),
+        Some(HeaderKind::Basic) => tape.append_basic(&name, &content),
+        Some(HeaderKind::Ascii) => tape.append_ascii(&name, &content),
+        Some(HeaderKind::Other(_)) => return false,
+        None => tape.append_custom(&content),
+    };
+    appended.is_ok()
+        && tape.blocks().len() == entry.blocks().len()
+        && tape.blocks().iter().zip(entry.blocks()).all(|(a, b)| a.data_without_prefix() == b.data_without_prefix())
+}
+
+/// Returns the given path, or the first of `<stem>-1.<ext>`, `<stem>-2.<ext>`... that is not
+/// reserved, as `mcp extract` names clashing files.
+fn unique_path(path: PathBuf, reserved: &[PathBuf]) -> PathBuf {
+    if !reserved.contains(&path) {
+        return path;
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..)
+        .map(|i| PathBuf::from(format!("{}-{}{}", stem, i, ext)))
+        .find(|p| !reserved.contains(p))
+        .unwrap()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Returns the given text as a TOML basic string.
+fn string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            c if c.is_control() => quoted += &format!("\\u{:04X}", c as u32),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::tape::file_name;
+
+    #[test]
+    fn should_generate_manifests() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("game").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_custom(&[0x01; 8]).unwrap();
+        tape.append_bin(&file_name("game").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        let manifest = generate(&tape, &[], "game.cas", "1.0");
+        assert_eq!(
+            format!(
+                "# Manifest of \"game.cas\", generated by mcp 1.0\n\
+                 # Its files are extracted with `mcp extract game.cas`\n\
+                 version = 1\n\
+                 \n[[file]]\ntype = \"bin\"\nname = \"game\"\npath = \"game.bin\"\nsha1 = \"{0}\"\n\
+                 \n[[file]]\ntype = \"custom\"\npath = \"custom.001\"\nsha1 = \"{1}\"\n\
+                 \n[[file]]\ntype = \"bin\"\nname = \"game\"\npath = \"game-1.bin\"\nsha1 = \"{0}\"\n",
+                hash::sha1(&[0xfe, 0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]),
+                hash::sha1(&[0x01; 8])),
+            manifest);
+    }
+
+    #[test]
+    fn should_keep_the_blocks_not_rebuilt() {
+        let mut tape = Tape::new();
+        // A header with extra bytes after the name
+        tape.append_raw_block(&[0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, b'G', b'A', b'M', b'E', b' ', b' ', 0xff, 0xff]);
+        tape.append_raw_block(&[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]);
+        let manifest = generate(&tape, &[0x00, 0x01], "game.cas", "1.0");
+        assert!(manifest.contains("version = 1\nleading = \"0001\"\n"));
+        assert!(manifest.contains("blocks = [\"d0d0d0d0d0d0d0d0d0d047414d452020ffff000000000000\", \"008001800080c9c9\"]\n"));
+    }
+}
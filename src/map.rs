@@ -0,0 +1,249 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::tape::{Block, HeaderKind, Tape};
+use crate::wav::Exporter;
+
+/// The characters used to label the files in the map, in order
+const LABELS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// A block of a tape placed in the map
+#[derive(Debug, PartialEq)]
+pub struct Segment {
+    /// The index of the block in the tape
+    pub block: usize,
+    /// The index of the file the block belongs to
+    pub file: usize,
+    /// The type of the file the block belongs to: `bin`, `basic`, `ascii` or `custom`
+    pub kind: &'static str,
+    /// The name of the file the block belongs to, if any
+    pub name: Option<String>,
+    /// The position of the block in the tape, in bytes
+    pub offset: usize,
+    /// The size of the block in the tape, in bytes (including its prefix)
+    pub size: usize,
+    /// The position of the block in the audio, in samples
+    pub start: usize,
+    /// The samples taken by the silence, the header and the data of the block
+    pub samples: (usize, usize, usize),
+}
+
+impl Segment {
+    /// Returns the total number of samples taken by the block.
+    pub fn len(&self) -> usize {
+        self.samples.0 + self.samples.1 + self.samples.2
+    }
+
+    /// Returns whether the block takes no samples at all.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A map of a tape, placing each of its blocks in the tape and in the audio timeline
+#[derive(Debug)]
+pub struct Map {
+    pub segments: Vec<Segment>,
+    pub sample_rate: u32,
+}
+
+impl Map {
+    /// Build the map of the given tape, as it would be exported by the given exporter.
+    pub fn of(tape: &Tape, exporter: &Exporter) -> Map {
+        let mut segments = vec![];
+        let mut offset = 0;
+        let mut start = 0;
+        for (block, (file, kind, name)) in tape.blocks().iter().zip(file_of_blocks(tape.blocks())) {
+            let samples = exporter.block_samples(block.is_file_header(), block.data_without_prefix().len());
+            let segment = Segment {
+                block: segments.len(),
+                file,
+                kind,
+                name,
+                offset,
+                size: block.data().len(),
+                start,
+                samples,
+            };
+            offset += segment.size;
+            start += segment.len();
+            segments.push(segment);
+        }
+        Map { segments, sample_rate: exporter.sample_rate() }
+    }
+
+    /// Returns the size of the tape, in bytes.
+    pub fn bytes(&self) -> usize {
+        self.segments.last().map(|s| s.offset + s.size).unwrap_or(0)
+    }
+
+    /// Returns the length of the audio, in samples.
+    pub fn samples(&self) -> usize {
+        self.segments.last().map(|s| s.start + s.len()).unwrap_or(0)
+    }
+
+    /// Returns the given number of samples in seconds.
+    pub fn seconds(&self, samples: usize) -> f64 {
+        samples as f64 / self.sample_rate as f64
+    }
+
+    /// Draw a bar of the given width with the place of each file in the tape.
+    ///
+    /// Each file is drawn using its label (see `label()`).
+    pub fn tape_bar(&self, width: usize) -> String {
+        let marks: Vec<(usize, char)> = self
+            .segments
+            .iter()
+            .map(|s| (s.offset, label(s.file)))
+            .collect();
+        bar(&marks, self.bytes(), width)
+    }
+
+    /// Draw a bar of the given width with the place of each file in the audio timeline.
+    ///
+    /// Silences are drawn as `.`, headers (pilot tones) as `-` and the data of each file
+    /// using its label (see `label()`).
+    pub fn audio_bar(&self, width: usize) -> String {
+        let mut marks = vec![];
+        for s in &self.segments {
+            marks.push((s.start, '.'));
+            marks.push((s.start + s.samples.0, '-'));
+            marks.push((s.start + s.samples.0 + s.samples.1, label(s.file)));
+        }
+        bar(&marks, self.samples(), width)
+    }
+}
+
+/// Returns the label used to draw the given file in the bars of a map.
+pub fn label(file: usize) -> char {
+    LABELS.get(file).map(|c| *c as char).unwrap_or('#')
+}
+
+/// Draw a bar of `width` characters representing `total` units. Each mark is the position
+/// where a character starts to be drawn, in ascending order.
+///
+/// Each column shows the character at its middle, but file labels take precedence over
+/// silences and headers, so short data blocks are never hidden by their long pilot tones.
+fn bar(marks: &[(usize, char)], total: usize, width: usize) -> String {
+    if total == 0 {
+        return " ".repeat(width);
+    }
+    let ends = marks.iter().skip(1).map(|(from, _)| *from).chain(Some(total));
+    let spans: Vec<(usize, usize, char)> = marks
+        .iter()
+        .zip(ends)
+        .map(|((from, c), to)| (*from, to, *c))
+        .filter(|(from, to, _)| from < to)
+        .collect();
+    (0..width)
+        .map(|col| {
+            let (lo, hi) = (col * total / width, (col + 1) * total / width);
+            let mid = (2 * col + 1) * total / (2 * width);
+            let active = |(from, to, _): &&(usize, usize, char)| *from < hi.max(lo + 1) && *to > lo;
+            let at_mid = spans.iter().find(|(from, to, _)| *from <= mid && mid < *to);
+            match at_mid {
+                Some((_, _, c)) if *c != '.' && *c != '-' => *c,
+                _ => spans
+                    .iter()
+                    .filter(active)
+                    .map(|(_, _, c)| *c)
+                    .find(|c| *c != '.' && *c != '-')
+                    .or_else(|| at_mid.map(|(_, _, c)| *c))
+                    .unwrap_or(' '),
+            }
+        })
+        .collect()
+}
+
+/// Returns the file index, type and name of each block, grouping them as files the same
+/// way `Tape::files()` does.
+///
+/// Unlike `Tape::files()`, incomplete files are tolerated.
+pub fn file_of_blocks(blocks: &[Block]) -> Vec<(usize, &'static str, Option<String>)> {
+    let mut result = vec![];
+    let mut file = 0;
+    let mut i = 0;
+    while i < blocks.len() {
+        let header = &blocks[i];
+        let name = header.file_name().map(|n| n.to_string());
+        let (kind, len) = match header.header_kind() {
+            Some(kind @ HeaderKind::Bin) | Some(kind @ HeaderKind::Basic) | Some(kind @ HeaderKind::Other(_)) => {
+                (kind.name(), 2)
+            }
+            Some(HeaderKind::Ascii) => {
+                // Data blocks up to the one with the EOF byte, or the next header if truncated
+                let chunks = blocks[i + 1..].iter().take_while(|b| !b.is_file_header());
+                let mut data = 0;
+                for chunk in chunks {
+                    data += 1;
+                    if chunk.data_without_prefix().contains(&0x1a) {
+                        break;
+                    }
+                }
+                (HeaderKind::Ascii.name(), 1 + data)
+            }
+            None => ("custom", 1),
+        };
+        for _ in 0..len.min(blocks.len() - i) {
+            result.push((file, kind, name.clone()));
+        }
+        i += len;
+        file += 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::tape::file_name;
+
+    use super::*;
+
+    fn sample_tape() -> Tape {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("foo").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_ascii(&file_name("bar").0, b"10 PRINT\r\n").unwrap();
+        tape.append_custom(&[0x01, 0x02]).unwrap();
+        tape
+    }
+
+    #[test]
+    fn should_group_blocks_in_files() {
+        let map = Map::of(&sample_tape(), &Exporter::new());
+        let files: Vec<(usize, &str)> = map.segments.iter().map(|s| (s.file, s.kind)).collect();
+        assert_eq!(
+            vec![(0, "bin"), (0, "bin"), (1, "ascii"), (1, "ascii"), (2, "custom")],
+            files);
+        assert_eq!(Some("foo".to_string()), map.segments[1].name);
+    }
+
+    #[test]
+    fn should_place_blocks() {
+        let map = Map::of(&sample_tape(), &Exporter::new());
+        assert_eq!(24, map.segments[1].offset);
+        assert_eq!(map.segments[0].len(), map.segments[1].start);
+        assert_eq!(map.bytes(), map.segments.iter().map(|s| s.size).sum::<usize>());
+        assert_eq!(map.samples(), map.segments.iter().map(|s| s.len()).sum::<usize>());
+    }
+
+    #[test]
+    fn should_draw_bars() {
+        assert_eq!("AAABBBBBBC", bar(&[(0, 'A'), (3, 'B'), (9, 'C')], 10, 10));
+        assert_eq!("AB", bar(&[(0, 'A'), (3, 'B'), (9, 'C')], 10, 2));
+        assert_eq!("   ", bar(&[], 0, 3));
+        assert_eq!("..A-", bar(&[(0, '.'), (5, 'A'), (6, '-')], 8, 4));
+    }
+
+    #[test]
+    fn should_label_files() {
+        assert_eq!('A', label(0));
+        assert_eq!('a', label(26));
+        assert_eq!('#', label(1000));
+    }
+}
@@ -0,0 +1,168 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::file;
+
+/// The keys of the metadata fields, in the order they are displayed
+pub const KEYS: [&str; 5] = ["title", "publisher", "year", "dumper", "notes"];
+
+/// Descriptive metadata of a tape
+///
+/// The metadata is stored in a JSON sidecar file next to the CAS file (e.g.,
+/// `game.cas.meta.json` for `game.cas`), so the provenance of a dump is kept without
+/// altering the CAS bytes.
+///
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dumper: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+impl Metadata {
+    /// Returns the path of the sidecar file for the given CAS file.
+    pub fn sidecar_path(cas_path: &Path) -> PathBuf {
+        let mut name = cas_path.file_name().unwrap_or_default().to_os_string();
+        name.push(".meta.json");
+        cas_path.with_file_name(name)
+    }
+
+    /// Load the metadata of the given CAS file.
+    ///
+    /// If the CAS file has no sidecar file, empty metadata is returned.
+    pub fn load(cas_path: &Path) -> io::Result<Metadata> {
+        let path = Metadata::sidecar_path(cas_path);
+        if !file::exists(&path) {
+            return Ok(Metadata::default());
+        }
+        let content = file::read_content(&path)?;
+        serde_json::from_slice(&content).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                tr!("invalid metadata file {}: {}", format!("{:?}", path), e),
+            )
+        })
+    }
+
+    /// Save the metadata of the given CAS file into its sidecar file.
+    pub fn save(&self, cas_path: &Path) -> io::Result<()> {
+        let mut content = serde_json::to_vec_pretty(self)?;
+        content.push(b'\n');
+        file::write_content(&Metadata::sidecar_path(cas_path), &content)
+    }
+
+    /// Returns `true` if no field is set.
+    pub fn is_empty(&self) -> bool {
+        self.fields().is_empty()
+    }
+
+    /// Returns the fields that are set as `(key, value)` pairs.
+    pub fn fields(&self) -> Vec<(&'static str, &str)> {
+        KEYS.iter()
+            .filter_map(|k| self.field(k).and_then(|f| f.as_deref()).map(|v| (*k, v)))
+            .collect()
+    }
+
+    /// Returns the value of the field with the given key.
+    pub fn get(&self, key: &str) -> io::Result<Option<&str>> {
+        self.field(key)
+            .map(|f| f.as_deref())
+            .ok_or_else(|| unknown_key(key))
+    }
+
+    /// Set the value of the field with the given key. An empty value unsets the field.
+    pub fn set(&mut self, key: &str, value: &str) -> io::Result<()> {
+        let field = match key {
+            "title" => &mut self.title,
+            "publisher" => &mut self.publisher,
+            "year" => &mut self.year,
+            "dumper" => &mut self.dumper,
+            "notes" => &mut self.notes,
+            _ => return Err(unknown_key(key)),
+        };
+        *field = if value.is_empty() { None } else { Some(value.to_string()) };
+        Ok(())
+    }
+
+    fn field(&self, key: &str) -> Option<&Option<String>> {
+        match key {
+            "title" => Some(&self.title),
+            "publisher" => Some(&self.publisher),
+            "year" => Some(&self.year),
+            "dumper" => Some(&self.dumper),
+            "notes" => Some(&self.notes),
+            _ => None,
+        }
+    }
+}
+
+fn unknown_key(key: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        tr!("unknown metadata key {} (expected one of {})", format!("{:?}", key), KEYS.join(", ")),
+    )
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::{Path, PathBuf};
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn should_compute_sidecar_path() {
+        assert_eq!(
+            PathBuf::from("/path/to/game.cas.meta.json"),
+            Metadata::sidecar_path(Path::new("/path/to/game.cas")));
+    }
+
+    #[test]
+    fn should_set_and_get_fields() {
+        let mut meta = Metadata::default();
+        assert!(meta.is_empty());
+        meta.set("title", "Arkanoid").unwrap();
+        meta.set("year", "1986").unwrap();
+        assert_eq!(Some("Arkanoid"), meta.get("title").unwrap());
+        assert_eq!(None, meta.get("publisher").unwrap());
+        assert_eq!(vec![("title", "Arkanoid"), ("year", "1986")], meta.fields());
+        meta.set("title", "").unwrap();
+        assert_eq!(None, meta.get("title").unwrap());
+    }
+
+    #[test]
+    fn should_reject_unknown_keys() {
+        let mut meta = Metadata::default();
+        assert!(meta.get("author").is_err());
+        assert!(meta.set("author", "Foo").is_err());
+    }
+
+    #[test]
+    fn should_save_and_load() {
+        let temp = TempDir::new("mcp").unwrap();
+        let cas_path = temp.path().join("game.cas");
+        assert_eq!(Metadata::default(), Metadata::load(&cas_path).unwrap());
+
+        let mut meta = Metadata::default();
+        meta.set("publisher", "Imagine").unwrap();
+        meta.save(&cas_path).unwrap();
+        assert_eq!(meta, Metadata::load(&cas_path).unwrap());
+    }
+}
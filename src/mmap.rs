@@ -0,0 +1,126 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fs;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+/// The read-only contents of a whole file, mapped into memory
+///
+/// Big tapes are parsed straight from the mapping, without copying them into memory first.
+/// The file must not be truncated while it is mapped. On platforms other than Unix, the file
+/// is read into memory instead.
+///
+pub struct Mapping {
+    #[cfg(unix)]
+    ptr: *mut libc::c_void,
+    #[cfg(unix)]
+    len: usize,
+    #[cfg(not(unix))]
+    data: Vec<u8>,
+}
+
+impl Mapping {
+    /// Map the contents of the given file.
+    pub fn open(path: &Path) -> io::Result<Mapping> {
+        let file = fs::File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+        Mapping::of(file, len)
+    }
+
+    #[cfg(unix)]
+    fn of(file: fs::File, len: usize) -> io::Result<Mapping> {
+        use std::os::unix::io::AsRawFd;
+
+        // Empty files cannot be mapped
+        if len == 0 {
+            return Ok(Mapping { ptr: std::ptr::null_mut(), len });
+        }
+        // The mapping stays valid after the file is closed
+        let ptr = unsafe {
+            libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping { ptr, len })
+    }
+
+    #[cfg(not(unix))]
+    fn of(mut file: fs::File, len: usize) -> io::Result<Mapping> {
+        use std::io::Read;
+
+        let mut data = Vec::with_capacity(len);
+        file.read_to_end(&mut data)?;
+        Ok(Mapping { data })
+    }
+}
+
+impl Deref for Mapping {
+    type Target = [u8];
+
+    #[cfg(unix)]
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl AsRef<[u8]> for Mapping {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+// The mapping is read-only, so it can be shared between threads
+#[cfg(unix)]
+unsafe impl Send for Mapping {}
+#[cfg(unix)]
+unsafe impl Sync for Mapping {}
+
+#[cfg(unix)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe {
+                libc::munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use tempdir::TempDir;
+
+    use crate::file;
+
+    use super::*;
+
+    #[test]
+    fn should_map_files() {
+        let temp = TempDir::new("mcp").unwrap();
+        let path = temp.path().join("tape.cas");
+        file::write_content(&path, &[0x1f, 0xa6, 0xde, 0xba, 0xcc, 0x13, 0x7d, 0x74]).unwrap();
+        assert_eq!(&[0x1f, 0xa6, 0xde, 0xba, 0xcc, 0x13, 0x7d, 0x74], &*Mapping::open(&path).unwrap());
+
+        file::write_content(&path, &[]).unwrap();
+        assert!(Mapping::open(&path).unwrap().is_empty());
+        assert!(Mapping::open(&temp.path().join("missing.cas")).is_err());
+    }
+}
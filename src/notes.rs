@@ -0,0 +1,122 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::file;
+
+/// The version of the format of the notes files written by `mcp`
+pub const VERSION: u32 = 1;
+
+/// Free-form notes attached to the files of a tape
+///
+/// The notes are stored in a JSON sidecar file next to the CAS file (e.g.,
+/// `game.cas.notes.json` for `game.cas`), keyed by the names of the files as stored in their
+/// headers. The sidecar file has a version, so newer formats are not misread by older
+/// versions of `mcp`.
+///
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Notes {
+    version: u32,
+    #[serde(default)]
+    notes: BTreeMap<String, String>,
+}
+
+impl Default for Notes {
+    fn default() -> Notes {
+        Notes { version: VERSION, notes: BTreeMap::new() }
+    }
+}
+
+impl Notes {
+    /// Returns the path of the sidecar file for the given CAS file.
+    pub fn sidecar_path(cas_path: &Path) -> PathBuf {
+        let mut name = cas_path.file_name().unwrap_or_default().to_os_string();
+        name.push(".notes.json");
+        cas_path.with_file_name(name)
+    }
+
+    /// Load the notes of the given CAS file.
+    ///
+    /// If the CAS file has no sidecar file, no notes are returned.
+    pub fn load(cas_path: &Path) -> io::Result<Notes> {
+        let path = Notes::sidecar_path(cas_path);
+        if !file::exists(&path) {
+            return Ok(Notes::default());
+        }
+        let content = file::read_content(&path)?;
+        let notes: Notes = serde_json::from_slice(&content).map_err(|e| invalid_notes(&path, &e.to_string()))?;
+        if notes.version > VERSION {
+            return Err(invalid_notes(&path, &tr!("unsupported version {}, upgrade mcp to read it", notes.version)));
+        }
+        Ok(Notes { version: VERSION, ..notes })
+    }
+
+    /// Save the notes of the given CAS file into its sidecar file.
+    pub fn save(&self, cas_path: &Path) -> io::Result<()> {
+        let mut content = serde_json::to_vec_pretty(self)?;
+        content.push(b'\n');
+        file::write_content(&Notes::sidecar_path(cas_path), &content)
+    }
+
+    /// Returns the note of the file with the given name, if any.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.notes.get(name).map(|n| n.as_str())
+    }
+
+    /// Set the note of the file with the given name. An empty note removes it.
+    pub fn set(&mut self, name: &str, note: &str) {
+        if note.is_empty() {
+            self.notes.remove(name);
+        } else {
+            self.notes.insert(name.to_string(), note.to_string());
+        }
+    }
+}
+
+fn invalid_notes(path: &Path, msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        tr!("invalid notes file {}: {}", format!("{:?}", path), msg),
+    )
+}
+
+#[cfg(test)]
+mod test {
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn should_set_and_get_notes() {
+        let mut notes = Notes::default();
+        notes.set("STAGE2", "loads at 0x9000");
+        assert_eq!(Some("loads at 0x9000"), notes.get("STAGE2"));
+        assert_eq!(None, notes.get("STAGE1"));
+        notes.set("STAGE2", "");
+        assert_eq!(None, notes.get("STAGE2"));
+    }
+
+    #[test]
+    fn should_save_and_load() {
+        let temp = TempDir::new("mcp").unwrap();
+        let cas_path = temp.path().join("game.cas");
+        assert_eq!(Notes::default(), Notes::load(&cas_path).unwrap());
+
+        let mut notes = Notes::default();
+        notes.set("STAGE2", "bank-switched");
+        notes.save(&cas_path).unwrap();
+        assert_eq!(notes, Notes::load(&cas_path).unwrap());
+
+        file::write_content(&Notes::sidecar_path(&cas_path), br#"{"version": 2, "notes": {}}"#).unwrap();
+        assert!(Notes::load(&cas_path).is_err());
+    }
+}
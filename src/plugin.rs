@@ -0,0 +1,197 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::env;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::file;
+
+/// The variable with the directory where the manifests of the plugins are found
+pub const PLUGINS_VAR: &str = "MCP_PLUGINS";
+
+/// The manifest of a plugin, as stored in its JSON file
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    name: String,
+    signature: String,
+    extension: String,
+    command: Vec<String>,
+}
+
+/// An external handler of custom blocks starting with a given signature
+///
+/// Plugins teach `mcp` the proprietary formats of some loaders. A plugin is a program that
+/// is run with one of these operations as its last argument, reading its input from the
+/// standard input and writing its result into the standard output:
+///
+/// * `name`, read the data of a custom block and write the name of the file it holds
+/// * `extract`, read the data of a custom block and write the file it holds
+/// * `pack`, read a file and write the data of the custom block that holds it
+///
+/// The program fails with a non-zero status if the operation cannot be done. Plugins are
+/// described by JSON manifests with their `name`, the `signature` of their blocks (the first
+/// bytes of their data, in hex), the `extension` of their files and the `command` to run.
+///
+#[derive(Debug, PartialEq)]
+pub struct Plugin {
+    pub name: String,
+    pub signature: Vec<u8>,
+    pub extension: String,
+    command: Vec<String>,
+}
+
+impl Plugin {
+    /// Load the plugin described by the given manifest.
+    pub fn load(path: &Path) -> io::Result<Plugin> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, tr!("plugin {}: {}", format!("{:?}", path), e));
+        let manifest: Manifest = serde_json::from_slice(&file::read_content(path)?).map_err(|e| invalid(e.to_string()))?;
+        let signature = (0..manifest.signature.len())
+            .step_by(2)
+            .map(|i| manifest.signature.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| invalid(tr!("invalid signature {}", manifest.signature)))?;
+        if manifest.command.is_empty() {
+            return Err(invalid(tr!("missing command")));
+        }
+        Ok(Plugin {
+            name: manifest.name,
+            signature,
+            extension: manifest.extension.to_lowercase(),
+            command: manifest.command,
+        })
+    }
+
+    /// Returns the name of the file held by the given custom block, without extension.
+    pub fn name_of(&self, data: &[u8]) -> io::Result<String> {
+        Ok(String::from_utf8_lossy(&self.run("name", data)?).trim().to_string())
+    }
+
+    /// Returns the file held by the given custom block.
+    pub fn extract(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        self.run("extract", data)
+    }
+
+    /// Returns the data of the custom block that holds the given file.
+    pub fn pack(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        self.run("pack", data)
+    }
+
+    fn run(&self, operation: &str, input: &[u8]) -> io::Result<Vec<u8>> {
+        let mut child = Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .arg(operation)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("the input of the plugin is piped");
+        // The input is written from another thread, so a plugin writing its output before
+        // reading all its input does not block
+        let output = thread::scope(|s| {
+            s.spawn(move || stdin.write_all(input));
+            child.wait_with_output()
+        })?;
+        if !output.status.success() {
+            return Err(io::Error::other(tr!("plugin {} failed to {} ({})", self.name, operation, output.status)));
+        }
+        Ok(output.stdout)
+    }
+}
+
+/// The plugins registered in `mcp`
+#[derive(Debug, Default)]
+pub struct Plugins {
+    plugins: Vec<Plugin>,
+}
+
+impl Plugins {
+    /// Load the plugins of the directory given by the `MCP_PLUGINS` variable, if any.
+    pub fn from_env() -> io::Result<Plugins> {
+        match env::var_os(PLUGINS_VAR) {
+            Some(dir) if !dir.is_empty() => Plugins::load_dir(&PathBuf::from(dir)),
+            _ => Ok(Plugins::default()),
+        }
+    }
+
+    /// Load the plugins described by the JSON manifests of the given directory.
+    pub fn load_dir(dir: &Path) -> io::Result<Plugins> {
+        let manifests = file::list_dir(dir, |p| p.extension().is_some_and(|e| e == "json"))?;
+        let plugins = manifests.iter().map(|m| Plugin::load(m)).collect::<io::Result<_>>()?;
+        Ok(Plugins { plugins })
+    }
+
+    /// Returns the plugin handling the custom block with the given data, if any.
+    pub fn for_block(&self, data: &[u8]) -> Option<&Plugin> {
+        self.plugins.iter().find(|p| data.starts_with(&p.signature))
+    }
+
+    /// Returns the plugin handling the files of the given path, by its extension, if any.
+    pub fn for_file(&self, path: &Path) -> Option<&Plugin> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        self.plugins.iter().find(|p| p.extension == ext)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    /// A plugin whose blocks are `TT` followed by a name of six bytes and the file
+    fn sample_plugins(temp: &TempDir) -> Plugins {
+        let script = temp.path().join("turbo.sh");
+        file::write_content(&script, br#"
+case "$1" in
+    name) head -c 8 | tail -c 6 ;;
+    extract) tail -c +9 ;;
+    pack) printf 'TTPACKED'; cat ;;
+    *) exit 1 ;;
+esac
+"#).unwrap();
+        let manifest = serde_json::json!({
+            "name": "turbo",
+            "signature": "5454",
+            "extension": "TRB",
+            "command": ["sh", script],
+        });
+        file::write_content(&temp.path().join("turbo.json"), manifest.to_string().as_bytes()).unwrap();
+        Plugins::load_dir(temp.path()).unwrap()
+    }
+
+    #[test]
+    fn should_run_plugins() {
+        let temp = TempDir::new("mcp").unwrap();
+        let plugins = sample_plugins(&temp);
+        let block = b"TTGAME  \x01\x02\x03";
+        let plugin = plugins.for_block(block).unwrap();
+        assert_eq!("turbo", plugin.name);
+        assert!(plugins.for_block(b"XXGAME  ").is_none());
+        assert_eq!("GAME", plugin.name_of(block).unwrap());
+        assert_eq!(vec![0x01, 0x02, 0x03], plugin.extract(block).unwrap());
+
+        let plugin = plugins.for_file(Path::new("level.trb")).unwrap();
+        assert_eq!(b"TTPACKED\x01\x02".to_vec(), plugin.pack(&[0x01, 0x02]).unwrap());
+        assert!(plugins.for_file(Path::new("level.bin")).is_none());
+    }
+
+    #[test]
+    fn should_reject_invalid_manifests() {
+        let temp = TempDir::new("mcp").unwrap();
+        let path = temp.path().join("broken.json");
+        file::write_content(&path, br#"{"name": "x", "signature": "5G", "extension": "x", "command": ["x"]}"#).unwrap();
+        assert!(Plugin::load(&path).is_err());
+        file::write_content(&path, br#"{"name": "x", "signature": "54", "extension": "x", "command": []}"#).unwrap();
+        assert!(Plugin::load(&path).is_err());
+    }
+}
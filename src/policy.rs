@@ -0,0 +1,305 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io;
+use std::path::Path;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::file;
+use crate::tape;
+
+/// A check applied to the files added to a tape
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// The BEGIN, END and START addresses of binary files
+    BinHeader,
+    /// The structure of tokenized Basic programs
+    Basic,
+    /// The length and characters of file names
+    Name,
+    /// The alignment of file contents with the 8-byte boundaries of the tape
+    Alignment,
+}
+
+impl Check {
+    /// Returns the check with the given name: `bin-header`, `basic`, `name` or `alignment`.
+    pub fn from_name(name: &str) -> Option<Check> {
+        match name {
+            "bin-header" => Some(Check::BinHeader),
+            "basic" => Some(Check::Basic),
+            "name" => Some(Check::Name),
+            "alignment" => Some(Check::Alignment),
+            _ => None,
+        }
+    }
+}
+
+/// How a check is enforced
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    /// The check is not applied
+    Off,
+    /// The files that fail the check are added with a warning
+    Warn,
+    /// The files that fail the check are rejected
+    Strict,
+}
+
+impl Level {
+    /// Returns the level with the given name: `off`, `warn` or `strict`.
+    pub fn from_name(name: &str) -> Option<Level> {
+        match name {
+            "off" => Some(Level::Off),
+            "warn" => Some(Level::Warn),
+            "strict" => Some(Level::Strict),
+            _ => None,
+        }
+    }
+}
+
+/// A check failed by a file
+#[derive(Debug, PartialEq)]
+pub struct Violation {
+    pub check: Check,
+    pub message: String,
+}
+
+impl Violation {
+    fn new(check: Check, message: String) -> Violation {
+        Violation { check, message }
+    }
+}
+
+/// The level at which each check is enforced on the files added to a tape
+///
+/// By default, malformed binary and Basic files are rejected, while truncated names and
+/// unaligned contents are only warned about.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Policy {
+    bin_header: Level,
+    basic: Level,
+    name: Level,
+    alignment: Level,
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Policy { bin_header: Level::Strict, basic: Level::Strict, name: Level::Warn, alignment: Level::Warn }
+    }
+}
+
+impl Policy {
+    /// Returns the level of the given check.
+    pub fn level(&self, check: Check) -> Level {
+        match check {
+            Check::BinHeader => self.bin_header,
+            Check::Basic => self.basic,
+            Check::Name => self.name,
+            Check::Alignment => self.alignment,
+        }
+    }
+
+    /// Set the level of the given check.
+    pub fn set(&mut self, check: Check, level: Level) {
+        match check {
+            Check::BinHeader => self.bin_header = level,
+            Check::Basic => self.basic = level,
+            Check::Name => self.name = level,
+            Check::Alignment => self.alignment = level,
+        }
+    }
+
+    /// Apply a rule given as `<level>` for every check or `<check>=<level>` for one check
+    /// (e.g., `strict` or `name=off`). Returns `false` if the rule is not valid.
+    pub fn apply(&mut self, rule: &str) -> bool {
+        match rule.split_once('=') {
+            Some((check, level)) => match (Check::from_name(check), Level::from_name(level)) {
+                (Some(check), Some(level)) => {
+                    self.set(check, level);
+                    true
+                }
+                _ => false,
+            },
+            None => match Level::from_name(rule) {
+                Some(level) => {
+                    *self = Policy { bin_header: level, basic: level, name: level, alignment: level };
+                    true
+                }
+                None => false,
+            },
+        }
+    }
+
+    /// Enforce this policy on the given violations.
+    ///
+    /// Fails with the first violation of a strict check, and otherwise returns the
+    /// violations to warn about.
+    pub fn enforce(&self, violations: Vec<Violation>) -> io::Result<Vec<Violation>> {
+        let mut warnings = vec![];
+        for violation in violations {
+            match self.level(violation.check) {
+                Level::Off => {}
+                Level::Warn => warnings.push(violation),
+                Level::Strict => return Err(io::Error::new(io::ErrorKind::InvalidInput, violation.message)),
+            }
+        }
+        Ok(warnings)
+    }
+}
+
+/// Check the given file to be added to a tape with the given content, applying the checks
+/// of its type: binary (`.bin`), Basic (`.bas`), ASCII (`.asc`) or custom file.
+pub fn check_file(path: &Path, data: &[u8]) -> io::Result<Vec<Violation>> {
+    let violations = if file::is_bin_file(path) {
+        let bytes = tape::without_id_byte(data, 0xfe);
+        vec![check_name(file::stem_of(path)?), check_bin(bytes), check_alignment(bytes.len())]
+    } else if file::is_ascii_file(path) {
+        // ASCII files are always padded with EOF bytes, so their length does not matter
+        vec![check_name(file::stem_of(path)?)]
+    } else if file::is_basic_file(path) {
+        let bytes = tape::without_id_byte(data, 0xff);
+        vec![check_name(file::stem_of(path)?), check_basic(bytes), check_alignment(bytes.len())]
+    } else {
+        vec![check_alignment(data.len())]
+    };
+    Ok(violations.into_iter().flatten().collect())
+}
+
+/// Check the header of the given binary file (without its `0xfe` ID byte).
+pub fn check_bin(data: &[u8]) -> Vec<Violation> {
+    let violation = |message| vec![Violation::new(Check::BinHeader, message)];
+    if data.len() < 6 {
+        return violation(tr!("invalid binary file: it is too short ({} bytes) to even have a header", data.len()));
+    }
+
+    let begin = LittleEndian::read_u16(&data[0..2]) as usize;
+    let end = LittleEndian::read_u16(&data[2..4]) as usize;
+    let start = LittleEndian::read_u16(&data[4..6]) as usize;
+    if begin > end {
+        return violation(tr!(
+            "invalid binary file header: BEGIN address {} must be below END address {}",
+            format!("{:X}", begin),
+            format!("{:X}", end),
+        ));
+    }
+    let len = (end - begin) + 1;
+    let prog_size = data.len() - 6;
+    if len > prog_size {
+        return violation(tr!(
+            "invalid binary file header: BEGIN and END addresses reveal a length ({} bytes) larger than program size ({} bytes)",
+            len,
+            prog_size,
+        ));
+    }
+    if start < begin || end < start {
+        return violation(tr!(
+            "invalid binary file header: START address {} is not between BEGIN address {} and END address {}",
+            format!("{:X}", start),
+            format!("{:X}", begin),
+            format!("{:X}", end),
+        ));
+    }
+    vec![]
+}
+
+/// Check the structure of the given tokenized Basic program (without its `0xff` ID byte).
+pub fn check_basic(data: &[u8]) -> Vec<Violation> {
+    if data.len() < 2 {
+        return vec![Violation::new(
+            Check::Basic,
+            tr!("invalid basic file: it is too short ({} bytes) to contain a basic program", data.len()),
+        )];
+    }
+    vec![]
+}
+
+/// Check the given file name, which is truncated to six characters when it is added.
+pub fn check_name(name: &str) -> Vec<Violation> {
+    let mut violations = vec![];
+    if !name.bytes().all(|b| (0x20..0x7f).contains(&b)) {
+        violations.push(Violation::new(
+            Check::Name,
+            tr!("file name {} has characters other than printable ASCII", format!("{:?}", name)),
+        ));
+    }
+    if name.len() > 6 {
+        let (truncated, _) = crate::tape::file_name(name);
+        violations.push(Violation::new(
+            Check::Name,
+            tr!("file name truncated to {}", String::from_utf8_lossy(&truncated)),
+        ));
+    }
+    violations
+}
+
+/// Check that contents of the given length need no padding to be aligned with the 8-byte
+/// boundaries of the tape.
+pub fn check_alignment(len: usize) -> Vec<Violation> {
+    if !len.is_multiple_of(8) {
+        return vec![Violation::new(
+            Check::Alignment,
+            tr!("length ({} bytes) is not a multiple of 8 and requires padding", len),
+        )];
+    }
+    vec![]
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_check_files() {
+        assert!(check_bin(&[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).is_empty());
+        assert_eq!(1, check_bin(&[0x00, 0x80, 0x01, 0x80, 0x00, 0xc0, 0xc9, 0xc9]).len());
+        assert_eq!(1, check_bin(&[0x00, 0x80]).len());
+        assert!(check_basic(&[0x00, 0x00]).is_empty());
+        assert_eq!(1, check_basic(&[0x00]).len());
+        assert!(check_name("game").is_empty());
+        assert_eq!(2, check_name("juegazo\u{f1}").len());
+        assert!(check_alignment(16).is_empty());
+        assert_eq!(Check::Alignment, check_alignment(10)[0].check);
+
+        let checks = |path, data| check_file(Path::new(path), data).unwrap().iter().map(|v| v.check).collect::<Vec<_>>();
+        assert_eq!(vec![Check::BinHeader, Check::Alignment], checks("game.bin", &[0xfe, 0x00, 0x80]));
+        assert_eq!(vec![Check::Name], checks("program.asc", &[0x41]));
+        assert_eq!(vec![Check::Basic], checks("loader.bas", &[0xff]));
+        assert_eq!(vec![Check::Alignment], checks("level1.dat", &[0x00, 0x80]));
+    }
+
+    #[test]
+    fn should_apply_rules() {
+        let mut policy = Policy::default();
+        assert_eq!(Level::Strict, policy.level(Check::BinHeader));
+        assert_eq!(Level::Warn, policy.level(Check::Alignment));
+
+        assert!(policy.apply("bin-header=warn"));
+        assert_eq!(Level::Warn, policy.level(Check::BinHeader));
+        assert!(policy.apply("off"));
+        assert!(policy.apply("name=strict"));
+        assert_eq!(Level::Off, policy.level(Check::Basic));
+        assert_eq!(Level::Strict, policy.level(Check::Name));
+
+        assert!(!policy.apply("lenient"));
+        assert!(!policy.apply("charset=off"));
+    }
+
+    #[test]
+    fn should_enforce_levels() {
+        let violations = || vec![check_alignment(10).remove(0), check_name("program").remove(0)];
+        let mut policy = Policy::default();
+        assert_eq!(2, policy.enforce(violations()).unwrap().len());
+        policy.set(Check::Name, Level::Off);
+        assert_eq!(vec![Check::Alignment], policy.enforce(violations()).unwrap().iter().map(|v| v.check).collect::<Vec<_>>());
+        policy.set(Check::Alignment, Level::Strict);
+        assert!(policy.enforce(violations()).is_err());
+    }
+}
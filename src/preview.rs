@@ -0,0 +1,216 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io;
+use std::io::Write;
+
+use byteorder::{BigEndian, WriteBytesExt};
+use crc32fast::Hasher;
+
+const BACKGROUND: u8 = 0xff;
+const AXIS: u8 = 0xd0;
+const MARK: u8 = 0xa0;
+const SIGNAL: u8 = 0x00;
+
+/// The PNG color types of the images, with the bytes taken by each pixel
+const GRAYSCALE: (u8, usize) = (0, 1);
+const RGB: (u8, usize) = (2, 3);
+
+/// A grayscale image
+#[derive(Debug, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Create a new image of the given size filled with the background color.
+    pub fn new(width: usize, height: usize) -> Image {
+        Image { width, height, pixels: vec![BACKGROUND; width * height] }
+    }
+
+    fn vline(&mut self, x: usize, from: usize, to: usize, color: u8) {
+        for y in from..=to.min(self.height - 1) {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+
+    fn hline(&mut self, y: usize, color: u8) {
+        for x in 0..self.width {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+
+    /// Write this image in PNG format.
+    ///
+    /// The image data is stored without compression, which keeps the encoder trivial at
+    /// the cost of bigger files.
+    pub fn write_png<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_png(w, self.width, self.height, GRAYSCALE, &self.pixels)
+    }
+}
+
+/// A color image, with the red, green and blue components of each pixel
+#[derive(Debug, PartialEq)]
+pub struct RgbImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbImage {
+    /// Write this image in PNG format, without compression as `Image::write_png()`.
+    pub fn write_png<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_png(w, self.width, self.height, RGB, &self.pixels)
+    }
+}
+
+/// Render the given 8-bit unsigned samples into an image of the given size.
+///
+/// Each column of the image shows the range of values of the samples it covers. When
+/// there are fewer samples than columns, each sample takes several columns. The given
+/// marks (sample positions, such as the start of each block) are drawn as vertical lines.
+pub fn render(samples: &[u8], marks: &[usize], width: usize, height: usize) -> Image {
+    let mut image = Image::new(width, height);
+    image.hline(y_of(0x80, height), AXIS);
+    if samples.is_empty() {
+        return image;
+    }
+
+    let column = |x: usize| {
+        let from = x * samples.len() / width;
+        let to = ((x + 1) * samples.len() / width).max(from + 1);
+        (from, to)
+    };
+    for x in 0..width {
+        let (from, to) = column(x);
+        if marks.iter().any(|m| from <= *m && *m < to) {
+            image.vline(x, 0, height - 1, MARK);
+        }
+    }
+    for x in 0..width {
+        // The last sample of the previous column is included to draw a continuous trace
+        let (from, to) = column(x);
+        let range = &samples[from.saturating_sub(1)..to];
+        let max = range.iter().max().cloned().unwrap_or(0x80);
+        let min = range.iter().min().cloned().unwrap_or(0x80);
+        image.vline(x, y_of(max, height), y_of(min, height), SIGNAL);
+    }
+    image
+}
+
+fn y_of(sample: u8, height: usize) -> usize {
+    ((0xff - sample as usize) * (height - 1) + 0x7f) / 0xff
+}
+
+fn write_png<W: Write>(w: &mut W, width: usize, height: usize, color: (u8, usize), pixels: &[u8]) -> io::Result<()> {
+    w.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = vec![];
+    header.write_u32::<BigEndian>(width as u32)?;
+    header.write_u32::<BigEndian>(height as u32)?;
+    // 8 bits per sample, deflate, no filter, no interlace
+    header.write_all(&[8, color.0, 0, 0, 0])?;
+    write_chunk(w, b"IHDR", &header)?;
+
+    let mut raw = Vec::with_capacity((width * color.1 + 1) * height);
+    for row in pixels.chunks(width * color.1) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    write_chunk(w, b"IDAT", &zlib_stored(&raw))?;
+    write_chunk(w, b"IEND", &[])
+}
+
+fn write_chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    w.write_u32::<BigEndian>(data.len() as u32)?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    let mut hasher = Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+    w.write_u32::<BigEndian>(hasher.finalize())
+}
+
+/// Wrap the given data in a zlib stream made of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[0x01, 0x00, 0x00, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    impl Image {
+        fn pixel(&self, x: usize, y: usize) -> u8 {
+            self.pixels[y * self.width + x]
+        }
+    }
+
+    #[test]
+    fn should_compute_adler32() {
+        assert_eq!(0x11e60398, adler32(b"Wikipedia"));
+    }
+
+    #[test]
+    fn should_render_signal() {
+        let image = render(&[0x80, 0xff, 0x80, 0x00], &[2], 4, 5);
+        assert_eq!(SIGNAL, image.pixel(0, 2));
+        assert_eq!(BACKGROUND, image.pixel(0, 0));
+        assert_eq!(SIGNAL, image.pixel(1, 0));
+        assert_eq!(MARK, image.pixel(2, 4));
+        assert_eq!(SIGNAL, image.pixel(3, 4));
+        assert_eq!(BACKGROUND, image.pixel(3, 0));
+    }
+
+    #[test]
+    fn should_render_empty_signal() {
+        let image = render(&[], &[], 3, 3);
+        assert_eq!(vec![BACKGROUND, BACKGROUND, BACKGROUND, AXIS, AXIS, AXIS], image.pixels[0..6].to_vec());
+    }
+
+    #[test]
+    fn should_write_png() {
+        let mut png = vec![];
+        Image::new(2, 2).write_png(&mut png).unwrap();
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &png[0..8]);
+        assert_eq!(b"IHDR", &png[12..16]);
+        assert_eq!(b"IEND", &png[png.len() - 8..png.len() - 4]);
+
+        let mut png = vec![];
+        RgbImage { width: 2, height: 2, pixels: vec![0xff; 12] }.write_png(&mut png).unwrap();
+        // Color type 2 (RGB) in the header, and two rows of a filter byte and 6 bytes
+        assert_eq!(2, png[25]);
+        assert_eq!(14, u16::from_le_bytes([png[44], png[45]]));
+    }
+}
@@ -0,0 +1,86 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::file;
+
+/// The progress of an export, to resume it if it is interrupted
+///
+/// WAV files are written a block at a time, and the progress is saved into a JSON sidecar
+/// file next to the WAV file (e.g., `game.wav.progress.json` for `game.wav`) after each
+/// block, and removed once the export is done.
+///
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+    /// The hash of the tape and the settings of the export, which must not change to resume it
+    pub key: String,
+    /// The number of blocks written
+    pub blocks: usize,
+    /// The length of the WAV file with these blocks
+    pub len: u64,
+}
+
+impl Progress {
+    /// Returns the path of the sidecar file for the given WAV file.
+    pub fn sidecar_path(wav_path: &Path) -> PathBuf {
+        let mut name = wav_path.file_name().unwrap_or_default().to_os_string();
+        name.push(".progress.json");
+        wav_path.with_file_name(name)
+    }
+
+    /// Load the progress of the export into the given WAV file, if it was interrupted.
+    pub fn load(wav_path: &Path) -> io::Result<Option<Progress>> {
+        let path = Progress::sidecar_path(wav_path);
+        if !file::exists(&path) {
+            return Ok(None);
+        }
+        let content = file::read_content(&path)?;
+        serde_json::from_slice(&content).map(Some).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, tr!("invalid progress file {}: {}", format!("{:?}", path), e))
+        })
+    }
+
+    /// Save the progress of the export into the given WAV file.
+    pub fn save(&self, wav_path: &Path) -> io::Result<()> {
+        let content = serde_json::to_vec(self)?;
+        file::write_content(&Progress::sidecar_path(wav_path), &content)
+    }
+
+    /// Remove the progress of the export into the given WAV file, once it is done.
+    pub fn remove(wav_path: &Path) -> io::Result<()> {
+        let path = Progress::sidecar_path(wav_path);
+        if file::exists(&path) {
+            file::remove(&path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn should_save_and_load_progress() {
+        let dir = TempDir::new("mcp").unwrap();
+        let wav_path = dir.path().join("game.wav");
+        assert_eq!(dir.path().join("game.wav.progress.json"), Progress::sidecar_path(&wav_path));
+        assert_eq!(None, Progress::load(&wav_path).unwrap());
+
+        let progress = Progress { key: "abc".to_string(), blocks: 2, len: 1024 };
+        progress.save(&wav_path).unwrap();
+        assert_eq!(Some(progress), Progress::load(&wav_path).unwrap());
+        Progress::remove(&wav_path).unwrap();
+        assert_eq!(None, Progress::load(&wav_path).unwrap());
+    }
+}
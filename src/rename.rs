@@ -0,0 +1,109 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io;
+
+use regex::Regex;
+
+/// A rule renaming the files of a tape
+#[derive(Debug)]
+pub enum Rule {
+    /// Rename the files with the given name to the other one
+    Name(String, String),
+    /// Rename the files with a name matching the given regular expression, replacing the
+    /// matches with the given replacement, where `$1`, `$2`... are the captured groups
+    Regex(Regex, String),
+}
+
+impl Rule {
+    /// Returns the new name of a file with the given name, or `None` if the rule does not
+    /// match it.
+    pub fn apply(&self, name: &str) -> Option<String> {
+        match self {
+            Rule::Name(old, new) if old == name => Some(new.clone()),
+            Rule::Name(..) => None,
+            Rule::Regex(regex, replacement) if regex.is_match(name) => {
+                Some(regex.replace_all(name, replacement.as_str()).into_owned())
+            }
+            Rule::Regex(..) => None,
+        }
+    }
+}
+
+/// The rules renaming the files of a tape, as read from a rules file
+///
+/// Rules files have a rule per line, given as `<old> -> <new>` to rename the files with
+/// a name, or `/<regex>/ -> <replacement>` to rename the files matching a regular
+/// expression. Blank lines and lines starting with `#` are ignored. Each file is renamed
+/// by the first rule that matches its name, if any, so the rules are applied in one pass.
+///
+#[derive(Debug)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+impl Rules {
+    /// Parse the given content of a rules file.
+    pub fn parse(text: &str) -> io::Result<Rules> {
+        let mut rules = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (old, new) = match line.split_once("->").or_else(|| line.split_once('\u{2192}')) {
+                Some((old, new)) => (old.trim(), new.trim()),
+                None => return Err(invalid_rule(i + 1, &tr!("expected <old> -> <new>"))),
+            };
+            let rule = match old.strip_prefix('/').and_then(|old| old.strip_suffix('/')) {
+                Some(pattern) => {
+                    let regex = Regex::new(pattern).map_err(|e| invalid_rule(i + 1, &e.to_string()))?;
+                    Rule::Regex(regex, new.to_string())
+                }
+                None => Rule::Name(old.to_string(), new.to_string()),
+            };
+            rules.push(rule);
+        }
+        Ok(Rules { rules })
+    }
+
+    /// Returns the new name of a file with the given name, as given by the first rule that
+    /// matches it, or `None` if no rule matches it.
+    pub fn apply(&self, name: &str) -> Option<String> {
+        self.rules.iter().find_map(|rule| rule.apply(name))
+    }
+}
+
+fn invalid_rule(line: usize, msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, tr!("invalid rename rule at line {}: {}", line, msg))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_parse_rules() {
+        let rules = Rules::parse("# Junk names of the dumping tool\nGAME1 -> LOADER\n\n/^F([0-9]+)$/ -> STAGE$1\n").unwrap();
+        assert_eq!(Some("LOADER".to_string()), rules.apply("GAME1"));
+        assert_eq!(Some("STAGE12".to_string()), rules.apply("F12"));
+        assert_eq!(None, rules.apply("F12A"));
+        assert_eq!(None, rules.apply("GAME"));
+
+        assert!(Rules::parse("GAME1 LOADER").is_err());
+        assert!(Rules::parse("/(/ -> LOADER").is_err());
+    }
+
+    #[test]
+    fn should_apply_the_first_matching_rule() {
+        let rules = Rules::parse("/^F/ -> G\nF1 -> LOADER\n/^G/ -> H").unwrap();
+        assert_eq!(Some("G1".to_string()), rules.apply("F1"));
+        assert_eq!(Some("H1".to_string()), rules.apply("G1"));
+    }
+}
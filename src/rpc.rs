@@ -0,0 +1,273 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::file;
+use crate::input;
+use crate::policy::{self, Policy};
+use crate::serve;
+use crate::tape::Tape;
+use crate::wav::Exporter;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// An error performing a valid request, such as a file that cannot be read
+const FAILED: i64 = -32000;
+
+/// An error answered to a request
+#[derive(Debug, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: &str) -> RpcError {
+        RpcError { code, message: message.to_string() }
+    }
+}
+
+impl From<io::Error> for RpcError {
+    fn from(e: io::Error) -> RpcError {
+        RpcError { code: FAILED, message: e.to_string() }
+    }
+}
+
+/// A session of the JSON-RPC protocol, holding the tape opened by the client
+///
+/// Requests and responses are JSON-RPC 2.0 messages, one per line. These are the methods:
+///
+/// * `open {path, create?}`, open the tape stored in the given file (in any input format),
+///   or a new empty tape if it does not exist and `create` is `true`
+/// * `list`, the files of the opened tape
+/// * `add {files, validate?}`, add the given files to the opened tape, validating them with
+///   the given rules (see `Policy::apply()`), and answer the warnings
+/// * `save {path?}`, save the opened tape into its file or into the given one
+/// * `export {path}`, export the opened tape into the given WAV file, sending a `progress`
+///   notification with `{block, blocks}` after each block is encoded
+///
+pub struct Session<W: Write> {
+    output: W,
+    tape: Option<(PathBuf, Tape)>,
+}
+
+impl<W: Write> Session<W> {
+    /// Create a new session writing its responses to the given output.
+    pub fn new(output: W) -> Session<W> {
+        Session { output, tape: None }
+    }
+
+    /// Answer the requests read from the given input until it ends.
+    pub fn run<R: BufRead>(&mut self, input: R) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                self.handle(&line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer the request in the given line, unless it is a notification.
+    pub fn handle(&mut self, line: &str) -> io::Result<()> {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return self.respond(Value::Null, Err(RpcError::new(PARSE_ERROR, &e.to_string()))),
+        };
+        let id = request.get("id").cloned();
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        let result = match request.get("method").and_then(Value::as_str) {
+            Some(method) => self.call(method, &params),
+            None => Err(RpcError::new(INVALID_REQUEST, "missing method")),
+        };
+        match id {
+            Some(id) => self.respond(id, result),
+            None => Ok(()),
+        }
+    }
+
+    fn call(&mut self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        match method {
+            "open" => self.open(params),
+            "list" => Ok(json!(serve::file_infos(self.opened()?))),
+            "add" => self.add(params),
+            "save" => self.save(params),
+            "export" => self.export(params),
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, &format!("unknown method {}", method))),
+        }
+    }
+
+    fn open(&mut self, params: &Value) -> Result<Value, RpcError> {
+        let path = PathBuf::from(param_str(params, "path")?);
+        let create = params.get("create").and_then(Value::as_bool).unwrap_or(false);
+        let tape = if create && !path.exists() { Tape::new() } else { input::load(&path, None, &[])? };
+        let result = json!({ "blocks": tape.blocks().len(), "files": tape.files().count() });
+        self.tape = Some((path, tape));
+        Ok(result)
+    }
+
+    fn add(&mut self, params: &Value) -> Result<Value, RpcError> {
+        let mut policy = Policy::default();
+        for rule in params.get("validate").and_then(Value::as_array).into_iter().flatten() {
+            if !rule.as_str().is_some_and(|rule| policy.apply(rule)) {
+                return Err(RpcError::new(INVALID_PARAMS, &format!("invalid validation rule {}", rule)));
+            }
+        }
+        let files = params.get("files").and_then(Value::as_array)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing files"))?;
+        let tape = match &mut self.tape {
+            Some((_, tape)) => tape,
+            None => return Err(RpcError::new(FAILED, "no tape is open")),
+        };
+
+        let mut warnings = vec![];
+        for path in files {
+            let path = Path::new(path.as_str().ok_or_else(|| RpcError::new(INVALID_PARAMS, "invalid file"))?);
+            let data = file::read_content(path)?;
+            for warning in policy.enforce(policy::check_file(path, &data)?)? {
+                warnings.push(json!({ "file": path, "message": warning.message }));
+            }
+            let (name, _) = file::file_name_of(path)?;
+            if file::is_bin_file(path) {
+                tape.append_bin(&name, &data)?;
+            } else if file::is_ascii_file(path) {
+                tape.append_ascii(&name, &data)?;
+            } else if file::is_basic_file(path) {
+                tape.append_basic(&name, &data)?;
+            } else {
+                tape.append_custom(&data)?;
+            }
+        }
+        Ok(json!({ "warnings": warnings }))
+    }
+
+    fn save(&mut self, params: &Value) -> Result<Value, RpcError> {
+        let (opened_path, tape) = self.tape.as_ref().ok_or_else(|| RpcError::new(FAILED, "no tape is open"))?;
+        let path = params.get("path").and_then(Value::as_str).map(PathBuf::from).unwrap_or_else(|| opened_path.clone());
+        let _lock = file::lock(&path)?;
+        let bytes = tape.to_bytes();
+        file::write_content(&path, &bytes)?;
+        Ok(json!({ "path": path, "bytes": bytes.len() }))
+    }
+
+    fn export(&mut self, params: &Value) -> Result<Value, RpcError> {
+        let path = PathBuf::from(param_str(params, "path")?);
+        let mut exporter = Exporter::new();
+        let blocks = self.opened()?.blocks().len();
+        for block in 0..blocks {
+            exporter.write_block(&self.opened()?.blocks()[block])?;
+            self.notify("progress", json!({ "block": block + 1, "blocks": blocks }))?;
+        }
+        let mut wav = vec![];
+        exporter.export(&mut wav)?;
+        file::write_content(&path, &wav)?;
+        Ok(json!({ "path": path, "bytes": wav.len() }))
+    }
+
+    fn opened(&self) -> Result<&Tape, RpcError> {
+        self.tape.as_ref().map(|(_, tape)| tape).ok_or_else(|| RpcError::new(FAILED, "no tape is open"))
+    }
+
+    fn respond(&mut self, id: Value, result: Result<Value, RpcError>) -> io::Result<()> {
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": e.code, "message": e.message } }),
+        };
+        self.send(&response)
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> io::Result<()> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    fn send(&mut self, message: &Value) -> io::Result<()> {
+        writeln!(self.output, "{}", message)?;
+        self.output.flush()
+    }
+}
+
+fn param_str<'a>(params: &'a Value, name: &str) -> Result<&'a str, RpcError> {
+    params.get(name).and_then(Value::as_str)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, &format!("missing {}", name)))
+}
+
+#[cfg(test)]
+mod test {
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn run(requests: &[Value]) -> Vec<Value> {
+        let input: Vec<String> = requests.iter().map(|r| r.to_string()).collect();
+        let mut output = vec![];
+        Session::new(&mut output).run(input.join("\n").as_bytes()).unwrap();
+        output.split(|b| *b == b'\n').filter(|l| !l.is_empty()).map(|l| serde_json::from_slice(l).unwrap()).collect()
+    }
+
+    fn request(id: u32, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    #[test]
+    fn should_build_and_export_tapes() {
+        let temp = TempDir::new("mcp").unwrap();
+        let game = temp.path().join("game.bin");
+        file::write_content(&game, &[0xfe, 0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        let data = temp.path().join("level1.dat");
+        file::write_content(&data, &[0x01, 0x02, 0x03]).unwrap();
+        let cas = temp.path().join("game.cas");
+        let wav = temp.path().join("game.wav");
+
+        let responses = run(&[
+            request(1, "open", json!({ "path": cas, "create": true })),
+            request(2, "add", json!({ "files": [game, data] })),
+            request(3, "list", Value::Null),
+            request(4, "save", Value::Null),
+            request(5, "export", json!({ "path": wav })),
+        ]);
+        assert_eq!(json!({ "blocks": 0, "files": 0 }), responses[0]["result"]);
+        assert_eq!(1, responses[1]["result"]["warnings"].as_array().unwrap().len());
+        assert_eq!("game.bin", responses[2]["result"][0]["name"]);
+        assert_eq!("custom", responses[2]["result"][1]["kind"]);
+        assert_eq!(4, responses[3]["id"]);
+        assert!(cas.exists());
+
+        // Export sends progress of the 3 blocks before answering
+        assert_eq!(json!({ "block": 1, "blocks": 3 }), responses[4]["params"]);
+        assert_eq!("progress", responses[6]["method"]);
+        assert_eq!(5, responses[7]["id"]);
+        assert!(wav.exists());
+    }
+
+    #[test]
+    fn should_answer_errors() {
+        let responses = run(&[
+            request(1, "list", Value::Null),
+            request(2, "play", Value::Null),
+            request(3, "open", json!({})),
+            json!({ "jsonrpc": "2.0", "method": "list" }),
+        ]);
+        assert_eq!(3, responses.len());
+        assert_eq!(FAILED, responses[0]["error"]["code"]);
+        assert_eq!(METHOD_NOT_FOUND, responses[1]["error"]["code"]);
+        assert_eq!(INVALID_PARAMS, responses[2]["error"]["code"]);
+
+        let mut output = vec![];
+        Session::new(&mut output).handle("{ not json").unwrap();
+        let response: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(PARSE_ERROR, response["error"]["code"]);
+    }
+}
@@ -0,0 +1,139 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::path::PathBuf;
+
+use crate::meta::Metadata;
+
+/// The directories of a new project: the sources of the program and the data files added
+/// to the tape after it, such as loading screens
+pub const DIRS: &[&str] = &["src", "assets"];
+
+/// The makefile of a new project, building its tape and exporting it into audio files
+const MAKEFILE: &str = "\
+# Tape pipeline of {name}, as generated by `mcp init`
+#
+#   make        assemble the program and build {name}.cas, {name}.wav and {name}.tsx
+#   make clean  remove everything built
+#
+# The files of assets/ are added to the tape after the program, as custom blocks.
+
+NAME = {name}
+ASM = pasmo
+ASSETS = $(sort $(wildcard assets/*))
+
+all: $(NAME).cas $(NAME).wav $(NAME).tsx
+
+build/$(NAME).bin: src/main.asm
+\tmkdir -p build
+\t$(ASM) $< $@
+
+$(NAME).cas: build/$(NAME).bin $(ASSETS)
+\trm -f $@
+\tmcp add $@ $^
+
+$(NAME).wav: $(NAME).cas
+\tmcp export $< $@
+
+$(NAME).tsx: $(NAME).cas
+\tmcp export $< $@
+
+clean:
+\trm -rf build $(NAME).cas $(NAME).wav $(NAME).tsx
+
+.PHONY: all clean
+";
+
+/// The program of a new project, printing a message when it is run with `BLOAD"CAS:",R`
+///
+/// Binary files start with the BEGIN, END and START addresses that `BLOAD` reads, so the
+/// program writes them before its code.
+///
+const MAIN_ASM: &str = "\
+; {name}, loaded and run with BLOAD\"CAS:\",R
+
+CHPUT:  equ     0x00a2          ; BIOS routine printing the character in A
+
+        org     0x9000 - 6
+        dw      begin, last - 1, start
+
+begin:
+start:
+        ld      hl, message
+loop:
+        ld      a, (hl)
+        or      a
+        ret     z
+        call    CHPUT
+        inc     hl
+        jr      loop
+
+message:
+        db      \"Hello from {name}!\", 13, 10, 0
+last:
+";
+
+/// Returns the files of a new project with the given name, as their paths relative to the
+/// directory of the project and their contents.
+///
+/// Along with the sources and the makefile, the tape built by the project gets a metadata
+/// sidecar file titled after the project, shown when the tape is listed.
+///
+pub fn files(name: &str) -> Vec<(PathBuf, Vec<u8>)> {
+    let cas_path = PathBuf::from(format!("{}.cas", name));
+    let metadata = Metadata { title: Some(name.to_string()), ..Metadata::default() };
+    let mut meta = serde_json::to_vec_pretty(&metadata).unwrap_or_default();
+    meta.push(b'\n');
+    vec![
+        (PathBuf::from("Makefile"), MAKEFILE.replace("{name}", name).into_bytes()),
+        (PathBuf::from("src").join("main.asm"), MAIN_ASM.replace("{name}", name).into_bytes()),
+        (Metadata::sidecar_path(&cas_path), meta),
+    ]
+}
+
+/// Returns the name of a project created in a directory with the given name, made of its
+/// lowercase letters and digits (e.g., `space-race` for `Space Race`).
+pub fn project_name(dir_name: &str) -> String {
+    let name: String = dir_name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let words: Vec<&str> = name.split('-').filter(|w| !w.is_empty()).collect();
+    if words.is_empty() { "game".to_string() } else { words.join("-") }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_name_projects() {
+        assert_eq!("space-race", project_name("Space Race"));
+        assert_eq!("game2", project_name("game2"));
+        assert_eq!("game", project_name("..."));
+    }
+
+    #[test]
+    fn should_generate_project_files() {
+        let files = files("race");
+        let paths: Vec<&PathBuf> = files.iter().map(|(path, _)| path).collect();
+        assert_eq!(
+            vec![&PathBuf::from("Makefile"), &PathBuf::from("src/main.asm"), &PathBuf::from("race.cas.meta.json")],
+            paths);
+
+        let makefile = String::from_utf8(files[0].1.clone()).unwrap();
+        assert!(makefile.contains("NAME = race\n"));
+        assert!(makefile.contains("all: $(NAME).cas $(NAME).wav $(NAME).tsx\n"));
+        assert!(makefile.contains("\n\tmcp add $@ $^\n"));
+
+        let metadata: Metadata = serde_json::from_slice(&files[2].1).unwrap();
+        assert_eq!(Some("race".to_string()), metadata.title);
+    }
+}
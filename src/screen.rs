@@ -0,0 +1,162 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::preview::RgbImage;
+
+/// The size in pixels of SCREEN 2 images
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 192;
+
+/// The size of the pattern and color tables of SCREEN 2, 8 bytes per character of each third
+const TABLE_SIZE: usize = 0x1800;
+
+/// The addresses of the tables of SCREEN 2 in VRAM, as set by the BIOS
+const NAMES_ADDR: usize = 0x1800;
+const COLORS_ADDR: usize = 0x2000;
+
+/// The size of the name table, a byte per character of the screen
+const NAMES_SIZE: usize = 768;
+
+/// The size of the VRAM of MSX1 computers
+const VRAM_SIZE: usize = 0x4000;
+
+/// The colors of the TMS9918 video chip, with transparent shown as black
+const PALETTE: [[u8; 3]; 16] = [
+    [0, 0, 0], [0, 0, 0], [33, 200, 66], [94, 220, 120],
+    [84, 85, 237], [125, 118, 252], [212, 82, 77], [66, 235, 245],
+    [252, 85, 84], [255, 121, 120], [212, 193, 84], [230, 206, 128],
+    [33, 176, 59], [201, 91, 186], [204, 204, 204], [255, 255, 255],
+];
+
+/// A SCREEN 2 image, as stored by loaders in custom blocks
+///
+/// Loaders store loading screens either as a dump of the VRAM, with the pattern, name and
+/// color tables at the addresses set by the BIOS, or as the pattern table followed by the
+/// color table, to be shown with the default name table (characters 0 to 255 in each third
+/// of the screen). Both may come with the 7-byte header of `BSAVE`.
+///
+#[derive(Debug, PartialEq)]
+pub struct Screen<'a> {
+    patterns: &'a [u8],
+    names: Option<&'a [u8]>,
+    colors: &'a [u8],
+}
+
+impl<'a> Screen<'a> {
+    /// Detect a SCREEN 2 image in the given data, if its tables look plausible.
+    ///
+    /// Color tables are expected to repeat the same colors in consecutive lines of the
+    /// characters, as drawings do and code or compressed data does not.
+    pub fn detect(data: &'a [u8]) -> Option<Screen<'a>> {
+        let data = strip_bsave_header(data);
+        // Custom blocks are padded to a multiple of 8 bytes
+        let screen = if data.len() >= COLORS_ADDR + TABLE_SIZE && data.len() < VRAM_SIZE + 8 {
+            Screen {
+                patterns: &data[..TABLE_SIZE],
+                names: Some(&data[NAMES_ADDR..NAMES_ADDR + NAMES_SIZE]),
+                colors: &data[COLORS_ADDR..COLORS_ADDR + TABLE_SIZE],
+            }
+        } else if data.len() >= 2 * TABLE_SIZE && data.len() < 2 * TABLE_SIZE + 8 {
+            Screen { patterns: &data[..TABLE_SIZE], names: None, colors: &data[TABLE_SIZE..2 * TABLE_SIZE] }
+        } else {
+            return None;
+        };
+        let repeated = screen.colors.windows(2).filter(|w| w[0] == w[1]).count();
+        let drawn = screen.patterns.iter().any(|b| *b != screen.patterns[0]);
+        if drawn && repeated * 4 >= screen.colors.len() {
+            Some(screen)
+        } else {
+            None
+        }
+    }
+
+    /// Render this image.
+    pub fn render(&self) -> RgbImage {
+        let mut pixels = Vec::with_capacity(WIDTH * HEIGHT * 3);
+        for y in 0..HEIGHT {
+            for col in 0..WIDTH / 8 {
+                let index = y / 8 * 32 + col;
+                let name = self.names.map(|names| names[index] as usize).unwrap_or(index % 256);
+                // Each third of the screen has its own patterns and colors
+                let offset = (y / 64 * 256 + name) * 8 + y % 8;
+                let (pattern, color) = (self.patterns[offset], self.colors[offset]);
+                for bit in (0..8).rev() {
+                    let c = if pattern >> bit & 1 == 1 { color >> 4 } else { color & 0x0f };
+                    pixels.extend_from_slice(&PALETTE[c as usize]);
+                }
+            }
+        }
+        RgbImage { width: WIDTH, height: HEIGHT, pixels }
+    }
+}
+
+/// Returns the given data without the header of `BSAVE`, if it has one loading it at the
+/// beginning of the VRAM.
+fn strip_bsave_header(data: &[u8]) -> &[u8] {
+    if data.len() > 7 && data[0] == 0xfe && LittleEndian::read_u16(&data[1..3]) == 0 {
+        let end = LittleEndian::read_u16(&data[3..5]) as usize;
+        &data[7..data.len().min(end + 8)]
+    } else {
+        data
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /// Returns the patterns of an image where each character has its first line and the left
+    /// half of the others drawn.
+    fn patterns() -> Vec<u8> {
+        (0..TABLE_SIZE).map(|i| if i % 8 == 0 { 0xff } else { 0xf0 }).collect()
+    }
+
+    /// Returns the tables of an image drawn in white over dark blue.
+    fn tables() -> Vec<u8> {
+        let mut data = patterns();
+        data.extend_from_slice(&[0xf4; TABLE_SIZE]);
+        data
+    }
+
+    #[test]
+    fn should_detect_screens() {
+        let data = tables();
+        let screen = Screen::detect(&data).unwrap();
+        assert_eq!(None, screen.names);
+
+        let mut vram = vec![0x00; VRAM_SIZE];
+        vram[..TABLE_SIZE].copy_from_slice(&patterns());
+        vram[COLORS_ADDR..COLORS_ADDR + TABLE_SIZE].copy_from_slice(&[0xf4; TABLE_SIZE]);
+        let mut bsave = vec![0xfe, 0x00, 0x00, 0xff, 0x3f, 0x00, 0x00];
+        bsave.extend_from_slice(&vram);
+        let screen = Screen::detect(&bsave).unwrap();
+        assert_eq!(Some(&vram[NAMES_ADDR..NAMES_ADDR + NAMES_SIZE]), screen.names);
+
+        assert_eq!(None, Screen::detect(&data[..TABLE_SIZE]));
+        assert_eq!(None, Screen::detect(&vec![0x00; 2 * TABLE_SIZE]));
+        assert_eq!(None, Screen::detect(&vec![0x00; 2 * VRAM_SIZE]));
+        let noise: Vec<u8> = (0..2 * TABLE_SIZE).map(|i| (i * 7 + i / 3) as u8).collect();
+        assert_eq!(None, Screen::detect(&noise));
+    }
+
+    #[test]
+    fn should_render_screens() {
+        let data = tables();
+        let image = Screen::detect(&data).unwrap().render();
+        assert_eq!((WIDTH, HEIGHT), (image.width, image.height));
+        let pixel = |x: usize, y: usize| &image.pixels[(y * WIDTH + x) * 3..(y * WIDTH + x) * 3 + 3];
+        assert_eq!(&PALETTE[15], pixel(4, 0));
+        assert_eq!(&PALETTE[15], pixel(0, 1));
+        assert_eq!(&PALETTE[4], pixel(4, 1));
+        assert_eq!(&PALETTE[15], pixel(248, 191));
+        assert_eq!(&PALETTE[4], pixel(255, 191));
+    }
+}
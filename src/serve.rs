@@ -0,0 +1,320 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use serde::Serialize;
+
+use crate::check;
+use crate::file;
+use crate::meta::Metadata;
+use crate::tape::{File, Tape};
+use crate::wav::Exporter;
+
+/// A file of a tape, as listed by the server
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FileInfo {
+    /// `bin`, `basic`, `ascii`, `other` or `custom`
+    pub kind: &'static str,
+    /// The name of the file when it is extracted
+    pub name: String,
+    pub bytes: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub begin: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<usize>,
+}
+
+/// The contents of a tape, as listed by the server
+#[derive(Debug, Serialize)]
+pub struct Listing {
+    pub tape: String,
+    pub metadata: Metadata,
+    pub files: Vec<FileInfo>,
+}
+
+/// A response to a request to the server
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn json<T: Serialize>(value: &T) -> Response {
+        match serde_json::to_vec_pretty(value) {
+            Ok(body) => Response { status: 200, content_type: "application/json", body },
+            Err(e) => Response::error(500, &e.to_string()),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        let body = serde_json::json!({ "error": message }).to_string().into_bytes();
+        Response { status, content_type: "application/json", body }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+/// Serve the tapes of the given directory over HTTP on the given local port.
+///
+/// Each connection is handled in its own thread, and closed after one request. This
+/// function only returns if the port cannot be listened to.
+pub fn serve(dir: &Path, port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let dir = dir.to_path_buf();
+        thread::spawn(move || {
+            // The client may hang up at any time, there is nobody to report it to
+            let _ = respond(&dir, stream);
+        });
+    }
+    Ok(())
+}
+
+fn respond(dir: &Path, stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // Skip the headers, requests have no body
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let response = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => handle(dir, method, target),
+        _ => Response::error(400, "malformed request"),
+    };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.reason(),
+        response.content_type,
+        response.body.len(),
+    )?;
+    stream.write_all(&response.body)
+}
+
+/// Handle a request with the given method and target over the tapes of the given directory.
+///
+/// These are the endpoints, all of them answering to `GET` requests:
+///
+/// * `/tapes`, the names of the CAS files of the directory
+/// * `/tapes/<tape>`, the metadata and files of the tape (see `Listing`)
+/// * `/tapes/<tape>/check`, the health report of the tape (see `check::Report`)
+/// * `/tapes/<tape>/files/<name>`, the content of a file of the tape, as it is extracted
+/// * `/tapes/<tape>/wav`, the tape converted into a WAV file
+///
+pub fn handle(dir: &Path, method: &str, target: &str) -> Response {
+    if method != "GET" {
+        return Response::error(405, "only GET requests are supported");
+    }
+    let path = target.split('?').next().unwrap_or_default();
+    let segments: Option<Vec<String>> = path.split('/').filter(|s| !s.is_empty()).map(percent_decode).collect();
+    let segments = match segments {
+        Some(segments) => segments,
+        None => return Response::error(400, "malformed path"),
+    };
+    let segments: Vec<&str> = segments.iter().map(|s| s.as_str()).collect();
+    let result = match segments[..] {
+        ["tapes"] => tape_names(dir).map(|names| Response::json(&names)),
+        ["tapes", name] => find_tape(dir, name).and_then(|path| list(&path, name)),
+        ["tapes", name, "check"] => find_tape(dir, name)
+            .and_then(|path| file::read_content(&path))
+            .map(|bytes| Response::json(&check::check(name, &bytes))),
+        ["tapes", name, "files", file_name] => find_tape(dir, name).and_then(|path| extract(&path, file_name)),
+        ["tapes", name, "wav"] => find_tape(dir, name).and_then(|path| convert(&path)),
+        _ => return Response::error(404, "no such endpoint"),
+    };
+    result.unwrap_or_else(|e| match e.kind() {
+        io::ErrorKind::NotFound => Response::error(404, &e.to_string()),
+        _ => Response::error(500, &e.to_string()),
+    })
+}
+
+fn tape_names(dir: &Path) -> io::Result<Vec<String>> {
+    let tapes = file::list_dir(dir, file::is_cas_file)?;
+    Ok(tapes.iter().filter_map(|t| t.file_name()).map(|n| n.to_string_lossy().into_owned()).collect())
+}
+
+/// Find the tape with the given name among the tapes of the directory, so no other
+/// files can be reached.
+fn find_tape(dir: &Path, name: &str) -> io::Result<PathBuf> {
+    if tape_names(dir)?.iter().any(|n| n == name) {
+        Ok(dir.join(name))
+    } else {
+        Err(io::Error::new(io::ErrorKind::NotFound, tr!("no tape named {}", name)))
+    }
+}
+
+/// Returns the files of the given tape, along with their names when extracted.
+fn named_files(tape: &Tape) -> Vec<(String, File<'_>)> {
+    let mut next_custom = 0;
+    tape.files()
+        .map(|file| {
+            let name = file.name().unwrap_or_else(|| {
+                next_custom += 1;
+                format!("custom.{:03}", next_custom)
+            });
+            (name, file)
+        })
+        .collect()
+}
+
+/// Returns the description of the files of the given tape.
+pub fn file_infos(tape: &Tape) -> Vec<FileInfo> {
+    named_files(tape)
+        .into_iter()
+        .map(|(name, file)| {
+            let info = |kind, bytes| FileInfo { kind, name: name.clone(), bytes, begin: None, end: None, start: None };
+            match file {
+                File::Bin(_, begin, end, start, data) => FileInfo {
+                    begin: Some(begin),
+                    end: Some(end),
+                    start: Some(start),
+                    ..info("bin", data.len())
+                },
+                File::Basic(_, data) => info("basic", data.len()),
+                File::Ascii(_, chunks) => info("ascii", chunks.iter().map(|c| c.len()).sum()),
+                File::Other(_, _, data) => info("other", data.len()),
+                File::Custom(data) => info("custom", data.len()),
+            }
+        })
+        .collect()
+}
+
+fn list(path: &Path, name: &str) -> io::Result<Response> {
+    let tape = Tape::from_file(path)?;
+    let files = file_infos(&tape);
+    let metadata = Metadata::load(path)?;
+    Ok(Response::json(&Listing { tape: name.to_string(), metadata, files }))
+}
+
+fn extract(path: &Path, file_name: &str) -> io::Result<Response> {
+    let tape = Tape::from_file(path)?;
+    let file = named_files(&tape).into_iter().find(|(name, _)| name == file_name);
+    match file {
+        Some((_, file)) => Ok(Response { status: 200, content_type: "application/octet-stream", body: file.content() }),
+        None => Err(tape.no_file_named(file_name, &[])),
+    }
+}
+
+fn convert(path: &Path) -> io::Result<Response> {
+    let tape = Tape::from_file(path)?;
+    let mut exporter = Exporter::new();
+    for block in tape.blocks() {
+        exporter.write_block(block)?;
+    }
+    let mut body = vec![];
+    exporter.export(&mut body)?;
+    Ok(Response { status: 200, content_type: "audio/wav", body })
+}
+
+/// Decode the `%XX` escapes of the given path segment, or `None` if they are malformed.
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod test {
+
+    use tempdir::TempDir;
+
+    use crate::tape::file_name;
+
+    use super::*;
+
+    fn sample_dir() -> TempDir {
+        let temp = TempDir::new("mcp").unwrap();
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("game").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_custom(&[0x01, 0x02, 0x03]).unwrap();
+        file::write_content(&temp.path().join("my game.cas"), &tape.to_bytes()).unwrap();
+        file::write_content(&temp.path().join("notes.txt"), b"not a tape").unwrap();
+        temp
+    }
+
+    fn json(response: Response) -> serde_json::Value {
+        assert_eq!(200, response.status);
+        serde_json::from_slice(&response.body).unwrap()
+    }
+
+    #[test]
+    fn should_list_tapes_and_files() {
+        let dir = sample_dir();
+        assert_eq!(serde_json::json!(["my game.cas"]), json(handle(dir.path(), "GET", "/tapes")));
+
+        let listing = json(handle(dir.path(), "GET", "/tapes/my%20game.cas"));
+        assert_eq!("game.bin", listing["files"][0]["name"]);
+        assert_eq!(0x8000, listing["files"][0]["start"]);
+        assert_eq!("custom", listing["files"][1]["kind"]);
+        assert_eq!("custom.001", listing["files"][1]["name"]);
+
+        let report = json(handle(dir.path(), "GET", "/tapes/my%20game.cas/check"));
+        assert_eq!(3, report["blocks"]);
+    }
+
+    #[test]
+    fn should_extract_and_convert_files() {
+        let dir = sample_dir();
+        let response = handle(dir.path(), "GET", "/tapes/my%20game.cas/files/game.bin");
+        assert_eq!(200, response.status);
+        assert_eq!(vec![0xfe, 0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9], response.body);
+
+        let response = handle(dir.path(), "GET", "/tapes/my%20game.cas/wav");
+        assert_eq!("audio/wav", response.content_type);
+        assert_eq!(b"RIFF", &response.body[..4]);
+    }
+
+    #[test]
+    fn should_reject_invalid_requests() {
+        let dir = sample_dir();
+        assert_eq!(404, handle(dir.path(), "GET", "/tapes/notes.txt").status);
+        assert_eq!(404, handle(dir.path(), "GET", "/tapes/..%2Fetc%2Fpasswd").status);
+        assert_eq!(404, handle(dir.path(), "GET", "/tapes/my%20game.cas/files/other.bin").status);
+        assert_eq!(404, handle(dir.path(), "GET", "/").status);
+        assert_eq!(400, handle(dir.path(), "GET", "/tapes/%zz").status);
+        assert_eq!(405, handle(dir.path(), "DELETE", "/tapes").status);
+    }
+}
@@ -0,0 +1,100 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::file;
+
+/// The speed of the blocks with no recorded speed
+pub const DEFAULT_BAUDS: u32 = 1200;
+
+/// The speed of each block of a tape
+///
+/// CAS files have no room for the speed of their blocks, so it is stored in a JSON sidecar
+/// file next to the CAS file (e.g., `game.cas.speed.json` for `game.cas`). It is recorded
+/// when a tape with turbo blocks is imported, so they are exported at the same speed.
+///
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Speeds {
+    /// The bauds of each block, in the order of the blocks
+    pub bauds: Vec<u32>,
+}
+
+impl Speeds {
+    /// Returns the path of the sidecar file for the given CAS file.
+    pub fn sidecar_path(cas_path: &Path) -> PathBuf {
+        let mut name = cas_path.file_name().unwrap_or_default().to_os_string();
+        name.push(".speed.json");
+        cas_path.with_file_name(name)
+    }
+
+    /// Load the speeds of the blocks of the given CAS file.
+    ///
+    /// If the CAS file has no sidecar file, every block has the default speed.
+    pub fn load(cas_path: &Path) -> io::Result<Speeds> {
+        let path = Speeds::sidecar_path(cas_path);
+        if !file::exists(&path) {
+            return Ok(Speeds::default());
+        }
+        let content = file::read_content(&path)?;
+        let speeds: Speeds = serde_json::from_slice(&content).map_err(|e| invalid_speeds(&path, &e.to_string()))?;
+        if speeds.bauds.contains(&0) {
+            return Err(invalid_speeds(&path, &tr!("speeds must be above zero")));
+        }
+        Ok(speeds)
+    }
+
+    /// Save the speeds of the blocks of the given CAS file into its sidecar file.
+    pub fn save(&self, cas_path: &Path) -> io::Result<()> {
+        let mut content = serde_json::to_vec_pretty(self)?;
+        content.push(b'\n');
+        file::write_content(&Speeds::sidecar_path(cas_path), &content)
+    }
+
+    /// Returns the bauds of the given block.
+    pub fn of(&self, block: usize) -> u32 {
+        self.bauds.get(block).copied().unwrap_or(DEFAULT_BAUDS)
+    }
+
+    /// Returns `true` if every block has the default speed.
+    pub fn is_default(&self) -> bool {
+        self.bauds.iter().all(|b| *b == DEFAULT_BAUDS)
+    }
+}
+
+fn invalid_speeds(path: &Path, msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        tr!("invalid speed file {}: {}", format!("{:?}", path), msg),
+    )
+}
+
+#[cfg(test)]
+mod test {
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn should_save_and_load_speeds() {
+        let temp = TempDir::new("mcp").unwrap();
+        let cas = temp.path().join("game.cas");
+        assert_eq!(Speeds::default(), Speeds::load(&cas).unwrap());
+
+        let speeds = Speeds { bauds: vec![1200, 3600] };
+        speeds.save(&cas).unwrap();
+        assert!(file::exists(&temp.path().join("game.cas.speed.json")));
+        let loaded = Speeds::load(&cas).unwrap();
+        assert_eq!(speeds, loaded);
+        assert_eq!(3600, loaded.of(1));
+        assert_eq!(DEFAULT_BAUDS, loaded.of(2));
+        assert!(!loaded.is_default());
+    }
+}
@@ -0,0 +1,114 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io;
+use std::path::Path;
+
+use crate::file;
+
+/// The symbols of a program, as written by assemblers into `.sym` files
+///
+/// Symbol files have a symbol per line, given as `<name>: EQU <value>` (as sjasm, pasmo and
+/// tniasm write them), `<name> = <value>` or `<name> <value>`. Values are given in hex, as
+/// `0x9000`, `$9000`, `#9000`, `&H9000` or `9000h`, or in decimal. Blank lines and lines
+/// starting with `;` are ignored. The first name given to an address is the one shown.
+///
+#[derive(Debug, Default)]
+pub struct Symbols {
+    names: BTreeMap<u16, String>,
+    addresses: Vec<(String, u16)>,
+}
+
+impl Symbols {
+    /// Load the symbols of the given file.
+    pub fn load(path: &Path) -> io::Result<Symbols> {
+        Symbols::parse(&String::from_utf8_lossy(&file::read_content(path)?))
+    }
+
+    /// Parse the given content of a symbol file.
+    pub fn parse(text: &str) -> io::Result<Symbols> {
+        let mut names = BTreeMap::new();
+        let mut addresses = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let words: Vec<&str> = line.split(|c: char| c.is_whitespace() || c == '=').filter(|w| !w.is_empty()).collect();
+            let (name, value) = match words[..] {
+                [name, value] => (name, value),
+                [name, equ, value] if equ.eq_ignore_ascii_case("equ") || equ == ":" => (name, value),
+                _ => return Err(invalid_symbol(i + 1)),
+            };
+            let name = name.trim_end_matches(':');
+            let addr = parse_value(value).ok_or_else(|| invalid_symbol(i + 1))?;
+            names.entry(addr).or_insert_with(|| name.to_string());
+            addresses.push((name.to_string(), addr));
+        }
+        Ok(Symbols { names, addresses })
+    }
+
+    /// Returns the name of the given address, if any.
+    pub fn name_of(&self, addr: u16) -> Option<&str> {
+        self.names.get(&addr).map(String::as_str)
+    }
+
+    /// Returns the address of the symbol with the given name, in any case, if any.
+    pub fn address_of(&self, name: &str) -> Option<u16> {
+        self.addresses.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, addr)| *addr)
+    }
+}
+
+/// Parse the given value of a symbol, in hex or in decimal.
+fn parse_value(s: &str) -> Option<u16> {
+    let lower = s.to_ascii_lowercase();
+    let hex = ["0x", "$", "#", "&h"].iter().find_map(|prefix| lower.strip_prefix(prefix)).or_else(|| lower.strip_suffix('h'));
+    let value = match hex {
+        Some(digits) => u32::from_str_radix(digits, 16).ok()?,
+        None => lower.parse().ok()?,
+    };
+    u16::try_from(value).ok()
+}
+
+fn invalid_symbol(line: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, tr!("invalid symbol at line {}: expected <name>: EQU <value>", line))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_parse_symbols() {
+        let symbols = Symbols::parse(
+            "; sjasm symbols\nmain: EQU 0x00009000\nLOOP EQU 09007H\nputs = $c044\nTABLE #c05a\nalias: equ 36864\n").unwrap();
+        assert_eq!(Some("main"), symbols.name_of(0x9000));
+        assert_eq!(Some("LOOP"), symbols.name_of(0x9007));
+        assert_eq!(Some("puts"), symbols.name_of(0xc044));
+        assert_eq!(Some("TABLE"), symbols.name_of(0xc05a));
+        assert_eq!(None, symbols.name_of(0x9001));
+        assert_eq!(Some(0x9000), symbols.address_of("ALIAS"));
+        assert_eq!(None, symbols.address_of("start"));
+
+        assert!(Symbols::parse("main EQU").is_err());
+        assert!(Symbols::parse("main: EQU 0x10000").is_err());
+        assert!(Symbols::parse("main: EQU zz").is_err());
+    }
+
+    #[test]
+    fn should_parse_values() {
+        assert_eq!(Some(0x9000), parse_value("0x9000"));
+        assert_eq!(Some(0x9000), parse_value("&H9000"));
+        assert_eq!(Some(0xc000), parse_value("0C000h"));
+        assert_eq!(Some(100), parse_value("100"));
+        assert_eq!(None, parse_value("h"));
+    }
+}
@@ -6,13 +6,31 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::fs;
+use std::borrow::Cow;
+use std::fmt;
 use std::io;
 use std::io::{Read, Write};
 use std::path::Path;
+use std::ops::{Range, RangeInclusive};
 use std::str::from_utf8;
+use std::sync::Arc;
 
 use byteorder::{ByteOrder, LittleEndian};
+use memchr::memmem;
+
+use crate::mmap::Mapping;
+
+/// The prefix bytes found at the beginning of every block
+pub const BLOCK_PREFIX: [u8; 8] = [0x1f, 0xa6, 0xde, 0xba, 0xcc, 0x13, 0x7d, 0x74];
+
+/// The size of the chunks in which file contents are written out of the tape
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The largest edit distance between a name and a file name suggested for it
+const MAX_TYPOS: usize = 2;
+
+/// The bytes a tape was parsed from, shared by its blocks
+pub type Source = Arc<dyn AsRef<[u8]> + Send + Sync>;
 
 /// A block of data contained in a tape.
 ///
@@ -20,29 +38,91 @@ use byteorder::{ByteOrder, LittleEndian};
 /// `1fa6debacc137d74` followed by the block data. The `Block` type stores the block data
 /// including the prefix bytes.
 ///
-#[derive(Debug)]
+/// The blocks of a parsed tape refer to the bytes the tape was parsed from, and they only
+/// copy their data when they are modified.
+///
 pub struct Block {
-    data: Vec<u8>,
+    data: Data,
+    /// The non-standard sync sequence the block was read with, if any
+    sync: Option<[u8; 8]>,
+}
+
+enum Data {
+    /// A range of the bytes the tape was parsed from
+    Shared(Source, Range<usize>),
+    Owned(Vec<u8>),
+}
+
+impl fmt::Debug for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Block").field("data", &self.data()).finish()
+    }
 }
 
 impl Block {
     /// Generates a new block from the data bytes (without the prefix bytes).
     pub fn from_data(bytes: &[u8]) -> Block {
         let mut data = Vec::with_capacity(bytes.len() + 8);
-        data.write(&[0x1f, 0xa6, 0xde, 0xba, 0xcc, 0x13, 0x7d, 0x74])
-            .unwrap();
-        data.write(bytes).unwrap();
-        Block { data: data }
+        data.write_all(&BLOCK_PREFIX).unwrap();
+        data.write_all(bytes).unwrap();
+        Block { data: Data::Owned(data), sync: None }
+    }
+
+    /// Generates a new block from a range of the given bytes (including the prefix bytes).
+    fn shared(source: &Source, range: Range<usize>) -> Block {
+        Block { data: Data::Shared(source.clone(), range), sync: None }
+    }
+
+    /// Generates a new file header block of the given kind for the given file name.
+    pub fn file_header(kind: HeaderKind, name: &[u8; 6]) -> Block {
+        let mut bytes = [kind.id_byte(); 16];
+        bytes[10..].copy_from_slice(name);
+        Block::from_data(&bytes)
     }
 
     /// Returns the block data (including the prefix bytes).
     pub fn data(&self) -> &[u8] {
-        &self.data[..]
+        match &self.data {
+            Data::Shared(source, range) => &(**source).as_ref()[range.clone()],
+            Data::Owned(data) => &data[..],
+        }
     }
 
     /// Returns the block data (without the prefix bytes).
     pub fn data_without_prefix(&self) -> &[u8] {
-        &self.data[8..]
+        &self.data()[8..]
+    }
+
+    /// Returns the block data (including the prefix bytes) to modify it, copying it if it
+    /// is shared.
+    fn data_mut(&mut self) -> &mut Vec<u8> {
+        if let Data::Shared(..) = self.data {
+            self.data = Data::Owned(self.data().to_vec());
+        }
+        match &mut self.data {
+            Data::Owned(data) => data,
+            Data::Shared(..) => unreachable!(),
+        }
+    }
+
+    /// Returns the kind of file header of this block, or `None` if it is not a file header.
+    ///
+    /// A file header is comprised by ten times the ID byte of its kind (see `HeaderKind`)
+    /// followed by six bytes for the name of the file.
+    pub fn header_kind(&self) -> Option<HeaderKind> {
+        self.header_kind_with(&[])
+    }
+
+    /// Returns the kind of file header of this block, considering the given ID bytes as
+    /// non-standard headers besides the standard ones.
+    pub fn header_kind_with(&self, extra_ids: &[u8]) -> Option<HeaderKind> {
+        let data = self.data_without_prefix();
+        if data.len() >= 10 && data[1..10].iter().all(|b| *b == data[0]) {
+            HeaderKind::from_id_byte(data[0])
+                .or_else(|| Some(HeaderKind::Other(data[0])).filter(|_| extra_ids.contains(&data[0])))
+        } else {
+            None
+        }
     }
 
     /// Returns `true` if the block is detected as a binary header.
@@ -51,8 +131,7 @@ impl Block {
     /// the name of the binary file. This function returns `true` if the block data match
     /// this pattern, `false` otherwise.
     pub fn is_bin_header(&self) -> bool {
-        let data = self.data_without_prefix();
-        data.len() >= 10 && data[..10] == [0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0]
+        self.header_kind() == Some(HeaderKind::Bin)
     }
 
     /// Returns `true` if the block is detected as a Basic header.
@@ -61,8 +140,7 @@ impl Block {
     /// the name of the Basic file. This function returns `true` if the block data match
     /// this pattern, `false` otherwise.
     pub fn is_basic_header(&self) -> bool {
-        let data = self.data_without_prefix();
-        data.len() >= 10 && data[..10] == [0xd3, 0xd3, 0xd3, 0xd3, 0xd3, 0xd3, 0xd3, 0xd3, 0xd3, 0xd3]
+        self.header_kind() == Some(HeaderKind::Basic)
     }
 
     /// Returns `true` if the block is detected as an ASCII header.
@@ -70,26 +148,125 @@ impl Block {
     /// An ASCII header is comprised by `0xeaeaeaeaeaeaeaeaeaea` followed by six bytes for
     /// the name of the ASCII file. This function returns `true` if the block data match
     /// this pattern, `false` otherwise.
+    #[allow(dead_code)]
     pub fn is_ascii_header(&self) -> bool {
-        let data = self.data_without_prefix();
-        data.len() >= 10 && data[..10] == [0xea, 0xea, 0xea, 0xea, 0xea, 0xea, 0xea, 0xea, 0xea, 0xea]
+        self.header_kind() == Some(HeaderKind::Ascii)
     }
 
     /// Returns `true` if the block is detected as a file header (either bin, basic or ascii).
     pub fn is_file_header(&self) -> bool {
-        self.is_bin_header() || self.is_basic_header() || self.is_ascii_header()
+        self.header_kind().is_some()
     }
 
     /// Returns the file name in case of a binary, ascii or basic header, `None` otherwise.
     pub fn file_name(&self) -> Option<&str> {
-        if self.is_bin_header() || self.is_basic_header() || self.is_ascii_header() {
-            let name = &self.data_without_prefix()[10..16];
-            let whites: &[_] = &['\0', ' '];
-            from_utf8(name).ok().map(|n| n.trim_end_matches(whites))
+        if self.is_file_header() {
+            self.header_name()
         } else {
             None
         }
     }
+
+    fn header_name(&self) -> Option<&str> {
+        let name = self.data_without_prefix().get(10..16)?;
+        let whites: &[_] = &['\0', ' '];
+        from_utf8(name).ok().map(|n| n.trim_end_matches(whites))
+    }
+}
+
+/// The kind of a file header block
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeaderKind {
+    /// A binary file header, with ID byte `0xd0`
+    Bin,
+    /// A Basic file header, with ID byte `0xd3`
+    Basic,
+    /// An ASCII file header, with ID byte `0xea`
+    Ascii,
+    /// A non-standard file header with the given ID byte, used by some tools and games
+    Other(u8),
+}
+
+impl HeaderKind {
+    /// Returns the kind of header identified by the given ID byte, if any.
+    pub fn from_id_byte(byte: u8) -> Option<HeaderKind> {
+        match byte {
+            0xd0 => Some(HeaderKind::Bin),
+            0xd3 => Some(HeaderKind::Basic),
+            0xea => Some(HeaderKind::Ascii),
+            _ => None,
+        }
+    }
+
+    /// Returns the byte that identifies this kind of header.
+    pub fn id_byte(self) -> u8 {
+        match self {
+            HeaderKind::Bin => 0xd0,
+            HeaderKind::Basic => 0xd3,
+            HeaderKind::Ascii => 0xea,
+            HeaderKind::Other(id) => id,
+        }
+    }
+
+    /// Returns the name of this kind of header: `bin`, `basic`, `ascii` or `other`.
+    pub fn name(self) -> &'static str {
+        match self {
+            HeaderKind::Bin => "bin",
+            HeaderKind::Basic => "basic",
+            HeaderKind::Ascii => "ascii",
+            HeaderKind::Other(_) => "other",
+        }
+    }
+}
+
+impl fmt::Display for HeaderKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+/// A change made to a tape to turn it into its canonical form (see `Tape::normalize()`) or
+/// to defragment it
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    /// The block was read with the given non-standard sync sequence
+    Sync { block: usize, sync: [u8; 8] },
+    /// The given number of bytes were removed from the end of the block
+    Trimmed { block: usize, bytes: usize },
+    /// The given number of bytes of the block were set or added as padding
+    Padded { block: usize, bytes: usize, byte: u8 },
+    /// The given number of bytes before the first block were removed
+    Leading { bytes: usize },
+    /// The block was moved from the given offset to the given 8-byte aligned one
+    Aligned { block: usize, from: usize, to: usize },
+    /// The implausible addresses (BEGIN, END and START) of the binary file in the block were
+    /// replaced by the inferred ones
+    Addresses { block: usize, from: (usize, usize, usize), to: (usize, usize, usize) },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            Change::Sync { block, sync } => {
+                let sync: Vec<String> = sync.iter().map(|b| format!("{:02X}", b)).collect();
+                tr!("block {}: replaced sync sequence {} by the standard one", block, sync.join(" "))
+            }
+            Change::Trimmed { block, bytes } => tr!("block {}: removed {} bytes at the end", block, bytes),
+            Change::Padded { block, bytes, byte } => {
+                tr!("block {}: set {} padding bytes to {}", block, bytes, format!("0x{:02x}", byte))
+            }
+            Change::Leading { bytes } => tr!("removed {} bytes before the first block", bytes),
+            Change::Aligned { block, from, to } => {
+                tr!("block {}: moved from offset {} to offset {}, at an 8-byte boundary", block, from, to)
+            }
+            Change::Addresses { block, from, to } => tr!(
+                "block {}: replaced the addresses {} by {}",
+                block,
+                format!("[0x{:x},0x{:x}]:0x{:x}", from.0, from.1, from.2),
+                format!("[0x{:x},0x{:x}]:0x{:x}", to.0, to.1, to.2)),
+        };
+        write!(f, "{}", text)
+    }
 }
 
 /// A file contained in a tape
@@ -104,6 +281,8 @@ impl Block {
 /// * Custom files. They contain arbitrary data generated by a program using direct calls to
 ///   casette IO addresses. Its contents cannot be processed from Basic but loaded from the
 ///   program that generates them in a custom way.
+/// * Other files. They have a header with a non-standard ID byte, registered when the files
+///   are iterated (see `Tape::files_with()`), followed by a single data block.
 ///
 /// `File` instances are generated in iteration from `files()` function of `Tape` type.
 ///
@@ -113,21 +292,55 @@ pub enum File<'a> {
     Basic(String, &'a [u8]),
     Ascii(String, Vec<&'a [u8]>),
     Custom(&'a [u8]),
+    Other(u8, String, &'a [u8]),
 }
 
 impl<'a> File<'a> {
     /// Returns the name of this file, or `None` if it has no name.
     pub fn name(&self) -> Option<String> {
         match self {
-            &File::Bin(ref name, _, _, _, _) => {
+            File::Bin(name, _, _, _, _) => {
                 Some(format!("{}.bin", File::normalized_name(name)))
             }
-            &File::Basic(ref name, _) => Some(format!("{}.bas", File::normalized_name(name))),
-            &File::Ascii(ref name, _) => Some(format!("{}.asc", File::normalized_name(name))),
+            File::Basic(name, _) => Some(format!("{}.bas", File::normalized_name(name))),
+            File::Ascii(name, _) => Some(format!("{}.asc", File::normalized_name(name))),
+            File::Other(id, name, _) => Some(format!("{}.{:02x}", File::normalized_name(name), id)),
             _ => None,
         }
     }
 
+    /// Returns the content of this file as it is stored out of the tape.
+    ///
+    /// Binary files are prefixed with the ID byte `0xfe` not present in the tape, and
+    /// ASCII files are cut at the first EOF byte.
+    pub fn content(&self) -> Vec<u8> {
+        let mut content = Vec::new();
+        self.write_to(&mut content).unwrap();
+        content
+    }
+
+    /// Write the content of this file as it is stored out of the tape (see `content()`).
+    ///
+    /// The content is written straight from the blocks of the tape in chunks, without
+    /// copying it into memory first, so big files are written with flat memory usage.
+    pub fn write_to<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        let chunks = match self {
+            File::Bin(_, _, _, _, data) => {
+                output.write_all(&[0xfe])?;
+                vec![*data]
+            }
+            File::Basic(_, data) | File::Custom(data) | File::Other(_, _, data) => vec![*data],
+            File::Ascii(_, chunks) => chunks
+                .iter()
+                .map(|chunk| &chunk[..chunk.iter().position(|b| *b == 0x1a).unwrap_or(chunk.len())])
+                .collect(),
+        };
+        for chunk in chunks.iter().flat_map(|data| data.chunks(CHUNK_SIZE)) {
+            output.write_all(chunk)?;
+        }
+        Ok(())
+    }
+
     fn normalized_name(name: &str) -> String {
         if name.trim().is_empty() {
             "noname".to_string()
@@ -137,52 +350,121 @@ impl<'a> File<'a> {
     }
 }
 
-/// An iterator over the files of a `Tape`
-pub struct Files<'a> {
+/// A file of a `Tape`, located by the blocks it spans but not read yet
+///
+/// Locating the files of a tape only reads their headers (and the data blocks of ASCII
+/// files, which end with an EOF byte), so files may be picked by their name without
+/// reading the rest.
+pub struct Entry<'a> {
+    kind: Option<HeaderKind>,
+    first: usize,
+    blocks: &'a [Block],
+}
+
+impl<'a> Entry<'a> {
+    /// Returns the name stored in the header of this file, or `None` if it is a custom file.
+    pub fn header_name(&self) -> Option<&'a str> {
+        self.kind.and_then(|_| self.blocks[0].header_name())
+    }
+
+    /// Returns `true` if this file has the given name, either as stored in its header
+    /// (e.g., `game`) or as it is extracted (e.g., `game.bin`).
+    pub fn is_named(&self, name: &str) -> bool {
+        self.header_name().is_some_and(|n| n == name) || self.file().name().is_some_and(|n| n == name)
+    }
+
+    /// Returns the kind of the header of this file, or `None` if it is a custom file.
+    pub fn kind(&self) -> Option<HeaderKind> {
+        self.kind
+    }
+
+    /// Returns the blocks spanned by this file.
+    pub fn blocks(&self) -> &'a [Block] {
+        self.blocks
+    }
+
+    /// Returns the positions in the tape of the blocks spanned by this file, counted from 0.
+    pub fn block_range(&self) -> Range<usize> {
+        self.first..self.first + self.blocks.len()
+    }
+
+    /// Returns the number of bytes padding the content of this file at the end of its blocks.
+    ///
+    /// These are the bytes past the END address in the data block of binary files, the bytes
+    /// after the first EOF in the last block of ASCII files, and the trailing zeroes that align
+    /// the last block of other files, as `mcp add` pads them.
+    pub fn padding(&self) -> usize {
+        let last = match self.blocks.last() {
+            Some(block) if self.kind.is_none() || self.blocks.len() > 1 => block.data_without_prefix(),
+            _ => return 0,
+        };
+        let zeroes = |max: usize| last.iter().rev().take(max).take_while(|b| **b == 0).count();
+        match self.kind {
+            Some(HeaderKind::Bin) => bin_data_len(last).map(|len| last.len().saturating_sub(len)).unwrap_or(0),
+            Some(HeaderKind::Ascii) => last.iter().position(|b| *b == 0x1a).map(|eof| last.len() - eof - 1).unwrap_or(0),
+            // BASIC programs end with three zeroes of their own
+            Some(HeaderKind::Basic) => zeroes(10).saturating_sub(3),
+            _ => zeroes(7),
+        }
+    }
+
+    /// Read this file.
+    pub fn file(&self) -> File<'a> {
+        let data = |i: usize| self.blocks.get(i).map(|b| b.data_without_prefix()).unwrap_or_default();
+        let name = || self.header_name().unwrap_or_default().to_string();
+        match self.kind {
+            Some(HeaderKind::Bin) => {
+                let content = data(1);
+                let address = |i: usize| content.get(i..i + 2).map(LittleEndian::read_u16).unwrap_or(0) as usize;
+                File::Bin(name(), address(0), address(2), address(4), content)
+            }
+            Some(HeaderKind::Basic) => File::Basic(name(), data(1)),
+            Some(HeaderKind::Ascii) => File::Ascii(name(), (1..self.blocks.len()).map(data).collect()),
+            Some(HeaderKind::Other(id)) => File::Other(id, name(), data(1)),
+            None => File::Custom(data(0)),
+        }
+    }
+}
+
+/// An iterator over the entries of the files of a `Tape`
+pub struct Entries<'a> {
     tape: &'a Tape,
+    extra_ids: &'a [u8],
     i: usize,
 }
 
+impl<'a> Iterator for Entries<'a> {
+    type Item = Entry<'a>;
+
+    fn next(&mut self) -> Option<Entry<'a>> {
+        let blocks = &self.tape.blocks[self.i..];
+        let kind = blocks.first()?.header_kind_with(self.extra_ids);
+        let len = match kind {
+            // Data blocks up to the one with the EOF byte
+            Some(HeaderKind::Ascii) => match blocks[1..].iter().position(|b| b.data_without_prefix().contains(&0x1a)) {
+                Some(last) => last + 2,
+                None => blocks.len(),
+            },
+            Some(_) => 2,
+            None => 1,
+        };
+        let len = len.min(blocks.len());
+        let first = self.i;
+        self.i += len;
+        Some(Entry { kind, first, blocks: &blocks[..len] })
+    }
+}
+
+/// An iterator over the files of a `Tape`
+pub struct Files<'a> {
+    entries: Entries<'a>,
+}
+
 impl<'a> Iterator for Files<'a> {
     type Item = File<'a>;
 
     fn next(&mut self) -> Option<File<'a>> {
-        let nblocks = self.tape.blocks.len();
-        while self.i < nblocks {
-            let block = &self.tape.blocks[self.i];
-            if block.is_bin_header() {
-                let name = block.file_name().unwrap().to_string();
-                let content = &self.tape.blocks[self.i + 1].data_without_prefix();
-                let begin = LittleEndian::read_u16(&content[0..2]) as usize;
-                let end = LittleEndian::read_u16(&content[2..4]) as usize;
-                let start = LittleEndian::read_u16(&content[4..6]) as usize;
-                let data = &content[..];
-                self.i += 2;
-                return Some(File::Bin(name, begin, end, start, data));
-            } else if block.is_basic_header() {
-                let name = block.file_name().unwrap().to_string();
-                let content = &self.tape.blocks[self.i + 1].data_without_prefix();
-                self.i += 2;
-                return Some(File::Basic(name, &content[..]));
-            } else if block.is_ascii_header() {
-                let name = block.file_name().unwrap().to_string();
-                let mut data = Vec::<&[u8]>::new();
-                self.i += 1;
-                while {
-                    let chunk = &self.tape.blocks[self.i].data_without_prefix();
-                    data.push(chunk);
-                    self.i < nblocks && !chunk.contains(&0x1a)
-                } {
-                    self.i += 1
-                }
-                self.i += 1;
-                return Some(File::Ascii(name, data));
-            } else {
-                self.i += 1;
-                return Some(File::Custom(&block.data_without_prefix()[..]));
-            }
-        }
-        None
+        self.entries.next().map(|entry| entry.file())
     }
 }
 
@@ -191,7 +473,7 @@ impl<'a> Iterator for Files<'a> {
 /// A tape is a sequence of byte blocks (see `Blocks` for more details). The blocks may be
 /// grouped such as the tape is seen as a sequence of files through `files()` method.
 ///
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Tape {
     blocks: Vec<Block>,
 }
@@ -202,9 +484,11 @@ impl Tape {
         Tape { blocks: vec![] }
     }
 
+    /// Read a `Tape` instance from the given file.
+    ///
+    /// The file is mapped into memory rather than read, so big tapes are parsed right away.
     pub fn from_file(filename: &Path) -> io::Result<Tape> {
-        let mut file = fs::File::open(filename)?;
-        Tape::read(&mut file)
+        Ok(Tape::from_source(Arc::new(Mapping::open(filename)?), &[]))
     }
 
     /// Read a `Tape` instance from the given `Read` object.
@@ -217,7 +501,7 @@ impl Tape {
     pub fn read<R: Read>(input: &mut R) -> io::Result<Tape> {
         let mut bytes: Vec<u8> = vec![];
         input.read_to_end(&mut bytes)?;
-        Ok(Tape::from_bytes(&bytes[..]))
+        Ok(Tape::from_source(Arc::new(bytes), &[]))
     }
 
     /// Read a `Tape` instance from the given bytes.
@@ -225,8 +509,50 @@ impl Tape {
     /// This function returns a new `Tape` instance as result of processing the bytes passed
     /// as argument.
     pub fn from_bytes(bytes: &[u8]) -> Tape {
+        Tape::from_bytes_with(bytes, &[])
+    }
+
+    /// Read a `Tape` instance from the given bytes, accepting the given sync sequences as
+    /// block prefixes besides the standard one.
+    ///
+    /// Blocks are stored with the standard prefix regardless of the one they were read
+    /// with, so saving the tape normalizes it.
+    pub fn from_bytes_with(bytes: &[u8], syncs: &[[u8; 8]]) -> Tape {
+        Tape::from_source(Arc::new(bytes.to_vec()), syncs)
+    }
+
+    /// Read a `Tape` instance from the given shared bytes, accepting the given sync sequences
+    /// besides the standard block prefix.
+    ///
+    /// The blocks of the tape refer to the given bytes instead of copying them.
+    pub fn from_source(source: Source, syncs: &[[u8; 8]]) -> Tape {
         Tape {
-            blocks: Tape::parse_blocks(bytes),
+            blocks: Tape::parse_blocks(&source, syncs),
+        }
+    }
+
+    /// Returns the bytes of this tape, as they are saved into a file.
+    ///
+    /// If the tape is made of the whole bytes it was parsed from, as they were, these bytes
+    /// are returned without copying them.
+    pub fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut next = 0;
+        let mut whole = None;
+        for block in &self.blocks {
+            match &block.data {
+                Data::Shared(source, range) if range.start == next && whole.is_none_or(|s| Arc::ptr_eq(s, source)) => {
+                    next = range.end;
+                    whole = Some(source);
+                }
+                _ => {
+                    whole = None;
+                    break;
+                }
+            }
+        }
+        match whole {
+            Some(source) if next == (**source).as_ref().len() => Cow::Borrowed((**source).as_ref()),
+            _ => Cow::Owned(self.blocks.iter().flat_map(|b| b.data()).cloned().collect()),
         }
     }
 
@@ -239,8 +565,338 @@ impl Tape {
     ///
     /// This function returns an `Iterator` over the files found in the tape blocks.
     ///
-    pub fn files(&self) -> Files {
-        Files { tape: self, i: 0 }
+    pub fn files(&self) -> Files<'_> {
+        self.files_with(&[])
+    }
+
+    /// Return the files contained in the tape, considering the given ID bytes as
+    /// non-standard file headers besides the standard ones.
+    pub fn files_with<'a>(&'a self, extra_ids: &'a [u8]) -> Files<'a> {
+        Files { entries: self.entries_with(extra_ids) }
+    }
+
+    /// Return the entries of the files contained in the tape, considering the given ID bytes
+    /// as non-standard file headers besides the standard ones.
+    ///
+    /// Unlike `files_with()`, the files are located but not read.
+    pub fn entries_with<'a>(&'a self, extra_ids: &'a [u8]) -> Entries<'a> {
+        Entries { tape: self, extra_ids, i: 0 }
+    }
+
+    /// Returns the error of a file with the given name not found in the tape, suggesting
+    /// the names of the files that are close to it (a few typos away).
+    pub fn no_file_named(&self, name: &str, extra_ids: &[u8]) -> io::Error {
+        // Names are compared as stored in the headers: six characters without extension
+        let stem = name.split('.').next().unwrap_or_default();
+        let stem = &stem[..stem.char_indices().nth(6).map(|(i, _)| i).unwrap_or(stem.len())];
+        let mut similar: Vec<(usize, String)> = self
+            .entries_with(extra_ids)
+            .filter_map(|e| Some((edit_distance(stem, e.header_name()?), e.file().name()?)))
+            .filter(|(distance, _)| *distance <= MAX_TYPOS.min(stem.len().saturating_sub(1)))
+            .collect();
+        similar.sort();
+        similar.dedup_by(|a, b| a.1 == b.1);
+        let names: Vec<String> = similar.into_iter().take(3).map(|(_, n)| n).collect();
+        let message = match names.len() {
+            0 => tr!("no file named {} in the tape", name),
+            _ => tr!("no file named {} in the tape, did you mean {}?", name, names.join(crate::i18n::tr(" or "))),
+        };
+        io::Error::new(io::ErrorKind::NotFound, message)
+    }
+
+    /// Returns the bytes at the end of the tape that do not belong to any block or file.
+    ///
+    /// These are the bytes past the last 8-byte boundary, where no block may end, and the
+    /// bytes that follow the program (and its padding) of a binary file at the end of the
+    /// tape. They are kept at the end of the last block unless they are trimmed.
+    pub fn trailing_garbage(&self) -> &[u8] {
+        let n = self.blocks.len();
+        let last = match self.blocks.last() {
+            Some(block) => block.data_without_prefix(),
+            None => return &[],
+        };
+        let mut end = last.len() - last.len() % 8;
+        if n > 1 && self.blocks[n - 2].is_bin_header() {
+            if let Some(len) = bin_data_len(last) {
+                end = end.min(len.next_multiple_of(8));
+            }
+        }
+        &last[end..]
+    }
+
+    /// Remove the trailing garbage of the tape (see `trailing_garbage()`).
+    ///
+    /// The number of removed bytes is returned.
+    pub fn trim_trailing_garbage(&mut self) -> usize {
+        let garbage = self.trailing_garbage().len();
+        if let Some(last) = self.blocks.last_mut() {
+            let len = last.data().len() - garbage;
+            last.data_mut().truncate(len);
+        }
+        garbage
+    }
+
+    /// Turn this tape into its canonical form, returning the changes made to it.
+    ///
+    /// Tapes that only differ in how they are stored have the same canonical form, so
+    /// their bytes can be compared. In the canonical form:
+    ///
+    /// * Every block starts with the standard sync sequence.
+    /// * File headers are 16 bytes long, without garbage after the file name.
+    /// * Every block is 8-byte aligned, without trailing garbage.
+    /// * The data blocks of binary files end with the zero padding of their program, and
+    ///   the data blocks of ASCII files are filled with EOF bytes after the first one.
+    ///
+    pub fn normalize(&mut self) -> Vec<Change> {
+        // The layout of each block: bytes to keep, bytes to end with, and padding byte
+        let mut layouts = vec![];
+        for entry in self.entries_with(&[]) {
+            for (i, block) in entry.blocks.iter().enumerate() {
+                let len = block.data_without_prefix().len();
+                let layout = match (entry.kind, i) {
+                    (Some(_), 0) => (16, 16, 0),
+                    (Some(HeaderKind::Bin), 1) => match entry.file() {
+                        File::Bin(_, begin, end, _, _) if begin <= end && 6 + end - begin < len => {
+                            let keep = 6 + end - begin + 1;
+                            (keep, keep.next_multiple_of(8), 0)
+                        }
+                        _ => (len - len % 8, len - len % 8, 0),
+                    },
+                    // The bytes past the last 8-byte boundary are garbage (see `trailing_garbage()`)
+                    (Some(HeaderKind::Ascii), _) => {
+                        let data = block.data_without_prefix();
+                        let end = len - len % 8;
+                        let keep = data.iter().position(|b| *b == 0x1a).map(|eof| eof + 1).unwrap_or(len);
+                        (keep.min(end), end, 0x1a)
+                    }
+                    _ => (len - len % 8, len - len % 8, 0),
+                };
+                layouts.push(layout);
+            }
+        }
+
+        let mut changes = vec![];
+        for (i, (block, (keep, end, padding_byte))) in self.blocks.iter_mut().zip(layouts).enumerate() {
+            if let Some(sync) = block.sync.take() {
+                changes.push(Change::Sync { block: i, sync });
+            }
+            let data = block.data_without_prefix();
+            if data.len() > end {
+                changes.push(Change::Trimmed { block: i, bytes: data.len() - end });
+            }
+            let padded = (keep..end).filter(|j| data.get(*j) != Some(&padding_byte)).count();
+            if padded > 0 {
+                changes.push(Change::Padded { block: i, bytes: padded, byte: padding_byte });
+            }
+            if data.len() > end || padded > 0 {
+                let data = block.data_mut();
+                data.resize(8 + end, padding_byte);
+                data[8 + keep..].fill(padding_byte);
+            }
+        }
+        changes
+    }
+
+    /// Join the blocks in the given range into a single block.
+    ///
+    /// Only the blocks of custom files can be joined. Their data is concatenated as it is,
+    /// and the joined block is padded with zeroes to be 8-byte aligned. Returns the number
+    /// of padding bytes.
+    pub fn join_blocks(&mut self, range: RangeInclusive<usize>) -> io::Result<usize> {
+        let (from, to) = (*range.start(), *range.end());
+        if from >= to || to >= self.blocks.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                tr!("invalid range {}..{} of a tape with {} blocks", from, to, self.blocks.len())));
+        }
+        let custom: Vec<bool> = self
+            .entries_with(&[])
+            .flat_map(|entry| vec![entry.kind.is_none(); entry.blocks.len()])
+            .collect();
+        if let Some(i) = (from..=to).find(|i| !custom[*i]) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                tr!("block {} is not a custom block, only custom blocks can be joined", i)));
+        }
+        let mut data: Vec<u8> = self.blocks[range.clone()].iter().flat_map(|b| b.data_without_prefix()).copied().collect();
+        let padding = data.len().next_multiple_of(8) - data.len();
+        data.resize(data.len() + padding, 0);
+        self.blocks.splice(range, [Block::from_data(&data)]);
+        Ok(padding)
+    }
+
+    /// Remove the first file with the given name, either as stored in its header or as it is
+    /// extracted, dropping its header and data blocks.
+    ///
+    /// Returns the number of blocks removed, or an error if there is no such file.
+    pub fn remove_file(&mut self, name: &str) -> io::Result<usize> {
+        let mut start = 0;
+        for entry in self.entries_with(&[]) {
+            let len = entry.blocks().len();
+            if entry.is_named(name) {
+                self.blocks.drain(start..start + len);
+                return Ok(len);
+            }
+            start += len;
+        }
+        Err(self.no_file_named(name, &[]))
+    }
+
+    /// Rename the first file with the given name, either as stored in its header or as it is
+    /// extracted, writing the new name into its header.
+    ///
+    /// The new name must be up to six printable ASCII characters, as `set_header_name()`
+    /// requires. Returns an error if there is no such file.
+    pub fn rename_file(&mut self, name: &str, new_name: &str) -> Result<(), String> {
+        let mut block = 0;
+        for entry in self.entries_with(&[]) {
+            if entry.is_named(name) {
+                return self.set_header_name(block, new_name);
+            }
+            block += entry.blocks().len();
+        }
+        Err(self.no_file_named(name, &[]).to_string())
+    }
+
+    /// Write the given name into the file header at the given block.
+    ///
+    /// The name must be up to six printable ASCII characters, and it is padded with spaces.
+    pub fn set_header_name(&mut self, block: usize, name: &str) -> Result<(), String> {
+        if name.len() > 6 || !name.bytes().all(|b| (0x20..0x7f).contains(&b)) {
+            return Err(tr!(
+                "invalid file name {}, up to six printable ASCII characters are allowed",
+                format!("{:?}", name)));
+        }
+        let header = match self.blocks.get_mut(block) {
+            Some(header) if header.is_file_header() && header.data_without_prefix().len() >= 16 => header,
+            _ => return Err(tr!("block {} is not a file header", block)),
+        };
+        header.data_mut()[8 + 10..8 + 16].copy_from_slice(&file_name(name).0);
+        Ok(())
+    }
+
+    /// Trim the data blocks of binary files past their END address, keeping them 8-byte aligned.
+    ///
+    /// `BLOAD` stops reading at the END address, so the trimmed bytes never reach the memory.
+    /// Returns the number of bytes removed.
+    pub fn trim_bin_blocks(&mut self) -> usize {
+        let mut lens = vec![];
+        let mut i = 0;
+        for entry in self.entries_with(&[]) {
+            if entry.kind == Some(HeaderKind::Bin) && entry.blocks.len() == 2 {
+                if let Some(len) = bin_data_len(entry.blocks[1].data_without_prefix()) {
+                    lens.push((i + 1, len.next_multiple_of(8)));
+                }
+            }
+            i += entry.blocks.len();
+        }
+        let mut trimmed = 0;
+        for (i, len) in lens {
+            let block = &mut self.blocks[i];
+            if block.data_without_prefix().len() > len {
+                trimmed += block.data_without_prefix().len() - len;
+                block.data_mut().truncate(8 + len);
+            }
+        }
+        trimmed
+    }
+
+    /// Replace the implausible addresses of binary files by the ones inferred from their data
+    /// and the binary files around them, returning the changes made.
+    ///
+    /// Addresses are implausible when END is before BEGIN, or when they are the same but the
+    /// data block holds more than one byte of program. The program is taken to be the whole data block
+    /// (padding included), and to be loaded at its BEGIN address unless it is zero, in which
+    /// case it is loaded right after the previous binary file, or right before the next one.
+    /// START is set to BEGIN unless it is within the program. The files whose addresses cannot
+    /// be inferred are left as they are.
+    ///
+    pub fn recover_bin_headers(&mut self) -> Vec<Change> {
+        // The data block of every binary file, with its addresses and the length of its program
+        let mut bins = vec![];
+        let mut i = 0;
+        for entry in self.entries_with(&[]) {
+            if entry.kind == Some(HeaderKind::Bin) && entry.blocks.len() == 2 {
+                let data = entry.blocks[1].data_without_prefix();
+                if data.len() > 6 {
+                    let addr = |at: usize| LittleEndian::read_u16(&data[at..at + 2]) as usize;
+                    bins.push((i + 1, (addr(0), addr(2), addr(4)), data.len() - 6));
+                }
+            }
+            i += entry.blocks.len();
+        }
+
+        let mut changes = vec![];
+        for n in 0..bins.len() {
+            let (block, (begin, end, start), len) = bins[n];
+            if begin < end || (begin == end && len <= 2) {
+                continue;
+            }
+            let plausible = |(begin, end, _): &(usize, usize, usize)| begin <= end;
+            let prev = n.checked_sub(1).map(|prev| bins[prev].1).filter(plausible);
+            let next = bins.get(n + 1).map(|next| next.1).filter(plausible);
+            let begin = match (begin, prev, next) {
+                (0, Some((_, prev_end, _)), _) => prev_end + 1,
+                (0, None, Some((next_begin, _, _))) if next_begin >= len => next_begin - len,
+                (0, _, _) => continue,
+                (begin, _, _) => begin,
+            };
+            let end = begin + len - 1;
+            if end > 0xffff {
+                continue;
+            }
+            let start = if (begin..=end).contains(&start) { start } else { begin };
+            let data = self.blocks[block].data_mut();
+            for (at, addr) in [(8, begin), (10, end), (12, start)] {
+                LittleEndian::write_u16(&mut data[at..at + 2], addr as u16);
+            }
+            changes.push(Change::Addresses { block, from: bins[n].1, to: (begin, end, start) });
+            bins[n].1 = (begin, end, start);
+        }
+        changes
+    }
+
+    /// Returns the runs of two or more consecutive blocks of custom files.
+    pub fn custom_runs(&self) -> Vec<RangeInclusive<usize>> {
+        let mut runs = vec![];
+        let mut start = None;
+        let mut i = 0;
+        for entry in self.entries_with(&[]) {
+            match (entry.kind, start) {
+                (None, None) => start = Some(i),
+                (Some(_), Some(from)) => {
+                    if i - from > 1 {
+                        runs.push(from..=i - 1);
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+            i += entry.blocks.len();
+        }
+        if let Some(from) = start.filter(|from| i - from > 1) {
+            runs.push(from..=i - 1);
+        }
+        runs
+    }
+
+    /// Returns the data of the blocks of this tape without the padding of its files.
+    ///
+    /// These are the bytes read by MSX computers, regardless of how the tape was dumped or
+    /// converted, as converters do not agree on padding.
+    pub fn logical_blocks(&self) -> Vec<&[u8]> {
+        let mut blocks = vec![];
+        for entry in self.entries_with(&[]) {
+            let padding = entry.padding();
+            for (i, block) in entry.blocks.iter().enumerate() {
+                let data = block.data_without_prefix();
+                match i + 1 == entry.blocks.len() {
+                    true => blocks.push(&data[..data.len() - padding]),
+                    false => blocks.push(data),
+                }
+            }
+        }
+        blocks
     }
 
     /// Append a binary file to this tape
@@ -252,20 +908,11 @@ impl Tape {
     ///   obtain it from a regular string.
     /// * `data`: the binary file content
     ///
+    /// The content is not validated. Use `policy::check_bin()` to check it beforehand.
+    ///
     pub fn append_bin(&mut self, name: &[u8; 6], data: &[u8]) -> io::Result<usize> {
-        // Skip bin file ID byte if present
-        let bytes = if data[0] == 0xfe {
-            &data[1..]
-        } else {
-            &data[..]
-        };
-
-        Self::validate_bin(&bytes)?;
-
-        let hblock = Block::from_data(&[
-            0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, name[0], name[1], name[2],
-            name[3], name[4], name[5],
-        ]);
+        let bytes = without_id_byte(data, 0xfe);
+        let hblock = Block::file_header(HeaderKind::Bin, name);
         let dblock = Block::from_data(bytes);
 
         self.append_block(hblock, 8, 0);
@@ -281,20 +928,11 @@ impl Tape {
     ///   obtain it from a regular string.
     /// * `data`: the binary file content
     ///
+    /// The content is not validated. Use `policy::check_basic()` to check it beforehand.
+    ///
     pub fn append_basic(&mut self, name: &[u8; 6], data: &[u8]) -> io::Result<usize> {
-        // Skip tokenized basic file ID byte if present
-        let bytes = if data[0] == 0xff {
-            &data[1..]
-        } else {
-            &data[..]
-        };
-
-        Self::validate_basic(&bytes)?;
-
-        let hblock = Block::from_data(&[
-            0xd3, 0xd3, 0xd3, 0xd3, 0xd3, 0xd3, 0xd3, 0xd3, 0xd3, 0xd3, name[0], name[1], name[2],
-            name[3], name[4], name[5],
-        ]);
+        let bytes = without_id_byte(data, 0xff);
+        let hblock = Block::file_header(HeaderKind::Basic, name);
         let dblock = Block::from_data(bytes);
         self.append_block(hblock, 8, 0);
         Ok(self.append_block(dblock, 8, 0))
@@ -315,18 +953,12 @@ impl Tape {
     /// If the text length is a multiple of 256, the last block is 256 EOF bytes.
     ///
     pub fn append_ascii(&mut self, name: &[u8; 6], data: &[u8]) -> io::Result<usize> {
-        let hblock = Block::from_data(&[
-            0xea, 0xea, 0xea, 0xea, 0xea, 0xea, 0xea, 0xea, 0xea, 0xea, name[0], name[1], name[2],
-            name[3], name[4], name[5],
-        ]);
+        let hblock = Block::file_header(HeaderKind::Ascii, name);
         self.append_block(hblock, 8, 0);
 
         let mut padding = 0;
         for chunk in data.chunks(256) {
-            let dblock = Block::from_data(chunk);
-            if chunk.len() < 256 {
-                padding += self.append_block(dblock, 256, 0x1a);
-            }
+            padding = self.append_block(Block::from_data(chunk), 256, 0x1a);
         }
         if padding == 0 {
             // Last block had no free space to pad it with EOFs.
@@ -336,22 +968,46 @@ impl Tape {
         Ok(padding)
     }
 
+    /// Insert the blocks of the given tape before the blocks of this one.
+    pub fn prepend(&mut self, tape: Tape) {
+        self.blocks.splice(0..0, tape.blocks);
+    }
+
     /// Append a custom file to the tape.
     pub fn append_custom(&mut self, data: &[u8]) -> io::Result<usize> {
-        Ok(self.append_block(Block::from_data(data), 8, 0))
+        Ok(self.append_raw_block(data))
     }
 
-    fn parse_blocks(bytes: &[u8]) -> Vec<Block> {
+    /// Append a custom file to the tape, split into consecutive blocks of the given size.
+    ///
+    /// Only the last block is padded, as long as the block size is a multiple of 8.
+    pub fn append_custom_blocks(&mut self, data: &[u8], block_size: usize) -> io::Result<usize> {
+        if data.is_empty() {
+            return self.append_custom(data);
+        }
+        Ok(data.chunks(block_size).map(|chunk| self.append_raw_block(chunk)).sum())
+    }
+
+    /// Append a block with the given data (without the prefix bytes) to the tape.
+    ///
+    /// The block is padded with zeroes to be 8-byte aligned, and the number of padding
+    /// bytes is returned.
+    pub fn append_raw_block(&mut self, data: &[u8]) -> usize {
+        self.append_block(Block::from_data(data), 8, 0)
+    }
+
+    fn parse_blocks(source: &Source, syncs: &[[u8; 8]]) -> Vec<Block> {
+        let bytes = (**source).as_ref();
         let mut blocks: Vec<Block> = vec![];
-        let mut hindex: Vec<usize> = vec![];
-        let mut i = 0;
 
         // First of all, we compute the indices of all block headers.
-        for chunk in bytes.chunks(8) {
-            if chunk == [0x1f, 0xa6, 0xde, 0xba, 0xcc, 0x13, 0x7d, 0x74] {
-                hindex.push(i);
-            }
-            i = i + 8;
+        let mut hindex = sync_offsets(bytes, &BLOCK_PREFIX);
+        for sync in syncs {
+            hindex.extend(sync_offsets(bytes, sync));
+        }
+        if !syncs.is_empty() {
+            hindex.sort_unstable();
+            hindex.dedup();
         }
 
         // Now we use the block header indices to generate the blocks
@@ -362,7 +1018,14 @@ impl Tape {
             } else {
                 hindex[i + 1]
             };
-            let block = Block::from_data(&bytes[from..to]);
+            // Blocks read with another sync are copied to store them with the standard one
+            let block = if bytes[hindex[i]..from] == BLOCK_PREFIX {
+                Block::shared(source, hindex[i]..to)
+            } else {
+                let mut sync = [0; 8];
+                sync.copy_from_slice(&bytes[hindex[i]..from]);
+                Block { sync: Some(sync), ..Block::from_data(&bytes[from..to]) }
+            };
             blocks.push(block);
         }
         blocks
@@ -377,74 +1040,90 @@ impl Tape {
         if let Some(last_block) = self.blocks.last_mut() {
             let mut n = 0;
             while last_block.data_without_prefix().len() % align != 0 {
-                last_block.data.push(padding_byte);
-                n = n + 1;
+                last_block.data_mut().push(padding_byte);
+                n += 1;
             }
             return n;
         }
         0
     }
+}
 
-    fn validate_basic(data: &[u8]) -> io::Result<()> {
-        if data.len() < 2 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!(
-                    "invalid basic file: it is too short ({} bytes) to contain a basic program",
-                    data.len(),
-                ),
-            ));
+/// Returns the offsets of the given sync sequence in the given bytes, as long as they are
+/// 8-byte aligned (as every block of a tape is).
+fn sync_offsets(bytes: &[u8], sync: &[u8; 8]) -> Vec<usize> {
+    let finder = memmem::Finder::new(sync);
+    let mut offsets = vec![];
+    let mut i = 0;
+    while let Some(found) = finder.find(&bytes[i..]) {
+        let offset = i + found;
+        if offset.is_multiple_of(8) {
+            offsets.push(offset);
+            i = offset + 8;
+        } else {
+            i = offset + 1;
         }
-        Ok(())
     }
+    offsets
+}
 
-    fn validate_bin(data: &[u8]) -> io::Result<()> {
-        if data.len() < 6 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!(
-                    "invalid binary file: it is too short ({} bytes) to even have a header",
-                    data.len(),
-                ),
-            ));
-        }
+/// Returns the given file content without its leading ID byte (`0xfe` for binary files and
+/// `0xff` for tokenized Basic files), if present.
+pub fn without_id_byte(data: &[u8], id: u8) -> &[u8] {
+    match data.first() {
+        Some(byte) if *byte == id => &data[1..],
+        _ => data,
+    }
+}
 
-        let begin = LittleEndian::read_u16(&data[0..2]) as usize;
-        let end = LittleEndian::read_u16(&data[2..4]) as usize;
-        let start = LittleEndian::read_u16(&data[4..6]) as usize;
-        if begin > end {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!(
-                    "invalid binary file header: BEGIN address {:X} must be below END address {:X}",
-                    begin, end,
-                ),
-            ));
-        }
-        let len = (end - begin) + 1;
-        let prog_size = data.len() - 6;
-        if len > prog_size {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!(
-                    "invalid binary file header: BEGIN and END addresses reveal a length ({} bytes) larger than program size ({} bytes)", 
-                    len, prog_size,
-                ),
-            ));
-        }
+/// Returns the length of the program in the given data block of a binary file, with its
+/// addresses, or `None` if its addresses are not valid.
+fn bin_data_len(data: &[u8]) -> Option<usize> {
+    if data.len() < 6 {
+        return None;
+    }
+    let begin = LittleEndian::read_u16(&data[0..2]) as usize;
+    let end = LittleEndian::read_u16(&data[2..4]) as usize;
+    if begin <= end {
+        Some(6 + end - begin + 1)
+    } else {
+        None
+    }
+}
 
-        if start < begin || end < start {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!(
-                    "invalid binary file header: START address {:X} is not between BEGIN address {:X} and END address {:X}",                    
-                    start, begin, end,
-                ),
-            ));
-        }
+/// Returns the region of the memory of a MSX computer the given address lies in, as
+/// seen by a program loaded with `BLOAD` (BIOS and BASIC in ROM, user RAM and the system
+/// work area).
+pub fn memory_region(addr: usize) -> &'static str {
+    match addr {
+        0x0000..=0x3fff => "BIOS ROM, page 0",
+        0x4000..=0x7fff => "BASIC ROM, page 1",
+        0x8000..=0xbfff => "RAM, page 2",
+        0xc000..=0xf37f => "RAM, page 3",
+        _ => "system area, page 3",
+    }
+}
 
-        Ok(())
+/// Returns the number of characters to insert, remove, replace or swap with the next one
+/// to turn one of the given strings into the other, ignoring case.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    d[0] = (0..=b.len()).collect();
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
     }
+    d[a.len()][b.len()]
 }
 
 /// Converts a string into a tape filename
@@ -460,9 +1139,7 @@ pub fn file_name(s: &str) -> ([u8; 6], bool) {
 
     let mut name: [u8; 6] = [0x20; 6];
     let bytes = &s.as_bytes()[..last];
-    for i in 0..last {
-        name[i] = bytes[i]
-    }
+    name[..last].copy_from_slice(bytes);
     (name, s.len() > last)
 }
 
@@ -474,6 +1151,8 @@ mod test {
 
     use quickcheck::{quickcheck, TestResult};
 
+    use crate::policy;
+
     use super::*;
 
     macro_rules! assert_bin {
@@ -522,11 +1201,11 @@ mod test {
         let data = block.data();
         require_prop!(
             "prefix bytes are present",
-            &data[0..8] == [0x1f, 0xa6, 0xde, 0xba, 0xcc, 0x13, 0x7d, 0x74]
+            data[0..8] == [0x1f, 0xa6, 0xde, 0xba, 0xcc, 0x13, 0x7d, 0x74]
         );
         require_prop!(
             "data is present",
-            &data[8..] == &bytes[..] && block.data_without_prefix() == &bytes[..]
+            data[8..] == bytes[..] && block.data_without_prefix() == &bytes[..]
         );
         TestResult::from_bool(true)
     }
@@ -544,6 +1223,7 @@ mod test {
         ];
         let block = Block::from_data(&bytes);
         assert!(block.is_bin_header());
+        assert_eq!(Some(HeaderKind::Bin), block.header_kind());
         assert_eq!("FOOBAR", block.file_name().unwrap());
     }
 
@@ -555,6 +1235,7 @@ mod test {
         ];
         let block = Block::from_data(&bytes);
         assert!(block.is_basic_header());
+        assert_eq!(Some(HeaderKind::Basic), block.header_kind());
         assert_eq!("FOOBAR", block.file_name().unwrap());
     }
 
@@ -566,6 +1247,7 @@ mod test {
         ];
         let block = Block::from_data(&bytes);
         assert!(block.is_ascii_header());
+        assert_eq!(Some(HeaderKind::Ascii), block.header_kind());
         assert_eq!("FOOBAR", block.file_name().unwrap());
     }
 
@@ -576,9 +1258,28 @@ mod test {
             0x00, 0x00,
         ];
         let block = Block::from_data(&bytes);
+        assert_eq!(None, block.header_kind());
+        assert_eq!(None, block.file_name());
+    }
+
+    #[test]
+    fn should_not_detect_header_with_mixed_id_bytes() {
+        let bytes: Vec<u8> = vec![0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xea];
+        let block = Block::from_data(&bytes);
+        assert_eq!(None, block.header_kind());
+        assert!(!block.is_file_header());
         assert_eq!(None, block.file_name());
     }
 
+    #[test]
+    fn should_map_header_kinds_and_id_bytes() {
+        for kind in &[HeaderKind::Bin, HeaderKind::Basic, HeaderKind::Ascii] {
+            assert_eq!(Some(*kind), HeaderKind::from_id_byte(kind.id_byte()));
+        }
+        assert_eq!(None, HeaderKind::from_id_byte(0x00));
+        assert_eq!("basic", HeaderKind::Basic.to_string());
+    }
+
     #[test]
     fn should_detect_block_with_short_name() {
         let bytes: Vec<u8> = vec![
@@ -603,9 +1304,9 @@ mod test {
                 return TestResult::discard();
             }
             bytes
-                .write(&[0x1f, 0xa6, 0xde, 0xba, 0xcc, 0x13, 0x7d, 0x74])
+                .write_all(&[0x1f, 0xa6, 0xde, 0xba, 0xcc, 0x13, 0x7d, 0x74])
                 .unwrap();
-            bytes.write(&block[..]).unwrap();
+            bytes.write_all(&block[..]).unwrap();
         }
         let tape = Tape::from_bytes(&bytes);
 
@@ -672,12 +1373,388 @@ mod test {
         );
     }
 
+    #[test]
+    fn should_load_tape_with_non_standard_headers() {
+        let mut tape = Tape::new();
+        tape.append_raw_block(&[0xc5, 0xc5, 0xc5, 0xc5, 0xc5, 0xc5, 0xc5, 0xc5, 0xc5, 0xc5, 0x4c, 0x45, 0x56, 0x45, 0x4c, 0x31]);
+        tape.append_raw_block(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        assert_eq!(2, tape.files().count());
+        assert_eq!(None, tape.blocks()[0].header_kind());
+
+        let files = Vec::from_iter(tape.files_with(&[0xc5]));
+        assert_eq!(
+            vec![File::Other(0xc5, "LEVEL1".to_string(), &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08])],
+            files);
+        assert_eq!("LEVEL1.c5", files[0].name().unwrap());
+        assert_eq!(Some(HeaderKind::Other(0xc5)), tape.blocks()[0].header_kind_with(&[0xc5]));
+    }
+
+    #[test]
+    fn should_write_files_in_chunks() {
+        let data: Vec<u8> = (0..3 * CHUNK_SIZE + 5).map(|i| i as u8).collect();
+        let mut output = vec![];
+        File::Custom(&data).write_to(&mut output).unwrap();
+        assert_eq!(data, output);
+
+        let mut output = vec![];
+        File::Bin("foo".to_string(), 0x8000, 0x8001, 0x8000, &data).write_to(&mut output).unwrap();
+        assert_eq!(0xfe, output[0]);
+        assert_eq!(&data[..], &output[1..]);
+
+        let chunks: Vec<&[u8]> = vec![b"10 PRINT", b"\r\n\x1a\x1a\x1a"];
+        let mut output = vec![];
+        File::Ascii("bar".to_string(), chunks).write_to(&mut output).unwrap();
+        assert_eq!(b"10 PRINT\r\n".to_vec(), output);
+    }
+
+    #[test]
+    fn should_locate_files_without_reading_them() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("foo").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_ascii(&file_name("bar").0, b"10 PRINT\r\n").unwrap();
+        tape.append_custom(&[0x01, 0x02, 0x03]).unwrap();
+        // A truncated binary file, missing its data block
+        tape.append_raw_block(&[0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0xd0, 0x42, 0x41, 0x44]);
+
+        let entries = Vec::from_iter(tape.entries_with(&[]));
+        assert_eq!(4, entries.len());
+        assert!(entries[0].is_named("foo"));
+        assert!(entries[0].is_named("foo.bin"));
+        assert!(entries[1].is_named("bar.asc"));
+        assert!(!entries[1].is_named("foo"));
+        assert!(!entries[2].is_named(""));
+        assert_eq!(File::Bin("BAD".to_string(), 0, 0, 0, &[]), entries[3].file());
+        assert_eq!(vec![0..2, 2..4, 4..5, 5..6], Vec::from_iter(entries.iter().map(|e| e.block_range())));
+
+        let files = Vec::from_iter(tape.entries_with(&[]).filter(|e| e.is_named("bar")).map(|e| e.file()));
+        assert_eq!(1, files.len());
+        assert_eq!(b"10 PRINT\r\n".to_vec(), files[0].content());
+    }
+
+    #[test]
+    fn should_split_custom_files_into_blocks() {
+        let mut tape = Tape::new();
+        let data: Vec<u8> = (0..20).collect();
+        assert_eq!(4, tape.append_custom_blocks(&data, 8).unwrap());
+        assert_eq!(3, tape.blocks().len());
+        assert_eq!(&data[..8], tape.blocks()[0].data_without_prefix());
+        assert_eq!(&data[8..16], tape.blocks()[1].data_without_prefix());
+        assert_eq!(&[16, 17, 18, 19, 0, 0, 0, 0], tape.blocks()[2].data_without_prefix());
+    }
+
+    #[test]
+    fn should_join_custom_blocks() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("foo").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_custom(&[0x01; 8]).unwrap();
+        tape.append_custom(&[0x02; 8]).unwrap();
+        tape.append_custom(&[0x03; 4]).unwrap();
+        assert_eq!(0, tape.join_blocks(2..=4).unwrap());
+        assert_eq!(3, tape.blocks().len());
+        assert_eq!(
+            [[0x01; 8], [0x02; 8], [0x03, 0x03, 0x03, 0x03, 0x00, 0x00, 0x00, 0x00]].concat(),
+            tape.blocks()[2].data_without_prefix());
+
+        assert!(tape.join_blocks(1..=2).is_err());
+        assert!(tape.join_blocks(2..=3).is_err());
+        assert!(tape.join_blocks(2..=2).is_err());
+    }
+
+    #[test]
+    fn should_remove_files() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("foo").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_ascii(&file_name("bar").0, b"10 PRINT").unwrap();
+        tape.append_bin(&file_name("baz").0, &[0x00, 0x90, 0x01, 0x90, 0x00, 0x90, 0xc9, 0xc9]).unwrap();
+        assert_eq!(2, tape.remove_file("bar.asc").unwrap());
+        assert_eq!(2, tape.remove_file("foo").unwrap());
+        assert_eq!(2, tape.blocks().len());
+        assert_eq!(Some("baz"), tape.blocks()[0].file_name());
+        assert!(tape.remove_file("foo").is_err());
+    }
+
+    #[test]
+    fn should_rename_files() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("foo").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_ascii(&file_name("bar").0, b"10 PRINT").unwrap();
+        tape.rename_file("bar.asc", "LOADER").unwrap();
+        assert_eq!(Some("LOADER"), tape.blocks()[2].file_name());
+        assert!(tape.rename_file("foo", "LOADER2").is_err());
+        assert!(tape.rename_file("bar", "GAME").is_err());
+        assert_eq!(Some("foo"), tape.blocks()[0].file_name().map(str::trim_end));
+    }
+
+    #[test]
+    fn should_set_header_names() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("foo").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.set_header_name(0, "GAME").unwrap();
+        assert_eq!(Some("GAME"), tape.blocks()[0].file_name());
+        assert_eq!(b"GAME  ", &tape.blocks()[0].data_without_prefix()[10..16]);
+        assert!(tape.set_header_name(0, "LOADER2").is_err());
+        assert!(tape.set_header_name(0, "ma\u{f1}ana").is_err());
+        assert!(tape.set_header_name(1, "GAME").is_err());
+    }
+
+    #[test]
+    fn should_measure_padding() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("foo").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_ascii(&file_name("bar").0, b"10 PRINT").unwrap();
+        tape.append_custom(&[0x01; 5]).unwrap();
+        let padding: Vec<usize> = tape.entries_with(&[]).map(|e| e.padding()).collect();
+        assert_eq!(vec![0, 247, 3], padding);
+    }
+
+    #[test]
+    fn should_recover_bin_headers() {
+        let mut tape = Tape::new();
+        let bin = |addrs: [u16; 3], len: usize| {
+            let mut data: Vec<u8> = addrs.iter().flat_map(|a| a.to_le_bytes()).collect();
+            data.resize(6 + len, 0xc9);
+            data
+        };
+        tape.append_bin(&file_name("first").0, &bin([0x9000, 0x900f, 0x9000], 16)).unwrap();
+        tape.append_bin(&file_name("second").0, &bin([0x0000, 0x0000, 0x0000], 18)).unwrap();
+        tape.append_bin(&file_name("third").0, &bin([0xc000, 0x1000, 0xc004], 10)).unwrap();
+        tape.append_bin(&file_name("fourth").0, &bin([0xd000, 0xd000, 0xd000], 1)).unwrap();
+        assert_eq!(
+            vec![
+                Change::Addresses { block: 3, from: (0, 0, 0), to: (0x9010, 0x9021, 0x9010) },
+                Change::Addresses { block: 5, from: (0xc000, 0x1000, 0xc004), to: (0xc000, 0xc009, 0xc004) },
+            ],
+            tape.recover_bin_headers());
+        assert_eq!(&[0x10, 0x90, 0x21, 0x90, 0x10, 0x90], &tape.blocks()[3].data_without_prefix()[..6]);
+        assert_eq!(Vec::<Change>::new(), tape.recover_bin_headers());
+
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("lonely").0, &bin([0x0000, 0x0000, 0x0000], 16)).unwrap();
+        assert_eq!(Vec::<Change>::new(), tape.recover_bin_headers());
+    }
+
+    #[test]
+    fn should_trim_bin_blocks() {
+        let mut tape = Tape::new();
+        let mut data = vec![0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9];
+        data.extend_from_slice(&[0xff; 20]);
+        tape.append_bin(&file_name("foo").0, &data).unwrap();
+        tape.append_custom(&[0x01; 8]).unwrap();
+        assert_eq!(24, tape.entries_with(&[]).next().unwrap().padding());
+        assert_eq!(24, tape.trim_bin_blocks());
+        assert_eq!(&data[..8], tape.blocks()[1].data_without_prefix());
+        assert_eq!(0, tape.trim_bin_blocks());
+    }
+
+    #[test]
+    fn should_strip_padding_from_logical_blocks() {
+        let mut tape = Tape::new();
+        let mut data = vec![0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9];
+        data.extend_from_slice(&[0xff; 20]);
+        tape.append_bin(&file_name("foo").0, &data).unwrap();
+        tape.append_custom(&[0x01; 5]).unwrap();
+        let blocks = tape.logical_blocks();
+        assert_eq!(3, blocks.len());
+        assert_eq!(tape.blocks()[0].data_without_prefix(), blocks[0]);
+        assert_eq!(&data[..8], blocks[1]);
+        assert_eq!(&[0x01; 5], blocks[2]);
+    }
+
+    #[test]
+    fn should_find_custom_runs() {
+        let mut tape = Tape::new();
+        tape.append_custom(&[0x01; 8]).unwrap();
+        tape.append_custom(&[0x02; 8]).unwrap();
+        tape.append_bin(&file_name("foo").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_custom(&[0x03; 8]).unwrap();
+        tape.append_bin(&file_name("bar").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_custom(&[0x04; 8]).unwrap();
+        tape.append_custom(&[0x05; 8]).unwrap();
+        tape.append_custom(&[0x06; 8]).unwrap();
+        assert_eq!(vec![0..=1, 7..=9], tape.custom_runs());
+    }
+
+    #[test]
+    fn should_detect_trailing_garbage() {
+        let mut bytes = BLOCK_PREFIX.to_vec();
+        bytes.extend_from_slice(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0xff, 0xfe]);
+        let mut tape = Tape::from_bytes(&bytes);
+        assert_eq!(&[0xff, 0xfe], tape.trailing_garbage());
+        assert_eq!(2, tape.trim_trailing_garbage());
+        assert_eq!(&[] as &[u8], tape.trailing_garbage());
+        assert_eq!(&bytes[..16], tape.blocks()[0].data());
+
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("foo").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        assert_eq!(&[] as &[u8], tape.trailing_garbage());
+        tape.append_raw_block(&[]);
+        let mut bytes = vec![];
+        for block in &tape.blocks()[..2] {
+            bytes.extend_from_slice(block.data());
+        }
+        bytes.extend_from_slice(&[0xff; 8]);
+        let tape = Tape::from_bytes(&bytes);
+        assert_eq!(&[0xff; 8], tape.trailing_garbage());
+    }
+
+    #[test]
+    fn should_save_unmodified_tapes_without_copies() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("foo").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_custom(&[0x01, 0x02, 0x03]).unwrap();
+        let bytes = tape.to_bytes().into_owned();
+
+        let mut tape = Tape::from_bytes(&bytes);
+        assert!(matches!(tape.to_bytes(), Cow::Borrowed(_)));
+        assert_eq!(bytes, tape.to_bytes().into_owned());
+
+        tape.append_custom(&[0x04]).unwrap();
+        assert!(matches!(tape.to_bytes(), Cow::Owned(_)));
+        assert_eq!(&bytes[..], &tape.to_bytes()[..bytes.len()]);
+
+        // Leading garbage is dropped when saving
+        let mut garbage = vec![0x00; 8];
+        garbage.extend_from_slice(&bytes);
+        assert_eq!(bytes, Tape::from_bytes(&garbage).to_bytes().into_owned());
+
+        // Blocks are copied when modified
+        let mut unaligned = bytes.clone();
+        unaligned.extend_from_slice(&[0x00, 0x01, 0x02]);
+        let mut tape = Tape::from_bytes(&unaligned);
+        assert_eq!(3, tape.trim_trailing_garbage());
+        assert_eq!(bytes, tape.to_bytes().into_owned());
+    }
+
+    #[test]
+    fn should_only_find_aligned_syncs() {
+        let mut bytes = BLOCK_PREFIX.to_vec();
+        bytes.extend_from_slice(&[0x01, 0x02, 0x03, 0x04]);
+        bytes.extend_from_slice(&BLOCK_PREFIX);
+        bytes.extend_from_slice(&[0x05, 0x06, 0x07, 0x08]);
+        assert_eq!(1, Tape::from_bytes(&bytes).blocks().len());
+
+        // A sync overlapping itself must be found at the aligned offset
+        let sync = [0x55; 8];
+        let mut bytes = BLOCK_PREFIX.to_vec();
+        bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x55, 0x55, 0x55, 0x55, 0x55]);
+        bytes.extend_from_slice(&sync);
+        bytes.extend_from_slice(&[0x01; 8]);
+        let tape = Tape::from_bytes_with(&bytes, &[sync]);
+        assert_eq!(2, tape.blocks().len());
+        assert_eq!(&[0x01; 8], tape.blocks()[1].data_without_prefix());
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture bench_parse_big_tape` to see how
+    /// long it takes to parse a tape of 16 MB.
+    #[test]
+    #[ignore]
+    fn bench_parse_big_tape() {
+        use std::time::Instant;
+
+        let mut bytes = vec![];
+        let mut seed = 1u32;
+        while bytes.len() < 16 * 1024 * 1024 {
+            bytes.extend_from_slice(&BLOCK_PREFIX);
+            for _ in 0..4096 {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                bytes.push((seed >> 16) as u8);
+            }
+        }
+
+        let started = Instant::now();
+        let naive = bytes.chunks(8).filter(|chunk| *chunk == BLOCK_PREFIX).count();
+        let scan = started.elapsed();
+        let started = Instant::now();
+        let blocks = sync_offsets(&bytes, &BLOCK_PREFIX).len();
+        let search = started.elapsed();
+        let started = Instant::now();
+        let tape = Tape::from_bytes(&bytes);
+        let parse = started.elapsed();
+
+        assert_eq!(naive, blocks);
+        assert_eq!(blocks, tape.blocks().len());
+        println!("{} blocks in {} MB", blocks, bytes.len() / 1024 / 1024);
+        println!("scanning every 8-byte chunk: {:?}", scan);
+        println!("searching the sync sequence: {:?}", search);
+        println!("parsing the whole tape: {:?}", parse);
+    }
+
+    #[test]
+    fn should_load_tape_with_alternative_syncs() {
+        let sync = [0x1f, 0xa6, 0xde, 0xba, 0xcc, 0x13, 0x7d, 0x75];
+        let mut bytes = sync.to_vec();
+        bytes.extend_from_slice(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        bytes.extend_from_slice(&BLOCK_PREFIX);
+        bytes.extend_from_slice(&[0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10]);
+        assert_eq!(1, Tape::from_bytes(&bytes).blocks().len());
+
+        let tape = Tape::from_bytes_with(&bytes, &[sync]);
+        assert_eq!(2, tape.blocks().len());
+        assert_eq!(&BLOCK_PREFIX[..], &tape.blocks()[0].data()[0..8]);
+        assert_eq!(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08], tape.blocks()[0].data_without_prefix());
+    }
+
+    #[test]
+    fn should_suggest_similar_names() {
+        assert_eq!(0, edit_distance("GAME", "game"));
+        assert_eq!(1, edit_distance("gmae", "game"));
+        assert_eq!(2, edit_distance("gmae", "gam"));
+        assert_eq!(6, edit_distance("", "loader"));
+
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("game").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_ascii(&file_name("gane").0, b"10 PRINT\r\n").unwrap();
+        tape.append_ascii(&file_name("loader").0, b"10 PRINT\r\n").unwrap();
+        tape.append_custom(&[0x01, 0x02, 0x03]).unwrap();
+        let message = |name| tape.no_file_named(name, &[]).to_string();
+        assert_eq!("no file named gmae.bin in the tape, did you mean game.bin or gane.asc?", message("gmae.bin"));
+        assert_eq!("no file named loaderx in the tape, did you mean loader.asc?", message("loaderx"));
+        assert_eq!("no file named intro in the tape", message("intro"));
+        assert_eq!("no file named x in the tape", message("x"));
+    }
+
+    #[test]
+    fn should_normalize_tapes() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("foo").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_ascii(&file_name("bar").0, b"10 PRINT\r\n").unwrap();
+        let canonical = tape.to_bytes().into_owned();
+        assert_eq!(Vec::<Change>::new(), tape.normalize());
+
+        // The same files, with an alternative sync, garbage in the headers and the padding,
+        // and trailing garbage
+        let sync = [0x1f, 0xa6, 0xde, 0xba, 0xcc, 0x13, 0x7d, 0x75];
+        let mut bytes = sync.to_vec();
+        bytes.extend_from_slice(tape.blocks()[0].data_without_prefix());
+        bytes.extend_from_slice(&[0xff; 8]);
+        bytes.extend_from_slice(&BLOCK_PREFIX);
+        bytes.extend_from_slice(&[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa]);
+        bytes.extend_from_slice(tape.blocks()[2].data());
+        let mut ascii = tape.blocks()[3].data().to_vec();
+        ascii[8 + 11..].fill(0x00);
+        bytes.extend_from_slice(&ascii);
+        bytes.extend_from_slice(&[0xee, 0xee, 0xee]);
+
+        let mut tape = Tape::from_bytes_with(&bytes, &[sync]);
+        assert_ne!(canonical, tape.to_bytes().into_owned());
+        assert_eq!(
+            vec![
+                Change::Sync { block: 0, sync },
+                Change::Trimmed { block: 0, bytes: 8 },
+                Change::Trimmed { block: 1, bytes: 8 },
+                Change::Trimmed { block: 3, bytes: 3 },
+                Change::Padded { block: 3, bytes: 245, byte: 0x1a },
+            ],
+            tape.normalize());
+        assert_eq!(canonical, tape.to_bytes().into_owned());
+    }
+
     fn should_add_bin_file_prop(bytes: Vec<u8>) -> TestResult {
-        if Tape::validate_bin(&bytes[..]).is_err() {
+        if !policy::check_bin(&bytes[..]).is_empty() {
             return TestResult::discard();
         }
         let mut tape = Tape::new();
-        let (fname, _) = file_name(&"foobar");
+        let (fname, _) = file_name("foobar");
         let padding = tape.append_bin(&fname, &bytes[..]).unwrap();
 
         let files = Vec::from_iter(tape.files());
@@ -688,7 +1765,7 @@ mod test {
         );
         require_prop!(
             "block content is as expected",
-            &tape.blocks()[1].data_without_prefix()[0..bytes.len()] == &bytes[..]
+            tape.blocks()[1].data_without_prefix()[0..bytes.len()] == bytes[..]
         );
         require_prop!(
             "padding bytes as expected",
@@ -703,11 +1780,11 @@ mod test {
     }
 
     fn should_add_basic_file_prop(bytes: Vec<u8>) -> TestResult {
-        if Tape::validate_basic(&bytes[..]).is_err() {
+        if !policy::check_basic(&bytes[..]).is_empty() {
             return TestResult::discard();
         }
         let mut tape = Tape::new();
-        let (fname, _) = file_name(&"foobar");
+        let (fname, _) = file_name("foobar");
         let padding = tape.append_basic(&fname, &bytes[..]).unwrap();
 
         let files = Vec::from_iter(tape.files());
@@ -719,7 +1796,7 @@ mod test {
         );
         require_prop!(
             "block content is as expected",
-            &tape.blocks()[1].data_without_prefix()[0..bytes.len()] == &bytes[..]
+            tape.blocks()[1].data_without_prefix()[0..bytes.len()] == bytes[..]
         );
         require_prop!(
             "padding bytes as expected",
@@ -728,6 +1805,15 @@ mod test {
         TestResult::from_bool(true)
     }
 
+    #[test]
+    fn should_resolve_memory_regions() {
+        assert_eq!("BIOS ROM, page 0", memory_region(0x0000));
+        assert_eq!("BASIC ROM, page 1", memory_region(0x4000));
+        assert_eq!("RAM, page 2", memory_region(0x8000));
+        assert_eq!("RAM, page 3", memory_region(0xf37f));
+        assert_eq!("system area, page 3", memory_region(0xf380));
+    }
+
     #[test]
     fn should_add_basic_file() {
         quickcheck(should_add_basic_file_prop as fn(Vec<u8>) -> TestResult);
@@ -735,7 +1821,7 @@ mod test {
 
     fn should_add_ascii_file_prop(text: String) -> TestResult {
         let mut tape = Tape::new();
-        let (fname, _) = file_name(&"foobar");
+        let (fname, _) = file_name("foobar");
         tape.append_ascii(&fname, text.as_bytes()).unwrap();
 
         let files = Vec::from_iter(tape.files());
@@ -775,4 +1861,20 @@ mod test {
     fn should_add_ascii_file() {
         quickcheck(should_add_ascii_file_prop as fn(String) -> TestResult);
     }
+
+    #[test]
+    fn should_add_ascii_files_of_several_blocks() {
+        let unix_text: String = (1..=100).map(|i| format!("{} PRINT {}\n", i * 10, i)).collect();
+        let text = crate::file::with_crlf(unix_text.as_bytes());
+        assert!(text.len() > 1024);
+        for data in [&text[..], &text[..1024]] {
+            let mut tape = Tape::new();
+            tape.append_ascii(&file_name("foobar").0, data).unwrap();
+            // The header, a block per 256 bytes and a block with the EOF
+            assert_eq!(1 + data.len() / 256 + 1, tape.blocks().len());
+            let files = Vec::from_iter(tape.files());
+            assert_eq!(1, files.len());
+            assert_eq!(data.to_vec(), files[0].content());
+        }
+    }
 }
@@ -0,0 +1,129 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::file;
+use crate::hash;
+use crate::tape::Tape;
+
+/// A set of tapes released together, as the sides of a cassette or the volumes of a game
+///
+/// Sets are described by JSON files giving the title of the set, if any, and the label and
+/// the CAS file of each side, in order. The paths of the CAS files are relative to the
+/// descriptor:
+///
+/// ```json
+/// {
+///   "title": "Game",
+///   "sides": [
+///     { "label": "Side A", "path": "game-a.cas" },
+///     { "label": "Side B", "path": "game-b.cas" }
+///   ]
+/// }
+/// ```
+///
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct TapeSet {
+    #[serde(default)]
+    pub title: Option<String>,
+    pub sides: Vec<Side>,
+}
+
+/// A side (or volume) of a set of tapes
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct Side {
+    pub label: String,
+    pub path: PathBuf,
+}
+
+/// A file found in more than one side of a set, given as the side and the name of each copy
+pub type Duplicate = Vec<(usize, String)>;
+
+impl TapeSet {
+    /// Load the set described by the given file.
+    pub fn load(path: &Path) -> io::Result<TapeSet> {
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        TapeSet::parse(&file::read_content(path)?, dir).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, tr!("invalid tape set {}: {}", format!("{:?}", path), e))
+        })
+    }
+
+    /// Parse the given descriptor of a set, whose paths are relative to the given directory.
+    pub fn parse(json: &[u8], dir: &Path) -> Result<TapeSet, serde_json::Error> {
+        let mut set: TapeSet = serde_json::from_slice(json)?;
+        for side in &mut set.sides {
+            side.path = dir.join(&side.path);
+        }
+        Ok(set)
+    }
+
+    /// Returns the name of the WAV file the given side is exported into, made of the given
+    /// stem and the label of the side (e.g., `game-side-a.wav` for `Side A`).
+    pub fn wav_name(&self, stem: &str, side: usize) -> String {
+        let label: String = self.sides[side]
+            .label
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '-' })
+            .collect();
+        let label: Vec<&str> = label.split('-').filter(|w| !w.is_empty()).collect();
+        format!("{}-{}.wav", stem, label.join("-"))
+    }
+}
+
+/// Returns the files found in more than one of the given sides of a set.
+///
+/// Files are the same when their contents are, whatever their names.
+pub fn duplicates(sides: &[Tape]) -> Vec<Duplicate> {
+    let mut copies: BTreeMap<String, Duplicate> = BTreeMap::new();
+    for (side, tape) in sides.iter().enumerate() {
+        for entry in tape.entries_with(&[]) {
+            let name = entry.header_name().map(|n| n.trim_end().to_string()).unwrap_or_default();
+            copies.entry(hash::sha1(&entry.file().content())).or_default().push((side, name));
+        }
+    }
+    let mut duplicates: Vec<Duplicate> = copies
+        .into_values()
+        .filter(|copies| copies.iter().any(|(side, _)| *side != copies[0].0))
+        .collect();
+    duplicates.sort();
+    duplicates
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::tape::file_name;
+
+    use super::*;
+
+    #[test]
+    fn should_parse_sets() {
+        let json = br#"{"title": "Game", "sides": [{"label": "Side A", "path": "a.cas"}, {"label": "Side B", "path": "b.cas"}]}"#;
+        let set = TapeSet::parse(json, Path::new("tapes")).unwrap();
+        assert_eq!(Some("Game".to_string()), set.title);
+        assert_eq!(Side { label: "Side B".to_string(), path: PathBuf::from("tapes/b.cas") }, set.sides[1]);
+        assert_eq!("game-side-a.wav", set.wav_name("game", 0));
+        assert!(TapeSet::parse(br#"{"title": "Game"}"#, Path::new("")).is_err());
+    }
+
+    #[test]
+    fn should_find_duplicates_across_sides() {
+        let mut a = Tape::new();
+        a.append_bin(&file_name("loader").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        a.append_custom(&[0x01; 8]).unwrap();
+        a.append_custom(&[0x01; 8]).unwrap();
+        let mut b = Tape::new();
+        b.append_bin(&file_name("boot").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        b.append_custom(&[0x02; 8]).unwrap();
+        assert_eq!(vec![vec![(0, "loader".to_string()), (1, "boot".to_string())]], duplicates(&[a, b]));
+    }
+}
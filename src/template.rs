@@ -0,0 +1,121 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// The placeholders accepted in the format of the listed files
+///
+/// * `type`, the type of the file: `bin`, `basic`, `ascii`, `other` or `custom`
+/// * `name`, the name stored in the header of the file, empty for custom files
+/// * `bytes`, the size of the file in bytes
+/// * `size`, the size of the file in human units
+/// * `time`, the time the file takes to load, as `mm:ss`
+/// * `begin`, `end` and `start`, the addresses of binary files in hex, empty for other files
+/// * `crc32` and `sha1`, the hashes of the file as it is extracted
+///
+pub const FILE_FIELDS: &[&str] = &["type", "name", "bytes", "size", "time", "begin", "end", "start", "crc32", "sha1"];
+
+#[derive(Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Field(String),
+}
+
+/// A template of a line of output, with text and `{field}` placeholders
+///
+/// Braces are written as `{{` and `}}`, and tabs and new lines as `\t` and `\n`, so they
+/// can be easily given from the shell.
+///
+#[derive(Debug, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parse the given template, accepting only the given fields as placeholders.
+    pub fn parse(template: &str, fields: &[&str]) -> Result<Template, String> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest.find('}').ok_or_else(|| format!("unclosed placeholder in {:?}", template))?;
+                    let field = &rest[..end];
+                    if !fields.contains(&field) {
+                        return Err(tr!("unknown placeholder {}, expected one of {}", format!("{{{}}}", field), fields.join(", ")));
+                    }
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                    parts.push(Part::Field(field.to_string()));
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err(tr!("unmatched {} in {}", "}", format!("{:?}", template))),
+                '\\' if chars.as_str().starts_with('t') => {
+                    chars.next();
+                    text.push('\t');
+                }
+                '\\' if chars.as_str().starts_with('n') => {
+                    chars.next();
+                    text.push('\n');
+                }
+                '\\' if chars.as_str().starts_with('\\') => {
+                    chars.next();
+                    text.push('\\');
+                }
+                c => text.push(c),
+            }
+        }
+        parts.push(Part::Text(text));
+        parts.retain(|p| *p != Part::Text(String::new()));
+        Ok(Template { parts })
+    }
+
+    /// Render the template, taking the value of each placeholder from the given function.
+    pub fn render<F: Fn(&str) -> String>(&self, value: F) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Field(field) => value(field),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_render_templates() {
+        let template = Template::parse("{name}\\t{type} {{{bytes}}}\\n", FILE_FIELDS).unwrap();
+        assert_eq!("game\tbin {16}\n", template.render(|field| match field {
+            "name" => "game".to_string(),
+            "type" => "bin".to_string(),
+            _ => "16".to_string(),
+        }));
+        assert_eq!("a\\b", Template::parse("a\\\\b", FILE_FIELDS).unwrap().render(|_| String::new()));
+    }
+
+    #[test]
+    fn should_reject_invalid_templates() {
+        assert!(Template::parse("{name", FILE_FIELDS).is_err());
+        assert!(Template::parse("name}", FILE_FIELDS).is_err());
+        assert_eq!(
+            Err("unknown placeholder {md5}, expected one of type, name".to_string()),
+            Template::parse("{md5}", &["type", "name"]));
+    }
+}
@@ -0,0 +1,311 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::speed::{Speeds, DEFAULT_BAUDS};
+use crate::tape::{Block, Tape};
+
+/// The signature every TSX (and TZX) file starts with
+pub const MAGIC: &[u8] = b"ZXTape!\x1a";
+
+/// The version of the TZX format written by `Tsx::to_bytes()`
+const VERSION: [u8; 2] = [1, 21];
+
+/// The ID of the Kansas City Standard block, used to store MSX blocks
+const KCS_BLOCK: u8 = 0x4b;
+
+/// The clock that durations are expressed in, in T-states per second
+const CLOCK: u32 = 3_500_000;
+
+/// Durations of the pulses of a 1200 bauds MSX signal, in T-states
+const PILOT: u16 = 729;
+const ZERO: u16 = 1458;
+const ONE: u16 = 729;
+
+/// Number of pulses of the long pilot tone preceding file headers, and of the short pilot
+/// tone preceding the rest of blocks
+const LONG_PILOT: u16 = 32000;
+const SHORT_PILOT: u16 = 8000;
+
+/// Pauses (in milliseconds) before file headers and before the rest of blocks, the same
+/// silences written when exporting to WAV
+const LONG_PAUSE: u16 = 2000;
+const SHORT_PAUSE: u16 = 1000;
+
+/// A Kansas City Standard block of a TSX file
+///
+/// This is the block used to store each block of an MSX tape. Durations are expressed
+/// in T-states of a 3.5 MHz clock.
+///
+#[derive(Debug, PartialEq)]
+pub struct KcsBlock {
+    /// Pause after this block, in milliseconds
+    pub pause: u16,
+    /// Duration of a pilot pulse
+    pub pilot: u16,
+    /// Number of pulses of the pilot tone
+    pub pilot_pulses: u16,
+    /// Duration of a pulse of a `0` bit
+    pub zero: u16,
+    /// Duration of a pulse of a `1` bit
+    pub one: u16,
+    /// Number of pulses per bit (high nibble for `0`, low nibble for `1`)
+    pub bit_pulses: u8,
+    /// Leading and trailing bits of each byte, and bit order
+    pub byte_framing: u8,
+    pub data: Vec<u8>,
+}
+
+/// A TSX file
+///
+/// TSX is the TZX format extended with the Kansas City Standard block (ID `0x4B`) to
+/// store MSX tapes. Only these blocks are retained when a file is read: the rest of
+/// blocks (ZX Spectrum data, pauses, groups, texts...) are skipped.
+///
+#[derive(Debug, PartialEq)]
+pub struct Tsx {
+    pub blocks: Vec<KcsBlock>,
+}
+
+impl KcsBlock {
+    /// Create a block with the given data using the timing of MSX at the given bauds.
+    ///
+    /// File headers have a long pilot tone, while the rest of blocks have a short one. Both
+    /// last the same regardless of the speed.
+    pub fn msx(data: &[u8], file_header: bool, bauds: u32, pause: u16) -> KcsBlock {
+        let scale = |value: u16, num: u32, den: u32| (value as u32 * num / den).min(u16::MAX as u32) as u16;
+        let pilot_pulses = if file_header { LONG_PILOT } else { SHORT_PILOT };
+        KcsBlock {
+            pause,
+            pilot: scale(PILOT, DEFAULT_BAUDS, bauds),
+            pilot_pulses: scale(pilot_pulses, bauds, DEFAULT_BAUDS),
+            zero: scale(ZERO, DEFAULT_BAUDS, bauds),
+            one: scale(ONE, DEFAULT_BAUDS, bauds),
+            bit_pulses: 0x24,
+            byte_framing: 0x54,
+            data: data.to_vec(),
+        }
+    }
+
+    /// Returns the speed of this block in bauds, computed from the duration of a `0` bit.
+    ///
+    /// As durations in T-states are not exact, the speed is rounded to tens of bauds.
+    pub fn bauds(&self) -> u32 {
+        let pulses = match self.bit_pulses >> 4 {
+            0 => 16,
+            n => n as u32,
+        };
+        let bauds = CLOCK / (pulses * self.zero as u32).max(1);
+        ((bauds + 5) / 10 * 10).max(10)
+    }
+}
+
+impl Tsx {
+    /// Create a TSX file with the blocks of the given tape, at the given speeds.
+    ///
+    /// The pause after each block is the silence that precedes the next one when exporting
+    /// to WAV: long before file headers and short otherwise. The given pauses, if any (e.g.,
+    /// those of the TSX file the tape was read from), take precedence.
+    pub fn of(tape: &Tape, speeds: &Speeds, pauses: Option<&[u16]>) -> Tsx {
+        let blocks = tape.blocks();
+        let pause_after = |i: usize| match pauses.and_then(|p| p.get(i)) {
+            Some(pause) => *pause,
+            None if blocks.get(i + 1).is_some_and(Block::is_file_header) => LONG_PAUSE,
+            None => SHORT_PAUSE,
+        };
+        Tsx {
+            blocks: blocks
+                .iter()
+                .enumerate()
+                .map(|(i, b)| KcsBlock::msx(b.data_without_prefix(), b.is_file_header(), speeds.of(i), pause_after(i)))
+                .collect(),
+        }
+    }
+
+    /// Returns the bytes of this TSX file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION);
+        for block in &self.blocks {
+            bytes.push(KCS_BLOCK);
+            bytes.extend_from_slice(&(12 + block.data.len() as u32).to_le_bytes());
+            for word in &[block.pause, block.pilot, block.pilot_pulses, block.zero, block.one] {
+                bytes.extend_from_slice(&word.to_le_bytes());
+            }
+            bytes.push(block.bit_pulses);
+            bytes.push(block.byte_framing);
+            bytes.extend_from_slice(&block.data);
+        }
+        bytes
+    }
+
+    /// Read a TSX file from the given bytes.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Tsx> {
+        if !bytes.starts_with(MAGIC) || bytes.len() < 10 {
+            return Err(invalid_tsx(&tr!("missing TSX header")));
+        }
+        let mut blocks = vec![];
+        let mut i = 10;
+        while i < bytes.len() {
+            let id = bytes[i];
+            let body = &bytes[i + 1..];
+            let len = block_len(id, body)?;
+            if len > body.len() {
+                return Err(invalid_tsx(&tr!("block {} at offset {} is truncated", format!("{:02X}", id), i)));
+            }
+            if id == KCS_BLOCK {
+                blocks.push(KcsBlock {
+                    pause: LittleEndian::read_u16(&body[0x04..0x06]),
+                    pilot: LittleEndian::read_u16(&body[0x06..0x08]),
+                    pilot_pulses: LittleEndian::read_u16(&body[0x08..0x0a]),
+                    zero: LittleEndian::read_u16(&body[0x0a..0x0c]),
+                    one: LittleEndian::read_u16(&body[0x0c..0x0e]),
+                    bit_pulses: body[0x0e],
+                    byte_framing: body[0x0f],
+                    data: body[0x10..len].to_vec(),
+                });
+            }
+            i += 1 + len;
+        }
+        Ok(Tsx { blocks })
+    }
+}
+
+/// Returns the length of the body of the block with the given ID.
+fn block_len(id: u8, body: &[u8]) -> io::Result<usize> {
+    let byte = |at: usize| body.get(at).map(|b| *b as usize);
+    let word = |at: usize| body.get(at..at + 2).map(|w| LittleEndian::read_u16(w) as usize);
+    let triple = |at: usize| body.get(at..at + 3).map(|t| LittleEndian::read_u24(t) as usize);
+    let dword = |at: usize| body.get(at..at + 4).map(|d| LittleEndian::read_u32(d) as usize);
+    let len = match id {
+        0x10 => word(0x02).map(|n| 0x04 + n),
+        0x11 => triple(0x0f).map(|n| 0x12 + n),
+        0x12 => Some(0x04),
+        0x13 => byte(0x00).map(|n| 0x01 + 2 * n),
+        0x14 => triple(0x07).map(|n| 0x0a + n),
+        0x15 => triple(0x05).map(|n| 0x08 + n),
+        0x18 | 0x19 => dword(0x00).map(|n| 0x04 + n),
+        0x20 | 0x23 | 0x24 => Some(0x02),
+        0x21 | 0x30 => byte(0x00).map(|n| 0x01 + n),
+        0x22 | 0x25 | 0x27 => Some(0x00),
+        0x26 => word(0x00).map(|n| 0x02 + 2 * n),
+        0x28 | 0x32 => word(0x00).map(|n| 0x02 + n),
+        0x2a => Some(0x04),
+        0x2b => Some(0x05),
+        0x31 => byte(0x01).map(|n| 0x02 + n),
+        0x33 => byte(0x00).map(|n| 0x01 + 3 * n),
+        0x35 => dword(0x10).map(|n| 0x14 + n),
+        KCS_BLOCK => dword(0x00).map(|n| 0x04 + n).filter(|n| *n >= 0x10),
+        0x5a => Some(0x09),
+        _ => return Err(invalid_tsx(&tr!("unknown block ID {}", format!("{:02X}", id)))),
+    };
+    len.ok_or_else(|| invalid_tsx(&tr!("block {} is truncated", format!("{:02X}", id))))
+}
+
+fn invalid_tsx(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, tr!("invalid TSX file: {}", msg))
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::tape::file_name;
+
+    use super::*;
+
+    fn kcs_block(data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![KCS_BLOCK];
+        bytes.extend_from_slice(&(12 + data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0xe8, 0x03, 0xe9, 0x02, 0x40, 0x1f, 0xd2, 0x05, 0xe9, 0x02, 0x24, 0x54]);
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn should_read_kcs_blocks() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[0x01, 0x15]);
+        bytes.extend_from_slice(&[0x30, 0x03, b'M', b'S', b'X']);
+        bytes.extend_from_slice(&kcs_block(&[0xd0, 0xd0]));
+        bytes.extend_from_slice(&[0x20, 0xe8, 0x03]);
+        bytes.extend_from_slice(&kcs_block(&[0x01]));
+        let tsx = Tsx::from_bytes(&bytes).unwrap();
+        assert_eq!(2, tsx.blocks.len());
+        assert_eq!(
+            KcsBlock {
+                pause: 1000,
+                pilot: 745,
+                pilot_pulses: 8000,
+                zero: 1490,
+                one: 745,
+                bit_pulses: 0x24,
+                byte_framing: 0x54,
+                data: vec![0xd0, 0xd0],
+            },
+            tsx.blocks[0]);
+        assert_eq!(vec![0x01], tsx.blocks[1].data);
+    }
+
+    #[test]
+    fn should_write_tape_blocks() {
+        let mut tape = Tape::new();
+        tape.append_bin(&file_name("foo").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+        tape.append_custom(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]).unwrap();
+        tape.append_bin(&file_name("bar").0, &[0x00, 0x80, 0x01, 0x80, 0x00, 0x80, 0xc9, 0xc9]).unwrap();
+
+        let tsx = Tsx::of(&tape, &Speeds::default(), None);
+        let pauses: Vec<u16> = tsx.blocks.iter().map(|b| b.pause).collect();
+        assert_eq!(vec![1000, 1000, 2000, 1000, 1000], pauses);
+        let pilots: Vec<u16> = tsx.blocks.iter().map(|b| b.pilot_pulses).collect();
+        assert_eq!(vec![32000, 8000, 8000, 32000, 8000], pilots);
+
+        let read = Tsx::from_bytes(&tsx.to_bytes()).unwrap();
+        assert_eq!(tsx, read);
+        for (block, kcs) in tape.blocks().iter().zip(&read.blocks) {
+            assert_eq!(block.data_without_prefix(), &kcs.data[..]);
+        }
+
+        let tsx = Tsx::of(&tape, &Speeds::default(), Some(&[100, 200]));
+        let pauses: Vec<u16> = tsx.blocks.iter().map(|b| b.pause).collect();
+        assert_eq!(vec![100, 200, 2000, 1000, 1000], pauses);
+    }
+
+    #[test]
+    fn should_compute_block_speeds() {
+        let speeds = Speeds { bauds: vec![1200, 2400, 3600] };
+        let mut tape = Tape::new();
+        for _ in 0..3 {
+            tape.append_custom(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]).unwrap();
+        }
+        let tsx = Tsx::of(&tape, &speeds, None);
+        let bauds: Vec<u32> = tsx.blocks.iter().map(|b| b.bauds()).collect();
+        assert_eq!(speeds.bauds, bauds);
+        assert_eq!((729, 1458), (tsx.blocks[0].one, tsx.blocks[0].zero));
+        assert_eq!((243, 486), (tsx.blocks[2].one, tsx.blocks[2].zero));
+        assert_eq!(24000, tsx.blocks[2].pilot_pulses);
+    }
+
+    #[test]
+    fn should_reject_truncated_blocks() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[0x01, 0x15]);
+        let block = kcs_block(&[0xd0, 0xd0]);
+        bytes.extend_from_slice(&block[..block.len() - 1]);
+        assert!(Tsx::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn should_reject_unknown_blocks() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[0x01, 0x15, 0x99]);
+        assert!(Tsx::from_bytes(&bytes).is_err());
+    }
+}
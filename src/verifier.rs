@@ -0,0 +1,169 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use byteorder::{ByteOrder, LittleEndian};
+
+/// The addresses where the verifier may be loaded, in order of preference
+const PLACES: [usize; 7] = [0xc000, 0xd000, 0xe000, 0xb000, 0xa000, 0x9000, 0x8800];
+
+/// The BIOS routine printing the character in register A
+const CHPUT: u16 = 0x00a2;
+
+/// The code of the verifier, to be followed by its table of files
+///
+/// For each file in the table, it adds up the bytes of its memory, prints its name and
+/// whether the sum is the expected one. Each entry of the table has the BEGIN address and
+/// the length of the file, its expected sum and its name, padded to six characters.
+///
+/// The addresses in the code are left as zeroes, to be patched by `program()`.
+const CODE: [u8; 90] = [
+    0x21, 0x00, 0x00,             // LD HL,table
+    0x06, 0x00,                   // LD B,count
+    // loop:
+    0xc5,                         // PUSH BC
+    0x5e, 0x23, 0x56, 0x23,       // LD E,(HL) / INC HL / LD D,(HL) / INC HL
+    0x4e, 0x23, 0x46, 0x23,       // LD C,(HL) / INC HL / LD B,(HL) / INC HL
+    0xe5,                         // PUSH HL
+    0x21, 0x00, 0x00,             // LD HL,0
+    // sum:
+    0x1a,                         // LD A,(DE)
+    0x85, 0x6f,                   // ADD A,L / LD L,A
+    0x30, 0x01,                   // JR NC,+1
+    0x24,                         // INC H
+    0x13, 0x0b,                   // INC DE / DEC BC
+    0x78, 0xb1,                   // LD A,B / OR C
+    0x20, 0xf4,                   // JR NZ,sum
+    0xeb,                         // EX DE,HL
+    0xe1,                         // POP HL
+    0x7e, 0x23, 0x93, 0x4f,       // LD A,(HL) / INC HL / SUB E / LD C,A
+    0x7e, 0x23, 0x92, 0xb1,       // LD A,(HL) / INC HL / SUB D / OR C
+    0xf5,                         // PUSH AF
+    0x06, 0x06,                   // LD B,6
+    // name:
+    0x7e,                         // LD A,(HL)
+    0xcd, 0x00, 0x00,             // CALL CHPUT
+    0x23,                         // INC HL
+    0x10, 0xf9,                   // DJNZ name
+    0xf1,                         // POP AF
+    0xe5,                         // PUSH HL
+    0x21, 0x00, 0x00,             // LD HL,ok
+    0x28, 0x03,                   // JR Z,+3
+    0x21, 0x00, 0x00,             // LD HL,bad
+    0xcd, 0x00, 0x00,             // CALL puts
+    0xe1,                         // POP HL
+    0xc1,                         // POP BC
+    0x10, 0xc2,                   // DJNZ loop
+    0xc9,                         // RET
+    // puts:
+    0x7e, 0xb7, 0xc8,             // LD A,(HL) / OR A / RET Z
+    0xcd, 0x00, 0x00,             // CALL CHPUT
+    0x23,                         // INC HL
+    0x18, 0xf7,                   // JR puts
+    // ok:
+    b' ', b'O', b'K', b'\r', b'\n', 0x00,
+    // bad:
+    b' ', b'B', b'A', b'D', b'\r', b'\n', 0x00,
+];
+
+/// The offsets of the code where the table, the messages and the routines are
+const TABLE: usize = 90;
+const OK: usize = 77;
+const BAD: usize = 83;
+const PUTS: usize = 68;
+
+/// A binary file checked by the verifier, with its name, BEGIN address and program
+pub type Checked<'a> = (&'a str, usize, &'a [u8]);
+
+/// Returns the sum of the given bytes, as computed by the verifier.
+pub fn checksum(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |sum, b| sum.wrapping_add(*b as u16))
+}
+
+/// Returns the address where a verifier for the given number of files may be loaded
+/// without overwriting any of the given memory ranges, if any.
+pub fn place(files: usize, taken: &[(usize, usize)]) -> Option<usize> {
+    let size = TABLE + 12 * files;
+    PLACES
+        .iter()
+        .copied()
+        .find(|addr| taken.iter().all(|(begin, end)| addr + size <= *begin || *end < *addr))
+}
+
+/// Generate the binary file of a verifier loaded at the given address, checking the given
+/// files, with its `0xfe` ID byte.
+///
+/// The verifier checks the memory of the files once they are loaded with `BLOAD"CAS:"`, so
+/// it must be loaded and run after them, with `BLOAD"CAS:",R`. At most 255 files are checked.
+///
+pub fn program(org: usize, files: &[Checked]) -> Vec<u8> {
+    let mut code = CODE.to_vec();
+    let patch = |code: &mut Vec<u8>, offset: usize, value: u16| LittleEndian::write_u16(&mut code[offset..], value);
+    patch(&mut code, 1, (org + TABLE) as u16);
+    code[4] = files.len().min(255) as u8;
+    for offset in [45, 72] {
+        patch(&mut code, offset, CHPUT);
+    }
+    patch(&mut code, 53, (org + OK) as u16);
+    patch(&mut code, 58, (org + BAD) as u16);
+    patch(&mut code, 61, (org + PUTS) as u16);
+    for (name, begin, data) in files.iter().take(255) {
+        let mut entry = [0x20; 12];
+        LittleEndian::write_u16(&mut entry[0..], *begin as u16);
+        LittleEndian::write_u16(&mut entry[2..], data.len() as u16);
+        LittleEndian::write_u16(&mut entry[4..], checksum(data));
+        let name = name.as_bytes();
+        let len = name.len().min(6);
+        entry[6..6 + len].copy_from_slice(&name[..len]);
+        code.extend_from_slice(&entry);
+    }
+
+    let end = org + code.len() - 1;
+    let mut bin = vec![0xfe];
+    for addr in [org, end, org] {
+        bin.extend_from_slice(&(addr as u16).to_le_bytes());
+    }
+    bin.extend_from_slice(&code);
+    bin
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn should_sum_bytes() {
+        assert_eq!(0, checksum(&[]));
+        assert_eq!(0x0301, checksum(&[0xff, 0xff, 0xff, 0x04]));
+        assert_eq!(0xffff, checksum(&[0xff; 257]));
+    }
+
+    #[test]
+    fn should_place_the_verifier_out_of_the_files() {
+        assert_eq!(Some(0xc000), place(2, &[(0x9000, 0x9fff)]));
+        assert_eq!(Some(0xd000), place(2, &[(0xc000, 0xc0ff)]));
+        // The verifier takes 90 bytes and 12 bytes per file
+        assert_eq!(Some(0xd000), place(2, &[(0xc000 + 90 + 23, 0xc0ff)]));
+        assert_eq!(Some(0xc000), place(2, &[(0xc000 + 90 + 24, 0xc0ff)]));
+        assert_eq!(None, place(2, &[(0x8000, 0xefff)]));
+    }
+
+    #[test]
+    fn should_generate_programs() {
+        let bin = program(0xc000, &[("GAME", 0x9000, &[0x01, 0x02, 0x03])]);
+        assert_eq!(&[0xfe, 0x00, 0xc0, 0x65, 0xc0, 0x00, 0xc0], &bin[..7]);
+        let code = &bin[7..];
+        assert_eq!(TABLE + 12, code.len());
+        assert_eq!(&[0x21, 0x5a, 0xc0, 0x06, 0x01], &code[..5]);
+        assert_eq!(&[0x00, 0x90, 0x03, 0x00, 0x06, 0x00], &code[TABLE..TABLE + 6]);
+        assert_eq!(b"GAME  ", &code[TABLE + 6..]);
+        assert_eq!(b" OK\r\n\0", &code[OK..OK + 6]);
+        assert_eq!(b" BAD\r\n\0", &code[BAD..TABLE]);
+        assert_eq!(0x7e, code[PUTS]);
+    }
+}
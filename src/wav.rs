@@ -13,12 +13,33 @@ use std::iter::FromIterator;
 
 use byteorder::{LittleEndian, WriteBytesExt};
 
+use crate::tape::Block;
+
 const SHORT_PULSE: u32 = 2400;
 const LONG_PULSE: u32 = 1200;
 
 const SHORT_HEADER: u32 = 4000;
 const LONG_HEADER: u32 = 16000;
 
+/// The shape of the pulses of the exported signal
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Waveform {
+	Sine,
+	Square,
+}
+
+/// A set of export settings tuned for some kind of player
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+	/// A sine wave at 43200 samples per second, fine for sound cards and most players
+	Default,
+	/// A full-amplitude square wave at 48000 samples per second with longer silences,
+	/// for Arduino-based tape players such as TZXDuino and MaxDuino
+	Tzxduino,
+}
+
 /// An object capable to export binary data in WAV format
 ///
 /// The exporter object works by encoding silences, headers and data into
@@ -28,33 +49,160 @@ const LONG_HEADER: u32 = 16000;
 pub struct Exporter {
 	bauds: u32,
 	sample_rate: u32,
+	waveform: Waveform,
+	amplitude: u8,
+	short_silence: u32,
+	long_silence: u32,
+	silence: Option<u32>,
+	tags: Vec<([u8; 4], String)>,
 	buffer: Vec<u8>,
 }
 
+impl Default for Exporter {
+	fn default() -> Exporter {
+		Exporter::new()
+	}
+}
+
 impl Exporter {
 
 	/// Create a new exporter using default settings
 	///
 	/// Default settins are 1200 bauds and 43200 samples per second.
 	pub fn new() -> Exporter {
+		Exporter::with_preset(Preset::Default)
+	}
+
+	/// Create a new exporter using the settings of the given preset
+	pub fn with_preset(preset: Preset) -> Exporter {
+		let (sample_rate, waveform, amplitude, short_silence, long_silence) = match preset {
+			Preset::Default => (43200, Waveform::Sine, 127, 1000, 2000),
+			Preset::Tzxduino => (48000, Waveform::Square, 127, 1500, 3000),
+		};
 		Exporter {
 			bauds: 1200,
-			sample_rate: 43200,
+			sample_rate,
+			waveform,
+			amplitude,
+			short_silence,
+			long_silence,
+			silence: None,
+			tags: Vec::new(),
 			buffer: Vec::new(),
 		}
 	}
 
+	/// Returns the speed of the data encoded from now on
+	pub fn bauds(&self) -> u32 {
+		self.bauds
+	}
+
+	/// Set the speed of the data encoded from now on
+	pub fn set_bauds(&mut self, bauds: u32) {
+		self.bauds = bauds;
+	}
+
+	/// Set the silence (in milliseconds) before the blocks encoded from now on, replacing
+	/// the short and long silences, or restore them if `None`
+	pub fn set_silence(&mut self, millis: Option<u32>) {
+		self.silence = millis;
+	}
+
+	/// Returns the number of samples per second of the exported data
+	pub fn sample_rate(&self) -> u32 {
+		self.sample_rate
+	}
+
+	/// Returns the number of samples taken by the encoding of a block
+	///
+	/// A block is encoded as a silence, a header and its data. File headers use a long
+	/// silence and a long header, while any other block uses the short ones. The result
+	/// is a triple with the samples of the silence, the header and the data.
+	pub fn block_samples(&self, file_header: bool, data_len: usize) -> (usize, usize, usize) {
+		let (silence, header) = if file_header {
+			(self.silence_len(self.silence.unwrap_or(self.long_silence)), LONG_HEADER)
+		} else {
+			(self.silence_len(self.silence.unwrap_or(self.short_silence)), SHORT_HEADER)
+		};
+		let short = self.pulse_len(SHORT_PULSE) as usize;
+		let long = self.pulse_len(LONG_PULSE) as usize;
+		// Every byte is a start bit, 8 data bits and 2 stop bits, and every bit takes the
+		// same time regardless of its value
+		let byte = long + 8 * long + 4 * short;
+		let header = (header * self.bauds / 1200) as usize * short;
+		(silence as usize, header, data_len * byte)
+	}
+
+	/// Returns the number of samples taken by a silence of the given milliseconds
+	pub fn pause_samples(&self, millis: u32) -> usize {
+		self.silence_len(millis) as usize
+	}
+
+	/// Returns the time taken by the encoding of the given blocks, in seconds
+	pub fn seconds_of(&self, blocks: &[Block]) -> f64 {
+		let samples: usize = blocks.iter()
+			.map(|b| self.block_samples(b.is_file_header(), b.data_without_prefix().len()))
+			.map(|(silence, header, data)| silence + header + data)
+			.sum();
+		samples as f64 / self.sample_rate as f64
+	}
+
+	/// Returns a description of the settings of this exporter
+	///
+	/// Two exporters with the same settings encode the same data the same way.
+	pub fn settings(&self) -> String {
+		let (short_silence, long_silence) = match self.silence {
+			Some(silence) => (silence, silence),
+			None => (self.short_silence, self.long_silence),
+		};
+		format!(
+			"{} bauds, {} Hz, {:?} wave of amplitude {}, silences of {}/{} ms",
+			self.bauds, self.sample_rate, self.waveform, self.amplitude, short_silence, long_silence)
+	}
+
+	/// Add a tag to the exported WAV file, given by its RIFF INFO ID (e.g., `INAM` for the
+	/// title or `ICMT` for comments).
+	pub fn add_tag(&mut self, id: &[u8; 4], value: &str) {
+		self.tags.push((*id, value.to_string()));
+	}
+
+	/// Write already encoded samples to the internal buffer
+	pub fn write_samples(&mut self, samples: &[u8]) -> io::Result<usize> {
+		self.buffer.write(samples)
+	}
+
+	/// Returns the samples encoded so far
+	///
+	/// Samples are 8-bit unsigned values, being `0x80` the silence level.
+	pub fn samples(&self) -> &[u8] {
+		&self.buffer
+	}
+
 	/// Export the encoded data to the given `Write` instance
 	///
 	/// This method dumps the encoded data into the given `Write` instance. Before
 	/// calling this method, you must use the `write_X()` functions to encode
 	/// some data.
 	pub fn export<W: Write>(&self, w: &mut W) -> io::Result<()> {
-		self.write_wave(w)?;
-		w.write(&*self.buffer)?;
+		self.write_wave(w, self.buffer.len())?;
+		w.write_all(&self.buffer)?;
 		Ok(())
 	}
 
+	/// Write the WAV header for the given number of samples to the given `Write` instance
+	///
+	/// Along with `clear_samples()`, this allows writing the samples as they are encoded
+	/// rather than keeping all of them in memory: the header is written first, and written
+	/// again with the final number of samples once the samples are written after it.
+	pub fn write_wave_header<W: Write>(&self, w: &mut W, samples: usize) -> io::Result<()> {
+		self.write_wave(w, samples)
+	}
+
+	/// Remove the samples encoded so far from the internal buffer, once they are written
+	pub fn clear_samples(&mut self) {
+		self.buffer.clear();
+	}
+
 	/// Write a short header to the internal buffer
 	pub fn write_short_header(&mut self) -> io::Result<usize> {
 		self.write_header(SHORT_HEADER)
@@ -75,15 +223,21 @@ impl Exporter {
 		Ok(nbytes)
 	}
 
-	/// Write a short silence (1 second) to the internal buffer
+	/// Write a short silence (1 second by default) to the internal buffer
 	pub fn write_short_silence(&mut self) -> io::Result<usize> {
-		let pulses = self.sample_rate;
+		let pulses = self.silence_len(self.short_silence);
 		self.write_silence(pulses)
 	}
 
-	/// Write a long silence (2 seconds) to the internal buffer
+	/// Write a long silence (2 seconds by default) to the internal buffer
 	pub fn write_long_silence(&mut self) -> io::Result<usize> {
-		let pulses = self.sample_rate * 2;
+		let pulses = self.silence_len(self.long_silence);
+		self.write_silence(pulses)
+	}
+
+	/// Write a silence of the given milliseconds to the internal buffer
+	pub fn write_pause(&mut self, millis: u32) -> io::Result<usize> {
+		let pulses = self.silence_len(millis);
 		self.write_silence(pulses)
 	}
 
@@ -96,6 +250,29 @@ impl Exporter {
 		Ok(nbytes)
 	}
 
+	/// Write the given block to the internal buffer, preceded by a silence and a header
+	///
+	/// File headers are preceded by a long silence and header, so the MSX has time to
+	/// process the file header, while the rest of blocks get short ones. The silence set
+	/// with `set_silence()`, if any, replaces both.
+	pub fn write_block(&mut self, block: &Block) -> io::Result<usize> {
+		let mut nbytes = 0;
+		if let Some(silence) = self.silence {
+			nbytes += self.write_pause(silence)?;
+		} else if block.is_file_header() {
+			nbytes += self.write_long_silence()?;
+		} else {
+			nbytes += self.write_short_silence()?;
+		}
+		if block.is_file_header() {
+			nbytes += self.write_long_header()?;
+		} else {
+			nbytes += self.write_short_header()?;
+		}
+		nbytes += self.write_data(block.data_without_prefix())?;
+		Ok(nbytes)
+	}
+
 	/// Write binary data to the internal buffer
 	pub fn write_data(&mut self, data: &[u8]) -> io::Result<usize> {
 		let mut nbytes = 0;
@@ -105,9 +282,10 @@ impl Exporter {
 		Ok(nbytes)
 	}
 
-	fn write_wave<W: Write>(&self, w: &mut W) -> io::Result<()> {
-		let data_len = self.buffer.len() as u32;
-		let file_len = data_len + 44;
+	fn write_wave<W: Write>(&self, w: &mut W, samples: usize) -> io::Result<()> {
+		let info = self.info_chunk();
+		let data_len = samples as u32;
+		let file_len = data_len + 44 + info.len() as u32;
 
 		// RIFF chunk start
 		write!(w, "RIFF")?;
@@ -142,6 +320,9 @@ impl Exporter {
 		// Bits per sample
 		w.write_u16::<LittleEndian>(8)?;
 
+		// Tags, before the data so players reading the file as it arrives find them
+		w.write_all(&info)?;
+
 		// Data chunk start
 		write!(w, "data")?;
 
@@ -151,6 +332,29 @@ impl Exporter {
 		Ok(())
 	}
 
+	/// Returns the LIST chunk with the tags of the exported file, if any.
+	fn info_chunk(&self) -> Vec<u8> {
+		if self.tags.is_empty() {
+			return vec![];
+		}
+		let mut info = b"INFO".to_vec();
+		for (id, value) in &self.tags {
+			// Values are null-terminated strings, padded to an even length
+			let mut value = value.as_bytes().to_vec();
+			value.push(0);
+			info.extend_from_slice(id);
+			info.extend_from_slice(&(value.len() as u32).to_le_bytes());
+			if value.len() % 2 == 1 {
+				value.push(0);
+			}
+			info.extend_from_slice(&value);
+		}
+		let mut chunk = b"LIST".to_vec();
+		chunk.extend_from_slice(&(info.len() as u32).to_le_bytes());
+		chunk.extend_from_slice(&info);
+		chunk
+	}
+
 	fn write_byte(&mut self, byte: u8) -> io::Result<usize> {
 		let mut nbytes = 0;
 		nbytes += self.write_pulse(LONG_PULSE)?;
@@ -162,7 +366,7 @@ impl Exporter {
 			} else {
 				nbytes += self.write_pulse(LONG_PULSE)?;
 			}
-			bits = bits >> 1;
+			bits >>= 1;
 		}
 		for _ in 0..4 {
 			nbytes += self.write_pulse(SHORT_PULSE)?;
@@ -171,12 +375,29 @@ impl Exporter {
 	}
 
 	fn write_pulse(&mut self, freq: u32) -> io::Result<usize> {
-		let len = self.sample_rate / (self.bauds * (freq / 1200));
-		let scale = 2.0 * f32::consts::PI  / len as f32;
-		let func = |x: f32| (f32::sin(scale * x) * 127.0) as i8 as u8 ^ 0x80;
-		let bytes = Vec::from_iter((0..len).map(|x| func(x as f32)));
+		let len = self.pulse_len(freq);
+		let amplitude = self.amplitude as f32;
+		let bytes = match self.waveform {
+			Waveform::Sine => {
+				let scale = 2.0 * f32::consts::PI  / len as f32;
+				let func = |x: f32| (f32::sin(scale * x) * amplitude) as i8 as u8 ^ 0x80;
+				Vec::from_iter((0..len).map(|x| func(x as f32)))
+			},
+			Waveform::Square => {
+				let func = |x: u32| if x < len / 2 { 0x80 + self.amplitude } else { 0x80 - self.amplitude };
+				Vec::from_iter((0..len).map(func))
+			},
+		};
 		self.buffer.write(&bytes[..])
 	}
+
+	fn silence_len(&self, millis: u32) -> u32 {
+		self.sample_rate * millis / 1000
+	}
+
+	fn pulse_len(&self, freq: u32) -> u32 {
+		self.sample_rate / (self.bauds * (freq / 1200))
+	}
 }
 
 #[cfg(test)]
@@ -205,4 +426,87 @@ mod test {
 		assert_eq!("data".as_bytes(), &output[36..40]);
 		assert_eq!(0, LittleEndian::read_u32(&output[40..44]));
 	}
+
+	#[test]
+	fn should_export_tags() {
+		let mut exporter = Exporter::new();
+		exporter.add_tag(b"INAM", "Game");
+		exporter.add_tag(b"ICMT", "bin LOADER");
+		let mut output: Vec<u8> = Vec::new();
+		exporter.export(&mut output).ok();
+		assert_eq!(44 + 46, LittleEndian::read_u32(&output[4..8]));
+		assert_eq!("LIST".as_bytes(), &output[36..40]);
+		assert_eq!(38, LittleEndian::read_u32(&output[40..44]));
+		assert_eq!("INFOINAM".as_bytes(), &output[44..52]);
+		assert_eq!(5, LittleEndian::read_u32(&output[52..56]));
+		assert_eq!("Game\0\0ICMT".as_bytes(), &output[56..66]);
+		assert_eq!(11, LittleEndian::read_u32(&output[66..70]));
+		assert_eq!("bin LOADER\0\0data".as_bytes(), &output[70..86]);
+	}
+
+	#[test]
+	fn should_write_samples_after_header() {
+		let mut exporter = Exporter::new();
+		exporter.write_short_silence().unwrap();
+		let mut expected: Vec<u8> = Vec::new();
+		exporter.export(&mut expected).ok();
+
+		let mut output: Vec<u8> = Vec::new();
+		exporter.write_wave_header(&mut output, 0).unwrap();
+		assert_eq!(0, LittleEndian::read_u32(&output[40..44]));
+		let samples = exporter.samples().len();
+		output.extend_from_slice(exporter.samples());
+		exporter.clear_samples();
+		assert!(exporter.samples().is_empty());
+		exporter.write_wave_header(&mut &mut output[..44], samples).unwrap();
+		assert_eq!(expected, output);
+	}
+
+	#[test]
+	fn should_compute_block_samples() {
+		let mut exporter = Exporter::new();
+		let silence = exporter.write_short_silence().unwrap();
+		let header = exporter.write_short_header().unwrap();
+		let data = exporter.write_data(&[0x00, 0xff, 0x5a]).unwrap();
+		assert_eq!((silence, header, data), exporter.block_samples(false, 3));
+	}
+
+	#[test]
+	fn should_replace_silences() {
+		let mut exporter = Exporter::new();
+		let header = Block::from_data(&[0xd0; 16]);
+		let (long, _, _) = exporter.block_samples(true, 16);
+		exporter.set_silence(Some(300));
+		let (silence, _, _) = exporter.block_samples(true, 16);
+		assert_eq!((86400, 12960), (long, silence));
+		let nbytes = exporter.write_block(&header).unwrap();
+		let (silence, header, data) = exporter.block_samples(true, 16);
+		assert_eq!(silence + header + data, nbytes);
+		assert!(exporter.samples()[..silence].iter().all(|s| *s == 0x80));
+		assert!(exporter.settings().ends_with("silences of 300/300 ms"));
+	}
+
+	#[test]
+	fn should_compute_seconds_of_blocks() {
+		let exporter = Exporter::new();
+		let header = Block::from_data(&[0xd0; 16]);
+		let data = Block::from_data(&[0x00; 1100]);
+		// Silences, headers and bytes of 11 bits at 1200 bauds
+		let header_seconds = 2.0 + 16000.0 / 2400.0 + 16.0 * 11.0 / 1200.0;
+		let data_seconds = 1.0 + 4000.0 / 2400.0 + 1100.0 * 11.0 / 1200.0;
+		let seconds = exporter.seconds_of(&[header, data]);
+		assert!((seconds - header_seconds - data_seconds).abs() < 0.01);
+	}
+
+	#[test]
+	fn should_export_with_preset() {
+		let mut exporter = Exporter::with_preset(Preset::Tzxduino);
+		let silence = exporter.write_long_silence().unwrap();
+		let header = exporter.write_long_header().unwrap();
+		let data = exporter.write_data(&[0x00, 0xff, 0x5a]).unwrap();
+		assert_eq!((silence, header, data), exporter.block_samples(true, 3));
+		assert_eq!(48000 * 3, silence);
+		assert!(exporter.samples()[silence..].iter().all(|s| *s == 0xff || *s == 0x01));
+		assert_ne!(Exporter::new().settings(), exporter.settings());
+	}
 }
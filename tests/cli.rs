@@ -0,0 +1,36 @@
+//
+// MSX CAS Packager
+// Copyright (c) 2015 Alvaro Polo
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+extern crate tempdir;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempdir::TempDir;
+
+fn mcp(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mcp"))
+        .current_dir(dir)
+        .env("MCP_LANG", "en")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn should_keep_errors_out_of_stdout_when_extracting_to_it() {
+    let temp = TempDir::new("mcp").unwrap();
+    fs::write(temp.path().join("game.asc"), b"10 PRINT \"HELLO\"\r\n").unwrap();
+    assert!(mcp(temp.path(), &["add", "g.cas", "game.asc"]).status.success());
+
+    let output = mcp(temp.path(), &["extract", "--stdout", "g.cas", "nope"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Error: "));
+}